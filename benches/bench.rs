@@ -1,180 +1,202 @@
-use std::collections::HashMap;
-use std::hash::{BuildHasher, Hasher, RandomState};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, RandomState};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Barrier, RwLock};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use ahash::AHasher;
-use criterion::{black_box, Criterion, criterion_group, criterion_main};
+use criterion::measurement::WallTime;
+use criterion::{
+    black_box, BatchSize, BenchmarkGroup, BenchmarkId, Criterion, Throughput, criterion_group,
+    criterion_main,
+};
 
-use hash_funsies::VoxelChunkIndex;
+use hash_funsies::analysis::collision_report;
+use hash_funsies::hashers::{fib_hash_batch, field_mix_hash_batch, FibHasher, IdentityHasher};
+use hash_funsies::prehash::{HashedIndex, PrehashedMap};
+use hash_funsies::trace::{ReplayTarget, TraceOp};
+use hash_funsies::workload::{caves, outposts, player_walk, read_world_dump};
+use hash_funsies::{
+    ChunkAabb, DenseRegionMap, EpochChunkMap, LinearChunkMap, PagedChunkMap, PerfectChunkMap,
+    ShardedChunkMap, SortedChunkMap, Trace, TracedMap, VoxelChunkIndex, VoxelChunkMap,
+};
 
-/// Just doesn't do any hashing. Uses the number itself as hashed value.
-#[derive(Default)]
-struct IdentityHasher(u64);
-impl Hasher for IdentityHasher {
-    fn finish(&self) -> u64 {
-        self.0
-    }
-
-    fn write(&mut self, bytes: &[u8]) {
-        const U128SIZE: usize = std::mem::size_of::<u128>();
-        const U64SIZE: usize = std::mem::size_of::<u64>();
-        const U32SIZE: usize = std::mem::size_of::<u32>();
-        const U16SIZE: usize = std::mem::size_of::<u16>();
-        const U8SIZE: usize = std::mem::size_of::<u8>();
-
-        self.0 += match bytes.len() {
-            U64SIZE => u64::from_be_bytes(bytes.try_into().unwrap()),
-            U32SIZE => u32::from_be_bytes(bytes.try_into().unwrap()) as u64,
-            U16SIZE => u16::from_be_bytes(bytes.try_into().unwrap()) as u64,
-            U8SIZE => u8::from_be_bytes(bytes.try_into().unwrap()) as u64,
-
-            U128SIZE => {
-                let mut sum = 0u64;
-                for chunk in bytes.chunks(U64SIZE) {
-                    sum += u64::from_be_bytes(chunk.try_into().unwrap());
-                }
-                sum
-            }
+type CrcHasherBuilder = core::hash::BuildHasherDefault<crc32fast::Hasher>;
+type AHashBuilder = core::hash::BuildHasherDefault<AHasher>;
+type IdentityHasherBuilder = core::hash::BuildHasherDefault<IdentityHasher>;
 
-            _ => {
-                let mut sum = 0u64;
-                let mut it = bytes.chunks_exact(U64SIZE);
-                for chunk in it.by_ref() {
-                    sum += u64::from_be_bytes(chunk.try_into().unwrap());
-                }
+// For the Fibonacci Hasher, we need at least size 20 because we're going to hash 200 * 200 * 20
+// = 800_000 values, and it would be nice to not have a mapped space full of collisions.
+// 2^20 = 1_048_576, which should be a large enough space to hold the amount of keys.
+// `report_fib_sweep` prints how other sizes fare.
+const FIB_SIZE: u8 = 20;
+type FibHasherBuilder = core::hash::BuildHasherDefault<FibHasher<FIB_SIZE>>;
 
-                {
-                    let bytes = it.remainder();
-                    let mut result = [0u8; 8];
-                    for i in ((U64SIZE - bytes.len())..(U64SIZE - 1)).rev() {
-                        result[i] = bytes[i];
-                    }
-                    sum += u64::from_be_bytes(result);
-                }
-                sum
-            }
-        };
-    }
+pub fn inserts<T: BuildHasher>(
+    coords: &Vec<VoxelChunkIndex>,
+    bh: T,
+) -> HashMap<VoxelChunkIndex, u32, T> {
+    let mut hmap = HashMap::with_hasher(bh);
+    hmap.reserve(coords.len());
 
-    fn write_u8(&mut self, i: u8) {
-        self.0 += i as u64;
+    for &c in coords {
+        hmap.insert(c, 0);
     }
 
-    fn write_u16(&mut self, i: u16) {
-        self.0 += i as u64;
-    }
+    hmap
+}
 
-    fn write_u32(&mut self, i: u32) {
-        self.0 += i as u64;
+pub fn reads<T: BuildHasher>(coords: &Vec<VoxelChunkIndex>, hm: &HashMap<VoxelChunkIndex, u32, T>) {
+    for c in coords {
+        black_box(hm.get(c));
     }
+}
+
+pub fn sorted_inserts(coords: &[VoxelChunkIndex]) -> SortedChunkMap<u32> {
+    coords.iter().map(|&c| (c, 0)).collect()
+}
 
-    fn write_u64(&mut self, i: u64) {
-        self.0 += i;
+pub fn sorted_reads(coords: &[VoxelChunkIndex], sm: &SortedChunkMap<u32>) {
+    for &c in coords {
+        black_box(sm.get(c));
     }
 }
 
-/// Fibonacci hashing - see https://probablydance.com/2018/06/16/fibonacci-hashing-the-optimization-that-the-world-forgot-or-a-better-alternative-to-integer-modulo/
-pub struct FibHasher<const N: u8> {
-    hash: u64,
+pub fn btree_inserts(coords: &[VoxelChunkIndex]) -> BTreeMap<VoxelChunkIndex, u32> {
+    let mut map = BTreeMap::new();
+
+    for &c in coords {
+        map.insert(c, 0);
+    }
+
+    map
 }
-impl<const N: u8> FibHasher<N> {
-    const SHIFT: u8 = 64 - N;
+
+pub fn btree_reads(coords: &[VoxelChunkIndex], map: &BTreeMap<VoxelChunkIndex, u32>) {
+    for c in coords {
+        black_box(map.get(c));
+    }
 }
-impl<const N: u8> Default for FibHasher<N> {
-    fn default() -> Self {
-        assert!(Self::SHIFT < 64);
-        FibHasher { hash: 0 }
+
+/// Dense array over the box `gen_coords` covers.
+pub fn dense_inserts(coords: &[VoxelChunkIndex]) -> DenseRegionMap<u32> {
+    let mut map = DenseRegionMap::new(ChunkAabb::new(
+        (XY_LOW, XY_LOW, Z_LOW),
+        (XY_UP, XY_UP, Z_UP),
+    ));
+
+    for &c in coords {
+        map.insert(c, 0);
     }
+
+    map
 }
-impl<const N: u8> Hasher for FibHasher<N> {
-    fn finish(&self) -> u64 {
-        self.hash.wrapping_mul(11400714819323198485) >> Self::SHIFT
+
+pub fn dense_reads(coords: &[VoxelChunkIndex], map: &DenseRegionMap<u32>) {
+    for &c in coords {
+        black_box(map.get(c));
     }
+}
 
-    fn write(&mut self, bytes: &[u8]) {
-        const U128SIZE: usize = std::mem::size_of::<u128>();
-        const U64SIZE: usize = std::mem::size_of::<u64>();
-        const U32SIZE: usize = std::mem::size_of::<u32>();
-        const U16SIZE: usize = std::mem::size_of::<u16>();
-        const U8SIZE: usize = std::mem::size_of::<u8>();
+pub fn chunk_map_inserts(coords: &[VoxelChunkIndex]) -> VoxelChunkMap<u32> {
+    let mut map = VoxelChunkMap::new();
+    map.reserve(coords.len());
 
-        self.hash += match bytes.len() {
-            U64SIZE => u64::from_be_bytes(bytes.try_into().unwrap()),
-            U32SIZE => u32::from_be_bytes(bytes.try_into().unwrap()) as u64,
-            U16SIZE => u16::from_be_bytes(bytes.try_into().unwrap()) as u64,
-            U8SIZE => u8::from_be_bytes(bytes.try_into().unwrap()) as u64,
+    for &c in coords {
+        map.insert(c, 0);
+    }
 
-            U128SIZE => {
-                let mut sum = 0u64;
-                for chunk in bytes.chunks(U64SIZE) {
-                    sum += u64::from_be_bytes(chunk.try_into().unwrap());
-                }
-                sum
-            }
+    map
+}
 
-            _ => {
-                let mut sum = 0u64;
-                let mut it = bytes.chunks_exact(U64SIZE);
-                for chunk in it.by_ref() {
-                    sum += u64::from_be_bytes(chunk.try_into().unwrap());
-                }
+pub fn chunk_map_bulk_inserts(coords: &[VoxelChunkIndex]) -> VoxelChunkMap<u32> {
+    let mut map = VoxelChunkMap::new();
+    map.extend_sorted(coords.iter().map(|&c| (c, 0)));
+    map
+}
 
-                {
-                    let bytes = it.remainder();
-                    let mut result = [0u8; 8];
-                    for i in ((U64SIZE - bytes.len())..(U64SIZE - 1)).rev() {
-                        result[i] = bytes[i];
-                    }
-                    sum += u64::from_be_bytes(result);
-                }
-                sum
-            }
-        };
+pub fn chunk_map_reads(coords: &[VoxelChunkIndex], map: &VoxelChunkMap<u32>) {
+    for &c in coords {
+        black_box(map.get(c));
     }
+}
 
-    fn write_u8(&mut self, i: u8) {
-        self.hash += i as u64;
-    }
+pub fn perfect_map_build(coords: &[VoxelChunkIndex]) -> PerfectChunkMap<u32> {
+    PerfectChunkMap::build(coords.iter().copied(), coords.iter().map(|_| 0))
+}
 
-    fn write_u16(&mut self, i: u16) {
-        self.hash += i as u64;
+pub fn perfect_map_reads(coords: &[VoxelChunkIndex], map: &PerfectChunkMap<u32>) {
+    for &c in coords {
+        black_box(map.get(c));
     }
+}
+
+pub fn linear_map_inserts(coords: &[VoxelChunkIndex]) -> LinearChunkMap<u32> {
+    let mut map = LinearChunkMap::new();
+    map.reserve(coords.len());
 
-    fn write_u32(&mut self, i: u32) {
-        self.hash += i as u64;
+    for &c in coords {
+        map.insert(c, 0);
     }
 
-    fn write_u64(&mut self, i: u64) {
-        self.hash += i;
+    map
+}
+
+pub fn linear_map_reads(coords: &[VoxelChunkIndex], map: &LinearChunkMap<u32>) {
+    for &c in coords {
+        black_box(map.get(c));
     }
 }
 
-type CrcHasherBuilder = core::hash::BuildHasherDefault<crc32fast::Hasher>;
-type AHashBuilder = core::hash::BuildHasherDefault<AHasher>;
-type IdentityHasherBuilder = core::hash::BuildHasherDefault<IdentityHasher>;
+pub fn paged_map_inserts(coords: &[VoxelChunkIndex]) -> PagedChunkMap<u32> {
+    let mut map = PagedChunkMap::new();
 
-// For the Fibonacci Hasher, we need at least size 20 because we're going to hash 200 * 200 * 20
-// = 800_000 values, and it would be nice to not have a mapped space full of collisions.
-// 2^20 = 1_048_576, which should be a large enough space to hold the amount of keys.
-const FIB_SIZE: u8 = 20;
-type FibHasherBuilder = core::hash::BuildHasherDefault<FibHasher<FIB_SIZE>>;
+    for &c in coords {
+        map.insert(c, 0);
+    }
 
-pub fn inserts<T: BuildHasher>(
-    coords: &Vec<VoxelChunkIndex>,
-    bh: T,
-) -> HashMap<VoxelChunkIndex, u32, T> {
-    let mut hmap = HashMap::with_hasher(bh);
-    hmap.reserve(coords.len());
+    map
+}
 
+pub fn paged_map_reads(coords: &[VoxelChunkIndex], map: &PagedChunkMap<u32>) {
     for &c in coords {
-        hmap.insert(c, 0);
+        black_box(map.get(c));
     }
+}
 
-    hmap
+fn face_neighbors(c: VoxelChunkIndex) -> [VoxelChunkIndex; 6] {
+    let (x, y, z) = c.to_coords();
+    [
+        VoxelChunkIndex::from_coords(x - 1, y, z),
+        VoxelChunkIndex::from_coords(x + 1, y, z),
+        VoxelChunkIndex::from_coords(x, y - 1, z),
+        VoxelChunkIndex::from_coords(x, y + 1, z),
+        VoxelChunkIndex::from_coords(x, y, z - 1),
+        VoxelChunkIndex::from_coords(x, y, z + 1),
+    ]
 }
 
-pub fn reads<T: BuildHasher>(coords: &Vec<VoxelChunkIndex>, hm: &HashMap<VoxelChunkIndex, u32, T>) {
-    for c in coords {
-        black_box(hm.get(c));
+pub fn neighbor_reads(coords: &[VoxelChunkIndex], map: &VoxelChunkMap<u32>) {
+    for &c in coords {
+        for n in face_neighbors(c) {
+            black_box(map.get(n));
+        }
+    }
+}
+
+pub fn paged_neighbor_reads(coords: &[VoxelChunkIndex], map: &PagedChunkMap<u32>) {
+    for &c in coords {
+        for n in face_neighbors(c) {
+            black_box(map.get(n));
+        }
+    }
+}
+
+pub fn neighbor_batch_reads(coords: &[VoxelChunkIndex], map: &VoxelChunkMap<u32>) {
+    for &c in coords {
+        black_box(map.get_many(face_neighbors(c)));
     }
 }
 
@@ -203,6 +225,410 @@ pub fn gen_coords() -> Vec<VoxelChunkIndex> {
     coords
 }
 
+/// System allocator keeping count of the bytes currently allocated and of their peak, for
+/// `report_memory`, and of every allocation, for `report_allocations`.
+struct CountingAllocator;
+
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static REALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static TOTAL_ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+fn add_allocated(size: usize) {
+    let now = ALLOCATED.fetch_add(size, Ordering::Relaxed) + size;
+    PEAK.fetch_max(now, Ordering::Relaxed);
+    TOTAL_ALLOCATED.fetch_add(size, Ordering::Relaxed);
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc(layout);
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            add_allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn alloc_zeroed(&self, layout: Layout) -> *mut u8 {
+        let ptr = System.alloc_zeroed(layout);
+        if !ptr.is_null() {
+            ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            add_allocated(layout.size());
+        }
+        ptr
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout);
+        ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        let new_ptr = System.realloc(ptr, layout, new_size);
+        if !new_ptr.is_null() {
+            // Count the new block before releasing the old one, as a moving realloc does.
+            REALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+            add_allocated(new_size);
+            ALLOCATED.fetch_sub(layout.size(), Ordering::Relaxed);
+        }
+        new_ptr
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Bytes the value `build` returns keeps allocated, and the most bytes allocated at once while
+/// building it, both on top of what was allocated before.
+fn measure_memory<T>(build: impl FnOnce() -> T) -> (usize, usize) {
+    let base = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let value = build();
+    let retained = ALLOCATED.load(Ordering::Relaxed) - base;
+    let peak = PEAK.load(Ordering::Relaxed) - base;
+    drop(value);
+    (retained, peak)
+}
+
+/// Allocator activity while running a scenario.
+#[derive(Default)]
+struct AllocProfile {
+    allocations: usize,
+    reallocations: usize,
+    /// Bytes of every allocation and reallocation, freed or not.
+    bytes: usize,
+    /// Most bytes allocated at once, on top of what was allocated before.
+    peak: usize,
+}
+
+fn profile_allocations<T>(run: impl FnOnce() -> T) -> (T, AllocProfile) {
+    let base = ALLOCATED.load(Ordering::Relaxed);
+    PEAK.store(base, Ordering::Relaxed);
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+    let reallocations = REALLOCATIONS.load(Ordering::Relaxed);
+    let bytes = TOTAL_ALLOCATED.load(Ordering::Relaxed);
+    let value = run();
+    let profile = AllocProfile {
+        allocations: ALLOCATIONS.load(Ordering::Relaxed) - allocations,
+        reallocations: REALLOCATIONS.load(Ordering::Relaxed) - reallocations,
+        bytes: TOTAL_ALLOCATED.load(Ordering::Relaxed) - bytes,
+        peak: PEAK.load(Ordering::Relaxed) - base,
+    };
+    (value, profile)
+}
+
+fn print_alloc_profile(scenario: &str, container: &str, profile: AllocProfile) {
+    println!(
+        "{scenario:<16}{container:<14}{:>10}{:>10}{:>14.1}{:>10.1}",
+        profile.allocations,
+        profile.reallocations,
+        profile.bytes as f64 / (1 << 20) as f64,
+        profile.peak as f64 / (1 << 20) as f64
+    );
+}
+
+/// Profiles replaying `trace` on each container of `bench_replays`, prefilled outside of the
+/// profile.
+fn profile_replays(scenario: &str, coords: &Vec<VoxelChunkIndex>, trace: &Trace) {
+    fn replay<T: ReplayTarget>(scenario: &str, container: &str, mut map: T, trace: &Trace) {
+        let (_, profile) = profile_allocations(|| trace.replay(&mut map));
+        print_alloc_profile(scenario, container, profile);
+    }
+
+    replay(
+        scenario,
+        "Vanilla",
+        inserts(coords, RandomState::new()),
+        trace,
+    );
+    replay(
+        scenario,
+        "Fib",
+        inserts(coords, FibHasherBuilder::default()),
+        trace,
+    );
+    replay(
+        scenario,
+        "AHash",
+        inserts(coords, AHashBuilder::default()),
+        trace,
+    );
+    replay(
+        scenario,
+        "Id",
+        inserts(coords, IdentityHasherBuilder::default()),
+        trace,
+    );
+    replay(scenario, "ChunkMap", chunk_map_inserts(coords), trace);
+    replay(scenario, "LinearMap", linear_map_inserts(coords), trace);
+    replay(scenario, "PagedMap", paged_map_inserts(coords), trace);
+}
+
+/// When `HASH_FUNSIES_ALLOC_PROFILE` is set, prints how many allocations and bytes each
+/// container goes through in the insert, mixed and churn scenarios, to check growth policy and
+/// reserve changes against numbers rather than timings.
+pub fn report_allocations(_: &mut Criterion) {
+    if std::env::var_os("HASH_FUNSIES_ALLOC_PROFILE").is_none() {
+        return;
+    }
+    let coords = gen_coords();
+    println!("Allocations per scenario:");
+    println!(
+        "{:<16}{:<14}{:>10}{:>10}{:>14}{:>10}",
+        "", "", "allocs", "reallocs", "allocated MB", "peak MB"
+    );
+
+    let insert =
+        |container: &str, profile: AllocProfile| print_alloc_profile("Inserts", container, profile);
+    insert(
+        "Vanilla",
+        profile_allocations(|| inserts(&coords, RandomState::new())).1,
+    );
+    insert(
+        "VanillaGrow",
+        profile_allocations(|| {
+            let mut map = HashMap::new();
+            for &c in &coords {
+                map.insert(c, 0u32);
+            }
+            map
+        })
+        .1,
+    );
+    insert(
+        "Fib",
+        profile_allocations(|| inserts(&coords, FibHasherBuilder::default())).1,
+    );
+    insert("Sorted", profile_allocations(|| sorted_inserts(&coords)).1);
+    insert("BTree", profile_allocations(|| btree_inserts(&coords)).1);
+    insert(
+        "ChunkMap",
+        profile_allocations(|| chunk_map_inserts(&coords)).1,
+    );
+    insert(
+        "ChunkMapGrow",
+        profile_allocations(|| {
+            let mut map = VoxelChunkMap::new();
+            for &c in &coords {
+                map.insert(c, 0u32);
+            }
+            map
+        })
+        .1,
+    );
+    insert(
+        "ChunkMapBulk",
+        profile_allocations(|| chunk_map_bulk_inserts(&coords)).1,
+    );
+    insert(
+        "LinearMap",
+        profile_allocations(|| linear_map_inserts(&coords)).1,
+    );
+    insert(
+        "PagedMap",
+        profile_allocations(|| paged_map_inserts(&coords)).1,
+    );
+
+    for read_percent in MIXED_READ_PERCENTS {
+        let trace = mixed_trace(&coords, read_percent);
+        profile_replays(&format!("Mixed {read_percent}%"), &coords, &trace);
+    }
+    profile_replays("Slide", &coords, &slide_trace());
+    profile_replays("Refill", &coords, &refill_trace(&coords));
+}
+
+/// Prints the memory each container of the `Inserts` group ends up using per entry, and the
+/// peak allocation while inserting, to weigh against their speed.
+pub fn report_memory(_: &mut Criterion) {
+    let coords = gen_coords();
+    println!("Memory after inserting {} chunks:", coords.len());
+    println!("{:<14}{:>12}{:>14}", "", "bytes/entry", "peak MB");
+    let row = |name: &str, (retained, peak): (usize, usize)| {
+        println!(
+            "{name:<14}{:>12.1}{:>14.1}",
+            retained as f64 / coords.len() as f64,
+            peak as f64 / (1 << 20) as f64
+        );
+    };
+
+    row(
+        "Vanilla",
+        measure_memory(|| inserts(&coords, RandomState::new())),
+    );
+    row(
+        "Crc",
+        measure_memory(|| inserts(&coords, CrcHasherBuilder::default())),
+    );
+    row(
+        "Fib",
+        measure_memory(|| inserts(&coords, FibHasherBuilder::default())),
+    );
+    row(
+        "AHash",
+        measure_memory(|| inserts(&coords, AHashBuilder::default())),
+    );
+    row(
+        "Id",
+        measure_memory(|| inserts(&coords, IdentityHasherBuilder::default())),
+    );
+    row("Sorted", measure_memory(|| sorted_inserts(&coords)));
+    row("BTree", measure_memory(|| btree_inserts(&coords)));
+    row("DenseMap", measure_memory(|| dense_inserts(&coords)));
+    row("ChunkMap", measure_memory(|| chunk_map_inserts(&coords)));
+    row(
+        "ChunkMapBulk",
+        measure_memory(|| chunk_map_bulk_inserts(&coords)),
+    );
+    row("LinearMap", measure_memory(|| linear_map_inserts(&coords)));
+    row("PagedMap", measure_memory(|| paged_map_inserts(&coords)));
+}
+
+/// Prints the hardware events per entry of inserting every chunk in each container, then of
+/// reading them back, as the cache misses tell apart containers whose timings are close.
+#[cfg(all(feature = "perf", target_os = "linux"))]
+pub fn report_perf_counters(_: &mut Criterion) {
+    use hash_funsies::perf::{PerfCounters, PerfEvent};
+
+    let counters = match PerfCounters::open(&PerfEvent::ALL) {
+        Ok(counters) => counters,
+        Err(err) => {
+            println!("Performance counters unavailable: {err}");
+            return;
+        }
+    };
+    let coords = gen_coords();
+    println!(
+        "Hardware events per entry, inserting {} chunks then reading them:",
+        coords.len()
+    );
+    print!("{:<20}", "");
+    for event in counters.events() {
+        print!("{:>17}", event.name());
+    }
+    println!();
+    let row = |name: &str, counts: Vec<u64>| {
+        print!("{name:<20}");
+        for count in counts {
+            print!("{:>17.2}", count as f64 / coords.len() as f64);
+        }
+        println!();
+    };
+
+    macro_rules! measure {
+        ($name:literal, $insert:expr, $read:expr) => {
+            let (map, counts) = counters.measure(|| $insert).expect("reading counters");
+            row(concat!($name, " inserts"), counts);
+            let ((), counts) = counters.measure(|| $read(&map)).expect("reading counters");
+            row(concat!($name, " reads"), counts);
+        };
+    }
+
+    measure!("Vanilla", inserts(&coords, RandomState::new()), |m| reads(
+        &coords, m
+    ));
+    measure!("Fib", inserts(&coords, FibHasherBuilder::default()), |m| {
+        reads(&coords, m)
+    });
+    measure!("AHash", inserts(&coords, AHashBuilder::default()), |m| {
+        reads(&coords, m)
+    });
+    measure!(
+        "Id",
+        inserts(&coords, IdentityHasherBuilder::default()),
+        |m| reads(&coords, m)
+    );
+    measure!("Sorted", sorted_inserts(&coords), |m| sorted_reads(
+        &coords, m
+    ));
+    measure!("DenseMap", dense_inserts(&coords), |m| dense_reads(
+        &coords, m
+    ));
+    measure!("ChunkMap", chunk_map_inserts(&coords), |m| chunk_map_reads(
+        &coords, m
+    ));
+    measure!("PagedMap", paged_map_inserts(&coords), |m| paged_map_reads(
+        &coords, m
+    ));
+}
+
+#[cfg(not(all(feature = "perf", target_os = "linux")))]
+pub fn report_perf_counters(_: &mut Criterion) {}
+
+/// Initial capacities of the maps in `report_fib_sweep`, as multiples of the number of keys,
+/// 0 letting the map grow from empty.
+const SWEEP_CAPACITIES: [usize; 4] = [0, 1, 2, 4];
+
+/// Buckets of a standard map holding `len` entries after reserving `capacity`: it keeps at
+/// most 7 entries out of 8 buckets, in a power-of-two table.
+fn std_buckets(capacity: usize, len: usize) -> usize {
+    (capacity.max(len) * 8).div_ceil(7).next_power_of_two()
+}
+
+/// Best of three runs of `f`, less noisy than a single one.
+fn best_of_three<T>(mut f: impl FnMut() -> T) -> Duration {
+    (0..3)
+        .map(|_| {
+            let start = Instant::now();
+            black_box(f());
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+/// Prints probe lengths and timings of a standard map with `FibHasher<N>` for each initial
+/// capacity of `SWEEP_CAPACITIES`.
+fn fib_sweep_rows<const N: u8>(coords: &Vec<VoxelChunkIndex>) {
+    let build_hasher = core::hash::BuildHasherDefault::<FibHasher<N>>::default();
+    for factor in SWEEP_CAPACITIES {
+        let capacity = factor * coords.len();
+        let buckets = std_buckets(capacity, coords.len());
+        if N < 64 && 1 << N < buckets {
+            // Keys would all hash to the first buckets, in one giant collision chain.
+            println!("{N:>4}{capacity:>10}  hashes can't address all {buckets} buckets");
+            continue;
+        }
+        let report = collision_report(coords, &build_hasher, buckets);
+        let fill = || {
+            let mut map = HashMap::with_capacity_and_hasher(capacity, build_hasher.clone());
+            for &c in coords {
+                map.insert(c, 0);
+            }
+            map
+        };
+        let insert_time = best_of_three(fill);
+        let map = fill();
+        let read_time = best_of_three(|| reads(coords, &map));
+        println!(
+            "{N:>4}{capacity:>10}{:>12.2}{:>10}{:>12.1}{:>10.1}",
+            report.mean_probe,
+            report.max_probe,
+            insert_time.as_secs_f64() * 1e3,
+            read_time.as_secs_f64() * 1e3
+        );
+    }
+}
+
+/// Prints a grid of `FibHasher` output sizes and initial map capacities with the probe lengths
+/// and timings each gives on `gen_coords`, to pick `FIB_SIZE` from data. Probe lengths come
+/// from the linear probing model of `collision_report`, over as many buckets as the map has.
+pub fn report_fib_sweep(_: &mut Criterion) {
+    let coords = gen_coords();
+    println!("FibHasher<N> sweep over {} chunks:", coords.len());
+    println!(
+        "{:>4}{:>10}{:>12}{:>10}{:>12}{:>10}",
+        "N", "capacity", "mean probe", "max probe", "insert ms", "read ms"
+    );
+    fib_sweep_rows::<20>(&coords);
+    fib_sweep_rows::<21>(&coords);
+    fib_sweep_rows::<22>(&coords);
+    fib_sweep_rows::<24>(&coords);
+    fib_sweep_rows::<32>(&coords);
+    fib_sweep_rows::<64>(&coords);
+}
+
 pub fn bench_inserts(c: &mut Criterion) {
     let coords = gen_coords();
 
@@ -224,6 +650,21 @@ pub fn bench_inserts(c: &mut Criterion) {
     group.bench_function("Id", |b| {
         b.iter(|| inserts(&coords, black_box(IdentityHasherBuilder::default())))
     });
+    group.bench_function("Sorted", |b| b.iter(|| sorted_inserts(black_box(&coords))));
+    group.bench_function("BTree", |b| b.iter(|| btree_inserts(black_box(&coords))));
+    group.bench_function("DenseMap", |b| b.iter(|| dense_inserts(black_box(&coords))));
+    group.bench_function("ChunkMap", |b| {
+        b.iter(|| chunk_map_inserts(black_box(&coords)))
+    });
+    group.bench_function("ChunkMapBulk", |b| {
+        b.iter(|| chunk_map_bulk_inserts(black_box(&coords)))
+    });
+    group.bench_function("LinearMap", |b| {
+        b.iter(|| linear_map_inserts(black_box(&coords)))
+    });
+    group.bench_function("PagedMap", |b| {
+        b.iter(|| paged_map_inserts(black_box(&coords)))
+    });
 
     group.finish();
 }
@@ -235,6 +676,13 @@ pub fn bench_reads(c: &mut Criterion) {
     let hm3 = inserts(&coords, FibHasherBuilder::default());
     let hm4 = inserts(&coords, AHashBuilder::default());
     let hm5 = inserts(&coords, IdentityHasherBuilder::default());
+    let sm = sorted_inserts(&coords);
+    let cm = chunk_map_inserts(&coords);
+    let lm = linear_map_inserts(&coords);
+    let pm = paged_map_inserts(&coords);
+    let phm = perfect_map_build(&coords);
+    let bm = btree_inserts(&coords);
+    let dm = dense_inserts(&coords);
 
     let mut group = c.benchmark_group("Reads");
     group.sample_size(300);
@@ -244,6 +692,25 @@ pub fn bench_reads(c: &mut Criterion) {
     group.bench_function("Fib", |b| b.iter(|| reads(&coords, black_box(&hm3))));
     group.bench_function("AHash", |b| b.iter(|| reads(&coords, black_box(&hm4))));
     group.bench_function("Id", |b| b.iter(|| reads(&coords, black_box(&hm5))));
+    group.bench_function("Sorted", |b| {
+        b.iter(|| sorted_reads(&coords, black_box(&sm)))
+    });
+    group.bench_function("BTree", |b| b.iter(|| btree_reads(&coords, black_box(&bm))));
+    group.bench_function("DenseMap", |b| {
+        b.iter(|| dense_reads(&coords, black_box(&dm)))
+    });
+    group.bench_function("ChunkMap", |b| {
+        b.iter(|| chunk_map_reads(&coords, black_box(&cm)))
+    });
+    group.bench_function("LinearMap", |b| {
+        b.iter(|| linear_map_reads(&coords, black_box(&lm)))
+    });
+    group.bench_function("PagedMap", |b| {
+        b.iter(|| paged_map_reads(&coords, black_box(&pm)))
+    });
+    group.bench_function("PerfectMap", |b| {
+        b.iter(|| perfect_map_reads(&coords, black_box(&phm)))
+    });
 
     group.finish();
 }
@@ -273,5 +740,905 @@ pub fn bench_hashes(c: &mut Criterion) {
     group.finish();
 }
 
-criterion_group!(benches, bench_hashes, bench_inserts, bench_reads);
+/// Hashing every chunk in one call, the way a compute shader would, against one `Hasher` per
+/// chunk.
+pub fn bench_batch_hashes(c: &mut Criterion) {
+    let coords = gen_coords();
+    let mut wide = vec![0u64; coords.len()];
+    let mut narrow = vec![0u32; coords.len()];
+
+    let mut group = c.benchmark_group("BatchHashes");
+    group.throughput(Throughput::Elements(coords.len() as u64));
+
+    group.bench_function("Fib", |b| {
+        b.iter(|| {
+            let build_hasher = black_box(FibHasherBuilder::default());
+            for (hash, c) in wide.iter_mut().zip(&coords) {
+                *hash = build_hasher.hash_one(c);
+            }
+            black_box(&wide);
+        })
+    });
+    group.bench_function("FibBatch", |b| {
+        b.iter(|| fib_hash_batch::<FIB_SIZE>(black_box(&coords), black_box(&mut wide)))
+    });
+    group.bench_function("FieldMixBatch", |b| {
+        b.iter(|| field_mix_hash_batch(black_box(&coords), black_box(&mut narrow)))
+    });
+
+    group.finish();
+}
+
+pub fn bench_neighbors(c: &mut Criterion) {
+    let coords = gen_coords();
+    let cm = chunk_map_inserts(&coords);
+    let pm = paged_map_inserts(&coords);
+
+    let mut group = c.benchmark_group("Neighbors");
+    group.sample_size(100);
+
+    group.bench_function("Get", |b| {
+        b.iter(|| neighbor_reads(&coords, black_box(&cm)))
+    });
+    group.bench_function("GetMany", |b| {
+        b.iter(|| neighbor_batch_reads(&coords, black_box(&cm)))
+    });
+    group.bench_function("Paged", |b| {
+        b.iter(|| paged_neighbor_reads(&coords, black_box(&pm)))
+    });
+
+    group.finish();
+}
+
+/// Percentages of lookups of absent chunks in the `Misses` group.
+const MISS_PERCENTS: [usize; 4] = [10, 50, 90, 100];
+
+/// `percent` lookups out of every hundred in `coords` replaced by chunks 20 levels higher,
+/// above the generated ones, so they all miss.
+pub fn with_misses(coords: &[VoxelChunkIndex], percent: usize) -> Vec<VoxelChunkIndex> {
+    coords
+        .iter()
+        .enumerate()
+        .map(|(i, &c)| {
+            if i % 100 < percent {
+                let (x, y, z) = c.to_coords();
+                VoxelChunkIndex::from_coords(x, y, z + Z_UP - Z_LOW)
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// Reads where part of the lookups miss, the "is that chunk loaded?" case, which probing
+/// strategies answer at very different costs.
+pub fn bench_misses(c: &mut Criterion) {
+    let coords = gen_coords();
+    let hm1 = inserts(&coords, RandomState::new());
+    let hm2 = inserts(&coords, FibHasherBuilder::default());
+    let hm3 = inserts(&coords, AHashBuilder::default());
+    let hm4 = inserts(&coords, IdentityHasherBuilder::default());
+    let sm = sorted_inserts(&coords);
+    let cm = chunk_map_inserts(&coords);
+    let lm = linear_map_inserts(&coords);
+    let pm = paged_map_inserts(&coords);
+    let bm = btree_inserts(&coords);
+    let dm = dense_inserts(&coords);
+
+    let mut group = c.benchmark_group("Misses");
+    group.sample_size(50);
+
+    for percent in MISS_PERCENTS {
+        let lookups = with_misses(&coords, percent);
+        let rate = format!("{percent}%");
+        group.bench_with_input(BenchmarkId::new("Vanilla", &rate), &lookups, |b, l| {
+            b.iter(|| reads(l, black_box(&hm1)))
+        });
+        group.bench_with_input(BenchmarkId::new("Fib", &rate), &lookups, |b, l| {
+            b.iter(|| reads(l, black_box(&hm2)))
+        });
+        group.bench_with_input(BenchmarkId::new("AHash", &rate), &lookups, |b, l| {
+            b.iter(|| reads(l, black_box(&hm3)))
+        });
+        group.bench_with_input(BenchmarkId::new("Id", &rate), &lookups, |b, l| {
+            b.iter(|| reads(l, black_box(&hm4)))
+        });
+        group.bench_with_input(BenchmarkId::new("Sorted", &rate), &lookups, |b, l| {
+            b.iter(|| sorted_reads(l, black_box(&sm)))
+        });
+        group.bench_with_input(BenchmarkId::new("BTree", &rate), &lookups, |b, l| {
+            b.iter(|| btree_reads(l, black_box(&bm)))
+        });
+        group.bench_with_input(BenchmarkId::new("DenseMap", &rate), &lookups, |b, l| {
+            b.iter(|| dense_reads(l, black_box(&dm)))
+        });
+        group.bench_with_input(BenchmarkId::new("ChunkMap", &rate), &lookups, |b, l| {
+            b.iter(|| chunk_map_reads(l, black_box(&cm)))
+        });
+        group.bench_with_input(BenchmarkId::new("LinearMap", &rate), &lookups, |b, l| {
+            b.iter(|| linear_map_reads(l, black_box(&lm)))
+        });
+        group.bench_with_input(BenchmarkId::new("PagedMap", &rate), &lookups, |b, l| {
+            b.iter(|| paged_map_reads(l, black_box(&pm)))
+        });
+    }
+
+    group.finish();
+}
+
+/// Read percentages of the `Mixed` group, the rest of the accesses being writes.
+const MIXED_READ_PERCENTS: [u64; 3] = [95, 80, 50];
+const MIXED_OPS: usize = 500_000;
+/// Accesses between two unloads of a 16×16 column of chunks.
+const MIXED_UNLOAD_EVERY: usize = 10_000;
+
+/// Steady-state gameplay on the chunks of `coords`: random reads and writes in the given
+/// proportion, writes reloading chunks too, with a whole column unloaded now and then.
+pub fn mixed_trace(coords: &[VoxelChunkIndex], read_percent: u64) -> Trace {
+    let mut rng = 0x2545_F491_4F6C_DD1D_u64;
+    let mut next = move || {
+        rng ^= rng << 13;
+        rng ^= rng >> 7;
+        rng ^= rng << 17;
+        rng
+    };
+
+    let mut trace = Trace::new();
+    for i in 0..MIXED_OPS {
+        if i % MIXED_UNLOAD_EVERY == MIXED_UNLOAD_EVERY - 1 {
+            let x0 = XY_LOW + (next() % (XY_UP - XY_LOW - 16) as u64) as i32;
+            let y0 = XY_LOW + (next() % (XY_UP - XY_LOW - 16) as u64) as i32;
+            let column = ChunkAabb::new((x0, y0, Z_LOW), (x0 + 16, y0 + 16, Z_UP));
+            trace.extend(column.iter().map(TraceOp::Remove));
+            continue;
+        }
+        let idx = coords[(next() % coords.len() as u64) as usize];
+        trace.push(if next() % 100 < read_percent {
+            TraceOp::Get(idx)
+        } else {
+            TraceOp::Insert(idx)
+        });
+    }
+    trace
+}
+
+/// Replays `trace` on each container and hasher, all starting with every chunk of `coords`
+/// loaded, under the benchmark parameter `parameter`.
+fn bench_replays(
+    group: &mut BenchmarkGroup<WallTime>,
+    parameter: &str,
+    coords: &Vec<VoxelChunkIndex>,
+    trace: &Trace,
+) {
+    group.bench_function(BenchmarkId::new("Vanilla", parameter), |b| {
+        b.iter_batched(
+            || inserts(coords, RandomState::new()),
+            |mut map| trace.replay(&mut map),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("Fib", parameter), |b| {
+        b.iter_batched(
+            || inserts(coords, FibHasherBuilder::default()),
+            |mut map| trace.replay(&mut map),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("AHash", parameter), |b| {
+        b.iter_batched(
+            || inserts(coords, AHashBuilder::default()),
+            |mut map| trace.replay(&mut map),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("Id", parameter), |b| {
+        b.iter_batched(
+            || inserts(coords, IdentityHasherBuilder::default()),
+            |mut map| trace.replay(&mut map),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("ChunkMap", parameter), |b| {
+        b.iter_batched(
+            || chunk_map_inserts(coords),
+            |mut map| trace.replay(&mut map),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("LinearMap", parameter), |b| {
+        b.iter_batched(
+            || linear_map_inserts(coords),
+            |mut map| trace.replay(&mut map),
+            BatchSize::LargeInput,
+        )
+    });
+    group.bench_function(BenchmarkId::new("PagedMap", parameter), |b| {
+        b.iter_batched(
+            || paged_map_inserts(coords),
+            |mut map| trace.replay(&mut map),
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+/// Mixed reads and writes on maps starting with every chunk of `gen_coords` loaded, rather than
+/// separate pure-insert and pure-read phases.
+pub fn bench_mixed(c: &mut Criterion) {
+    let coords = gen_coords();
+
+    let mut group = c.benchmark_group("Mixed");
+    group.sample_size(20);
+
+    for read_percent in MIXED_READ_PERCENTS {
+        let trace = mixed_trace(&coords, read_percent);
+        bench_replays(
+            &mut group,
+            &format!("{read_percent}% reads"),
+            &coords,
+            &trace,
+        );
+    }
+
+    group.finish();
+}
+
+/// The loaded box sliding along X one row of chunks at a time, each chunk of the row left
+/// behind removed right before one of the new row is inserted. Tombstones pile up in the
+/// containers using them until they rehash.
+pub fn slide_trace() -> Trace {
+    let mut trace = Trace::new();
+    for step in 0..XY_UP - XY_LOW {
+        for y in XY_LOW..XY_UP {
+            for z in Z_LOW..Z_UP {
+                trace.push(TraceOp::Remove(VoxelChunkIndex::from_coords(
+                    XY_LOW + step,
+                    y,
+                    z,
+                )));
+                trace.push(TraceOp::Insert(VoxelChunkIndex::from_coords(
+                    XY_UP + step,
+                    y,
+                    z,
+                )));
+            }
+        }
+    }
+    trace
+}
+
+/// Every other chunk of `coords` removed, then all of them inserted back, which refills the
+/// slots tombstones hold.
+pub fn refill_trace(coords: &[VoxelChunkIndex]) -> Trace {
+    let removed = coords.iter().step_by(2).copied();
+    let mut trace: Trace = removed.clone().map(TraceOp::Remove).collect();
+    trace.extend(removed.map(TraceOp::Insert));
+    trace
+}
+
+/// Removals interleaved with inserts on maps starting with every chunk of `gen_coords` loaded,
+/// exposing the costs of tombstones and of the rehashes cleaning them up.
+pub fn bench_churn(c: &mut Criterion) {
+    let coords = gen_coords();
+
+    let mut group = c.benchmark_group("Churn");
+    group.sample_size(20);
+
+    bench_replays(&mut group, "Slide", &coords, &slide_trace());
+    bench_replays(&mut group, "Refill", &coords, &refill_trace(&coords));
+
+    group.finish();
+}
+
+pub fn sum_values<'a>(entries: impl Iterator<Item = (VoxelChunkIndex, &'a u32)>) -> u64 {
+    entries.map(|(_, &v)| v as u64).sum()
+}
+
+/// Iteration over every entry, like a per-tick update of all loaded chunks, whose cost depends
+/// on how densely each container lays its entries out in memory rather than on hashing.
+pub fn bench_iteration(c: &mut Criterion) {
+    let coords = gen_coords();
+    let hm1 = inserts(&coords, RandomState::new());
+    let hm2 = inserts(&coords, CrcHasherBuilder::default());
+    let hm3 = inserts(&coords, FibHasherBuilder::default());
+    let hm4 = inserts(&coords, AHashBuilder::default());
+    let hm5 = inserts(&coords, IdentityHasherBuilder::default());
+    let sm = sorted_inserts(&coords);
+    let cm = chunk_map_inserts(&coords);
+    let lm = linear_map_inserts(&coords);
+    let pm = paged_map_inserts(&coords);
+    let bm = btree_inserts(&coords);
+    let dm = dense_inserts(&coords);
+
+    let mut group = c.benchmark_group("Iteration");
+    group.sample_size(100);
+
+    group.bench_function("Vanilla", |b| {
+        b.iter(|| sum_values(black_box(&hm1).iter().map(|(&k, v)| (k, v))))
+    });
+    group.bench_function("Crc", |b| {
+        b.iter(|| sum_values(black_box(&hm2).iter().map(|(&k, v)| (k, v))))
+    });
+    group.bench_function("Fib", |b| {
+        b.iter(|| sum_values(black_box(&hm3).iter().map(|(&k, v)| (k, v))))
+    });
+    group.bench_function("AHash", |b| {
+        b.iter(|| sum_values(black_box(&hm4).iter().map(|(&k, v)| (k, v))))
+    });
+    group.bench_function("Id", |b| {
+        b.iter(|| sum_values(black_box(&hm5).iter().map(|(&k, v)| (k, v))))
+    });
+    group.bench_function("Sorted", |b| b.iter(|| sum_values(black_box(&sm).iter())));
+    group.bench_function("BTree", |b| {
+        b.iter(|| sum_values(black_box(&bm).iter().map(|(&k, v)| (k, v))))
+    });
+    group.bench_function("ChunkMap", |b| b.iter(|| sum_values(black_box(&cm).iter())));
+    group.bench_function("LinearMap", |b| {
+        b.iter(|| sum_values(black_box(&lm).iter()))
+    });
+    group.bench_function("PagedMap", |b| b.iter(|| sum_values(black_box(&pm).iter())));
+    group.bench_function("DenseMap", |b| b.iter(|| sum_values(black_box(&dm).iter())));
+
+    group.finish();
+}
+
+/// Per-chunk data layers kept in separate maps in the `Layers` group.
+const LAYERS: usize = 4;
+
+pub fn layered_reads<S: BuildHasher>(
+    coords: &[VoxelChunkIndex],
+    layers: &[HashMap<VoxelChunkIndex, u32, S>],
+) {
+    for c in coords {
+        for layer in layers {
+            black_box(layer.get(c));
+        }
+    }
+}
+
+/// Like [`layered_reads`], but hashing each key once for every layer.
+pub fn prehashed_reads<S: BuildHasher>(
+    coords: &[VoxelChunkIndex],
+    layers: &[PrehashedMap<u32>],
+    bh: &S,
+) {
+    for &c in coords {
+        let key = HashedIndex::new(bh, c);
+        for layer in layers {
+            black_box(layer.get(&key));
+        }
+    }
+}
+
+fn bench_layers_with<S: BuildHasher + Clone>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    coords: &[VoxelChunkIndex],
+    bh: S,
+) {
+    let layers: Vec<_> = (0..LAYERS)
+        .map(|_| {
+            let mut layer = HashMap::with_capacity_and_hasher(coords.len(), bh.clone());
+            layer.extend(coords.iter().map(|&c| (c, 0)));
+            layer
+        })
+        .collect();
+    let prehashed: Vec<PrehashedMap<u32>> = (0..LAYERS)
+        .map(|_| {
+            coords
+                .iter()
+                .map(|&c| (HashedIndex::new(&bh, c), 0))
+                .collect()
+        })
+        .collect();
+
+    group.bench_function(name, |b| {
+        b.iter(|| layered_reads(coords, black_box(&layers)))
+    });
+    group.bench_function(format!("{name}Prehashed"), |b| {
+        b.iter(|| prehashed_reads(coords, black_box(&prehashed), &bh))
+    });
+}
+
+/// Reads of every chunk in several maps, as when each data layer of a chunk lives in its own
+/// map, against the same maps probed with a hash computed once per chunk.
+pub fn bench_layers(c: &mut Criterion) {
+    let coords = gen_coords();
+
+    let mut group = c.benchmark_group("Layers");
+    group.sample_size(20);
+
+    bench_layers_with(&mut group, "Vanilla", &coords, RandomState::new());
+    bench_layers_with(&mut group, "AHash", &coords, AHashBuilder::default());
+    bench_layers_with(&mut group, "Fib", &coords, FibHasherBuilder::default());
+
+    group.finish();
+}
+
+/// Inserts and reads of the hashers and of `VoxelChunkMap` on one workload, in a group of its own.
+fn bench_workload(c: &mut Criterion, name: &str, coords: &Vec<VoxelChunkIndex>) {
+    let hm1 = inserts(coords, RandomState::new());
+    let hm2 = inserts(coords, FibHasherBuilder::default());
+    let hm3 = inserts(coords, AHashBuilder::default());
+    let hm4 = inserts(coords, IdentityHasherBuilder::default());
+    let cm = chunk_map_inserts(coords);
+
+    let mut group = c.benchmark_group(name);
+    group.sample_size(50);
+
+    group.bench_function("InsertVanilla", |b| {
+        b.iter(|| inserts(coords, black_box(RandomState::new())))
+    });
+    group.bench_function("InsertFib", |b| {
+        b.iter(|| inserts(coords, black_box(FibHasherBuilder::default())))
+    });
+    group.bench_function("InsertAHash", |b| {
+        b.iter(|| inserts(coords, black_box(AHashBuilder::default())))
+    });
+    group.bench_function("InsertId", |b| {
+        b.iter(|| inserts(coords, black_box(IdentityHasherBuilder::default())))
+    });
+    group.bench_function("InsertChunkMap", |b| {
+        b.iter(|| chunk_map_inserts(black_box(coords)))
+    });
+    group.bench_function("ReadVanilla", |b| b.iter(|| reads(coords, black_box(&hm1))));
+    group.bench_function("ReadFib", |b| b.iter(|| reads(coords, black_box(&hm2))));
+    group.bench_function("ReadAHash", |b| b.iter(|| reads(coords, black_box(&hm3))));
+    group.bench_function("ReadId", |b| b.iter(|| reads(coords, black_box(&hm4))));
+    group.bench_function("ReadChunkMap", |b| {
+        b.iter(|| chunk_map_reads(coords, black_box(&cm)))
+    });
+
+    group.finish();
+}
+
+/// The dense box of `gen_coords` flatters hashers that do well on regular keys; these are
+/// shaped like actual worlds, with about as many chunks. The chunks of a real world are added
+/// when `HASH_FUNSIES_WORLD` points to a dump of them.
+pub fn bench_workloads(c: &mut Criterion) {
+    let region = ChunkAabb::new((XY_LOW, XY_LOW, Z_LOW), (XY_UP, XY_UP, Z_UP));
+    bench_workload(c, "Caves", &caves(region, 0.5, 1));
+    bench_workload(c, "Walk", &player_walk(2000, 16, 1));
+    bench_workload(c, "Outposts", &outposts(2000, 4, 1));
+    #[cfg(feature = "noise")]
+    {
+        use hash_funsies::noise::{self, Fbm};
+
+        let shell = noise::terrain_shell(region, &Fbm::new(1), 0.0, 24.0, 2);
+        bench_workload(c, "NoiseTerrain", &shell.iter().collect::<Vec<_>>());
+        let caves = noise::caves(region, &Fbm::new(1), 0.03);
+        bench_workload(c, "NoiseCaves", &caves.iter().collect::<Vec<_>>());
+    }
+
+    if let Ok(path) = std::env::var("HASH_FUNSIES_WORLD") {
+        let file = std::fs::File::open(&path).expect("can't open HASH_FUNSIES_WORLD");
+        let world = read_world_dump(std::io::BufReader::new(file)).expect("invalid world dump");
+        bench_workload(c, "World", &world);
+    }
+}
+
+/// Trace replayed by `bench_trace`: the file `HASH_FUNSIES_TRACE` points to if set, or else
+/// the accesses of chunks streamed in around a walking player, each looking its neighbors up,
+/// and streamed out once 20_000 newer ones were loaded.
+fn load_trace() -> Trace {
+    if let Ok(path) = std::env::var("HASH_FUNSIES_TRACE") {
+        let file = std::fs::File::open(&path).expect("can't open HASH_FUNSIES_TRACE");
+        return Trace::read_from(std::io::BufReader::new(file)).expect("invalid trace");
+    }
+
+    let walk = player_walk(2000, 16, 1);
+    let mut map = TracedMap::new();
+    for (i, &idx) in walk.iter().enumerate() {
+        map.insert(idx, i as u32);
+        for n in face_neighbors(idx) {
+            map.get(n);
+        }
+        if i >= 20_000 {
+            map.remove(walk[i - 20_000]);
+        }
+    }
+    map.take_trace()
+}
+
+pub fn bench_trace(c: &mut Criterion) {
+    let trace = load_trace();
+
+    let mut group = c.benchmark_group("Trace");
+    group.sample_size(30);
+
+    group.bench_function("Vanilla", |b| {
+        b.iter(|| trace.replay(&mut HashMap::with_hasher(RandomState::new())))
+    });
+    group.bench_function("Fib", |b| {
+        b.iter(|| trace.replay(&mut HashMap::with_hasher(FibHasherBuilder::default())))
+    });
+    group.bench_function("AHash", |b| {
+        b.iter(|| trace.replay(&mut HashMap::with_hasher(AHashBuilder::default())))
+    });
+    group.bench_function("Id", |b| {
+        b.iter(|| trace.replay(&mut HashMap::with_hasher(IdentityHasherBuilder::default())))
+    });
+    group.bench_function("ChunkMap", |b| {
+        b.iter(|| trace.replay(&mut VoxelChunkMap::new()))
+    });
+    group.bench_function("LinearMap", |b| {
+        b.iter(|| trace.replay(&mut LinearChunkMap::new()))
+    });
+    group.bench_function("PagedMap", |b| {
+        b.iter(|| trace.replay(&mut PagedChunkMap::new()))
+    });
+
+    group.finish();
+}
+
+/// Thread counts of the `Contention` group.
+const CONTENTION_THREADS: [usize; 4] = [1, 2, 4, 8];
+const CONTENTION_OPS: usize = 50_000;
+/// Side, in chunks, of the box around each thread's player.
+const CONTENTION_VIEW: i32 = 16;
+/// Lookups between two steps of a player, for about one write in nine operations.
+const CONTENTION_READS_PER_STEP: usize = 1024;
+
+/// Map shared between threads, with the operations through `&self` that the concurrent maps
+/// provide.
+pub trait SharedMap: Sync {
+    fn shared_get(&self, idx: VoxelChunkIndex) -> bool;
+    fn shared_insert(&self, idx: VoxelChunkIndex, value: u32);
+    fn shared_remove(&self, idx: VoxelChunkIndex);
+}
+
+impl SharedMap for ShardedChunkMap<u32> {
+    fn shared_get(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    fn shared_insert(&self, idx: VoxelChunkIndex, value: u32) {
+        self.insert(idx, value);
+    }
+
+    fn shared_remove(&self, idx: VoxelChunkIndex) {
+        self.remove(idx);
+    }
+}
+
+impl SharedMap for EpochChunkMap<u32> {
+    fn shared_get(&self, idx: VoxelChunkIndex) -> bool {
+        self.contains_key(idx)
+    }
+
+    fn shared_insert(&self, idx: VoxelChunkIndex, value: u32) {
+        self.insert(idx, value);
+    }
+
+    fn shared_remove(&self, idx: VoxelChunkIndex) {
+        self.remove(idx);
+    }
+}
+
+/// The baseline: a single map behind a single lock.
+impl SharedMap for RwLock<VoxelChunkMap<u32>> {
+    fn shared_get(&self, idx: VoxelChunkIndex) -> bool {
+        self.read().expect("map lock poisoned").contains_key(idx)
+    }
+
+    fn shared_insert(&self, idx: VoxelChunkIndex, value: u32) {
+        self.write().expect("map lock poisoned").insert(idx, value);
+    }
+
+    fn shared_remove(&self, idx: VoxelChunkIndex) {
+        self.write().expect("map lock poisoned").remove(idx);
+    }
+}
+
+/// Accesses of the player of `thread`, who roams their own corner of the world: mostly lookups
+/// in the box around them, and every few steps a column loaded ahead and one unloaded behind.
+pub fn contention_trace(thread: usize) -> Trace {
+    let origin = thread as i32 * 4 * CONTENTION_VIEW - 1024;
+    let mut state = 0x9E37_79B9_7F4A_7C15 ^ thread as u64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut ops = Trace::new();
+    let mut x = origin;
+    while ops.len() < CONTENTION_OPS {
+        for _ in 0..CONTENTION_READS_PER_STEP {
+            let r = next();
+            let dx = (r % CONTENTION_VIEW as u64) as i32;
+            let dy = (r >> 16) as i32 & (CONTENTION_VIEW - 1);
+            let z = (r >> 32) as i32 & 3;
+            ops.push(TraceOp::Get(VoxelChunkIndex::from_coords(x + dx, dy, z)));
+        }
+        for y in 0..CONTENTION_VIEW {
+            for z in 0..4 {
+                ops.push(TraceOp::Insert(VoxelChunkIndex::from_coords(
+                    x + CONTENTION_VIEW,
+                    y,
+                    z,
+                )));
+                ops.push(TraceOp::Remove(VoxelChunkIndex::from_coords(x, y, z)));
+            }
+        }
+        x += 1;
+    }
+    ops
+}
+
+/// Loads the starting box of every trace in `map`.
+fn prefill_contention<M: SharedMap>(map: &M, threads: usize) {
+    for thread in 0..threads {
+        let origin = thread as i32 * 4 * CONTENTION_VIEW - 1024;
+        for x in origin..origin + CONTENTION_VIEW {
+            for y in 0..CONTENTION_VIEW {
+                for z in 0..4 {
+                    map.shared_insert(VoxelChunkIndex::from_coords(x, y, z), 0);
+                }
+            }
+        }
+    }
+}
+
+fn apply_shared<M: SharedMap>(map: &M, op: TraceOp) -> bool {
+    match op {
+        TraceOp::Get(idx) => return map.shared_get(idx),
+        TraceOp::Insert(idx) => map.shared_insert(idx, 0),
+        TraceOp::Remove(idx) => map.shared_remove(idx),
+    }
+    false
+}
+
+/// Replays one trace per thread on `map` at the same time, returning the time from the start
+/// signal until the last thread is done.
+fn run_contended<M: SharedMap>(map: &M, traces: &[Trace]) -> Duration {
+    let barrier = Barrier::new(traces.len() + 1);
+    thread::scope(|scope| {
+        let workers: Vec<_> = traces
+            .iter()
+            .map(|trace| {
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    barrier.wait();
+                    let mut hits = 0;
+                    for &op in trace.ops() {
+                        hits += apply_shared(map, op) as usize;
+                    }
+                    black_box(hits);
+                })
+            })
+            .collect();
+        barrier.wait();
+        let start = Instant::now();
+        for worker in workers {
+            worker.join().expect("contention thread panicked");
+        }
+        start.elapsed()
+    })
+}
+
+/// Like [`run_contended`], but times every operation, returning all the latencies sorted.
+fn contended_latencies<M: SharedMap>(map: &M, traces: &[Trace]) -> Vec<Duration> {
+    let barrier = Barrier::new(traces.len());
+    let mut latencies: Vec<Duration> = thread::scope(|scope| {
+        let workers: Vec<_> = traces
+            .iter()
+            .map(|trace| {
+                let barrier = &barrier;
+                scope.spawn(move || {
+                    barrier.wait();
+                    trace
+                        .ops()
+                        .iter()
+                        .map(|&op| {
+                            let start = Instant::now();
+                            black_box(apply_shared(map, op));
+                            start.elapsed()
+                        })
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        workers
+            .into_iter()
+            .flat_map(|worker| worker.join().expect("contention thread panicked"))
+            .collect()
+    });
+    latencies.sort_unstable();
+    latencies
+}
+
+fn bench_contended<M: SharedMap>(
+    group: &mut BenchmarkGroup<WallTime>,
+    name: &str,
+    threads: usize,
+    traces: &[Trace],
+    new_map: impl Fn() -> M,
+) {
+    group.bench_with_input(BenchmarkId::new(name, threads), traces, |b, traces| {
+        b.iter_custom(|iters| {
+            (0..iters)
+                .map(|_| {
+                    let map = new_map();
+                    prefill_contention(&map, traces.len());
+                    run_contended(&map, traces)
+                })
+                .sum()
+        })
+    });
+}
+
+pub fn bench_contention(c: &mut Criterion) {
+    let mut group = c.benchmark_group("Contention");
+    group.sample_size(20);
+
+    for threads in CONTENTION_THREADS {
+        let traces: Vec<Trace> = (0..threads).map(contention_trace).collect();
+        let ops: usize = traces.iter().map(Trace::len).sum();
+        group.throughput(Throughput::Elements(ops as u64));
+
+        bench_contended(
+            &mut group,
+            "Sharded",
+            threads,
+            &traces,
+            ShardedChunkMap::new,
+        );
+        bench_contended(&mut group, "Epoch", threads, &traces, EpochChunkMap::new);
+        bench_contended(&mut group, "RwLock", threads, &traces, || {
+            RwLock::new(VoxelChunkMap::new())
+        });
+    }
+
+    group.finish();
+}
+
+/// Nanoseconds of the latency at quantile `q` of the sorted `latencies`.
+fn percentile(latencies: &[Duration], q: f64) -> u128 {
+    latencies[((latencies.len() - 1) as f64 * q) as usize].as_nanos()
+}
+
+/// Gets timed one by one in the latency report.
+const LATENCY_GETS: usize = 1_000_000;
+
+/// Times `get` on random chunks of `coords`, one call at a time, returning the latencies
+/// sorted. The time it takes to read the clock, the median of empty timings, is taken off each.
+fn get_latencies<R>(
+    coords: &[VoxelChunkIndex],
+    mut get: impl FnMut(VoxelChunkIndex) -> R,
+) -> Vec<Duration> {
+    let mut rng = 0x2545_F491_4F6C_DD1D_u64;
+    let keys: Vec<VoxelChunkIndex> = (0..LATENCY_GETS)
+        .map(|_| {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            coords[(rng % coords.len() as u64) as usize]
+        })
+        .collect();
+
+    let mut clock: Vec<Duration> = (0..10_000)
+        .map(|_| {
+            let start = Instant::now();
+            start.elapsed()
+        })
+        .collect();
+    clock.sort_unstable();
+    let clock = clock[clock.len() / 2];
+
+    let mut latencies: Vec<Duration> = keys
+        .into_iter()
+        .map(|c| {
+            let start = Instant::now();
+            black_box(get(black_box(c)));
+            start.elapsed().saturating_sub(clock)
+        })
+        .collect();
+    latencies.sort_unstable();
+    latencies
+}
+
+/// With `HASH_FUNSIES_LATENCY` set, prints the latency percentiles of single gets of random
+/// chunks from each container of the `Reads` group. A frame hitches on the slow gets, which the
+/// mean times Criterion measures hide.
+pub fn report_get_latency(_: &mut Criterion) {
+    if std::env::var_os("HASH_FUNSIES_LATENCY").is_none() {
+        return;
+    }
+    let coords = gen_coords();
+    println!("Get latency over {LATENCY_GETS} random chunks (ns):");
+    println!(
+        "{:<12}{:>8}{:>8}{:>8}{:>10}",
+        "", "p50", "p99", "p99.9", "max"
+    );
+    fn row(name: &str, latencies: Vec<Duration>) {
+        println!(
+            "{name:<12}{:>8}{:>8}{:>8}{:>10}",
+            percentile(&latencies, 0.5),
+            percentile(&latencies, 0.99),
+            percentile(&latencies, 0.999),
+            percentile(&latencies, 1.0)
+        );
+    }
+
+    let hm = inserts(&coords, RandomState::new());
+    row("Vanilla", get_latencies(&coords, |c| hm.get(&c).copied()));
+    let hm = inserts(&coords, CrcHasherBuilder::default());
+    row("Crc", get_latencies(&coords, |c| hm.get(&c).copied()));
+    let hm = inserts(&coords, FibHasherBuilder::default());
+    row("Fib", get_latencies(&coords, |c| hm.get(&c).copied()));
+    let hm = inserts(&coords, AHashBuilder::default());
+    row("AHash", get_latencies(&coords, |c| hm.get(&c).copied()));
+    let hm = inserts(&coords, IdentityHasherBuilder::default());
+    row("Id", get_latencies(&coords, |c| hm.get(&c).copied()));
+    let sm = sorted_inserts(&coords);
+    row("Sorted", get_latencies(&coords, |c| sm.get(c).copied()));
+    let bm = btree_inserts(&coords);
+    row("BTree", get_latencies(&coords, |c| bm.get(&c).copied()));
+    let dm = dense_inserts(&coords);
+    row("DenseMap", get_latencies(&coords, |c| dm.get(c).copied()));
+    let cm = chunk_map_inserts(&coords);
+    row("ChunkMap", get_latencies(&coords, |c| cm.get(c).copied()));
+    let lm = linear_map_inserts(&coords);
+    row("LinearMap", get_latencies(&coords, |c| lm.get(c).copied()));
+    let pm = paged_map_inserts(&coords);
+    row("PagedMap", get_latencies(&coords, |c| pm.get(c).copied()));
+    let phm = perfect_map_build(&coords);
+    row(
+        "PerfectMap",
+        get_latencies(&coords, |c| phm.get(c).copied()),
+    );
+}
+
+/// Prints the latency percentiles of single operations in the `Contention` scenario, which the
+/// throughput measured by Criterion hides. Timing every operation adds a few tens of
+/// nanoseconds to each.
+pub fn report_contention_latency(_: &mut Criterion) {
+    println!("Contended operation latency (ns):");
+    println!(
+        "{:<10}{:>8}{:>10}{:>10}{:>10}{:>12}",
+        "", "threads", "p50", "p99", "p99.9", "max"
+    );
+    fn row<M: SharedMap>(name: &str, threads: usize, map: M) {
+        let traces: Vec<Trace> = (0..threads).map(contention_trace).collect();
+        prefill_contention(&map, threads);
+        let latencies = contended_latencies(&map, &traces);
+        let at = |q: f64| percentile(&latencies, q);
+        println!(
+            "{name:<10}{threads:>8}{:>10}{:>10}{:>10}{:>12}",
+            at(0.5),
+            at(0.99),
+            at(0.999),
+            at(1.0)
+        );
+    }
+
+    for threads in CONTENTION_THREADS {
+        row("Sharded", threads, ShardedChunkMap::new());
+        row("Epoch", threads, EpochChunkMap::new());
+        row("RwLock", threads, RwLock::new(VoxelChunkMap::new()));
+    }
+}
+
+criterion_group!(
+    benches,
+    bench_hashes,
+    bench_batch_hashes,
+    report_memory,
+    report_perf_counters,
+    report_allocations,
+    report_fib_sweep,
+    bench_inserts,
+    report_get_latency,
+    bench_reads,
+    bench_misses,
+    bench_mixed,
+    bench_churn,
+    bench_iteration,
+    bench_layers,
+    bench_neighbors,
+    bench_workloads,
+    bench_trace,
+    report_contention_latency,
+    bench_contention
+);
 criterion_main!(benches);
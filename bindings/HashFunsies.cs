@@ -0,0 +1,202 @@
+// C# side of the C API of hash_funsies, for Godot .NET projects and other C# engines: chunk
+// keys, their neighbors and regions, and a map of 64-bit values, through P/Invoke of the capi
+// library (see src/capi.rs for the build command). Copy the file into the project, and the
+// library next to the executable, or into the paths the engine loads native libraries from.
+//
+// Godot projects get conversions from and to `Vector3I`, as they define the GODOT symbol.
+
+using System;
+using System.Collections.Generic;
+using System.Runtime.InteropServices;
+
+namespace HashFunsies
+{
+    internal static class Native
+    {
+        private const string Lib = "hash_funsies";
+
+        [UnmanagedFunctionPointer(CallingConvention.Cdecl)]
+        internal delegate void Visitor(uint key, ulong value, IntPtr user);
+
+        [DllImport(Lib)]
+        internal static extern uint hf_chunk_encode(int x, int y, int z);
+
+        [DllImport(Lib)]
+        internal static extern void hf_chunk_decode(uint key, out int x, out int y, out int z);
+
+        [DllImport(Lib)]
+        internal static extern uint hf_chunk_neighbors(uint key, [Out] uint[] output);
+
+        [DllImport(Lib)]
+        internal static extern nuint hf_chunk_region(int[] min, int[] max, [Out] uint[] output, nuint capacity);
+
+        [DllImport(Lib)]
+        internal static extern IntPtr hf_chunk_map_with_capacity(nuint capacity);
+
+        [DllImport(Lib)]
+        internal static extern void hf_chunk_map_free(IntPtr map);
+
+        [DllImport(Lib)]
+        internal static extern nuint hf_chunk_map_len(IntPtr map);
+
+        [DllImport(Lib)]
+        internal static extern void hf_chunk_map_clear(IntPtr map);
+
+        [DllImport(Lib)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool hf_chunk_map_insert(IntPtr map, uint key, ulong value, out ulong previous);
+
+        [DllImport(Lib)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool hf_chunk_map_get(IntPtr map, uint key, out ulong value);
+
+        [DllImport(Lib)]
+        [return: MarshalAs(UnmanagedType.U1)]
+        internal static extern bool hf_chunk_map_remove(IntPtr map, uint key, out ulong value);
+
+        [DllImport(Lib)]
+        internal static extern void hf_chunk_map_for_each(IntPtr map, Visitor visit, IntPtr user);
+    }
+
+    /// <summary>Packed key of a chunk, as the crate's <c>VoxelChunkIndex</c>.</summary>
+    public readonly struct ChunkIndex : IEquatable<ChunkIndex>
+    {
+        public readonly uint Key;
+
+        public ChunkIndex(uint key)
+        {
+            Key = key;
+        }
+
+        /// <summary>Key of chunk (x, y, z), which must lie in the representable world.</summary>
+        public static ChunkIndex FromCoords(int x, int y, int z)
+        {
+            return new ChunkIndex(Native.hf_chunk_encode(x, y, z));
+        }
+
+        public (int X, int Y, int Z) Coords
+        {
+            get
+            {
+                Native.hf_chunk_decode(Key, out int x, out int y, out int z);
+                return (x, y, z);
+            }
+        }
+
+#if GODOT
+        public static ChunkIndex FromVector(Godot.Vector3I coords)
+        {
+            return FromCoords(coords.X, coords.Y, coords.Z);
+        }
+
+        public Godot.Vector3I ToVector()
+        {
+            var (x, y, z) = Coords;
+            return new Godot.Vector3I(x, y, z);
+        }
+#endif
+
+        /// <summary>The up to 26 chunks around this one, clipped to the world.</summary>
+        public ChunkIndex[] Neighbors()
+        {
+            var keys = new uint[26];
+            var neighbors = new ChunkIndex[Native.hf_chunk_neighbors(Key, keys)];
+            for (int i = 0; i < neighbors.Length; i++)
+            {
+                neighbors[i] = new ChunkIndex(keys[i]);
+            }
+            return neighbors;
+        }
+
+        /// <summary>
+        /// Chunks of the box from <paramref name="min"/> inclusive to <paramref name="max"/>
+        /// exclusive, in the crate's <c>ChunkAabb::iter</c> order.
+        /// </summary>
+        /// <exception cref="ArgumentException"><paramref name="min"/> exceeds <paramref name="max"/> on some axis.</exception>
+        public static IEnumerable<ChunkIndex> Region((int X, int Y, int Z) min, (int X, int Y, int Z) max)
+        {
+            if (min.X > max.X || min.Y > max.Y || min.Z > max.Z)
+            {
+                throw new ArgumentException("box min must not exceed max", nameof(min));
+            }
+            return RegionKeys(min, max);
+        }
+
+        private static IEnumerable<ChunkIndex> RegionKeys((int X, int Y, int Z) min, (int X, int Y, int Z) max)
+        {
+            int[] lo = { min.X, min.Y, min.Z };
+            int[] hi = { max.X, max.Y, max.Z };
+            var keys = new uint[(int)Native.hf_chunk_region(lo, hi, null, 0)];
+            Native.hf_chunk_region(lo, hi, keys, (nuint)keys.Length);
+            foreach (uint key in keys)
+            {
+                yield return new ChunkIndex(key);
+            }
+        }
+
+        public bool Equals(ChunkIndex other) => Key == other.Key;
+
+        public override bool Equals(object obj) => obj is ChunkIndex other && Equals(other);
+
+        public override int GetHashCode() => (int)Key;
+
+        public override string ToString() => Coords.ToString();
+    }
+
+    /// <summary>
+    /// The crate's <c>VoxelChunkMap</c> of 64-bit values, e.g. indices into a C# array of chunk
+    /// data. Not thread-safe: share one between threads only behind a lock.
+    /// </summary>
+    public sealed class ChunkMap : IDisposable
+    {
+        private IntPtr map;
+
+        public ChunkMap(int capacity = 0)
+        {
+            map = Native.hf_chunk_map_with_capacity((nuint)capacity);
+        }
+
+        ~ChunkMap()
+        {
+            Free();
+        }
+
+        public void Dispose()
+        {
+            Free();
+            GC.SuppressFinalize(this);
+        }
+
+        private void Free()
+        {
+            Native.hf_chunk_map_free(map);
+            map = IntPtr.Zero;
+        }
+
+        private IntPtr Handle => map != IntPtr.Zero ? map : throw new ObjectDisposedException(nameof(ChunkMap));
+
+        public int Count => (int)Native.hf_chunk_map_len(Handle);
+
+        public void Clear() => Native.hf_chunk_map_clear(Handle);
+
+        /// <summary>Inserts <paramref name="value"/>, returning whether it replaced one.</summary>
+        public bool Insert(ChunkIndex idx, ulong value) => Native.hf_chunk_map_insert(Handle, idx.Key, value, out _);
+
+        public bool TryGetValue(ChunkIndex idx, out ulong value) => Native.hf_chunk_map_get(Handle, idx.Key, out value);
+
+        public bool ContainsKey(ChunkIndex idx) => TryGetValue(idx, out _);
+
+        /// <summary>Removes the value at <paramref name="idx"/>, returning whether there was one.</summary>
+        public bool Remove(ChunkIndex idx) => Native.hf_chunk_map_remove(Handle, idx.Key, out _);
+
+        /// <summary>The entries, in no particular order, copied out of the map.</summary>
+        public List<KeyValuePair<ChunkIndex, ulong>> Entries()
+        {
+            var entries = new List<KeyValuePair<ChunkIndex, ulong>>(Count);
+            Native.Visitor visit = (key, value, _) => entries.Add(new KeyValuePair<ChunkIndex, ulong>(new ChunkIndex(key), value));
+            Native.hf_chunk_map_for_each(Handle, visit, IntPtr.Zero);
+            GC.KeepAlive(visit);
+            return entries;
+        }
+    }
+}
@@ -0,0 +1,70 @@
+// JavaScript side of the `wasm` feature of hash_funsies: packs chunk keys exactly as the crate
+// does, from a WebAssembly build of it (see src/wasm.rs for the build command).
+
+export async function load(source) {
+  const { instance } = await WebAssembly.instantiateStreaming(source, {});
+  const hf = instance.exports;
+
+  const encode = (x, y, z) => hf.hf_encode(x, y, z) >>> 0;
+  const decode = (key) => [hf.hf_decode_x(key), hf.hf_decode_y(key), hf.hf_decode_z(key)];
+
+  function* neighbors(key) {
+    const count = hf.hf_neighbor_count(key);
+    for (let i = 0; i < count; i++) {
+      yield hf.hf_neighbor(key, i) >>> 0;
+    }
+  }
+
+  // A map from chunk keys to numbers, living in the WebAssembly memory: call `free` when done.
+  class ChunkMap {
+    constructor() {
+      this.ptr = hf.hf_map_new();
+    }
+
+    free() {
+      hf.hf_map_free(this.ptr);
+      this.ptr = 0;
+    }
+
+    get size() {
+      return hf.hf_map_len(this.ptr);
+    }
+
+    set(key, value) {
+      hf.hf_map_insert(this.ptr, key, value);
+      return this;
+    }
+
+    has(key) {
+      return hf.hf_map_contains(this.ptr, key) !== 0;
+    }
+
+    get(key) {
+      return this.has(key) ? hf.hf_map_get(this.ptr, key) : undefined;
+    }
+
+    delete(key) {
+      return hf.hf_map_remove(this.ptr, key) !== 0;
+    }
+
+    // Keys in increasing packed order, as of the start of the iteration.
+    *keys() {
+      const count = hf.hf_map_snapshot(this.ptr);
+      for (let i = 0; i < count; i++) {
+        yield hf.hf_map_snapshot_key(this.ptr, i) >>> 0;
+      }
+    }
+
+    *entries() {
+      for (const key of this.keys()) {
+        yield [key, hf.hf_map_get(this.ptr, key)];
+      }
+    }
+
+    [Symbol.iterator]() {
+      return this.entries();
+    }
+  }
+
+  return { encode, decode, neighbors, ChunkMap };
+}
@@ -0,0 +1,108 @@
+-- Lua side of the C API of hash_funsies, for mods scripting world queries against the chunk
+-- keys and maps of the engine: chunk keys, their neighbors and regions, and read-only views of
+-- the maps the engine hands to scripts (see src/capi.rs for the build command). It runs on
+-- LuaJIT, whose FFI calls the library with no compiled binding.
+--
+--   local hf = require("hash_funsies")
+--   local key = hf.encode(1, 2, 3)
+--   for _, neighbor in ipairs(hf.neighbors(key)) do print(hf.decode(neighbor)) end
+
+local ffi = require("ffi")
+
+ffi.cdef([[
+typedef struct HfChunkMap HfChunkMap;
+typedef void (*HfChunkVisitor)(uint32_t key, uint64_t value, void *user);
+
+uint32_t hf_chunk_encode(int32_t x, int32_t y, int32_t z);
+void hf_chunk_decode(uint32_t key, int32_t *x, int32_t *y, int32_t *z);
+uint32_t hf_chunk_neighbors(uint32_t key, uint32_t *out);
+size_t hf_chunk_region(const int32_t *min, const int32_t *max, uint32_t *out, size_t capacity);
+size_t hf_chunk_map_len(const HfChunkMap *map);
+bool hf_chunk_map_get(const HfChunkMap *map, uint32_t key, uint64_t *value);
+void hf_chunk_map_for_each(const HfChunkMap *map, HfChunkVisitor visit, void *user);
+]])
+
+local lib = ffi.load(os.getenv("HASH_FUNSIES_LIB") or "hash_funsies")
+
+local M = {}
+
+-- Packed key of chunk (x, y, z).
+function M.encode(x, y, z)
+  return lib.hf_chunk_encode(x, y, z)
+end
+
+-- Coordinates of the chunk of `key`, as three values.
+function M.decode(key)
+  local xyz = ffi.new("int32_t[3]")
+  lib.hf_chunk_decode(key, xyz, xyz + 1, xyz + 2)
+  return xyz[0], xyz[1], xyz[2]
+end
+
+-- Keys of the up to 26 chunks around `key`, clipped to the world, as an array.
+function M.neighbors(key)
+  local out = ffi.new("uint32_t[26]")
+  local keys = {}
+  for i = 0, lib.hf_chunk_neighbors(key, out) - 1 do
+    keys[#keys + 1] = out[i]
+  end
+  return keys
+end
+
+-- Keys of the chunks in the box from `min` inclusive to `max` exclusive, each an array of
+-- three coordinates, as an array.
+function M.region(min, max)
+  for axis = 1, 3 do
+    if min[axis] > max[axis] then
+      error("box min must not exceed max", 2)
+    end
+  end
+  local lo = ffi.new("int32_t[3]", min)
+  local hi = ffi.new("int32_t[3]", max)
+  local len = tonumber(lib.hf_chunk_region(lo, hi, nil, 0))
+  local out = ffi.new("uint32_t[?]", math.max(len, 1))
+  lib.hf_chunk_region(lo, hi, out, len)
+  local keys = {}
+  for i = 0, len - 1 do
+    keys[#keys + 1] = out[i]
+  end
+  return keys
+end
+
+local Map = {}
+Map.__index = Map
+
+-- Read-only view of the `HfChunkMap *` the engine passes to a script, valid as long as the
+-- engine keeps the map alive and does not modify it.
+function M.map(pointer)
+  return setmetatable({ ptr = ffi.cast("const HfChunkMap *", pointer) }, Map)
+end
+
+function Map:len()
+  return tonumber(lib.hf_chunk_map_len(self.ptr))
+end
+
+-- The value at `key` as a `uint64_t`, or nil if there is none.
+function Map:get(key)
+  local value = ffi.new("uint64_t[1]")
+  if lib.hf_chunk_map_get(self.ptr, key, value) then
+    return value[0]
+  end
+  return nil
+end
+
+function Map:contains(key)
+  return lib.hf_chunk_map_get(self.ptr, key, nil)
+end
+
+-- Array of the keys of the map, in no particular order.
+function Map:keys()
+  local keys = {}
+  local visit = ffi.cast("HfChunkVisitor", function(key)
+    keys[#keys + 1] = key
+  end)
+  lib.hf_chunk_map_for_each(self.ptr, visit, nil)
+  visit:free()
+  return keys
+end
+
+return M
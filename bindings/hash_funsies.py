@@ -0,0 +1,122 @@
+"""Python side of the `python` feature of hash_funsies: chunk keys, their neighbors and the
+hash quality analyses, run by the crate itself through ctypes (see src/python.rs for the build
+command). Reports come back as the dicts of their JSON form."""
+
+import ctypes
+import json
+import os
+
+_lib = ctypes.CDLL(os.environ.get("HASH_FUNSIES_LIB", "libhash_funsies.so"))
+
+_lib.hf_py_encode.argtypes = [ctypes.c_int32] * 3
+_lib.hf_py_encode.restype = ctypes.c_uint32
+_lib.hf_py_decode.argtypes = [ctypes.c_uint32, ctypes.POINTER(ctypes.c_int32)]
+_lib.hf_py_decode.restype = None
+_lib.hf_py_neighbors.argtypes = [ctypes.c_uint32, ctypes.POINTER(ctypes.c_uint32)]
+_lib.hf_py_neighbors.restype = ctypes.c_uint32
+_lib.hf_py_region.argtypes = [
+    ctypes.POINTER(ctypes.c_int32),
+    ctypes.POINTER(ctypes.c_int32),
+    ctypes.POINTER(ctypes.c_uint32),
+    ctypes.c_size_t,
+]
+_lib.hf_py_region.restype = ctypes.c_size_t
+_lib.hf_py_free_string.argtypes = [ctypes.c_void_p]
+_lib.hf_py_free_string.restype = None
+for _name in ["hf_py_hashers", "hf_py_collision_report", "hf_py_avalanche", "hf_py_quality_score"]:
+    getattr(_lib, _name).restype = ctypes.c_void_p
+_lib.hf_py_bucket_counts.argtypes = [
+    ctypes.c_char_p,
+    ctypes.POINTER(ctypes.c_uint32),
+    ctypes.c_size_t,
+    ctypes.c_size_t,
+    ctypes.POINTER(ctypes.c_uint64),
+]
+_lib.hf_py_bucket_counts.restype = ctypes.c_bool
+_lib.hf_py_collision_report.argtypes = [
+    ctypes.c_char_p,
+    ctypes.POINTER(ctypes.c_uint32),
+    ctypes.c_size_t,
+    ctypes.c_size_t,
+]
+_lib.hf_py_avalanche.argtypes = [ctypes.c_char_p, ctypes.c_size_t]
+_lib.hf_py_quality_score.argtypes = [ctypes.c_char_p, ctypes.c_uint32]
+
+
+def _json(pointer):
+    try:
+        return json.loads(ctypes.string_at(pointer).decode())
+    finally:
+        _lib.hf_py_free_string(pointer)
+
+
+HASHERS = dict(_json(_lib.hf_py_hashers()))
+"""Names of the hashers, with the low bits of the hash each fills."""
+
+
+def _hasher(name):
+    if name not in HASHERS:
+        raise ValueError(f"unknown hasher {name!r}, expected one of {', '.join(HASHERS)}")
+    return name.encode()
+
+
+def _keys(keys):
+    keys = list(keys)
+    return (ctypes.c_uint32 * len(keys))(*keys), len(keys)
+
+
+def encode(x, y, z):
+    """Packed key of chunk (x, y, z)."""
+    return _lib.hf_py_encode(x, y, z)
+
+
+def decode(key):
+    """Coordinates of the chunk of `key`."""
+    out = (ctypes.c_int32 * 3)()
+    _lib.hf_py_decode(key, out)
+    return tuple(out)
+
+
+def neighbors(key):
+    """Keys of the up to 26 chunks around the chunk of `key`."""
+    out = (ctypes.c_uint32 * 26)()
+    return list(out[: _lib.hf_py_neighbors(key, out)])
+
+
+def region(min, max):
+    """Keys of the chunks in the box from `min` inclusive to `max` exclusive."""
+    if any(lo > hi for lo, hi in zip(min, max)):
+        raise ValueError("box min must not exceed max")
+    lo, hi = (ctypes.c_int32 * 3)(*min), (ctypes.c_int32 * 3)(*max)
+    volume = _lib.hf_py_region(lo, hi, None, 0)
+    out = (ctypes.c_uint32 * volume)()
+    _lib.hf_py_region(lo, hi, out, volume)
+    return list(out)
+
+
+def bucket_counts(keys, hasher, table_size):
+    """Number of `keys` hashing to each of the `table_size` buckets."""
+    if table_size <= 0:
+        raise ValueError("a table needs at least one bucket")
+    keys, len_ = _keys(keys)
+    out = (ctypes.c_uint64 * table_size)()
+    _lib.hf_py_bucket_counts(_hasher(hasher), keys, len_, table_size, out)
+    return list(out)
+
+
+def collision_report(keys, hasher, table_size):
+    keys, len_ = _keys(keys)
+    if table_size < len_:
+        raise ValueError("more keys than buckets")
+    return _json(_lib.hf_py_collision_report(_hasher(hasher), keys, len_, table_size))
+
+
+def avalanche(hasher, samples=10000):
+    if samples <= 0:
+        raise ValueError("avalanche needs at least one sample")
+    return _json(_lib.hf_py_avalanche(_hasher(hasher), samples))
+
+
+def quality_score(hasher, output_bits=0):
+    """Scores of the `output_bits` low bits of the hash, by default the bits the hasher fills."""
+    return _json(_lib.hf_py_quality_score(_hasher(hasher), output_bits))
@@ -0,0 +1,77 @@
+//! Runs arbitrary operations on a `VoxelChunkMap` and on a std `HashMap`, which serves as the
+//! model, checking that both always agree.
+
+#![no_main]
+
+use std::collections::HashMap;
+
+use hash_funsies::{ChunkAabb, VoxelChunkIndex, VoxelChunkMap};
+use libfuzzer_sys::fuzz_target;
+
+/// Keys from a small box of chunks, so that operations keep hitting the same ones.
+fn key(bytes: [u8; 2]) -> VoxelChunkIndex {
+    let bits = u16::from_le_bytes(bytes);
+    VoxelChunkIndex::from_coords(
+        (bits & 0x1F) as i32 - 16,
+        (bits >> 5 & 0x1F) as i32 - 16,
+        (bits >> 10 & 0x7) as i32 - 4,
+    )
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut map = VoxelChunkMap::new();
+    let mut model = HashMap::new();
+
+    for (i, op) in data.chunks_exact(3).enumerate() {
+        let idx = key([op[1], op[2]]);
+        let value = i as u32;
+        match op[0] % 10 {
+            0..=2 => assert_eq!(map.insert(idx, value), model.insert(idx, value)),
+            3 | 4 => assert_eq!(map.remove(idx), model.remove(&idx)),
+            5 => {
+                assert_eq!(map.get(idx), model.get(&idx));
+                assert_eq!(map.contains_key(idx), model.contains_key(&idx));
+            }
+            6 => {
+                if let Some(v) = map.get_mut(idx) {
+                    *v = value;
+                }
+                if let Some(v) = model.get_mut(&idx) {
+                    *v = value;
+                }
+            }
+            7 => {
+                *map.entry(idx).or_insert(value) += 1;
+                *model.entry(idx).or_insert(value) += 1;
+            }
+            8 => match op[1] % 4 {
+                0 => map.shrink_to_fit(),
+                1 => map.reserve(op[2] as usize),
+                2 => {
+                    map.clear();
+                    model.clear();
+                }
+                _ => {
+                    // Keep the chunks of a box around `idx`.
+                    let (x, y, z) = idx.to_coords();
+                    let region = ChunkAabb::new((x - 8, y - 8, z - 2), (x + 8, y + 8, z + 2));
+                    map.retain_region(region);
+                    model.retain(|&k, _| region.contains_index(k));
+                }
+            },
+            _ => {
+                let other = key([op[2], op[1]]);
+                let [a, b] = map.get_many([idx, other]);
+                assert_eq!(a, model.get(&idx));
+                assert_eq!(b, model.get(&other));
+            }
+        }
+        assert_eq!(map.len(), model.len());
+    }
+
+    let mut entries: Vec<(VoxelChunkIndex, u32)> = map.iter().map(|(k, &v)| (k, v)).collect();
+    let mut expected: Vec<(VoxelChunkIndex, u32)> = model.into_iter().collect();
+    entries.sort_unstable();
+    expected.sort_unstable();
+    assert_eq!(entries, expected);
+});
@@ -0,0 +1,36 @@
+//! The hashers accept writes of any length, any number of times, without panicking, and split
+//! writes of whole words hash like the words written one by one.
+
+#![no_main]
+
+use std::hash::Hasher;
+
+use hash_funsies::hashers::{FibHasher, IdentityHasher};
+use libfuzzer_sys::fuzz_target;
+
+fn check<H: Hasher + Default>(data: &[u8]) {
+    // The first byte splits the input into writes of arbitrary lengths.
+    let Some((&split, data)) = data.split_first() else {
+        return;
+    };
+    let mut hasher = H::default();
+    for chunk in data.chunks(split as usize + 1) {
+        hasher.write(chunk);
+    }
+    let _ = hasher.finish();
+
+    let words = &data[..data.len() / 8 * 8];
+    let mut whole = H::default();
+    let mut split = H::default();
+    for word in words.chunks_exact(8) {
+        whole.write(word);
+        split.write_u64(u64::from_be_bytes(word.try_into().unwrap()));
+    }
+    assert_eq!(whole.finish(), split.finish());
+}
+
+fuzz_target!(|data: &[u8]| {
+    check::<IdentityHasher>(data);
+    check::<FibHasher<20>>(data);
+    check::<FibHasher<64>>(data);
+});
@@ -0,0 +1,24 @@
+//! Every packed index decodes to coordinates that encode back to it, and every coordinate in
+//! range survives the opposite trip.
+
+#![no_main]
+
+use hash_funsies::{
+    VoxelChunkIndex, X_BIAS, X_MASK, X_SHIFT, Y_BIAS, Y_MASK, Y_SHIFT, Z_BIAS, Z_MASK, Z_SHIFT,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    for word in data.chunks_exact(4) {
+        let packed = u32::from_le_bytes(word.try_into().unwrap());
+        let idx = VoxelChunkIndex(packed);
+        let (x, y, z) = idx.to_coords();
+        assert_eq!(VoxelChunkIndex::from_coords(x, y, z), idx);
+
+        // Reinterpret the same bytes as coordinates, folded into the representable range.
+        let x = (packed >> X_SHIFT & X_MASK) as i32 - X_BIAS;
+        let y = (packed >> Y_SHIFT & Y_MASK) as i32 - Y_BIAS;
+        let z = (packed >> Z_SHIFT & Z_MASK) as i32 - Z_BIAS;
+        assert_eq!(VoxelChunkIndex::from_coords(x, y, z).to_coords(), (x, y, z));
+    }
+});
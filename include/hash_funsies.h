@@ -0,0 +1,106 @@
+#ifndef HASH_FUNSIES_H
+#define HASH_FUNSIES_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// A [`VoxelChunkMap`] of `uint64_t`, opaque to C.
+typedef struct HfChunkMap HfChunkMap;
+
+// Called by [`hf_chunk_map_for_each`] with each key and value, and the caller's `user`
+// pointer.
+typedef void (*HfChunkVisitor)(uint32_t key, uint64_t value, void *user);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+uint32_t hf_chunk_encode(int32_t x, int32_t y, int32_t z);
+
+void hf_chunk_decode(uint32_t key, int32_t *x, int32_t *y, int32_t *z);
+
+// Writes to `keys[i]` the key of the chunk whose coordinates are `coords[3 * i..3 * i + 3]`,
+// for each `i` below `len`.
+void hf_chunk_encode_batch(const int32_t *coords, size_t len, uint32_t *keys);
+
+// Inverse of [`hf_chunk_encode_batch`].
+void hf_chunk_decode_batch(const uint32_t *keys, size_t len, int32_t *coords);
+
+// Writes the [`VoxelChunkIndex::neighbors`] of `key` to `out`, returning how many.
+uint32_t hf_chunk_neighbors(uint32_t key, uint32_t *out);
+
+// Number of chunks in the box from `min` inclusive to `max` exclusive, clipped to the
+// representable world, writing their keys to `out`, in [`ChunkAabb::iter`] order, if they fit
+// in `capacity`. An inverted box, with `min` past `max` on some axis, holds no chunks.
+size_t hf_chunk_region(const int32_t *min, const int32_t *max, uint32_t *out, size_t capacity);
+
+// Chunks entering and leaving the view of a viewer moving from the ball of `before_radius`
+// chunks around `before_center`, null if it had no view yet, to the ball of `after_radius`
+// around `after_center`: see [`view_delta`]. Writes their numbers to `enter_len` and
+// `leave_len`, and, if they fit in `enter_capacity` and `leave_capacity`, the keys to `enter`
+// and `leave`, returning whether they did. Callers short of room call again with more.
+// A null `after_center` or the negative radius of a center returns false with both lengths 0.
+bool hf_view_delta(const int32_t *before_center,
+                   int32_t before_radius,
+                   const int32_t *after_center,
+                   int32_t after_radius,
+                   uint32_t *enter,
+                   size_t enter_capacity,
+                   size_t *enter_len,
+                   uint32_t *leave,
+                   size_t leave_capacity,
+                   size_t *leave_len);
+
+// A new empty map, to free with [`hf_chunk_map_free`].
+HfChunkMap *hf_chunk_map_new(void);
+
+// A new empty map with room for `capacity` entries.
+HfChunkMap *hf_chunk_map_with_capacity(size_t capacity);
+
+// Frees `map`; null is ignored.
+void hf_chunk_map_free(HfChunkMap *map);
+
+size_t hf_chunk_map_len(const HfChunkMap *map);
+
+void hf_chunk_map_clear(HfChunkMap *map);
+
+// Inserts `value` at `key`, returning whether it replaced a value, then written to
+// `previous` unless it is null.
+bool hf_chunk_map_insert(HfChunkMap *map, uint32_t key, uint64_t value, uint64_t *previous);
+
+// Whether `map` has a value at `key`, then written to `value` unless it is null.
+bool hf_chunk_map_get(const HfChunkMap *map, uint32_t key, uint64_t *value);
+
+// Removes the value at `key`, returning whether there was one, then written to `value` unless
+// it is null.
+bool hf_chunk_map_remove(HfChunkMap *map, uint32_t key, uint64_t *value);
+
+// Inserts `values[i]` at `keys[i]` for each `i` below `len`, later pairs replacing earlier
+// ones with the same key.
+void hf_chunk_map_insert_batch(HfChunkMap *map,
+                               const uint32_t *keys,
+                               const uint64_t *values,
+                               size_t len);
+
+// Looks up `len` keys, prefetching several at a time: writes the value of `keys[i]` to
+// `values[i]` and whether there is one to `found[i]`, returning how many there are. Values of
+// keys not found are left alone.
+size_t hf_chunk_map_get_batch(const HfChunkMap *map,
+                              const uint32_t *keys,
+                              size_t len,
+                              uint64_t *values,
+                              bool *found);
+
+// Removes the values at `len` keys, returning how many there were.
+size_t hf_chunk_map_remove_batch(HfChunkMap *map, const uint32_t *keys, size_t len);
+
+// Calls `visit` with each key and value of `map`, in no particular order, and `user`. `visit`
+// must not modify `map`.
+void hf_chunk_map_for_each(const HfChunkMap *map, HfChunkVisitor visit, void *user);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* HASH_FUNSIES_H */
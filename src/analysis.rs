@@ -0,0 +1,640 @@
+//! Hash quality measurements over chunk key workloads, producing numbers to compare hashers by
+//! rather than only benchmark timings.
+
+use std::hash::BuildHasher;
+use std::ops::Range;
+
+use crate::filter::mix64;
+use crate::map::SlotInfo;
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+/// Largest distance from 1/2 of an output bit flip probability for [`avalanche`] to pass.
+pub const AVALANCHE_THRESHOLD: f64 = 0.05;
+
+type Coords = (i32, i32, i32);
+
+/// State of the table after some of the keys were inserted.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LoadPoint {
+    pub load_factor: f64,
+    /// Mean probe length over the keys inserted so far.
+    pub mean_probe: f64,
+    pub max_probe: usize,
+}
+
+/// How a hasher spreads a set of keys over a table, see [`collision_report`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct CollisionReport {
+    pub table_size: usize,
+    pub keys: usize,
+    /// `occupancy[k]` is the number of buckets exactly `k` keys hash to.
+    pub occupancy: Vec<usize>,
+    /// Slots a successful lookup inspects under linear probing, 1 for a key in its own bucket.
+    pub max_probe: usize,
+    pub mean_probe: f64,
+    /// Probe lengths as the table fills up, at every tenth of the keys.
+    pub load_curve: Vec<LoadPoint>,
+}
+
+impl CollisionReport {
+    /// Buckets no key hashes to.
+    pub fn empty_buckets(&self) -> usize {
+        self.occupancy.first().copied().unwrap_or(0)
+    }
+
+    /// Keys hashing to a bucket some earlier key already hashed to.
+    pub fn collisions(&self) -> usize {
+        self.keys - (self.table_size - self.empty_buckets())
+    }
+}
+
+/// Bucket of `idx` in a table of `table_size` buckets: the hash modulo the size, which for a
+/// power of two keeps the low bits, as most hash tables do.
+pub fn bucket_of<S: BuildHasher>(
+    build_hasher: &S,
+    idx: VoxelChunkIndex,
+    table_size: usize,
+) -> usize {
+    (build_hasher.hash_one(idx) % table_size as u64) as usize
+}
+
+/// Number of `keys` hashing to each of the `table_size` buckets.
+pub fn bucket_counts<S: BuildHasher>(
+    keys: &[VoxelChunkIndex],
+    build_hasher: &S,
+    table_size: usize,
+) -> Vec<usize> {
+    assert!(table_size > 0, "a table needs at least one bucket");
+    let mut counts = vec![0; table_size];
+    for &idx in keys {
+        counts[bucket_of(build_hasher, idx, table_size)] += 1;
+    }
+    counts
+}
+
+/// Hashes `keys` into a table of `table_size` buckets, and inserts them in order into a linear
+/// probing table of that size to measure probe lengths.
+pub fn collision_report<S: BuildHasher>(
+    keys: &[VoxelChunkIndex],
+    build_hasher: &S,
+    table_size: usize,
+) -> CollisionReport {
+    assert!(table_size >= keys.len(), "more keys than buckets");
+
+    let mut per_bucket = vec![0usize; table_size];
+    let mut taken = vec![false; table_size];
+    let mut total_probe = 0;
+    let mut max_probe = 0;
+    let mut load_curve = Vec::with_capacity(10);
+    let checkpoint = keys.len().div_ceil(10).max(1);
+
+    for (i, &idx) in keys.iter().enumerate() {
+        let home = bucket_of(build_hasher, idx, table_size);
+        per_bucket[home] += 1;
+
+        let mut slot = home;
+        let mut probe = 1;
+        while taken[slot] {
+            slot = if slot + 1 == table_size { 0 } else { slot + 1 };
+            probe += 1;
+        }
+        taken[slot] = true;
+        total_probe += probe;
+        max_probe = max_probe.max(probe);
+
+        let inserted = i + 1;
+        if inserted % checkpoint == 0 || inserted == keys.len() {
+            load_curve.push(LoadPoint {
+                load_factor: inserted as f64 / table_size as f64,
+                mean_probe: total_probe as f64 / inserted as f64,
+                max_probe,
+            });
+        }
+    }
+
+    let mut occupancy = vec![0; per_bucket.iter().copied().max().unwrap_or(0) + 1];
+    for count in per_bucket {
+        occupancy[count] += 1;
+    }
+
+    CollisionReport {
+        table_size,
+        keys: keys.len(),
+        occupancy,
+        max_probe,
+        mean_probe: if keys.is_empty() {
+            0.0
+        } else {
+            total_probe as f64 / keys.len() as f64
+        },
+        load_curve,
+    }
+}
+
+/// Where `keys` would sit if inserted in order into a linear probing table of `table_size`
+/// buckets, the one [`collision_report`] simulates, to inspect containers that don't expose
+/// their layout, like std's `HashMap`.
+pub fn simulated_slots<S: BuildHasher>(
+    keys: &[VoxelChunkIndex],
+    build_hasher: &S,
+    table_size: usize,
+) -> Vec<SlotInfo> {
+    assert!(table_size >= keys.len(), "more keys than buckets");
+    let mut taken = vec![false; table_size];
+    keys.iter()
+        .map(|&idx| {
+            let hash = build_hasher.hash_one(idx);
+            let home = (hash % table_size as u64) as usize;
+            let (mut bucket, mut probe_length) = (home, 1);
+            while taken[bucket] {
+                bucket = if bucket + 1 == table_size {
+                    0
+                } else {
+                    bucket + 1
+                };
+                probe_length += 1;
+            }
+            taken[bucket] = true;
+            SlotInfo {
+                idx,
+                hash,
+                home,
+                bucket,
+                probe_length,
+            }
+        })
+        .collect()
+}
+
+/// `samples` keys spread over every bit of the packed index, the same ones on every call so
+/// that hashers are compared on equal terms.
+pub(crate) fn sample_keys(samples: usize) -> impl Iterator<Item = VoxelChunkIndex> {
+    (0..samples as u64).map(|i| VoxelChunkIndex(mix64(i.wrapping_add(1)) as u32))
+}
+
+/// Output bit flip probabilities of a hasher, see [`avalanche`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct AvalancheReport {
+    pub samples: usize,
+    /// `matrix[i][o]` is the fraction of samples in which flipping input bit `i` flipped output
+    /// bit `o`.
+    #[cfg_attr(feature = "serde", serde(with = "crate::report::matrix"))]
+    pub matrix: Vec<[f64; 64]>,
+    /// Whether every probability is within [`AVALANCHE_THRESHOLD`] of 1/2.
+    pub passed: bool,
+}
+
+impl AvalancheReport {
+    /// Largest distance from 1/2 of the probabilities over the `output_bits` low output bits,
+    /// which for hashers returning fewer bits, like `FibHasher<N>`, are the only ones used.
+    pub fn max_bias(&self, output_bits: u32) -> f64 {
+        self.matrix
+            .iter()
+            .flat_map(|row| &row[..output_bits.min(64) as usize])
+            .map(|p| (p - 0.5).abs())
+            .fold(0.0, f64::max)
+    }
+
+    /// Whether every probability over the `output_bits` low output bits is within `threshold`
+    /// of 1/2.
+    pub fn passes(&self, output_bits: u32, threshold: f64) -> bool {
+        self.max_bias(output_bits) <= threshold
+    }
+}
+
+/// Measures, over `samples` keys, how often flipping each of the 32 bits of a packed index flips
+/// each of the 64 output bits of the hasher. A well-mixing hasher flips every output bit half of
+/// the time.
+pub fn avalanche<S: BuildHasher>(build_hasher: &S, samples: usize) -> AvalancheReport {
+    assert!(samples > 0, "avalanche needs at least one sample");
+
+    let mut flips = vec![[0usize; 64]; 32];
+    for idx in sample_keys(samples) {
+        let hash = build_hasher.hash_one(idx);
+        for (bit, row) in flips.iter_mut().enumerate() {
+            let diff = hash ^ build_hasher.hash_one(VoxelChunkIndex(idx.0 ^ 1 << bit));
+            for (out, count) in row.iter_mut().enumerate() {
+                *count += (diff >> out & 1) as usize;
+            }
+        }
+    }
+
+    let matrix: Vec<[f64; 64]> = flips
+        .iter()
+        .map(|row| row.map(|count| count as f64 / samples as f64))
+        .collect();
+    let mut report = AvalancheReport {
+        samples,
+        matrix,
+        passed: false,
+    };
+    report.passed = report.passes(64, AVALANCHE_THRESHOLD);
+    report
+}
+
+/// Outcome of a statistical test of the hypothesis that keys hash uniformly over the buckets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct UniformityTest {
+    pub statistic: f64,
+    /// Probability of a statistic at least this large if the buckets were drawn uniformly at
+    /// random: a tiny value, say below 0.01, means the hasher is visibly not uniform. A value
+    /// close to 1 on structured keys means the hasher spreads them more evenly than chance.
+    pub p_value: f64,
+}
+
+/// Pearson's chi-squared test of the bucket counts of `keys` against the uniform distribution,
+/// with `table_size - 1` degrees of freedom. It needs a few keys per bucket on average to be
+/// meaningful.
+pub fn chi_squared_test<S: BuildHasher>(
+    keys: &[VoxelChunkIndex],
+    build_hasher: &S,
+    table_size: usize,
+) -> UniformityTest {
+    assert!(
+        table_size > 1,
+        "chi-squared test needs at least two buckets"
+    );
+    let expected = keys.len() as f64 / table_size as f64;
+    let statistic = if keys.is_empty() {
+        0.0
+    } else {
+        bucket_counts(keys, build_hasher, table_size)
+            .into_iter()
+            .map(|count| (count as f64 - expected).powi(2) / expected)
+            .sum()
+    };
+    let dof = (table_size - 1) as f64;
+    UniformityTest {
+        statistic,
+        p_value: upper_gamma_regularized(dof / 2.0, statistic / 2.0),
+    }
+}
+
+/// Kolmogorov-Smirnov test of the distribution of the buckets of `keys` against the uniform
+/// distribution. The statistic is the largest gap between the fraction of keys hashing to the
+/// first buckets and the fraction of the buckets they are. Buckets being discrete, the p-value
+/// is conservative.
+pub fn ks_test<S: BuildHasher>(
+    keys: &[VoxelChunkIndex],
+    build_hasher: &S,
+    table_size: usize,
+) -> UniformityTest {
+    assert!(
+        !keys.is_empty(),
+        "Kolmogorov-Smirnov test needs at least one key"
+    );
+    let n = keys.len() as f64;
+    let mut below = 0;
+    let mut statistic = 0.0f64;
+    for (bucket, count) in bucket_counts(keys, build_hasher, table_size)
+        .into_iter()
+        .enumerate()
+    {
+        let before = bucket as f64 / table_size as f64;
+        statistic = statistic.max((below as f64 / n - before).abs());
+        below += count;
+        let after = (bucket + 1) as f64 / table_size as f64;
+        statistic = statistic.max((below as f64 / n - after).abs());
+    }
+    // Stephens' correction makes the asymptotic distribution usable for small samples.
+    let sqrt_n = n.sqrt();
+    let lambda = (sqrt_n + 0.12 + 0.11 / sqrt_n) * statistic;
+    UniformityTest {
+        statistic,
+        p_value: kolmogorov_survival(lambda),
+    }
+}
+
+/// `P(K > lambda)` for the Kolmogorov distribution.
+fn kolmogorov_survival(lambda: f64) -> f64 {
+    if lambda < 0.2 {
+        return 1.0;
+    }
+    let mut sum = 0.0;
+    for k in 1..=100 {
+        let term = (-2.0 * (k * k) as f64 * lambda * lambda).exp();
+        sum += if k % 2 == 1 { term } else { -term };
+        if term < 1e-16 {
+            break;
+        }
+    }
+    (2.0 * sum).clamp(0.0, 1.0)
+}
+
+/// Lanczos approximation of `ln(Γ(x))` for `x > 0`.
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000_000_000_190_015;
+    for (i, coeff) in COEFFS.iter().enumerate() {
+        series += coeff / (x + 1.0 + i as f64);
+    }
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+/// Regularized upper incomplete gamma function `Q(a, x) = Γ(a, x) / Γ(a)`, by its series below
+/// `a + 1` and its continued fraction above.
+fn upper_gamma_regularized(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-14;
+    const MAX_ITERATIONS: usize = 1_000_000;
+    if x <= 0.0 {
+        return 1.0;
+    }
+    let log_prefix = a * x.ln() - x - ln_gamma(a);
+
+    if x < a + 1.0 {
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        for n in 1..MAX_ITERATIONS {
+            term *= x / (a + n as f64);
+            sum += term;
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+        return (1.0 - sum * log_prefix.exp()).clamp(0.0, 1.0);
+    }
+
+    // Modified Lentz evaluation of the continued fraction.
+    const TINY: f64 = 1e-300;
+    let mut b = x + 1.0 - a;
+    let mut c = 1.0 / TINY;
+    let mut d = 1.0 / b;
+    let mut fraction = d;
+    for i in 1..MAX_ITERATIONS {
+        let an = -(i as f64) * (i as f64 - a);
+        b += 2.0;
+        d = an * d + b;
+        if d.abs() < TINY {
+            d = TINY;
+        }
+        c = b + an / c;
+        if c.abs() < TINY {
+            c = TINY;
+        }
+        d = 1.0 / d;
+        let delta = d * c;
+        fraction *= delta;
+        if (delta - 1.0).abs() < EPSILON {
+            break;
+        }
+    }
+    (fraction * log_prefix.exp()).clamp(0.0, 1.0)
+}
+
+/// Dependence between output bit flips of a hasher, see [`bit_independence`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BicReport {
+    pub samples: usize,
+    /// `matrix[j][k]` is the largest absolute correlation, over the 32 input bits, between the
+    /// flips of output bits `j` and `k` when that input bit flips. The diagonal is 0.
+    #[cfg_attr(feature = "serde", serde(with = "crate::report::matrix"))]
+    pub matrix: Vec<[f64; 64]>,
+}
+
+impl BicReport {
+    /// Largest correlation between two of the `output_bits` low output bits.
+    pub fn max_correlation(&self, output_bits: u32) -> f64 {
+        let bits = output_bits.min(64) as usize;
+        self.matrix[..bits]
+            .iter()
+            .flat_map(|row| &row[..bits])
+            .copied()
+            .fold(0.0, f64::max)
+    }
+}
+
+/// Bit independence criterion: measures, over `samples` keys, how correlated the flips of every
+/// pair of output bits are when a single input bit flips. A good hasher has them all close to 0,
+/// so that one output bit tells nothing about another.
+///
+/// A bit that always or never flips for some input bit is taken as fully correlated with every
+/// other one: its flips are then decided by the input alone. Hashers returning fewer bits, like
+/// `FibHasher<N>`, should be judged on their low bits with [`BicReport::max_correlation`].
+pub fn bit_independence<S: BuildHasher>(build_hasher: &S, samples: usize) -> BicReport {
+    assert!(samples > 0, "bit independence needs at least one sample");
+
+    // flips[i][j] counts flips of output bit j, pairs[i][j][k] joint flips of j and k.
+    let mut flips = vec![[0u32; 64]; 32];
+    let mut pairs = vec![[[0u32; 64]; 64]; 32];
+    for idx in sample_keys(samples) {
+        let hash = build_hasher.hash_one(idx);
+        for bit in 0..32 {
+            let diff = hash ^ build_hasher.hash_one(VoxelChunkIndex(idx.0 ^ 1 << bit));
+            let mut rest = diff;
+            while rest != 0 {
+                let j = rest.trailing_zeros() as usize;
+                rest &= rest - 1;
+                flips[bit][j] += 1;
+                let mut others = rest;
+                while others != 0 {
+                    let k = others.trailing_zeros() as usize;
+                    others &= others - 1;
+                    pairs[bit][j][k] += 1;
+                }
+            }
+        }
+    }
+
+    let n = samples as f64;
+    let mut matrix = vec![[0.0f64; 64]; 64];
+    for (flips, pairs) in flips.iter().zip(&pairs) {
+        for j in 0..64 {
+            for k in j + 1..64 {
+                let (pj, pk) = (flips[j] as f64 / n, flips[k] as f64 / n);
+                let variance = pj * (1.0 - pj) * pk * (1.0 - pk);
+                let correlation = if variance > 0.0 {
+                    ((pairs[j][k] as f64 / n - pj * pk) / variance.sqrt()).abs()
+                } else {
+                    1.0
+                };
+                matrix[j][k] = matrix[j][k].max(correlation);
+                matrix[k][j] = matrix[j][k];
+            }
+        }
+    }
+    BicReport { samples, matrix }
+}
+
+/// Pairs of chunks of `region` landing in the same bucket of a table indexing buckets with
+/// `hash & mask`, at most `limit` of them, grouped by bucket in increasing order. Listing them
+/// shows which spatial patterns alias each other, e.g. chunks a fixed offset apart.
+pub fn colliding_pairs<S: BuildHasher>(
+    region: ChunkAabb,
+    build_hasher: &S,
+    mask: u64,
+    limit: usize,
+) -> Vec<(Coords, Coords)> {
+    let mut buckets: Vec<(u64, VoxelChunkIndex)> = region
+        .iter()
+        .map(|idx| (build_hasher.hash_one(idx) & mask, idx))
+        .collect();
+    // Stable, so chunks of a bucket stay in region order.
+    buckets.sort_by_key(|&(bucket, _)| bucket);
+
+    let mut pairs = Vec::new();
+    for group in buckets.chunk_by(|a, b| a.0 == b.0) {
+        for (i, &(_, first)) in group.iter().enumerate() {
+            for &(_, second) in &group[i + 1..] {
+                if pairs.len() == limit {
+                    return pairs;
+                }
+                pairs.push((first.to_coords(), second.to_coords()));
+            }
+        }
+    }
+    pairs
+}
+
+/// Spread of a statistic over the seeds of a [`seed_sweep`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Spread {
+    pub min: f64,
+    pub median: f64,
+    pub worst: f64,
+    /// Seed giving the worst value.
+    pub worst_seed: u64,
+}
+
+impl Spread {
+    fn of(mut values: Vec<(f64, u64)>) -> Spread {
+        values.sort_by(|a, b| a.0.total_cmp(&b.0));
+        let (worst, worst_seed) = values[values.len() - 1];
+        Spread {
+            min: values[0].0,
+            median: values[values.len() / 2].0,
+            worst,
+            worst_seed,
+        }
+    }
+}
+
+/// Probe length statistics of a seedable hasher over many seeds, see [`seed_sweep`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SeedSweep {
+    pub seeds: usize,
+    pub mean_probe: Spread,
+    pub max_probe: Spread,
+}
+
+/// Runs [`collision_report`] with the hasher `with_seed` builds for each of `seeds`, e.g.
+/// `|seed| ahash::RandomState::with_seed(seed as usize)`, to show how much an unlucky fixed seed
+/// can cost compared to a typical one.
+pub fn seed_sweep<S: BuildHasher>(
+    keys: &[VoxelChunkIndex],
+    table_size: usize,
+    seeds: Range<u64>,
+    with_seed: impl Fn(u64) -> S,
+) -> SeedSweep {
+    assert!(!seeds.is_empty(), "seed sweep needs at least one seed");
+
+    let mut mean_probe = Vec::with_capacity(seeds.end.saturating_sub(seeds.start) as usize);
+    let mut max_probe = Vec::with_capacity(mean_probe.capacity());
+    for seed in seeds {
+        let report = collision_report(keys, &with_seed(seed), table_size);
+        mean_probe.push((report.mean_probe, seed));
+        max_probe.push((report.max_probe as f64, seed));
+    }
+    SeedSweep {
+        seeds: mean_probe.len(),
+        mean_probe: Spread::of(mean_probe),
+        max_probe: Spread::of(max_probe),
+    }
+}
+
+/// Samples of the avalanche and bit independence tests of [`quality_score`].
+const QUALITY_SAMPLES: usize = 2000;
+
+/// Results of the [`quality_score`] battery, each in `[0, 1]` with 1 being ideal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct QualityScore {
+    /// One minus twice the mean distance from 1/2 of the output bit flip probabilities.
+    pub avalanche: f64,
+    /// One minus the mean correlation between pairs of output bits.
+    pub independence: f64,
+    /// Probe efficiency on the indices with at most three bits set.
+    pub sparse: f64,
+    /// Probe efficiency on the first 65536 packed indices, i.e. consecutive rows along X.
+    pub sequential: f64,
+    /// Probe efficiency on a 64×64×16 box of chunks.
+    pub voxel: f64,
+    /// Mean of the other scores.
+    pub total: f64,
+}
+
+/// Expected mean probe length of uniform hashing over the mean probe length of `keys`, in a
+/// linear probing table twice their number, capped to 1 for hashers beating chance.
+fn probe_score<S: BuildHasher>(keys: &[VoxelChunkIndex], build_hasher: &S) -> f64 {
+    let table_size = (keys.len() * 2).next_power_of_two();
+    let load = keys.len() as f64 / table_size as f64;
+    let uniform = 0.5 * (1.0 + 1.0 / (1.0 - load));
+    (uniform / collision_report(keys, build_hasher, table_size).mean_probe).min(1.0)
+}
+
+/// Runs a small battery of tests, in the spirit of SMHasher, on the `output_bits` low output
+/// bits of a hasher and sums it up in a score to rank hashers by. The avalanche and bit
+/// independence tests measure mixing; the others how the buckets of a table fill up with keys
+/// that are structured in the ways actual chunk keys are.
+pub fn quality_score<S: BuildHasher>(build_hasher: &S, output_bits: u32) -> QualityScore {
+    let bits = output_bits.clamp(1, 64) as usize;
+
+    let flips = avalanche(build_hasher, QUALITY_SAMPLES).matrix;
+    let bias: f64 = flips
+        .iter()
+        .flat_map(|row| &row[..bits])
+        .map(|p| (p - 0.5).abs())
+        .sum::<f64>()
+        / (flips.len() * bits) as f64;
+
+    let independence = if bits < 2 {
+        1.0
+    } else {
+        let correlations = bit_independence(build_hasher, QUALITY_SAMPLES).matrix;
+        let sum: f64 = correlations[..bits]
+            .iter()
+            .map(|row| row[..bits].iter().sum::<f64>())
+            .sum();
+        1.0 - sum / (bits * (bits - 1)) as f64
+    };
+
+    let mut sparse = vec![VoxelChunkIndex(0)];
+    for a in 0..32 {
+        sparse.push(VoxelChunkIndex(1 << a));
+        for b in 0..a {
+            sparse.push(VoxelChunkIndex(1 << a | 1 << b));
+            for c in 0..b {
+                sparse.push(VoxelChunkIndex(1 << a | 1 << b | 1 << c));
+            }
+        }
+    }
+    let sequential: Vec<VoxelChunkIndex> = (0..1 << 16).map(VoxelChunkIndex).collect();
+    let voxel: Vec<VoxelChunkIndex> = ChunkAabb::new((-32, -32, -8), (32, 32, 8)).iter().collect();
+
+    let mut score = QualityScore {
+        avalanche: 1.0 - 2.0 * bias,
+        independence,
+        sparse: probe_score(&sparse, build_hasher),
+        sequential: probe_score(&sequential, build_hasher),
+        voxel: probe_score(&voxel, build_hasher),
+        total: 0.0,
+    };
+    score.total =
+        (score.avalanche + score.independence + score.sparse + score.sequential + score.voxel)
+            / 5.0;
+    score
+}
@@ -0,0 +1,181 @@
+//! [`ChunkArena`], slot storage handing out generational [`ChunkHandle`]s.
+
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+/// Cheap, copyable reference to a chunk stored in a [`ChunkArena`]. A handle goes stale once its
+/// chunk is removed, even if the slot is later reused for another chunk.
+#[derive(Debug, Hash, PartialEq, Eq, Copy, Clone)]
+pub struct ChunkHandle {
+    slot: u32,
+    generation: u32,
+}
+
+struct Slot<V> {
+    generation: u32,
+    entry: Option<(VoxelChunkIndex, V)>,
+}
+
+/// Chunk storage addressed both by [`ChunkHandle`] and by [`VoxelChunkIndex`]. Handles resolve with
+/// a bounds check and a generation compare, without hashing.
+pub struct ChunkArena<V> {
+    slots: Vec<Slot<V>>,
+    free: Vec<u32>,
+    handles: VoxelChunkMap<ChunkHandle>,
+}
+
+impl<V> Default for ChunkArena<V> {
+    fn default() -> Self {
+        ChunkArena {
+            slots: Vec::new(),
+            free: Vec::new(),
+            handles: VoxelChunkMap::new(),
+        }
+    }
+}
+
+impl<V> ChunkArena<V> {
+    pub fn new() -> ChunkArena<V> {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.handles.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.handles.is_empty()
+    }
+
+    /// Stores a chunk and returns its handle. A chunk that is already present keeps its handle and
+    /// gets its value replaced, and the old value is returned.
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> (ChunkHandle, Option<V>) {
+        if let Some(&handle) = self.handles.get(idx) {
+            let (_, old) = self.slots[handle.slot as usize].entry.as_mut().unwrap();
+            return (handle, Some(std::mem::replace(old, value)));
+        }
+
+        let handle = match self.free.pop() {
+            Some(slot) => {
+                let s = &mut self.slots[slot as usize];
+                s.entry = Some((idx, value));
+                ChunkHandle {
+                    slot,
+                    generation: s.generation,
+                }
+            }
+            None => {
+                let slot = u32::try_from(self.slots.len()).expect("too many arena slots");
+                self.slots.push(Slot {
+                    generation: 0,
+                    entry: Some((idx, value)),
+                });
+                ChunkHandle {
+                    slot,
+                    generation: 0,
+                }
+            }
+        };
+        self.handles.insert(idx, handle);
+        (handle, None)
+    }
+
+    /// Current handle of a chunk.
+    pub fn handle(&self, idx: VoxelChunkIndex) -> Option<ChunkHandle> {
+        self.handles.get(idx).copied()
+    }
+
+    fn slot(&self, handle: ChunkHandle) -> Option<&(VoxelChunkIndex, V)> {
+        let slot = self.slots.get(handle.slot as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.entry.as_ref()
+    }
+
+    fn slot_mut(&mut self, handle: ChunkHandle) -> Option<&mut (VoxelChunkIndex, V)> {
+        let slot = self.slots.get_mut(handle.slot as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        slot.entry.as_mut()
+    }
+
+    /// Whether `handle` still refers to a stored chunk.
+    pub fn contains(&self, handle: ChunkHandle) -> bool {
+        self.slot(handle).is_some()
+    }
+
+    /// Chunk a live handle refers to.
+    pub fn index_of(&self, handle: ChunkHandle) -> Option<VoxelChunkIndex> {
+        self.slot(handle).map(|&(idx, _)| idx)
+    }
+
+    pub fn get(&self, handle: ChunkHandle) -> Option<&V> {
+        self.slot(handle).map(|(_, value)| value)
+    }
+
+    pub fn get_mut(&mut self, handle: ChunkHandle) -> Option<&mut V> {
+        self.slot_mut(handle).map(|(_, value)| value)
+    }
+
+    pub fn get_by_index(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        self.get(self.handle(idx)?)
+    }
+
+    pub fn get_by_index_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        self.get_mut(self.handle(idx)?)
+    }
+
+    /// Removes the chunk `handle` refers to, invalidating every copy of the handle.
+    pub fn remove(&mut self, handle: ChunkHandle) -> Option<V> {
+        let slot = self.slots.get_mut(handle.slot as usize)?;
+        if slot.generation != handle.generation {
+            return None;
+        }
+        let (idx, value) = slot.entry.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(handle.slot);
+        self.handles.remove(idx);
+        Some(value)
+    }
+
+    pub fn remove_by_index(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        self.remove(self.handle(idx)?)
+    }
+
+    /// Removes every chunk. All outstanding handles go stale, slots are kept for reuse.
+    pub fn clear(&mut self) {
+        self.free.clear();
+        for (slot, s) in self.slots.iter_mut().enumerate() {
+            if s.entry.take().is_some() {
+                s.generation = s.generation.wrapping_add(1);
+            }
+            self.free.push(slot as u32);
+        }
+        self.handles.clear();
+    }
+
+    /// Iterates in slot order.
+    pub fn iter(&self) -> impl Iterator<Item = (ChunkHandle, VoxelChunkIndex, &V)> {
+        self.slots.iter().enumerate().filter_map(|(slot, s)| {
+            let (idx, value) = s.entry.as_ref()?;
+            let handle = ChunkHandle {
+                slot: slot as u32,
+                generation: s.generation,
+            };
+            Some((handle, *idx, value))
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ChunkHandle, VoxelChunkIndex, &mut V)> {
+        self.slots.iter_mut().enumerate().filter_map(|(slot, s)| {
+            let generation = s.generation;
+            let (idx, value) = s.entry.as_mut()?;
+            let handle = ChunkHandle {
+                slot: slot as u32,
+                generation,
+            };
+            Some((handle, *idx, value))
+        })
+    }
+}
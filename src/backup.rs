@@ -0,0 +1,310 @@
+//! Backups of a [`ChunkStore`] that [`import_backup`] checks in full before restoring any of
+//! it, so that a server doesn't come up on a corrupted or partial backup.
+//!
+//! A backup is a directory holding `chunks.bin`, every blob of the store one after the other in
+//! increasing key order, and `manifest.hfb`: the magic `HFBK`, the version, the X, Y and Z bit
+//! counts and the chunk count, then the key, length and [`ContentHasher`] hash of each blob, then
+//! the Merkle root of those, then a CRC32 of everything before it.
+//!
+//! The leaves of the Merkle tree hash the key, length and content hash of a blob, each node the
+//! two below it, and an odd node out moves up a level unchanged. Hashes are 64-bit and not
+//! cryptographic: they catch corruption, not forgery.
+
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::hashers::ContentHasher;
+use crate::store::ChunkStore;
+use crate::{VoxelChunkIndex, X_BITS, Y_BITS, Z_BITS};
+
+const MAGIC: &[u8; 4] = b"HFBK";
+const VERSION: u8 = 1;
+const HEADER_LEN: usize = 16;
+const ENTRY_LEN: usize = 16;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// What a backup holds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupSummary {
+    pub chunks: usize,
+    /// Total size of the blobs.
+    pub bytes: u64,
+    pub merkle_root: u64,
+}
+
+/// A blob as the manifest lists it.
+#[derive(Clone, Copy)]
+struct ManifestEntry {
+    idx: VoxelChunkIndex,
+    len: u32,
+    hash: u64,
+}
+
+impl ManifestEntry {
+    fn leaf(self) -> u64 {
+        let mut leaf = [0; ENTRY_LEN + 1];
+        leaf[1..5].copy_from_slice(&self.idx.0.to_le_bytes());
+        leaf[5..9].copy_from_slice(&self.len.to_le_bytes());
+        leaf[9..].copy_from_slice(&self.hash.to_le_bytes());
+        ContentHasher::hash(&leaf)
+    }
+}
+
+/// Merkle root of `entries`, the hash of no bytes for none. Leaves and nodes start with a
+/// different byte, so that a leaf can't pass for a node.
+fn merkle_root(entries: &[ManifestEntry]) -> u64 {
+    let mut level: Vec<u64> = entries.iter().map(|entry| entry.leaf()).collect();
+    if level.is_empty() {
+        return ContentHasher::hash(&[]);
+    }
+    while level.len() > 1 {
+        level = level
+            .chunks(2)
+            .map(|pair| match *pair {
+                [left, right] => {
+                    let mut node = [1; 17];
+                    node[1..9].copy_from_slice(&left.to_le_bytes());
+                    node[9..].copy_from_slice(&right.to_le_bytes());
+                    ContentHasher::hash(&node)
+                }
+                [odd] => odd,
+                _ => unreachable!("chunks of two"),
+            })
+            .collect();
+    }
+    level[0]
+}
+
+/// Writes every blob of `store` to a new backup in the directory `dir`, which may not hold one
+/// already. Both files are synced before returning.
+pub fn export_backup(
+    store: &mut impl ChunkStore,
+    dir: impl AsRef<Path>,
+) -> io::Result<BackupSummary> {
+    let dir = dir.as_ref();
+    fs::create_dir_all(dir)?;
+    let mut chunks = BufWriter::new(File::create_new(dir.join("chunks.bin"))?);
+    let mut entries = Vec::new();
+    let mut bytes = 0;
+    for idx in store.keys()? {
+        // Deleted since it was listed.
+        let Some(blob) = store.read(idx)? else {
+            continue;
+        };
+        let len = u32::try_from(blob.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "chunk blob over 4 GiB"))?;
+        chunks.write_all(&blob)?;
+        bytes += blob.len() as u64;
+        entries.push(ManifestEntry {
+            idx,
+            len,
+            hash: ContentHasher::hash(&blob),
+        });
+    }
+    chunks
+        .into_inner()
+        .map_err(|err| err.into_error())?
+        .sync_all()?;
+
+    let merkle_root = merkle_root(&entries);
+    let mut manifest = Vec::with_capacity(HEADER_LEN + entries.len() * ENTRY_LEN + 12);
+    manifest.extend_from_slice(MAGIC);
+    manifest.extend_from_slice(&[VERSION, X_BITS, Y_BITS, Z_BITS]);
+    manifest.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for entry in &entries {
+        manifest.extend_from_slice(&entry.idx.0.to_le_bytes());
+        manifest.extend_from_slice(&entry.len.to_le_bytes());
+        manifest.extend_from_slice(&entry.hash.to_le_bytes());
+    }
+    manifest.extend_from_slice(&merkle_root.to_le_bytes());
+    manifest.extend_from_slice(&crc32fast::hash(&manifest).to_le_bytes());
+    let mut file = File::create_new(dir.join("manifest.hfb"))?;
+    file.write_all(&manifest)?;
+    file.sync_all()?;
+    Ok(BackupSummary {
+        chunks: entries.len(),
+        bytes,
+        merkle_root,
+    })
+}
+
+/// Reads the manifest of the backup in `dir`, checking it against its checksum and its Merkle
+/// root against its entries.
+fn read_manifest(dir: &Path) -> io::Result<(Vec<ManifestEntry>, u64)> {
+    let manifest = fs::read(dir.join("manifest.hfb"))?;
+    if manifest.len() < HEADER_LEN + 12 || &manifest[..4] != MAGIC {
+        return Err(invalid("not a backup manifest".to_owned()));
+    }
+    let (body, crc) = manifest.split_at(manifest.len() - 4);
+    if crc32fast::hash(body).to_le_bytes() != crc {
+        return Err(invalid("backup manifest checksum mismatch".to_owned()));
+    }
+    if manifest[4] != VERSION {
+        return Err(invalid(format!(
+            "backup version {}, expected {VERSION}",
+            manifest[4]
+        )));
+    }
+    if manifest[5..8] != [X_BITS, Y_BITS, Z_BITS] {
+        return Err(invalid(format!(
+            "indices packed with {}/{}/{} X/Y/Z bits, this build packs them with \
+             {X_BITS}/{Y_BITS}/{Z_BITS}",
+            manifest[5], manifest[6], manifest[7]
+        )));
+    }
+    let len = u64::from_le_bytes(manifest[8..HEADER_LEN].try_into().unwrap());
+    if Some(body.len() as u64)
+        != len
+            .checked_mul(ENTRY_LEN as u64)
+            .map(|n| n + HEADER_LEN as u64 + 8)
+    {
+        return Err(invalid(format!("backup manifest can't list {len} chunks")));
+    }
+    let (listed, root) = body[HEADER_LEN..].split_at(body.len() - HEADER_LEN - 8);
+    let entries: Vec<ManifestEntry> = listed
+        .chunks_exact(ENTRY_LEN)
+        .map(|entry| ManifestEntry {
+            idx: VoxelChunkIndex(u32::from_le_bytes(entry[..4].try_into().unwrap())),
+            len: u32::from_le_bytes(entry[4..8].try_into().unwrap()),
+            hash: u64::from_le_bytes(entry[8..].try_into().unwrap()),
+        })
+        .collect();
+    let root = u64::from_le_bytes(root.try_into().unwrap());
+    if merkle_root(&entries) != root {
+        return Err(invalid("backup manifest Merkle root mismatch".to_owned()));
+    }
+    Ok((entries, root))
+}
+
+/// Hands every blob of the backup in `dir` to `f`, checking each against the manifest first.
+fn read_chunks(
+    dir: &Path,
+    entries: &[ManifestEntry],
+    mut f: impl FnMut(VoxelChunkIndex, &[u8]) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut chunks = BufReader::new(File::open(dir.join("chunks.bin"))?);
+    let mut blob = Vec::new();
+    for entry in entries {
+        blob.resize(entry.len as usize, 0);
+        chunks.read_exact(&mut blob).map_err(|err| {
+            if err.kind() == io::ErrorKind::UnexpectedEof {
+                invalid(format!(
+                    "backup cut short at chunk {:?}",
+                    entry.idx.to_coords()
+                ))
+            } else {
+                err
+            }
+        })?;
+        if ContentHasher::hash(&blob) != entry.hash {
+            return Err(invalid(format!(
+                "backup blob of chunk {:?} doesn't match its hash",
+                entry.idx.to_coords()
+            )));
+        }
+        f(entry.idx, &blob)?;
+    }
+    if chunks.read(&mut [0])? != 0 {
+        return Err(invalid(
+            "backup holds more than its manifest lists".to_owned(),
+        ));
+    }
+    Ok(())
+}
+
+/// Checks the backup in `dir` in full: its manifest, and every blob against its hash.
+pub fn verify_backup(dir: impl AsRef<Path>) -> io::Result<BackupSummary> {
+    let dir = dir.as_ref();
+    let (entries, merkle_root) = read_manifest(dir)?;
+    read_chunks(dir, &entries, |_, _| Ok(()))?;
+    Ok(BackupSummary {
+        chunks: entries.len(),
+        bytes: entries.iter().map(|entry| entry.len as u64).sum(),
+        merkle_root,
+    })
+}
+
+/// Checks the backup in `dir` like [`verify_backup`], then writes its blobs to `store` and
+/// syncs it. Nothing is written unless the whole backup checks out; chunks of `store` the
+/// backup doesn't have are left alone, so restore into an empty store for an exact copy.
+pub fn import_backup(
+    dir: impl AsRef<Path>,
+    store: &mut impl ChunkStore,
+) -> io::Result<BackupSummary> {
+    let dir = dir.as_ref();
+    let summary = verify_backup(dir)?;
+    let (entries, _) = read_manifest(dir)?;
+    read_chunks(dir, &entries, |idx, blob| store.write(idx, blob))?;
+    store.sync()?;
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::scratch_dir;
+    use crate::RegionStore;
+
+    fn filled_store(dir: &Path) -> RegionStore {
+        let mut store = RegionStore::open(dir).unwrap();
+        for i in 0..30 {
+            let idx = VoxelChunkIndex::from_coords(i * 5, -i, i % 3);
+            store.write(idx, &vec![i as u8; 10 + i as usize]).unwrap();
+        }
+        store
+    }
+
+    #[test]
+    fn backups_restore_every_blob() {
+        let dir = scratch_dir("backup-restore");
+        let mut store = filled_store(&dir.join("store"));
+        let summary = export_backup(&mut store, dir.join("backup")).unwrap();
+        assert_eq!(summary.chunks, 30);
+        assert_eq!(summary.bytes, (0..30).map(|i| 10 + i).sum::<u64>());
+        assert_eq!(verify_backup(dir.join("backup")).unwrap(), summary);
+        // A backup is never written over.
+        assert!(export_backup(&mut store, dir.join("backup")).is_err());
+
+        let mut restored = RegionStore::open(dir.join("restored")).unwrap();
+        assert_eq!(
+            import_backup(dir.join("backup"), &mut restored).unwrap(),
+            summary
+        );
+        let keys = store.keys().unwrap();
+        assert_eq!(restored.keys().unwrap(), keys);
+        for idx in keys {
+            assert_eq!(restored.read(idx).unwrap(), store.read(idx).unwrap());
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn damaged_backups_restore_nothing() {
+        let dir = scratch_dir("backup-damaged");
+        let mut store = filled_store(&dir.join("store"));
+        export_backup(&mut store, dir.join("backup")).unwrap();
+        let chunks = dir.join("backup/chunks.bin");
+        let mut bytes = fs::read(&chunks).unwrap();
+        let (last, at) = (bytes.len() - 1, bytes.len() / 2);
+
+        bytes[at] ^= 1;
+        fs::write(&chunks, &bytes).unwrap();
+        assert!(verify_backup(dir.join("backup")).is_err());
+        bytes[at] ^= 1;
+        fs::write(&chunks, &bytes[..last]).unwrap();
+        assert!(verify_backup(dir.join("backup")).is_err());
+        bytes.push(0);
+        fs::write(&chunks, &bytes).unwrap();
+        assert!(verify_backup(dir.join("backup")).is_err());
+
+        let mut restored = RegionStore::open(dir.join("restored")).unwrap();
+        let err = import_backup(dir.join("backup"), &mut restored).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert_eq!(restored.keys().unwrap(), []);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,602 @@
+//! Command-line front end to the [`analysis`](hash_funsies::analysis) functions, to evaluate a
+//! hasher on a workload without writing any Rust. Run `hash-funsies help` for the flags.
+
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::process::ExitCode;
+
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command};
+
+use hash_funsies::analysis::{
+    avalanche, bit_independence, bucket_counts, chi_squared_test, collision_report, ks_test,
+    quality_score, simulated_slots, CollisionReport, QualityScore, AVALANCHE_THRESHOLD,
+};
+use hash_funsies::hashers::{NamedHasher, HASHER_NAMES};
+use hash_funsies::heatmap::write_occupancy_png;
+use hash_funsies::migrate::{migrate, BitLayout, OutOfRange};
+use hash_funsies::report::{
+    read_criterion, regressions, write_html, write_json, write_load_curve_csv, write_markdown,
+    write_matrix_csv, write_occupancy_csv, write_slots_jsonl, Baseline, HasherReport, ProbeSummary,
+    Tolerance,
+};
+use hash_funsies::workload::{caves, outposts, player_walk, read_world_dump};
+use hash_funsies::{ChunkAabb, VoxelChunkIndex};
+
+const WORKLOADS: [&str; 4] = ["box", "caves", "walk", "outposts"];
+
+fn hasher_arg() -> Arg {
+    Arg::new("hasher")
+        .long("hasher")
+        .value_parser(HASHER_NAMES)
+        .default_value("fib")
+        .help("Hasher to analyse")
+}
+
+fn workload_args(command: Command) -> Command {
+    command
+        .arg(
+            Arg::new("workload")
+                .long("workload")
+                .value_parser(WORKLOADS)
+                .default_value("box")
+                .help(
+                    "Keys to hash: a box of chunks, caves in that box, a player walk or outposts",
+                ),
+        )
+        .arg(
+            Arg::new("world")
+                .long("world")
+                .value_name("FILE")
+                .help("Hashes the chunks of a world dump instead of a generated workload"),
+        )
+        .arg(
+            Arg::new("size")
+                .long("size")
+                .value_parser(value_parser!(u32).range(1..))
+                .default_value("128")
+                .help("Box side, walk steps, or outpost count"),
+        )
+        .arg(
+            Arg::new("seed")
+                .long("seed")
+                .value_parser(value_parser!(u64))
+                .default_value("0")
+                .help("Seed of the workload; boxes ignore it"),
+        )
+        .arg(
+            Arg::new("table-size")
+                .long("table-size")
+                .value_parser(value_parser!(usize))
+                .help(
+                    "Buckets of the simulated table [default: twice the keys, as a power of two]",
+                ),
+        )
+}
+
+fn samples_arg() -> Arg {
+    Arg::new("samples")
+        .long("samples")
+        .value_parser(value_parser!(u32).range(1..))
+        .default_value("10000")
+        .help("Random keys to flip the bits of")
+}
+
+fn criterion_arg() -> Arg {
+    Arg::new("criterion")
+        .long("criterion")
+        .value_name("DIR")
+        .default_value("target/criterion")
+        .help("Where Criterion saved the benchmark results")
+}
+
+fn output_arg(name: &'static str, help: &'static str) -> Arg {
+    Arg::new(name).long(name).value_name("FILE").help(help)
+}
+
+fn cli() -> Command {
+    Command::new("hash-funsies")
+        .about("Analyses how hashers spread voxel chunk indices")
+        .subcommand_required(true)
+        .subcommand(
+            workload_args(Command::new("collisions"))
+                .about("Bucket occupancy, probe lengths and uniformity tests of a workload")
+                .arg(hasher_arg())
+                .arg(output_arg("json", "Writes the collision report as JSON"))
+                .arg(output_arg(
+                    "occupancy-csv",
+                    "Writes the occupancy histogram as CSV",
+                ))
+                .arg(output_arg(
+                    "load-curve-csv",
+                    "Writes the load factor curve as CSV",
+                ))
+                .arg(output_arg(
+                    "heatmap",
+                    "Writes the bucket occupancy as a PNG, 1024 buckets wide",
+                ))
+                .arg(output_arg(
+                    "slots-jsonl",
+                    "Writes the bucket and probe length of every key as JSON Lines",
+                )),
+        )
+        .subcommand(
+            Command::new("avalanche")
+                .about("Avalanche and bit independence of a hasher")
+                .arg(hasher_arg())
+                .arg(samples_arg())
+                .arg(
+                    Arg::new("bits")
+                        .long("bits")
+                        .value_parser(value_parser!(u32).range(1..=64))
+                        .default_value("64")
+                        .help("Output bits the table uses"),
+                )
+                .arg(output_arg("json", "Writes both reports as JSON"))
+                .arg(output_arg(
+                    "matrix-csv",
+                    "Writes the avalanche matrix as CSV",
+                )),
+        )
+        .subcommand(
+            workload_args(Command::new("sweep"))
+                .about("Collision report of a workload for a range of workload seeds")
+                .arg(hasher_arg())
+                .arg(
+                    Arg::new("seeds")
+                        .long("seeds")
+                        .value_parser(value_parser!(u64).range(1..))
+                        .default_value("16")
+                        .help("Seeds to sweep, starting from --seed"),
+                )
+                .arg(output_arg("json", "Writes every collision report as JSON")),
+        )
+        .subcommand(
+            Command::new("rank")
+                .about("Ranks every hasher by its quality score")
+                .arg(output_arg("json", "Writes the scores as JSON")),
+        )
+        .subcommand(
+            workload_args(Command::new("report"))
+                .about("Document gathering the analyses and benchmarks of every hasher")
+                .arg(criterion_arg())
+                .arg(samples_arg())
+                .arg(
+                    output_arg(
+                        "out",
+                        "Writes the report, as HTML if FILE ends in .html, else as Markdown",
+                    )
+                    .required(true),
+                ),
+        )
+        .subcommand(
+            workload_args(Command::new("baseline"))
+                .about("Saves the benchmarks and probe lengths of every hasher to compare later runs to")
+                .arg(criterion_arg())
+                .arg(output_arg("out", "Writes the baseline as JSON").required(true)),
+        )
+        .subcommand(
+            workload_args(Command::new("check"))
+                .about("Compares the benchmarks and probe lengths of every hasher to a baseline")
+                .arg(
+                    Arg::new("baseline")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("Baseline saved by the baseline command"),
+                )
+                .arg(criterion_arg())
+                .arg(
+                    Arg::new("time-tolerance")
+                        .long("time-tolerance")
+                        .value_parser(value_parser!(f64))
+                        .default_value("0.1")
+                        .help("Relative increase of a mean time flagged as a regression"),
+                )
+                .arg(
+                    Arg::new("probe-tolerance")
+                        .long("probe-tolerance")
+                        .value_parser(value_parser!(f64))
+                        .default_value("0.01")
+                        .help("Relative increase of a mean probe length flagged as a regression"),
+                )
+                .arg(
+                    Arg::new("max-probe-tolerance")
+                        .long("max-probe-tolerance")
+                        .value_parser(value_parser!(usize))
+                        .default_value("2")
+                        .help("Increase of a longest probe flagged as a regression"),
+                ),
+        )
+        .subcommand(
+            Command::new("migrate")
+                .about("Converts an index list, set or map file to another bit layout")
+                .arg(
+                    Arg::new("in")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("File to convert"),
+                )
+                .arg(
+                    Arg::new("out")
+                        .value_name("FILE")
+                        .required(true)
+                        .help("Converted file to write"),
+                )
+                .arg(
+                    Arg::new("to")
+                        .long("to")
+                        .value_name("X/Y/Z")
+                        .value_parser(parse_layout)
+                        .required(true)
+                        .help("Bit counts of the X, Y and Z fields of the converted keys"),
+                )
+                .arg(
+                    Arg::new("drop-out-of-range")
+                        .long("drop-out-of-range")
+                        .action(ArgAction::SetTrue)
+                        .help("Leaves out the chunks the new layout can't reach instead of failing"),
+                ),
+        )
+}
+
+fn parse_layout(layout: &str) -> Result<BitLayout, String> {
+    let bits: Vec<u8> = layout
+        .split('/')
+        .map(|bits| {
+            bits.parse()
+                .map_err(|_| format!("{bits:?} isn't a bit count"))
+        })
+        .collect::<Result<_, _>>()?;
+    match bits[..] {
+        [x, y, z] => BitLayout::new(x, y, z)
+            .ok_or_else(|| "fields take 1 to 32 bits, and 64 at most together".to_owned()),
+        _ => Err("expected three bit counts, as in 12/12/8".to_owned()),
+    }
+}
+
+fn hasher(args: &ArgMatches) -> NamedHasher {
+    let name = args
+        .get_one::<String>("hasher")
+        .expect("hasher has a default");
+    NamedHasher::new(name).expect("validated by the parser")
+}
+
+fn workload(args: &ArgMatches, seed: u64) -> io::Result<Vec<VoxelChunkIndex>> {
+    if let Some(path) = args.get_one::<String>("world") {
+        let chunks = read_world_dump(BufReader::new(File::open(path)?))?;
+        if chunks.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the world dump lists no chunk",
+            ));
+        }
+        return Ok(chunks);
+    }
+    let size = *args.get_one::<u32>("size").expect("size has a default");
+    let side = size.min(8190) as i32;
+    let region = ChunkAabb::new(
+        (-side / 2, -side / 2, -8),
+        (side - side / 2, side - side / 2, 8),
+    );
+    Ok(
+        match args.get_one::<String>("workload").map(String::as_str) {
+            Some("box") => region.iter().collect(),
+            Some("caves") => caves(region, 0.5, seed),
+            Some("walk") => player_walk(size as usize, 8, seed),
+            Some("outposts") => outposts(size as usize, 4, seed),
+            _ => unreachable!("workload has a default and is validated by the parser"),
+        },
+    )
+}
+
+fn table_size(args: &ArgMatches, keys: usize) -> usize {
+    args.get_one::<usize>("table-size")
+        .copied()
+        .unwrap_or_else(|| (keys * 2).next_power_of_two())
+}
+
+fn create(args: &ArgMatches, output: &str) -> io::Result<Option<BufWriter<File>>> {
+    args.get_one::<String>(output)
+        .map(|path| File::create(path).map(BufWriter::new))
+        .transpose()
+}
+
+fn print_report(report: &CollisionReport) {
+    println!(
+        "{} keys in {} buckets: {} collisions, {} empty buckets, mean probe {:.3}, max probe {}",
+        report.keys,
+        report.table_size,
+        report.collisions(),
+        report.empty_buckets(),
+        report.mean_probe,
+        report.max_probe
+    );
+}
+
+fn collisions(args: &ArgMatches) -> io::Result<()> {
+    let hasher = hasher(args);
+    let keys = workload(
+        args,
+        *args.get_one::<u64>("seed").expect("seed has a default"),
+    )?;
+    let table_size = table_size(args, keys.len());
+    if table_size < keys.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "a table of {table_size} buckets can't hold {} keys",
+                keys.len()
+            ),
+        ));
+    }
+
+    let report = collision_report(&keys, &hasher, table_size);
+    print_report(&report);
+    let chi = chi_squared_test(&keys, &hasher, table_size);
+    let ks = ks_test(&keys, &hasher, table_size);
+    println!("chi-squared {:.1} (p = {:.4})", chi.statistic, chi.p_value);
+    println!(
+        "Kolmogorov-Smirnov {:.5} (p = {:.4})",
+        ks.statistic, ks.p_value
+    );
+
+    if let Some(out) = create(args, "json")? {
+        write_json(&report, out)?;
+    }
+    if let Some(out) = create(args, "occupancy-csv")? {
+        write_occupancy_csv(&report, out)?;
+    }
+    if let Some(out) = create(args, "load-curve-csv")? {
+        write_load_curve_csv(&report, out)?;
+    }
+    if let Some(out) = create(args, "heatmap")? {
+        write_occupancy_png(&bucket_counts(&keys, &hasher, table_size), 1024, out)?;
+    }
+    if let Some(out) = create(args, "slots-jsonl")? {
+        write_slots_jsonl(simulated_slots(&keys, &hasher, table_size), out)?;
+    }
+    Ok(())
+}
+
+fn avalanches(args: &ArgMatches) -> io::Result<()> {
+    let hasher = hasher(args);
+    let samples = *args
+        .get_one::<u32>("samples")
+        .expect("samples has a default") as usize;
+    let bits = *args.get_one::<u32>("bits").expect("bits has a default");
+
+    let report = avalanche(&hasher, samples);
+    let bic = bit_independence(&hasher, samples);
+    println!(
+        "avalanche: max bias {:.4} over {bits} bits, {}",
+        report.max_bias(bits),
+        if report.passes(bits, AVALANCHE_THRESHOLD) {
+            "passes"
+        } else {
+            "fails"
+        }
+    );
+    println!(
+        "bit independence: max correlation {:.4}",
+        bic.max_correlation(bits)
+    );
+
+    if let Some(out) = create(args, "json")? {
+        write_json(&(&report, &bic), out)?;
+    }
+    if let Some(out) = create(args, "matrix-csv")? {
+        write_matrix_csv(&report.matrix, out)?;
+    }
+    Ok(())
+}
+
+fn sweep(args: &ArgMatches) -> io::Result<()> {
+    let hasher = hasher(args);
+    let first = *args.get_one::<u64>("seed").expect("seed has a default");
+    let seeds = *args.get_one::<u64>("seeds").expect("seeds has a default");
+
+    let mut reports = Vec::new();
+    for seed in first..first.saturating_add(seeds) {
+        let keys = workload(args, seed)?;
+        let table_size = table_size(args, keys.len()).max(keys.len());
+        let report = collision_report(&keys, &hasher, table_size);
+        print!("seed {seed}: ");
+        print_report(&report);
+        reports.push(report);
+    }
+
+    if let Some(out) = create(args, "json")? {
+        write_json(&reports, out)?;
+    }
+    Ok(())
+}
+
+fn rank(args: &ArgMatches) -> io::Result<()> {
+    let mut scores: Vec<(&str, QualityScore)> = HASHER_NAMES
+        .into_iter()
+        .map(|name| {
+            let hasher = NamedHasher::new(name).unwrap();
+            (name, quality_score(&hasher, hasher.output_bits()))
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.total.total_cmp(&a.1.total));
+
+    println!("hasher   total  avalanche  independence  sparse  sequential  voxel");
+    for (name, score) in &scores {
+        println!(
+            "{name:<8} {:.3}  {:.3}      {:.3}         {:.3}   {:.3}       {:.3}",
+            score.total,
+            score.avalanche,
+            score.independence,
+            score.sparse,
+            score.sequential,
+            score.voxel
+        );
+    }
+
+    if let Some(out) = create(args, "json")? {
+        write_json(&scores, out)?;
+    }
+    Ok(())
+}
+
+fn report(args: &ArgMatches) -> io::Result<()> {
+    let results = read_criterion(
+        args.get_one::<String>("criterion")
+            .expect("criterion has a default"),
+    )?;
+    let samples = *args
+        .get_one::<u32>("samples")
+        .expect("samples has a default") as usize;
+    let workload_name = args
+        .get_one::<String>("world")
+        .or(args.get_one::<String>("workload"))
+        .expect("workload has a default");
+    let keys = workload(
+        args,
+        *args.get_one::<u64>("seed").expect("seed has a default"),
+    )?;
+    let table_size = table_size(args, keys.len()).max(keys.len());
+
+    let reports: Vec<HasherReport> = HASHER_NAMES
+        .into_iter()
+        .map(|name| {
+            let hasher = NamedHasher::new(name).unwrap();
+            let mut report = HasherReport::new(hasher.bench_name()).with_benchmarks(&results);
+            report.collisions = Some(collision_report(&keys, &hasher, table_size));
+            report.chi_squared = Some(chi_squared_test(&keys, &hasher, table_size));
+            report.ks = Some(ks_test(&keys, &hasher, table_size));
+            report.avalanche = Some(avalanche(&hasher, samples));
+            report.output_bits = hasher.output_bits();
+            report.quality = Some(quality_score(&hasher, hasher.output_bits()));
+            report
+        })
+        .collect();
+
+    let path = args.get_one::<String>("out").expect("out is required");
+    let title = format!(
+        "Hashers on the {workload_name} workload, {} keys",
+        keys.len()
+    );
+    let out = BufWriter::new(File::create(path)?);
+    if path.ends_with(".html") {
+        write_html(&title, &reports, out)
+    } else {
+        write_markdown(&title, &reports, out)
+    }
+}
+
+/// Benchmarks Criterion saved and probe lengths of every hasher on the workload.
+fn current_baseline(args: &ArgMatches) -> io::Result<Baseline> {
+    let benchmarks = read_criterion(
+        args.get_one::<String>("criterion")
+            .expect("criterion has a default"),
+    )?;
+    let keys = workload(
+        args,
+        *args.get_one::<u64>("seed").expect("seed has a default"),
+    )?;
+    let table_size = table_size(args, keys.len()).max(keys.len());
+    let probes = HASHER_NAMES
+        .into_iter()
+        .map(|name| {
+            let hasher = NamedHasher::new(name).unwrap();
+            let report = collision_report(&keys, &hasher, table_size);
+            ProbeSummary::new(hasher.bench_name(), &report)
+        })
+        .collect();
+    Ok(Baseline { benchmarks, probes })
+}
+
+fn baseline(args: &ArgMatches) -> io::Result<()> {
+    let baseline = current_baseline(args)?;
+    let path = args.get_one::<String>("out").expect("out is required");
+    baseline.write_to(BufWriter::new(File::create(path)?))?;
+    println!(
+        "saved {} benchmarks and the probes of {} hashers to {path}",
+        baseline.benchmarks.len(),
+        baseline.probes.len()
+    );
+    Ok(())
+}
+
+fn check(args: &ArgMatches) -> io::Result<()> {
+    let path = args
+        .get_one::<String>("baseline")
+        .expect("baseline is required");
+    let saved = Baseline::read_from(BufReader::new(File::open(path)?))?;
+    let tolerance = Tolerance {
+        time: *args.get_one("time-tolerance").expect("has a default"),
+        mean_probe: *args.get_one("probe-tolerance").expect("has a default"),
+        max_probe: *args.get_one("max-probe-tolerance").expect("has a default"),
+    };
+    let found = regressions(&saved, &current_baseline(args)?, tolerance);
+    for regression in &found {
+        println!(
+            "{}: {} went from {:.3} to {:.3} ({:+.1}%)",
+            regression.id,
+            regression.metric.name(),
+            regression.baseline,
+            regression.current,
+            regression.change() * 100.0
+        );
+    }
+    if found.is_empty() {
+        println!("no regression against {path}");
+        Ok(())
+    } else {
+        Err(io::Error::other(format!(
+            "{} regressions against {path}",
+            found.len()
+        )))
+    }
+}
+
+fn migrate_file(args: &ArgMatches) -> io::Result<()> {
+    let input = args.get_one::<String>("in").unwrap();
+    let output = args.get_one::<String>("out").unwrap();
+    let to = *args.get_one::<BitLayout>("to").unwrap();
+    let out_of_range = if args.get_flag("drop-out-of-range") {
+        OutOfRange::Drop
+    } else {
+        OutOfRange::Fail
+    };
+    // Written once converted, so that a failed migration leaves no file behind.
+    let mut converted = Vec::new();
+    let report = migrate(
+        BufReader::new(File::open(input)?),
+        &mut converted,
+        to,
+        out_of_range,
+    )?;
+    std::fs::write(output, converted)?;
+    println!(
+        "{} entries converted from {} to {to} bits",
+        report.entries, report.from
+    );
+    for coords in &report.dropped {
+        println!("dropped {coords:?}");
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = cli().get_matches();
+    let result = match args.subcommand() {
+        Some(("collisions", args)) => collisions(args),
+        Some(("avalanche", args)) => avalanches(args),
+        Some(("sweep", args)) => sweep(args),
+        Some(("rank", args)) => rank(args),
+        Some(("report", args)) => report(args),
+        Some(("baseline", args)) => baseline(args),
+        Some(("check", args)) => check(args),
+        Some(("migrate", args)) => migrate_file(args),
+        _ => unreachable!("a subcommand is required"),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,200 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use crate::bvh::Ray;
+use crate::{ChunkAabb, VoxelChunkIndex, VoxelChunkMap, X_BITS, Y_BITS, Z_BITS};
+
+/// Inclusive range of representable chunk coordinates on each axis.
+const RANGE: [(i32, i32); 3] = [
+    (-(1 << (X_BITS - 1)), (1 << (X_BITS - 1)) - 1),
+    (-(1 << (Y_BITS - 1)), (1 << (Y_BITS - 1)) - 1),
+    (-(1 << (Z_BITS - 1)), (1 << (Z_BITS - 1)) - 1),
+];
+
+struct Collider {
+    min: [f32; 3],
+    max: [f32; 3],
+    chunks: ChunkAabb,
+}
+
+/// Broad phase for physics engines such as parry or rapier: colliders, known by their handle
+/// and world-space bounding box, are bucketed by the chunks their box overlaps, and region and
+/// ray queries test only the colliders of the chunks they reach. Narrow-phase tests against the
+/// actual shapes are left to the engine.
+///
+/// Positions are in world units; boxes reaching past the representable world are bucketed in
+/// its edge chunks.
+pub struct ColliderBroadPhase<C> {
+    chunk_size: f32,
+    colliders: HashMap<C, Collider>,
+    buckets: VoxelChunkMap<Vec<C>>,
+}
+
+impl<C: Copy + Eq + Hash> ColliderBroadPhase<C> {
+    /// A broad phase over chunks `chunk_size` world units wide. Colliders much larger than a
+    /// chunk are held by every chunk they overlap, so pick chunks at least as large as most.
+    pub fn new(chunk_size: f32) -> ColliderBroadPhase<C> {
+        assert!(chunk_size > 0.0, "chunk size must be positive");
+        ColliderBroadPhase {
+            chunk_size,
+            colliders: HashMap::new(),
+            buckets: VoxelChunkMap::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.colliders.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.colliders.is_empty()
+    }
+
+    /// Chunks overlapped by the box from `min` to `max`, clipped to the representable world.
+    fn chunks(&self, min: [f32; 3], max: [f32; 3]) -> ChunkAabb {
+        assert!(
+            (0..3).all(|axis| min[axis] <= max[axis]),
+            "box min must not exceed max"
+        );
+        let lo: [i32; 3] = std::array::from_fn(|axis| {
+            let (first, last) = RANGE[axis];
+            ((min[axis] / self.chunk_size).floor() as i32).clamp(first, last)
+        });
+        let hi: [i32; 3] = std::array::from_fn(|axis| {
+            let (first, last) = RANGE[axis];
+            ((max[axis] / self.chunk_size).floor() as i32).clamp(first, last) + 1
+        });
+        ChunkAabb::new((lo[0], lo[1], lo[2]), (hi[0], hi[1], hi[2]))
+    }
+
+    /// Adds `collider` with the bounding box from `min` to `max`, or moves it there, returning
+    /// whether it was already in.
+    pub fn insert(&mut self, collider: C, min: (f32, f32, f32), max: (f32, f32, f32)) -> bool {
+        let replaced = self.remove(collider);
+        let (min, max) = ([min.0, min.1, min.2], [max.0, max.1, max.2]);
+        let chunks = self.chunks(min, max);
+        for idx in chunks.iter() {
+            self.buckets.entry(idx).or_default().push(collider);
+        }
+        self.colliders
+            .insert(collider, Collider { min, max, chunks });
+        replaced
+    }
+
+    /// Removes `collider`, returning whether it was in.
+    pub fn remove(&mut self, collider: C) -> bool {
+        let Some(removed) = self.colliders.remove(&collider) else {
+            return false;
+        };
+        for idx in removed.chunks.iter() {
+            let bucket = self.buckets.get_mut(idx).unwrap();
+            let at = bucket.iter().position(|&c| c == collider).unwrap();
+            bucket.swap_remove(at);
+            if bucket.is_empty() {
+                self.buckets.remove(idx);
+            }
+        }
+        true
+    }
+
+    /// Colliders whose bounding box overlaps the box from `min` to `max`, boundaries included,
+    /// in no particular order.
+    pub fn colliders_in_region(&self, min: (f32, f32, f32), max: (f32, f32, f32)) -> Vec<C> {
+        let (min, max) = ([min.0, min.1, min.2], [max.0, max.1, max.2]);
+        let region = self.chunks(min, max);
+        let mut hits = Vec::new();
+        let mut visit = |idx: VoxelChunkIndex, bucket: &[C]| {
+            let (x, y, z) = idx.to_coords();
+            for &handle in bucket {
+                let collider = &self.colliders[&handle];
+                // A collider overlapping several chunks of the region is reported by the
+                // first of them only.
+                let (lo, first) = (collider.chunks.min, region.min);
+                if (lo.0.max(first.0), lo.1.max(first.1), lo.2.max(first.2)) != (x, y, z) {
+                    continue;
+                }
+                if (0..3)
+                    .all(|axis| collider.min[axis] <= max[axis] && min[axis] <= collider.max[axis])
+                {
+                    hits.push(handle);
+                }
+            }
+        };
+        if region.volume() <= self.buckets.len() {
+            for idx in region.iter() {
+                if let Some(bucket) = self.buckets.get(idx) {
+                    visit(idx, bucket);
+                }
+            }
+        } else {
+            for (idx, bucket) in self.buckets.iter() {
+                if region.contains_index(idx) {
+                    visit(idx, bucket);
+                }
+            }
+        }
+        hits
+    }
+
+    /// Colliders whose bounding box the ray from `origin` along `dir` crosses within
+    /// `max_distance`, nearest first, with the distance at which the ray enters the box.
+    /// Distances are in units of `dir`, as with [`ChunkBvh::raycast`](crate::ChunkBvh::raycast).
+    ///
+    /// The ray walks the chunks it crosses one after the other, so long rays through empty
+    /// space cost a step per chunk.
+    pub fn colliders_along_ray(
+        &self,
+        origin: (f32, f32, f32),
+        dir: (f32, f32, f32),
+        max_distance: f32,
+    ) -> Vec<(C, f32)> {
+        let ray = Ray::new(origin, dir);
+        let world_min = RANGE.map(|(first, _)| first as f32 * self.chunk_size);
+        let world_max = RANGE.map(|(_, last)| (last + 1) as f32 * self.chunk_size);
+        let Some(start) = ray.enter(world_min, world_max, max_distance) else {
+            return Vec::new();
+        };
+        let (origin, dir) = ([origin.0, origin.1, origin.2], [dir.0, dir.1, dir.2]);
+
+        // Walk the chunks from the one the ray starts in, stepping each time along the axis
+        // whose next chunk boundary it crosses first.
+        let mut chunk: [i32; 3] = std::array::from_fn(|axis| {
+            let (first, last) = RANGE[axis];
+            let at = origin[axis] + start * dir[axis];
+            ((at / self.chunk_size).floor() as i32).clamp(first, last)
+        });
+        let step = dir.map(|d| if d > 0.0 { 1 } else { -1 });
+        let delta = dir.map(|d| (self.chunk_size / d).abs());
+        let mut next: [f32; 3] = std::array::from_fn(|axis| {
+            if dir[axis] == 0.0 {
+                return f32::INFINITY;
+            }
+            let boundary = (chunk[axis] + (step[axis] + 1) / 2) as f32 * self.chunk_size;
+            (boundary - origin[axis]) / dir[axis]
+        });
+
+        let mut tested = HashSet::new();
+        let mut hits = Vec::new();
+        loop {
+            let idx = VoxelChunkIndex::from_coords(chunk[0], chunk[1], chunk[2]);
+            for &handle in self.buckets.get(idx).map_or(&[][..], Vec::as_slice) {
+                if !tested.insert(handle) {
+                    continue;
+                }
+                let collider = &self.colliders[&handle];
+                if let Some(t) = ray.enter(collider.min, collider.max, max_distance) {
+                    hits.push((handle, t));
+                }
+            }
+            let axis = (0..3).min_by(|&a, &b| next[a].total_cmp(&next[b])).unwrap();
+            chunk[axis] += step[axis];
+            let (first, last) = RANGE[axis];
+            if next[axis] > max_distance || !(first..=last).contains(&chunk[axis]) {
+                break;
+            }
+            next[axis] += delta[axis];
+        }
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits
+    }
+}
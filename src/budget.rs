@@ -0,0 +1,333 @@
+//! [`BudgetCache`], a chunk cache bounded by the total byte cost of its values, with pluggable
+//! [`EvictionPolicy`]s.
+
+use crate::observe::Observers;
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+/// Byte cost a cached value charges against a [`BudgetCache`] budget.
+pub trait ChunkCost {
+    fn cost(&self) -> usize;
+}
+
+impl<T> ChunkCost for Vec<T> {
+    fn cost(&self) -> usize {
+        std::mem::size_of::<Self>() + self.capacity() * std::mem::size_of::<T>()
+    }
+}
+
+impl<T> ChunkCost for Box<[T]> {
+    fn cost(&self) -> usize {
+        std::mem::size_of::<Self>() + std::mem::size_of_val::<[T]>(self)
+    }
+}
+
+/// What a policy knows about a cached chunk.
+#[derive(Debug, Clone, Copy)]
+pub struct EntryInfo {
+    pub idx: VoxelChunkIndex,
+    pub cost: usize,
+    /// Cache clock at the last insert or access; the clock ticks once per such operation.
+    pub last_use: u64,
+    /// Accesses since the chunk was inserted.
+    pub hits: u32,
+}
+
+/// Decides which chunks go first when the cache is over budget: lowest score is evicted first.
+pub trait EvictionPolicy {
+    fn score(&self, entry: &EntryInfo) -> f64;
+}
+
+/// Evicts the least recently used chunk.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Lru;
+
+impl EvictionPolicy for Lru {
+    fn score(&self, entry: &EntryInfo) -> f64 {
+        entry.last_use as f64
+    }
+}
+
+/// Evicts the chunk farthest from `center`, typically the player's chunk.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Distance {
+    pub center: (i32, i32, i32),
+}
+
+impl EvictionPolicy for Distance {
+    fn score(&self, entry: &EntryInfo) -> f64 {
+        let (x, y, z) = entry.idx.to_coords();
+        let (dx, dy, dz) = (
+            (x - self.center.0) as f64,
+            (y - self.center.1) as f64,
+            (z - self.center.2) as f64,
+        );
+        -(dx * dx + dy * dy + dz * dz)
+    }
+}
+
+/// Evicts the chunk with the fewest hits per byte, so big rarely used chunks go first.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CostBenefit;
+
+impl EvictionPolicy for CostBenefit {
+    fn score(&self, entry: &EntryInfo) -> f64 {
+        (entry.hits as f64 + 1.0) / entry.cost.max(1) as f64
+    }
+}
+
+struct Slot<V> {
+    value: V,
+    info: EntryInfo,
+}
+
+/// Chunk cache bounded by the summed [`ChunkCost`] of its values rather than by entry count.
+///
+/// Picking victims scores every cached chunk, so an over-budget insert costs `O(n log n)`; size
+/// the budget so that evictions come in batches rather than on every insert.
+pub struct BudgetCache<V, P = Lru> {
+    slots: VoxelChunkMap<Slot<V>>,
+    policy: P,
+    budget: usize,
+    used: usize,
+    clock: u64,
+    on_evict: Option<Box<dyn FnMut(VoxelChunkIndex, V)>>,
+    observers: Observers<V>,
+    evictions: u64,
+}
+
+impl<V: ChunkCost, P: EvictionPolicy> BudgetCache<V, P> {
+    /// Cache holding at most `budget` bytes of values.
+    pub fn new(budget: usize, policy: P) -> BudgetCache<V, P> {
+        BudgetCache {
+            slots: VoxelChunkMap::new(),
+            policy,
+            budget,
+            used: 0,
+            clock: 0,
+            on_evict: None,
+            observers: Observers::default(),
+            evictions: 0,
+        }
+    }
+
+    /// Registers the function evicted chunks are handed to, e.g. to persist them.
+    pub fn on_evict<F: FnMut(VoxelChunkIndex, V) + 'static>(&mut self, f: F) {
+        self.on_evict = Some(Box::new(f));
+    }
+
+    /// Names the cache, and the map of its slots, in the [`instrument`](crate::instrument) events
+    /// they report.
+    #[cfg(feature = "instrument")]
+    pub fn set_label(&mut self, label: &'static str) {
+        self.slots.set_label(label);
+    }
+
+    /// Callbacks run on inserts, removals and evictions. Unlike the [`BudgetCache::on_evict`]
+    /// function, they only get to see evicted values, not keep them.
+    pub fn observers(&mut self) -> &mut Observers<V> {
+        &mut self.observers
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    pub fn budget(&self) -> usize {
+        self.budget
+    }
+
+    /// Summed cost of the cached values.
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    /// Chunks evicted since the cache was created.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Changes the budget, evicting chunks until the cache fits.
+    pub fn set_budget(&mut self, budget: usize) {
+        self.budget = budget;
+        self.trim();
+    }
+
+    pub fn policy(&self) -> &P {
+        &self.policy
+    }
+
+    /// Call [`BudgetCache::trim`] afterwards if the change should evict right away.
+    pub fn policy_mut(&mut self) -> &mut P {
+        &mut self.policy
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.slots.contains_key(idx)
+    }
+
+    /// Looks a chunk up without counting it as an access.
+    pub fn peek(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        self.slots.get(idx).map(|slot| &slot.value)
+    }
+
+    pub fn info(&self, idx: VoxelChunkIndex) -> Option<EntryInfo> {
+        self.slots.get(idx).map(|slot| slot.info)
+    }
+
+    pub fn get(&mut self, idx: VoxelChunkIndex) -> Option<&V> {
+        let slot = self.slots.get_mut(idx)?;
+        self.clock += 1;
+        slot.info.last_use = self.clock;
+        slot.info.hits = slot.info.hits.saturating_add(1);
+        Some(&slot.value)
+    }
+
+    /// Mutates a cached chunk in place and recharges its cost. Returns whether the chunk was
+    /// present; the cache is trimmed if the chunk grew past the budget.
+    pub fn modify<F: FnOnce(&mut V)>(&mut self, idx: VoxelChunkIndex, f: F) -> bool {
+        let Some(slot) = self.slots.get_mut(idx) else {
+            return false;
+        };
+        self.clock += 1;
+        slot.info.last_use = self.clock;
+        slot.info.hits = slot.info.hits.saturating_add(1);
+        f(&mut slot.value);
+
+        let cost = slot.value.cost();
+        self.used = self.used - slot.info.cost + cost;
+        slot.info.cost = cost;
+        self.trim();
+        true
+    }
+
+    /// Inserts a chunk, returning the value it replaced, then evicts until the cache fits. The
+    /// new chunk itself may be evicted if the policy scores it lowest.
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.clock += 1;
+        let info = EntryInfo {
+            idx,
+            cost: value.cost(),
+            last_use: self.clock,
+            hits: 0,
+        };
+        self.used += info.cost;
+        let old = self.slots.insert(idx, Slot { value, info });
+        if let Some(old) = &old {
+            self.used -= old.info.cost;
+        }
+        self.observers
+            .inserted(idx, &self.slots.get(idx).unwrap().value);
+        self.trim();
+        old.map(|slot| slot.value)
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let slot = self.slots.remove(idx)?;
+        self.used -= slot.info.cost;
+        self.observers.removed(idx, &slot.value);
+        Some(slot.value)
+    }
+
+    /// Evicts chunks, lowest score first, until the cache fits its budget.
+    pub fn trim(&mut self) {
+        if self.used <= self.budget {
+            return;
+        }
+
+        let mut victims: Vec<(f64, VoxelChunkIndex)> = self
+            .slots
+            .values()
+            .map(|slot| (self.policy.score(&slot.info), slot.info.idx))
+            .collect();
+        victims.sort_by(|a, b| a.0.total_cmp(&b.0));
+
+        for (_, idx) in victims {
+            if self.used <= self.budget {
+                break;
+            }
+            let slot = self.slots.remove(idx).unwrap();
+            self.used -= slot.info.cost;
+            self.evictions += 1;
+            #[cfg(feature = "instrument")]
+            crate::instrument::emit(
+                || crate::instrument::MapFields {
+                    container: "BudgetCache",
+                    label: self.slots.label(),
+                    len: self.slots.len(),
+                    capacity: self.budget,
+                },
+                || crate::instrument::ContainerEvent::Evict { idx },
+            );
+            self.observers.evicted(idx, &slot.value);
+            if let Some(f) = &mut self.on_evict {
+                f(idx, slot.value);
+            }
+        }
+    }
+
+    /// Drops every chunk without going through the eviction callback.
+    pub fn clear(&mut self) {
+        for (idx, slot) in self.slots.iter() {
+            self.observers.removed(idx, &slot.value);
+        }
+        self.slots.clear();
+        self.used = 0;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.slots.iter().map(|(idx, slot)| (idx, &slot.value))
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct BudgetRef<'a, V> {
+    budget: usize,
+    entries: crate::map::packed::EntriesRef<'a, V>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct BudgetEntries<V> {
+    budget: usize,
+    entries: crate::map::packed::Entries<V>,
+}
+
+/// Serializes as the budget and the entries from least to most recently used, so that an
+/// [`Lru`] cache evicts in the same order once deserialized. Hit counts, the eviction callback
+/// and observers are left out, and the policy is deserialized as its default.
+#[cfg(feature = "serde")]
+impl<V: serde::Serialize, P> serde::Serialize for BudgetCache<V, P> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut slots: Vec<&Slot<V>> = self.slots.values().collect();
+        slots.sort_unstable_by_key(|slot| slot.info.last_use);
+        BudgetRef {
+            budget: self.budget,
+            entries: slots
+                .into_iter()
+                .map(|slot| (slot.info.idx, &slot.value))
+                .collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, V, P> serde::Deserialize<'de> for BudgetCache<V, P>
+where
+    V: ChunkCost + serde::Deserialize<'de>,
+    P: EvictionPolicy + Default,
+{
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let BudgetEntries { budget, entries } = BudgetEntries::deserialize(deserializer)?;
+        let mut cache = BudgetCache::new(budget, P::default());
+        for (idx, value) in entries.into_pairs()? {
+            cache.insert(idx, value);
+        }
+        Ok(cache)
+    }
+}
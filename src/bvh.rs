@@ -0,0 +1,261 @@
+use crate::VoxelChunkIndex;
+
+/// Most chunks a leaf holds.
+const LEAF_SIZE: usize = 4;
+
+#[derive(Clone, Copy)]
+struct Node {
+    min: [f32; 3],
+    max: [f32; 3],
+    /// First chunk of a leaf, or left child of an inner node, the right one following it.
+    start: u32,
+    /// Chunks of a leaf; 0 for inner nodes.
+    count: u32,
+}
+
+/// Bounding volume hierarchy over the unit boxes of a set of chunks, answering ray and frustum
+/// queries without visiting every chunk. Positions are in chunk units: chunk `(x, y, z)` covers
+/// `[x, x + 1) × [y, y + 1) × [z, z + 1)`.
+///
+/// The hierarchy doesn't follow later changes to the map its keys came from: rebuild it with
+/// [`ChunkBvh::rebuild`], which reuses its allocations, when the loaded chunks change.
+#[derive(Clone, Default)]
+pub struct ChunkBvh {
+    nodes: Vec<Node>,
+    /// Chunks in leaf order, with their coordinates.
+    chunks: Vec<(VoxelChunkIndex, [i32; 3])>,
+}
+
+impl ChunkBvh {
+    pub fn new() -> ChunkBvh {
+        Self::default()
+    }
+
+    /// Hierarchy over `keys`, e.g. the keys of any of the crate's maps.
+    pub fn build<I: IntoIterator<Item = VoxelChunkIndex>>(keys: I) -> ChunkBvh {
+        let mut bvh = ChunkBvh::new();
+        bvh.rebuild(keys);
+        bvh
+    }
+
+    /// Replaces the chunks of the hierarchy by `keys`.
+    pub fn rebuild<I: IntoIterator<Item = VoxelChunkIndex>>(&mut self, keys: I) {
+        self.chunks.clear();
+        self.chunks.extend(keys.into_iter().map(|idx| {
+            let (x, y, z) = idx.to_coords();
+            (idx, [x, y, z])
+        }));
+        self.nodes.clear();
+        if !self.chunks.is_empty() {
+            self.nodes.push(Node {
+                min: [0.0; 3],
+                max: [0.0; 3],
+                start: 0,
+                count: 0,
+            });
+            self.split(0, 0, self.chunks.len());
+        }
+    }
+
+    /// Turns node `node` into the subtree over `chunks[start..end]`.
+    fn split(&mut self, node: usize, start: usize, end: usize) {
+        let chunks = &mut self.chunks[start..end];
+        let mut min = [i32::MAX; 3];
+        let mut max = [i32::MIN; 3];
+        for (_, pos) in chunks.iter() {
+            for axis in 0..3 {
+                min[axis] = min[axis].min(pos[axis]);
+                max[axis] = max[axis].max(pos[axis] + 1);
+            }
+        }
+        self.nodes[node].min = min.map(|v| v as f32);
+        self.nodes[node].max = max.map(|v| v as f32);
+
+        if chunks.len() <= LEAF_SIZE {
+            self.nodes[node].start = start as u32;
+            self.nodes[node].count = chunks.len() as u32;
+            return;
+        }
+
+        // Median split along the longest axis.
+        let axis = (0..3).max_by_key(|&axis| max[axis] - min[axis]).unwrap();
+        let mid = chunks.len() / 2;
+        chunks.select_nth_unstable_by_key(mid, |(_, pos)| pos[axis]);
+
+        let left = self.nodes.len();
+        let empty = self.nodes[node];
+        self.nodes.push(empty);
+        self.nodes.push(empty);
+        self.nodes[node].start = left as u32;
+        self.split(left, start, start + mid);
+        self.split(left + 1, start + mid, end);
+    }
+
+    pub fn len(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty()
+    }
+
+    /// Every chunk the ray from `origin` along `dir` crosses within `max_distance`, nearest
+    /// first, with the distance at which the ray enters it. Distances are in units of `dir`.
+    pub fn raycast(
+        &self,
+        origin: (f32, f32, f32),
+        dir: (f32, f32, f32),
+        max_distance: f32,
+    ) -> Vec<(VoxelChunkIndex, f32)> {
+        let ray = Ray::new(origin, dir);
+        let mut hits = Vec::new();
+        self.visit(
+            |node| ray.enter(node.min, node.max, max_distance).is_some(),
+            |idx, pos| {
+                let min = pos.map(|v| v as f32);
+                if let Some(t) = ray.enter(min, min.map(|v| v + 1.0), max_distance) {
+                    hits.push((idx, t));
+                }
+            },
+        );
+        hits.sort_by(|a, b| a.1.total_cmp(&b.1));
+        hits
+    }
+
+    /// Nearest chunk the ray crosses within `max_distance`, like [`ChunkBvh::raycast`] but
+    /// skipping every subtree that can't hold a nearer hit.
+    pub fn first_hit(
+        &self,
+        origin: (f32, f32, f32),
+        dir: (f32, f32, f32),
+        max_distance: f32,
+    ) -> Option<(VoxelChunkIndex, f32)> {
+        let ray = Ray::new(origin, dir);
+        let mut best: Option<(VoxelChunkIndex, f32)> = None;
+        let mut stack = Vec::new();
+        if let Some(root) = self.nodes.first() {
+            stack.extend(ray.enter(root.min, root.max, max_distance).map(|t| (0, t)));
+        }
+        while let Some((node, t)) = stack.pop() {
+            if best.is_some_and(|(_, best)| best <= t) {
+                continue;
+            }
+            let limit = best.map_or(max_distance, |(_, best)| best);
+            let Node { start, count, .. } = self.nodes[node];
+            if count > 0 {
+                for &(idx, pos) in &self.chunks[start as usize..][..count as usize] {
+                    let min = pos.map(|v| v as f32);
+                    if let Some(t) = ray.enter(min, min.map(|v| v + 1.0), limit) {
+                        if best.is_none_or(|(_, best)| t < best) {
+                            best = Some((idx, t));
+                        }
+                    }
+                }
+                continue;
+            }
+
+            let children = [start as usize, start as usize + 1].map(|child| {
+                let Node { min, max, .. } = self.nodes[child];
+                (child, ray.enter(min, max, limit))
+            });
+            // Push the farther child first so the nearer one is searched first.
+            let (near, far) = match children {
+                [(a, Some(ta)), (b, Some(tb))] if tb < ta => ((b, tb), Some((a, ta))),
+                [(a, Some(ta)), (b, Some(tb))] => ((a, ta), Some((b, tb))),
+                [(a, Some(ta)), (_, None)] | [(_, None), (a, Some(ta))] => ((a, ta), None),
+                [(_, None), (_, None)] => continue,
+            };
+            stack.extend(far);
+            stack.push(near);
+        }
+        best
+    }
+
+    /// Chunks not entirely outside any of `planes`, e.g. the six planes of a view frustum. A
+    /// plane `(a, b, c, d)` keeps the points where `a * x + b * y + c * z + d >= 0`.
+    pub fn frustum(&self, planes: &[(f32, f32, f32, f32)]) -> Vec<VoxelChunkIndex> {
+        let mut hits = Vec::new();
+        self.visit(
+            |node| inside_planes(planes, node.min, node.max),
+            |idx, pos| {
+                let min = pos.map(|v| v as f32);
+                if inside_planes(planes, min, min.map(|v| v + 1.0)) {
+                    hits.push(idx);
+                }
+            },
+        );
+        hits
+    }
+
+    /// Calls `leaf` on the chunks of every leaf reached through nodes passing `enter`.
+    fn visit(
+        &self,
+        enter: impl Fn(&Node) -> bool,
+        mut leaf: impl FnMut(VoxelChunkIndex, [i32; 3]),
+    ) {
+        let mut stack = if self.nodes.is_empty() {
+            vec![]
+        } else {
+            vec![0]
+        };
+        while let Some(node) = stack.pop() {
+            let node = &self.nodes[node];
+            if !enter(node) {
+                continue;
+            }
+            if node.count > 0 {
+                for &(idx, pos) in &self.chunks[node.start as usize..][..node.count as usize] {
+                    leaf(idx, pos);
+                }
+            } else {
+                stack.push(node.start as usize);
+                stack.push(node.start as usize + 1);
+            }
+        }
+    }
+}
+
+pub(crate) struct Ray {
+    origin: [f32; 3],
+    inv_dir: [f32; 3],
+}
+
+impl Ray {
+    pub(crate) fn new(origin: (f32, f32, f32), dir: (f32, f32, f32)) -> Ray {
+        Ray {
+            origin: [origin.0, origin.1, origin.2],
+            inv_dir: [1.0 / dir.0, 1.0 / dir.1, 1.0 / dir.2],
+        }
+    }
+
+    /// Distance at which the ray enters the box, if it does so before `max_distance`. A ray
+    /// starting inside the box enters it at 0; one running exactly along a face may miss it.
+    pub(crate) fn enter(&self, min: [f32; 3], max: [f32; 3], max_distance: f32) -> Option<f32> {
+        let mut near = 0.0f32;
+        let mut far = max_distance;
+        for axis in 0..3 {
+            let t0 = (min[axis] - self.origin[axis]) * self.inv_dir[axis];
+            let t1 = (max[axis] - self.origin[axis]) * self.inv_dir[axis];
+            near = near.max(t0.min(t1));
+            far = far.min(t0.max(t1));
+        }
+        (near <= far).then_some(near)
+    }
+}
+
+/// Whether the box isn't entirely on the outer side of any plane.
+fn inside_planes(planes: &[(f32, f32, f32, f32)], min: [f32; 3], max: [f32; 3]) -> bool {
+    planes.iter().all(|&(a, b, c, d)| {
+        // Corner of the box farthest along the plane normal.
+        let x = if a >= 0.0 { max[0] } else { min[0] };
+        let y = if b >= 0.0 { max[1] } else { min[1] };
+        let z = if c >= 0.0 { max[2] } else { min[2] };
+        a * x + b * y + c * z + d >= 0.0
+    })
+}
+
+impl FromIterator<VoxelChunkIndex> for ChunkBvh {
+    fn from_iter<I: IntoIterator<Item = VoxelChunkIndex>>(iter: I) -> ChunkBvh {
+        ChunkBvh::build(iter)
+    }
+}
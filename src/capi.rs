@@ -0,0 +1,472 @@
+//! C API to [`VoxelChunkMap`], so that C and C++ engines embed the map through a stable ABI.
+//! Values are opaque 64-bit numbers, e.g. pointers or handles into the engine's own storage.
+//!
+//! `include/hash_funsies.h` in the repository declares these functions, as `cbindgen` generates
+//! it from this module with the repository's `cbindgen.toml`. Link the static or dynamic
+//! library built by
+//! `cargo rustc --release --lib --features capi --crate-type staticlib` (or `cdylib`).
+//!
+//! Maps are not thread-safe: share one between threads only behind a lock.
+//!
+//! Engine plugins, e.g. from the C# of Unity or the C++ of Unreal, cross the FFI boundary once
+//! per batch with the functions taking arrays: [`hf_chunk_encode_batch`],
+//! [`hf_chunk_decode_batch`], [`hf_chunk_map_get_batch`] and [`hf_view_delta`]. `bool` is one
+//! byte, which C# declares with `[MarshalAs(UnmanagedType.U1)]`.
+//!
+//! `bindings/hash_funsies.lua` loads the library through the FFI of LuaJIT, for mods scripting
+//! world queries, and `bindings/HashFunsies.cs` through P/Invoke, for Godot .NET projects.
+
+use std::ffi::c_void;
+
+use crate::interest::view_delta;
+use crate::region::write_region_keys;
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+/// Keys looked up together by the batch functions, see [`VoxelChunkMap::get_many`].
+const BATCH: usize = 8;
+
+/// A [`VoxelChunkMap`] of `uint64_t`, opaque to C.
+pub struct HfChunkMap(VoxelChunkMap<u64>);
+
+/// Called by [`hf_chunk_map_for_each`] with each key and value, and the caller's `user`
+/// pointer.
+pub type HfChunkVisitor = extern "C" fn(key: u32, value: u64, user: *mut c_void);
+
+/// # Safety
+///
+/// `out` must be null or point to a writable `u64`.
+unsafe fn write_out(out: *mut u64, value: Option<u64>) -> bool {
+    match value {
+        Some(value) => {
+            if !out.is_null() {
+                // SAFETY: the caller passes null or a writable pointer.
+                unsafe { out.write(value) };
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+/// # Safety
+///
+/// `keys` must point to `len` keys, unless `len` is 0.
+unsafe fn keys<'a>(keys: *const u32, len: usize) -> &'a [VoxelChunkIndex] {
+    if len == 0 {
+        return &[];
+    }
+    // SAFETY: `VoxelChunkIndex` is a `repr(transparent)` `u32`, and the caller passes `len` of
+    // them.
+    unsafe { std::slice::from_raw_parts(keys.cast(), len) }
+}
+
+#[no_mangle]
+pub extern "C" fn hf_chunk_encode(x: i32, y: i32, z: i32) -> u32 {
+    VoxelChunkIndex::from_coords(x, y, z).0
+}
+
+/// # Safety
+///
+/// `x`, `y` and `z` must point to writable `i32`s.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_decode(key: u32, x: *mut i32, y: *mut i32, z: *mut i32) {
+    let coords = VoxelChunkIndex(key).to_coords();
+    // SAFETY: the caller passes writable pointers.
+    unsafe {
+        x.write(coords.0);
+        y.write(coords.1);
+        z.write(coords.2);
+    }
+}
+
+/// Writes to `keys[i]` the key of the chunk whose coordinates are `coords[3 * i..3 * i + 3]`,
+/// for each `i` below `len`.
+///
+/// # Safety
+///
+/// `coords` must point to `3 * len` coordinates, and `keys` to `len` writable keys.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_encode_batch(coords: *const i32, len: usize, keys: *mut u32) {
+    if len == 0 {
+        return;
+    }
+    // SAFETY: the caller passes `len` coordinate triples and room for `len` keys.
+    let (coords, keys) = unsafe {
+        (
+            std::slice::from_raw_parts(coords, 3 * len),
+            std::slice::from_raw_parts_mut(keys, len),
+        )
+    };
+    for (key, xyz) in keys.iter_mut().zip(coords.chunks_exact(3)) {
+        *key = VoxelChunkIndex::from_coords(xyz[0], xyz[1], xyz[2]).0;
+    }
+}
+
+/// Inverse of [`hf_chunk_encode_batch`].
+///
+/// # Safety
+///
+/// `keys` must point to `len` keys, and `coords` to `3 * len` writable coordinates.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_decode_batch(keys: *const u32, len: usize, coords: *mut i32) {
+    // SAFETY: the caller passes `len` keys.
+    let keys = unsafe { self::keys(keys, len) };
+    if len == 0 {
+        return;
+    }
+    // SAFETY: the caller passes room for `len` coordinate triples.
+    let coords = unsafe { std::slice::from_raw_parts_mut(coords, 3 * len) };
+    for (xyz, idx) in coords.chunks_exact_mut(3).zip(keys) {
+        let (x, y, z) = idx.to_coords();
+        xyz.copy_from_slice(&[x, y, z]);
+    }
+}
+
+/// Writes the [`VoxelChunkIndex::neighbors`] of `key` to `out`, returning how many.
+///
+/// # Safety
+///
+/// `out` must point to 26 writable keys.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_neighbors(key: u32, out: *mut u32) -> u32 {
+    // SAFETY: the caller passes room for 26 keys, as many as a chunk has neighbors.
+    let out = unsafe { std::slice::from_raw_parts_mut(out, 26) };
+    let mut len = 0;
+    for (slot, idx) in out.iter_mut().zip(VoxelChunkIndex(key).neighbors()) {
+        *slot = idx.0;
+        len += 1;
+    }
+    len
+}
+
+/// Number of chunks in the box from `min` inclusive to `max` exclusive, clipped to the
+/// representable world, writing their keys to `out`, in
+/// [`ChunkAabb::iter`](crate::ChunkAabb::iter) order, if they fit in `capacity`. An inverted
+/// box, with `min` past `max` on some axis, holds no chunks.
+///
+/// # Safety
+///
+/// `min` and `max` must point to 3 coordinates each, and `out` to `capacity` writable keys.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_region(
+    min: *const i32,
+    max: *const i32,
+    out: *mut u32,
+    capacity: usize,
+) -> usize {
+    // SAFETY: forwarded from the caller.
+    unsafe { write_region_keys(min, max, out, capacity) }
+}
+
+/// Chunks entering and leaving the view of a viewer moving from the ball of `before_radius`
+/// chunks around `before_center`, null if it had no view yet, to the ball of `after_radius`
+/// around `after_center`: see [`view_delta`]. Writes their numbers to `enter_len` and
+/// `leave_len`, and, if they fit in `enter_capacity` and `leave_capacity`, the keys to `enter`
+/// and `leave`, returning whether they did. Callers short of room call again with more.
+/// A null `after_center` or the negative radius of a center returns false with both lengths 0.
+///
+/// # Safety
+///
+/// The centers must be null, for `before_center` only, or point to 3 coordinates, `enter` and
+/// `leave` to their capacity of writable keys, and the lengths to writable `size_t`s.
+#[no_mangle]
+#[allow(clippy::too_many_arguments)]
+pub unsafe extern "C" fn hf_view_delta(
+    before_center: *const i32,
+    before_radius: i32,
+    after_center: *const i32,
+    after_radius: i32,
+    enter: *mut u32,
+    enter_capacity: usize,
+    enter_len: *mut usize,
+    leave: *mut u32,
+    leave_capacity: usize,
+    leave_len: *mut usize,
+) -> bool {
+    // SAFETY: the caller passes null or 3 coordinates.
+    let center = |center: *const i32| unsafe { center.cast::<[i32; 3]>().as_ref() };
+    let ball = |center: &[i32; 3], radius: i32| ((center[0], center[1], center[2]), radius);
+    let write_lens = |entering: usize, leaving: usize| {
+        // SAFETY: the caller passes writable lengths.
+        unsafe {
+            enter_len.write(entering);
+            leave_len.write(leaving);
+        }
+    };
+    let before = center(before_center).map(|center| ball(center, before_radius));
+    let after = center(after_center).map(|center| ball(center, after_radius));
+    let (before, after) = match after {
+        Some(after) if before.is_none_or(|(_, radius)| radius >= 0) && after.1 >= 0 => {
+            (before, after)
+        }
+        _ => {
+            write_lens(0, 0);
+            return false;
+        }
+    };
+    let (entering, leaving) = view_delta(before, after);
+    write_lens(entering.len(), leaving.len());
+    if entering.len() > enter_capacity || leaving.len() > leave_capacity {
+        return false;
+    }
+    for (out, keys) in [(enter, &entering), (leave, &leaving)] {
+        if !keys.is_empty() {
+            // SAFETY: the caller passes room for the capacity, which holds the keys.
+            let out = unsafe { std::slice::from_raw_parts_mut(out, keys.len()) };
+            for (slot, idx) in out.iter_mut().zip(keys) {
+                *slot = idx.0;
+            }
+        }
+    }
+    true
+}
+
+/// A new empty map, to free with [`hf_chunk_map_free`].
+#[no_mangle]
+pub extern "C" fn hf_chunk_map_new() -> *mut HfChunkMap {
+    hf_chunk_map_with_capacity(0)
+}
+
+/// A new empty map with room for `capacity` entries.
+#[no_mangle]
+pub extern "C" fn hf_chunk_map_with_capacity(capacity: usize) -> *mut HfChunkMap {
+    Box::into_raw(Box::new(HfChunkMap(VoxelChunkMap::with_capacity(capacity))))
+}
+
+/// Frees `map`; null is ignored.
+///
+/// # Safety
+///
+/// `map` must be null or come from [`hf_chunk_map_new`] or [`hf_chunk_map_with_capacity`],
+/// and not be used after.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_free(map: *mut HfChunkMap) {
+    if !map.is_null() {
+        // SAFETY: the caller passes a pointer from `Box::into_raw`, once.
+        drop(unsafe { Box::from_raw(map) });
+    }
+}
+
+/// # Safety
+///
+/// `map` must be a live map, as for every `hf_chunk_map_` function taking one.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_len(map: *const HfChunkMap) -> usize {
+    // SAFETY: the caller passes a live map.
+    unsafe { &(*map).0 }.len()
+}
+
+/// # Safety
+///
+/// See [`hf_chunk_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_clear(map: *mut HfChunkMap) {
+    // SAFETY: the caller passes a live map.
+    unsafe { &mut (*map).0 }.clear();
+}
+
+/// Inserts `value` at `key`, returning whether it replaced a value, then written to
+/// `previous` unless it is null.
+///
+/// # Safety
+///
+/// See [`hf_chunk_map_len`]; `previous` must be null or point to a writable `u64`.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_insert(
+    map: *mut HfChunkMap,
+    key: u32,
+    value: u64,
+    previous: *mut u64,
+) -> bool {
+    // SAFETY: the caller passes a live map.
+    let map = unsafe { &mut (*map).0 };
+    // SAFETY: forwarded from the caller.
+    unsafe { write_out(previous, map.insert(VoxelChunkIndex(key), value)) }
+}
+
+/// Whether `map` has a value at `key`, then written to `value` unless it is null.
+///
+/// # Safety
+///
+/// See [`hf_chunk_map_len`]; `value` must be null or point to a writable `u64`.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_get(
+    map: *const HfChunkMap,
+    key: u32,
+    value: *mut u64,
+) -> bool {
+    // SAFETY: the caller passes a live map.
+    let map = unsafe { &(*map).0 };
+    // SAFETY: forwarded from the caller.
+    unsafe { write_out(value, map.get(VoxelChunkIndex(key)).copied()) }
+}
+
+/// Removes the value at `key`, returning whether there was one, then written to `value` unless
+/// it is null.
+///
+/// # Safety
+///
+/// See [`hf_chunk_map_len`]; `value` must be null or point to a writable `u64`.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_remove(
+    map: *mut HfChunkMap,
+    key: u32,
+    value: *mut u64,
+) -> bool {
+    // SAFETY: the caller passes a live map.
+    let map = unsafe { &mut (*map).0 };
+    // SAFETY: forwarded from the caller.
+    unsafe { write_out(value, map.remove(VoxelChunkIndex(key))) }
+}
+
+/// Inserts `values[i]` at `keys[i]` for each `i` below `len`, later pairs replacing earlier
+/// ones with the same key.
+///
+/// # Safety
+///
+/// See [`hf_chunk_map_len`]; `keys` and `values` must point to `len` elements each.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_insert_batch(
+    map: *mut HfChunkMap,
+    keys: *const u32,
+    values: *const u64,
+    len: usize,
+) {
+    // SAFETY: the caller passes a live map, and `len` keys and values.
+    let (map, keys) = unsafe { (&mut (*map).0, self::keys(keys, len)) };
+    if len == 0 {
+        return;
+    }
+    // SAFETY: as above.
+    let values = unsafe { std::slice::from_raw_parts(values, len) };
+    map.reserve(len);
+    for (&idx, &value) in keys.iter().zip(values) {
+        map.insert(idx, value);
+    }
+}
+
+/// Looks up `len` keys, prefetching several at a time: writes the value of `keys[i]` to
+/// `values[i]` and whether there is one to `found[i]`, returning how many there are. Values of
+/// keys not found are left alone.
+///
+/// # Safety
+///
+/// See [`hf_chunk_map_len`]; `keys`, `values` and `found` must point to `len` elements each.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_get_batch(
+    map: *const HfChunkMap,
+    keys: *const u32,
+    len: usize,
+    values: *mut u64,
+    found: *mut bool,
+) -> usize {
+    // SAFETY: the caller passes a live map, and `len` keys.
+    let (map, keys) = unsafe { (&(*map).0, self::keys(keys, len)) };
+    if len == 0 {
+        return 0;
+    }
+    // SAFETY: the caller passes room for `len` values and flags.
+    let (values, found) = unsafe {
+        (
+            std::slice::from_raw_parts_mut(values, len),
+            std::slice::from_raw_parts_mut(found, len),
+        )
+    };
+    let mut hits = 0;
+    let mut store = |i: usize, value: Option<&u64>| {
+        found[i] = value.is_some();
+        if let Some(&value) = value {
+            values[i] = value;
+            hits += 1;
+        }
+    };
+    let batches = keys.chunks_exact(BATCH);
+    let rest = batches.remainder();
+    for (b, batch) in batches.enumerate() {
+        let batch: [VoxelChunkIndex; BATCH] = batch.try_into().unwrap();
+        for (i, value) in map.get_many(batch).into_iter().enumerate() {
+            store(b * BATCH + i, value);
+        }
+    }
+    for (i, &idx) in rest.iter().enumerate() {
+        store(len - rest.len() + i, map.get(idx));
+    }
+    hits
+}
+
+/// Removes the values at `len` keys, returning how many there were.
+///
+/// # Safety
+///
+/// See [`hf_chunk_map_len`]; `keys` must point to `len` keys.
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_remove_batch(
+    map: *mut HfChunkMap,
+    keys: *const u32,
+    len: usize,
+) -> usize {
+    // SAFETY: the caller passes a live map, and `len` keys.
+    let (map, keys) = unsafe { (&mut (*map).0, self::keys(keys, len)) };
+    keys.iter()
+        .filter(|&&idx| map.remove(idx).is_some())
+        .count()
+}
+
+/// Calls `visit` with each key and value of `map`, in no particular order, and `user`. `visit`
+/// must not modify `map`.
+///
+/// # Safety
+///
+/// See [`hf_chunk_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_chunk_map_for_each(
+    map: *const HfChunkMap,
+    visit: HfChunkVisitor,
+    user: *mut c_void,
+) {
+    // SAFETY: the caller passes a live map.
+    let map = unsafe { &(*map).0 };
+    for (idx, &value) in map.iter() {
+        visit(idx.0, value, user);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn view_delta_rejects_bad_input_without_panicking() {
+        let center = [0; 3];
+        let (mut enter, mut leave) = ([0; 64], [0; 64]);
+        let (mut enter_len, mut leave_len) = (1, 1);
+        let mut delta = |before: *const i32, before_radius, after: *const i32, after_radius| {
+            // SAFETY: the centers are null or 3 coordinates, and the outputs are writable.
+            unsafe {
+                hf_view_delta(
+                    before,
+                    before_radius,
+                    after,
+                    after_radius,
+                    enter.as_mut_ptr(),
+                    enter.len(),
+                    &mut enter_len,
+                    leave.as_mut_ptr(),
+                    leave.len(),
+                    &mut leave_len,
+                )
+            }
+        };
+        assert!(!delta(center.as_ptr(), 1, std::ptr::null(), 1));
+        assert!(!delta(center.as_ptr(), -1, center.as_ptr(), 1));
+        assert!(!delta(std::ptr::null(), 1, center.as_ptr(), -1));
+        // The radius of a missing view is ignored.
+        assert!(delta(std::ptr::null(), -1, center.as_ptr(), 1));
+        let ball = view_delta(None, ((0, 0, 0), 1)).0;
+        assert_eq!((enter_len, leave_len), (ball.len(), 0));
+        assert_eq!(
+            &enter[..ball.len()],
+            ball.iter().map(|idx| idx.0).collect::<Vec<_>>()
+        );
+    }
+}
@@ -0,0 +1,31 @@
+//! [`RecordCipher`], the hook through which a [`RegionStore`](crate::RegionStore) and a
+//! [`WalChunkMap`](crate::WalChunkMap) encrypt what they persist.
+
+use std::io;
+
+/// Authenticated encryption of persisted records, e.g. AES-GCM or ChaCha20-Poly1305 from the
+/// crate of your choice, for games whose saves must not be read or tampered with. Nonces are up
+/// to the cipher: one that draws a random nonce per record and prepends it to the ciphertext
+/// counts it in [`RecordCipher::overhead`].
+///
+/// Every record is sealed with associated data binding it to its place, such as its chunk key,
+/// so that records can't be swapped around undetected either.
+pub trait RecordCipher: Send + Sync {
+    /// Bytes a sealed record takes on top of its plaintext, the same for every record.
+    fn overhead(&self) -> usize;
+
+    /// Encrypts `plaintext` and authenticates it along with `associated_data`.
+    fn seal(&self, associated_data: &[u8], plaintext: &[u8]) -> Vec<u8>;
+
+    /// Decrypts a record [`RecordCipher::seal`] returned, or fails if it or `associated_data`
+    /// doesn't authenticate.
+    fn open(&self, associated_data: &[u8], sealed: &[u8]) -> io::Result<Vec<u8>>;
+}
+
+/// The error of a record that doesn't authenticate, for [`RecordCipher::open`] to return.
+pub fn authentication_failed() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        "record failed authentication: tampered with, or sealed with another key",
+    )
+}
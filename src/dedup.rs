@@ -0,0 +1,498 @@
+//! [`DedupChunkStore`], a [`ChunkStore`] keeping identical blobs once, for worlds full of
+//! identical chunks: flat worlds, oceans, the air above the terrain.
+//!
+//! The directory of the store holds `contents.hfc`, the magic `HFCS` and a version byte, then
+//! one record per distinct blob made of its content id, its [`ContentHasher`] hash, its length,
+//! the blob and a CRC32 of the record; and `index.vcix`, the [`VoxelChunkMap`] from each chunk
+//! to the id of its blob, written by [`VoxelChunkMap::write_to`].
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::hashers::ContentHasher;
+use crate::store::ChunkStore;
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+const MAGIC: &[u8; 4] = b"HFCS";
+const VERSION: u8 = 1;
+const HEADER_LEN: u64 = 5;
+/// Content id, hash and length in front of each blob.
+const RECORD_HEADER_LEN: usize = 20;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A distinct blob of the contents file.
+struct Content {
+    offset: u64,
+    hash: u64,
+    len: u32,
+    /// Chunks whose blob it is; unreferenced contents stay until [`DedupChunkStore::compact`],
+    /// in case a chunk gets the same blob again.
+    refs: u32,
+}
+
+impl Content {
+    fn record_len(&self) -> u64 {
+        (RECORD_HEADER_LEN + self.len as usize + 4) as u64
+    }
+}
+
+/// Space a [`DedupChunkStore`] takes and saves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DedupStats {
+    /// Chunks with a blob.
+    pub chunks: usize,
+    /// Distinct blobs among them.
+    pub contents: usize,
+    /// Total size of the blobs of every chunk, as a store without deduplication would hold.
+    pub logical_bytes: u64,
+    /// Size of the contents file.
+    pub stored_bytes: u64,
+    /// Bytes of the contents file no chunk uses anymore, that a compaction would free.
+    pub garbage_bytes: u64,
+}
+
+/// [`ChunkStore`] in a directory, storing each distinct blob once and every chunk as the id of
+/// its blob. Writing a blob hashes it with [`ContentHasher`] and compares it with the stored
+/// blobs of the same hash and length, so that chunks only ever share truly equal blobs.
+///
+/// New blobs are appended to the contents file right away, but nothing is synced to disk, and
+/// the index isn't written, before [`ChunkStore::sync`]. Blobs no chunk uses anymore stay in the
+/// contents file until [`DedupChunkStore::compact`].
+pub struct DedupChunkStore {
+    dir: PathBuf,
+    file: File,
+    end: u64,
+    index: VoxelChunkMap<u64>,
+    contents: HashMap<u64, Content>,
+    by_hash: HashMap<u64, Vec<u64>>,
+    next_id: u64,
+    garbage: u64,
+    index_dirty: bool,
+}
+
+impl DedupChunkStore {
+    /// Opens the store in `dir`, creating the directory and an empty store if needed. Records
+    /// a crash cut short at the end of the contents file are dropped.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<DedupChunkStore> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+        for tmp in ["index.vcix.tmp", "contents.hfc.tmp"] {
+            match fs::remove_file(dir.join(tmp)) {
+                Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                _ => {}
+            }
+        }
+        let index = match File::open(dir.join("index.vcix")) {
+            Ok(file) => VoxelChunkMap::read_from(io::BufReader::new(file))?,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => VoxelChunkMap::new(),
+            Err(err) => return Err(err),
+        };
+
+        let path = dir.join("contents.hfc");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let bytes = fs::read(&path)?;
+        let mut header = [0; HEADER_LEN as usize];
+        header[..4].copy_from_slice(MAGIC);
+        header[4] = VERSION;
+        let mut contents = HashMap::new();
+        let end = if header.starts_with(&bytes) {
+            file.set_len(0)?;
+            file.write_all(&header)?;
+            HEADER_LEN
+        } else {
+            if bytes.len() < header.len() || bytes[..4] != header[..4] {
+                return Err(invalid(format!("{} isn't a contents file", path.display())));
+            }
+            if bytes[4] != VERSION {
+                return Err(invalid(format!(
+                    "contents file version {}, expected {VERSION}",
+                    bytes[4]
+                )));
+            }
+            scan(&bytes, &mut contents)
+        };
+        file.set_len(end)?;
+
+        let mut store = DedupChunkStore {
+            dir,
+            file,
+            end,
+            index,
+            by_hash: HashMap::new(),
+            next_id: contents.keys().max().map_or(0, |&id| id + 1),
+            contents,
+            garbage: 0,
+            index_dirty: false,
+        };
+        for (_, &id) in store.index.iter() {
+            store.next_id = store.next_id.max(id + 1);
+            if let Some(content) = store.contents.get_mut(&id) {
+                content.refs += 1;
+            }
+        }
+        for (&id, content) in &store.contents {
+            store.by_hash.entry(content.hash).or_default().push(id);
+            if content.refs == 0 {
+                store.garbage += content.record_len();
+            }
+        }
+        Ok(store)
+    }
+
+    /// Chunks with a blob.
+    pub fn len(&self) -> usize {
+        self.index.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.index.is_empty()
+    }
+
+    pub fn stats(&self) -> DedupStats {
+        let used = self.contents.values().filter(|content| content.refs > 0);
+        DedupStats {
+            chunks: self.index.len(),
+            contents: used.clone().count(),
+            logical_bytes: used
+                .map(|content| content.len as u64 * content.refs as u64)
+                .sum(),
+            stored_bytes: self.end,
+            garbage_bytes: self.garbage,
+        }
+    }
+
+    /// Reads the record of `content`, checking it against its checksum.
+    fn read_content(&mut self, id: u64) -> io::Result<Vec<u8>> {
+        let content = &self.contents[&id];
+        let mut record = vec![0; content.record_len() as usize];
+        self.file.seek(SeekFrom::Start(content.offset))?;
+        self.file.read_exact(&mut record)?;
+        let (body, crc) = record.split_at(record.len() - 4);
+        if crc32fast::hash(body).to_le_bytes() != crc {
+            return Err(invalid(format!("content {id} is corrupted")));
+        }
+        record.truncate(record.len() - 4);
+        record.drain(..RECORD_HEADER_LEN);
+        Ok(record)
+    }
+
+    /// Id of the stored blob equal to `blob`, appending it first if there is none.
+    fn intern(&mut self, blob: &[u8]) -> io::Result<u64> {
+        let len = u32::try_from(blob.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "chunk blob over 4 GiB"))?;
+        let hash = ContentHasher::hash(blob);
+        let candidates = self.by_hash.get(&hash).cloned().unwrap_or_default();
+        for id in candidates {
+            if self.contents[&id].len == len && self.read_content(id)? == blob {
+                return Ok(id);
+            }
+        }
+
+        let id = self.next_id;
+        let mut record = Vec::with_capacity(RECORD_HEADER_LEN + blob.len() + 4);
+        record.extend_from_slice(&id.to_le_bytes());
+        record.extend_from_slice(&hash.to_le_bytes());
+        record.extend_from_slice(&len.to_le_bytes());
+        record.extend_from_slice(blob);
+        record.extend_from_slice(&crc32fast::hash(&record).to_le_bytes());
+        self.file.seek(SeekFrom::Start(self.end))?;
+        self.file.write_all(&record)?;
+        let content = Content {
+            offset: self.end,
+            hash,
+            len,
+            refs: 0,
+        };
+        self.end += record.len() as u64;
+        // Counted as garbage until the chunk that wanted it takes a reference.
+        self.garbage += content.record_len();
+        self.next_id += 1;
+        self.contents.insert(id, content);
+        self.by_hash.entry(hash).or_default().push(id);
+        Ok(id)
+    }
+
+    fn add_ref(&mut self, id: u64) {
+        let content = self.contents.get_mut(&id).expect("interned content");
+        if content.refs == 0 {
+            self.garbage -= content.record_len();
+        }
+        content.refs += 1;
+    }
+
+    fn drop_ref(&mut self, id: u64) {
+        // Contents the index refers to but that are missing from the contents file have no
+        // reference to drop.
+        if let Some(content) = self.contents.get_mut(&id) {
+            content.refs -= 1;
+            if content.refs == 0 {
+                self.garbage += content.record_len();
+            }
+        }
+    }
+
+    /// Syncs the store, then rewrites the contents file without the blobs no chunk uses. The
+    /// new file is synced and renamed over the old one, so that a crash at any point leaves a
+    /// contents file holding every blob of the index.
+    pub fn compact(&mut self) -> io::Result<()> {
+        self.sync()?;
+        let tmp = self.dir.join("contents.hfc.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        writer.write_all(MAGIC)?;
+        writer.write_all(&[VERSION])?;
+        let mut ids: Vec<u64> = self
+            .contents
+            .iter()
+            .filter(|(_, content)| content.refs > 0)
+            .map(|(&id, _)| id)
+            .collect();
+        ids.sort_unstable_by_key(|id| self.contents[id].offset);
+        let mut end = HEADER_LEN;
+        let mut offsets = Vec::with_capacity(ids.len());
+        let mut record = Vec::new();
+        for &id in &ids {
+            let content = &self.contents[&id];
+            record.resize(content.record_len() as usize, 0);
+            self.file.seek(SeekFrom::Start(content.offset))?;
+            self.file.read_exact(&mut record)?;
+            writer.write_all(&record)?;
+            offsets.push(end);
+            end += record.len() as u64;
+        }
+        let file = writer.into_inner().map_err(|err| err.into_error())?;
+        file.sync_all()?;
+        fs::rename(&tmp, self.dir.join("contents.hfc"))?;
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+
+        self.file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(self.dir.join("contents.hfc"))?;
+        self.end = end;
+        self.garbage = 0;
+        self.contents.retain(|_, content| content.refs > 0);
+        for (id, offset) in ids.into_iter().zip(offsets) {
+            self.contents.get_mut(&id).unwrap().offset = offset;
+        }
+        self.by_hash.clear();
+        for (&id, content) in &self.contents {
+            self.by_hash.entry(content.hash).or_default().push(id);
+        }
+        Ok(())
+    }
+}
+
+impl ChunkStore for DedupChunkStore {
+    fn read(&mut self, idx: VoxelChunkIndex) -> io::Result<Option<Vec<u8>>> {
+        let Some(&id) = self.index.get(idx) else {
+            return Ok(None);
+        };
+        if !self.contents.contains_key(&id) {
+            return Err(invalid(format!("content {id} of {idx:?} is missing")));
+        }
+        self.read_content(id).map(Some)
+    }
+
+    fn write(&mut self, idx: VoxelChunkIndex, blob: &[u8]) -> io::Result<()> {
+        if blob.is_empty() {
+            return self.delete(idx).map(|_| ());
+        }
+        let id = self.intern(blob)?;
+        self.add_ref(id);
+        if let Some(old) = self.index.insert(idx, id) {
+            self.drop_ref(old);
+        }
+        self.index_dirty = true;
+        Ok(())
+    }
+
+    fn delete(&mut self, idx: VoxelChunkIndex) -> io::Result<bool> {
+        let Some(old) = self.index.remove(idx) else {
+            return Ok(false);
+        };
+        self.drop_ref(old);
+        self.index_dirty = true;
+        Ok(true)
+    }
+
+    fn keys(&mut self) -> io::Result<Vec<VoxelChunkIndex>> {
+        let mut keys: Vec<VoxelChunkIndex> = self.index.keys().collect();
+        keys.sort_unstable();
+        Ok(keys)
+    }
+
+    /// Syncs the contents file, then writes the index to a new file renamed over the previous
+    /// one, so that the index on disk only ever refers to synced blobs.
+    fn sync(&mut self) -> io::Result<()> {
+        self.file.sync_data()?;
+        if !self.index_dirty {
+            return Ok(());
+        }
+        let tmp = self.dir.join("index.vcix.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        self.index.write_to(&mut writer)?;
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp, self.dir.join("index.vcix"))?;
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+        self.index_dirty = false;
+        Ok(())
+    }
+}
+
+/// Adds the valid records of the contents file `bytes` to `contents`, returning the length of
+/// the file up to the end of the last one.
+fn scan(bytes: &[u8], contents: &mut HashMap<u64, Content>) -> u64 {
+    let mut at = HEADER_LEN as usize;
+    while let Some(header) = bytes.get(at..at + RECORD_HEADER_LEN) {
+        let id = u64::from_le_bytes(header[..8].try_into().unwrap());
+        let hash = u64::from_le_bytes(header[8..16].try_into().unwrap());
+        let len = u32::from_le_bytes(header[16..].try_into().unwrap());
+        let Some(record) = bytes.get(at..at + RECORD_HEADER_LEN + len as usize + 4) else {
+            break;
+        };
+        let (body, crc) = record.split_at(record.len() - 4);
+        if crc32fast::hash(body).to_le_bytes() != crc {
+            break;
+        }
+        let offset = at as u64;
+        contents.insert(
+            id,
+            Content {
+                offset,
+                hash,
+                len,
+                refs: 0,
+            },
+        );
+        at += record.len();
+    }
+    at as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::scratch_dir;
+
+    fn record_len(blob: &[u8]) -> u64 {
+        (RECORD_HEADER_LEN + blob.len() + 4) as u64
+    }
+
+    #[test]
+    fn identical_blobs_are_stored_once() {
+        let dir = scratch_dir("dedup-once");
+        let (air, stone) = (vec![0; 500], vec![1; 700]);
+        let mut store = DedupChunkStore::open(&dir).unwrap();
+        for x in 0..10 {
+            let blob = if x < 8 { &air } else { &stone };
+            store
+                .write(VoxelChunkIndex::from_coords(x, 0, 0), blob)
+                .unwrap();
+        }
+        assert_eq!(
+            store.stats(),
+            DedupStats {
+                chunks: 10,
+                contents: 2,
+                logical_bytes: 8 * 500 + 2 * 700,
+                stored_bytes: HEADER_LEN + record_len(&air) + record_len(&stone),
+                garbage_bytes: 0,
+            }
+        );
+        store.sync().unwrap();
+        drop(store);
+
+        let mut store = DedupChunkStore::open(&dir).unwrap();
+        assert_eq!(store.len(), 10);
+        assert_eq!(store.stats().contents, 2);
+        for x in 0..10 {
+            let blob = if x < 8 { &air } else { &stone };
+            let idx = VoxelChunkIndex::from_coords(x, 0, 0);
+            assert_eq!(store.read(idx).unwrap().as_ref(), Some(blob));
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_live_blobs() {
+        let dir = scratch_dir("dedup-compaction");
+        let key = |x| VoxelChunkIndex::from_coords(x, 1, 2);
+        let mut store = DedupChunkStore::open(&dir).unwrap();
+        for x in 0..6 {
+            store.write(key(x), &[x as u8; 100]).unwrap();
+        }
+        store.write(key(0), &[7; 50]).unwrap();
+        store.delete(key(1)).unwrap();
+        store.write(key(2), &[3; 100]).unwrap();
+        let garbage = 3 * record_len(&[0; 100]);
+        assert_eq!(store.stats().garbage_bytes, garbage);
+
+        let stored = store.stats().stored_bytes;
+        store.compact().unwrap();
+        assert_eq!(store.stats().garbage_bytes, 0);
+        assert_eq!(store.stats().stored_bytes, stored - garbage);
+        drop(store);
+
+        let mut store = DedupChunkStore::open(&dir).unwrap();
+        assert_eq!(store.read(key(0)).unwrap(), Some(vec![7; 50]));
+        assert_eq!(store.read(key(1)).unwrap(), None);
+        assert_eq!(store.read(key(2)).unwrap(), Some(vec![3; 100]));
+        for x in 3..6 {
+            assert_eq!(store.read(key(x)).unwrap(), Some(vec![x as u8; 100]));
+        }
+        // Chunk 2 now shares the blob of chunk 3.
+        assert_eq!(store.stats().contents, 4);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn records_cut_short_are_dropped() {
+        let dir = scratch_dir("dedup-torn");
+        let key = |x| VoxelChunkIndex::from_coords(x, 0, 0);
+        let mut store = DedupChunkStore::open(&dir).unwrap();
+        store.write(key(0), &[1; 100]).unwrap();
+        store.sync().unwrap();
+        // Appended but never synced, nor indexed.
+        store.write(key(1), &[2; 100]).unwrap();
+        drop(store);
+
+        let path = dir.join("contents.hfc");
+        let len = fs::metadata(&path).unwrap().len();
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_len(len - 10)
+            .unwrap();
+
+        let mut store = DedupChunkStore::open(&dir).unwrap();
+        assert_eq!(store.len(), 1);
+        assert_eq!(store.read(key(0)).unwrap(), Some(vec![1; 100]));
+        assert_eq!(
+            fs::metadata(&path).unwrap().len(),
+            len - record_len(&[2; 100])
+        );
+        // The next record starts where the dropped one did.
+        store.write(key(1), &[2; 100]).unwrap();
+        store.sync().unwrap();
+        drop(store);
+        let mut store = DedupChunkStore::open(&dir).unwrap();
+        assert_eq!(store.read(key(1)).unwrap(), Some(vec![2; 100]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+/// Direct-addressed map over a bounded region: each chunk of the declared [`ChunkAabb`] owns one
+/// slot of a flat array, so lookups are a bounds check and an offset computation, no hashing.
+pub struct DenseRegionMap<V> {
+    region: ChunkAabb,
+    cells: Vec<Option<V>>,
+    len: usize,
+}
+
+impl<V> DenseRegionMap<V> {
+    pub fn new(region: ChunkAabb) -> DenseRegionMap<V> {
+        let mut cells = Vec::with_capacity(region.volume());
+        cells.resize_with(region.volume(), || None);
+        DenseRegionMap {
+            region,
+            cells,
+            len: 0,
+        }
+    }
+
+    pub fn region(&self) -> &ChunkAabb {
+        &self.region
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Always the volume of the region: every chunk of it has its slot allocated upfront.
+    pub fn capacity(&self) -> usize {
+        self.cells.len()
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        if self.cells.is_empty() {
+            0.0
+        } else {
+            self.len as f64 / self.cells.len() as f64
+        }
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    /// Returns `None` for keys outside the region as well as for vacant ones.
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let offset = self.region.offset_of(idx)?;
+        self.cells[offset].as_ref()
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        let offset = self.region.offset_of(idx)?;
+        self.cells[offset].as_mut()
+    }
+
+    /// Panics if `idx` lies outside the region.
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        let offset = self
+            .region
+            .offset_of(idx)
+            .expect("index outside of the DenseRegionMap region");
+        let old = self.cells[offset].replace(value);
+        if old.is_none() {
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Gets the slot of `idx` for in-place manipulation. Panics if `idx` lies outside the region.
+    pub fn entry(&mut self, idx: VoxelChunkIndex) -> Entry<'_, V> {
+        let offset = self
+            .region
+            .offset_of(idx)
+            .expect("index outside of the DenseRegionMap region");
+        let cell = &mut self.cells[offset];
+        let len = &mut self.len;
+        if cell.is_some() {
+            Entry::Occupied(OccupiedEntry { idx, cell, len })
+        } else {
+            Entry::Vacant(VacantEntry { idx, cell, len })
+        }
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let offset = self.region.offset_of(idx)?;
+        let old = self.cells[offset].take();
+        if old.is_some() {
+            self.len -= 1;
+        }
+        old
+    }
+
+    pub fn clear(&mut self) {
+        self.cells.iter_mut().for_each(|cell| *cell = None);
+        self.len = 0;
+    }
+
+    /// Iterates occupied slots in region order.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        let region = self.region;
+        self.cells
+            .iter()
+            .enumerate()
+            .filter_map(move |(offset, cell)| Some((region.index_at(offset), cell.as_ref()?)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        let region = self.region;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .filter_map(move |(offset, cell)| Some((region.index_at(offset), cell.as_mut()?)))
+    }
+
+    /// Removes and yields occupied slots in region order. Slots not reached by a partially
+    /// consumed iterator stay in the map.
+    pub fn drain(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, V)> + '_ {
+        let region = self.region;
+        let len = &mut self.len;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .filter_map(move |(offset, cell)| {
+                let value = cell.take()?;
+                *len -= 1;
+                Some((region.index_at(offset), value))
+            })
+    }
+}
+
+/// A view into a single slot of a [`DenseRegionMap`], obtained from [`DenseRegionMap::entry`].
+pub enum Entry<'a, V> {
+    Occupied(OccupiedEntry<'a, V>),
+    Vacant(VacantEntry<'a, V>),
+}
+
+pub struct OccupiedEntry<'a, V> {
+    idx: VoxelChunkIndex,
+    cell: &'a mut Option<V>,
+    len: &'a mut usize,
+}
+
+pub struct VacantEntry<'a, V> {
+    idx: VoxelChunkIndex,
+    cell: &'a mut Option<V>,
+    len: &'a mut usize,
+}
+
+impl<'a, V> Entry<'a, V> {
+    pub fn key(&self) -> VoxelChunkIndex {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, V> OccupiedEntry<'a, V> {
+    pub fn key(&self) -> VoxelChunkIndex {
+        self.idx
+    }
+
+    pub fn get(&self) -> &V {
+        self.cell.as_ref().unwrap()
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        self.cell.as_mut().unwrap()
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        self.cell.as_mut().unwrap()
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        std::mem::replace(self.get_mut(), value)
+    }
+
+    pub fn remove(self) -> V {
+        *self.len -= 1;
+        self.cell.take().unwrap()
+    }
+}
+
+impl<'a, V> VacantEntry<'a, V> {
+    pub fn key(&self) -> VoxelChunkIndex {
+        self.idx
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        *self.len += 1;
+        self.cell.insert(value)
+    }
+}
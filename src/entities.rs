@@ -0,0 +1,116 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hash, RandomState};
+
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+/// Which chunk each entity is in, and which entities each chunk holds, kept in step. Entities
+/// are any id, e.g. a `u64` or an ECS's `Entity`.
+///
+/// Each entity knows its position in the list of its chunk, so that moving it to another chunk
+/// or removing it is `O(1)`: it swaps places with the last of the list, which is why lists keep
+/// no particular order.
+pub struct ChunkEntityIndex<E, S = RandomState> {
+    chunk_of: HashMap<E, (VoxelChunkIndex, usize), S>,
+    chunks: VoxelChunkMap<Vec<E>>,
+}
+
+impl<E: Copy + Eq + Hash, S: BuildHasher + Default> ChunkEntityIndex<E, S> {
+    pub fn new() -> ChunkEntityIndex<E, S> {
+        Self::with_hasher(S::default())
+    }
+}
+
+impl<E: Copy + Eq + Hash, S: BuildHasher + Default> Default for ChunkEntityIndex<E, S> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<E: Copy + Eq + Hash, S: BuildHasher> ChunkEntityIndex<E, S> {
+    pub fn with_hasher(build_hasher: S) -> ChunkEntityIndex<E, S> {
+        ChunkEntityIndex {
+            chunk_of: HashMap::with_hasher(build_hasher),
+            chunks: VoxelChunkMap::new(),
+        }
+    }
+
+    /// Number of entities.
+    pub fn len(&self) -> usize {
+        self.chunk_of.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chunk_of.is_empty()
+    }
+
+    /// Number of chunks holding at least one entity.
+    pub fn chunk_count(&self) -> usize {
+        self.chunks.len()
+    }
+
+    pub fn chunk_of(&self, entity: E) -> Option<VoxelChunkIndex> {
+        self.chunk_of.get(&entity).map(|&(idx, _)| idx)
+    }
+
+    /// Entities in `idx`, in no particular order.
+    pub fn entities_in(&self, idx: VoxelChunkIndex) -> &[E] {
+        self.chunks.get(idx).map_or(&[], Vec::as_slice)
+    }
+
+    /// Chunks holding entities, with them.
+    pub fn chunks(&self) -> impl Iterator<Item = (VoxelChunkIndex, &[E])> {
+        self.chunks
+            .iter()
+            .map(|(idx, entities)| (idx, entities.as_slice()))
+    }
+
+    /// Puts `entity` in `idx`, moving it there if it was in another chunk, whose index is
+    /// returned.
+    pub fn insert(&mut self, entity: E, idx: VoxelChunkIndex) -> Option<VoxelChunkIndex> {
+        let previous = match self.chunk_of.get(&entity) {
+            Some(&(current, _)) if current == idx => return Some(idx),
+            Some(_) => self.unlink(entity),
+            None => None,
+        };
+        let entities = self.chunks.entry(idx).or_default();
+        self.chunk_of.insert(entity, (idx, entities.len()));
+        entities.push(entity);
+        previous
+    }
+
+    /// Removes `entity`, returning the chunk it was in.
+    pub fn remove(&mut self, entity: E) -> Option<VoxelChunkIndex> {
+        let previous = self.unlink(entity)?;
+        self.chunk_of.remove(&entity);
+        Some(previous)
+    }
+
+    /// Takes `entity` out of the list of its chunk, leaving its own entry stale.
+    fn unlink(&mut self, entity: E) -> Option<VoxelChunkIndex> {
+        let (idx, at) = *self.chunk_of.get(&entity)?;
+        let entities = self.chunks.get_mut(idx).unwrap();
+        entities.swap_remove(at);
+        match entities.get(at) {
+            Some(&moved) => self.chunk_of.get_mut(&moved).unwrap().1 = at,
+            None if entities.is_empty() => {
+                self.chunks.remove(idx);
+            }
+            None => {}
+        }
+        Some(idx)
+    }
+
+    /// Removes every entity in `idx`, e.g. when unloading it, returning them.
+    pub fn remove_chunk(&mut self, idx: VoxelChunkIndex) -> Vec<E> {
+        let entities = self.chunks.remove(idx).unwrap_or_default();
+        for entity in &entities {
+            self.chunk_of.remove(entity);
+        }
+        entities
+    }
+
+    pub fn clear(&mut self) {
+        self.chunk_of.clear();
+        self.chunks.clear();
+    }
+}
@@ -0,0 +1,101 @@
+use std::sync::atomic::Ordering::{AcqRel, Acquire};
+use std::sync::Mutex;
+
+use crossbeam_epoch::{self as epoch, Atomic, Owned};
+
+use crate::snapshot::ChunkMapSnapshot;
+use crate::{SnapshotChunkMap, VoxelChunkIndex};
+
+/// Concurrent chunk map whose readers never block nor get blocked: they read the latest
+/// published [`ChunkMapSnapshot`] under an epoch guard. Writers serialize on a lock, mutate a
+/// [`SnapshotChunkMap`] and publish a new snapshot, which costs `O(pages)`.
+///
+/// A replaced snapshot, and with it every value removed since, is freed by epoch-based
+/// reclamation once no reader can still be looking at it, so no reader ever waits for a writer
+/// nor the other way around. Values must be `'static`, as they may be freed on another
+/// thread after the map itself is gone.
+pub struct EpochChunkMap<V> {
+    writer: Mutex<SnapshotChunkMap<V>>,
+    published: Atomic<ChunkMapSnapshot<V>>,
+}
+
+impl<V: Clone + Send + Sync + 'static> Default for EpochChunkMap<V> {
+    fn default() -> Self {
+        let map = SnapshotChunkMap::new();
+        EpochChunkMap {
+            published: Atomic::new(map.snapshot()),
+            writer: Mutex::new(map),
+        }
+    }
+}
+
+impl<V: Clone + Send + Sync + 'static> EpochChunkMap<V> {
+    pub fn new() -> EpochChunkMap<V> {
+        Self::default()
+    }
+
+    /// Runs `f` on the latest published snapshot. Memory reclamation waits for `f` to return,
+    /// writers don't.
+    pub fn read<R>(&self, f: impl FnOnce(&ChunkMapSnapshot<V>) -> R) -> R {
+        let guard = epoch::pin();
+        let published = self.published.load(Acquire, &guard);
+        // SAFETY: the pointer is never null, and a replaced snapshot is only destroyed once every
+        // guard pinned before the replacement, including ours, is gone.
+        f(unsafe { published.deref() })
+    }
+
+    /// The latest published state, kept alive for as long as the caller wants.
+    pub fn snapshot(&self) -> ChunkMapSnapshot<V> {
+        self.read(ChunkMapSnapshot::clone)
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<V> {
+        self.read(|map| map.get(idx).cloned())
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.read(|map| map.contains_key(idx))
+    }
+
+    pub fn len(&self) -> usize {
+        self.read(|map| map.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read(|map| map.is_empty())
+    }
+
+    /// Runs a batch of writes, then publishes them all at once.
+    pub fn write<R>(&self, f: impl FnOnce(&mut SnapshotChunkMap<V>) -> R) -> R {
+        let mut map = self.writer.lock().expect("writer lock poisoned");
+        let result = f(&mut map);
+
+        let guard = epoch::pin();
+        let old = self
+            .published
+            .swap(Owned::new(map.snapshot()), AcqRel, &guard);
+        // SAFETY: `old` is unreachable from `published` now, so only readers pinned before the
+        // swap can still see it, and the destruction is deferred until they are all gone. The
+        // collector may run it on any thread at any later time, hence `V: Send + 'static`.
+        unsafe { guard.defer_destroy(old) };
+        result
+    }
+
+    pub fn insert(&self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.write(|map| map.insert(idx, value))
+    }
+
+    pub fn remove(&self, idx: VoxelChunkIndex) -> Option<V> {
+        self.write(|map| map.remove(idx))
+    }
+}
+
+impl<V> Drop for EpochChunkMap<V> {
+    fn drop(&mut self) {
+        // SAFETY: `&mut self` means no reader is left.
+        unsafe {
+            let published = self.published.load(Acquire, epoch::unprotected());
+            drop(published.into_owned());
+        }
+    }
+}
@@ -0,0 +1,424 @@
+//! Probabilistic membership filters over chunk indices: [`ChunkBloom`], and [`CuckooFilter`]
+//! which also supports removal.
+
+use std::io::{self, Read, Write};
+
+use crate::format::BLOCK;
+use crate::map::FIB_MULTIPLIER;
+use crate::VoxelChunkIndex;
+
+/// Second multiplier of [`hash_pair`], odd and unrelated to [`FIB_MULTIPLIER`].
+const PAIR_MULTIPLIER: u64 = 0x9E6C_63D0_676A_9A99;
+
+/// Two independent hashes of `idx`, well mixed in their high bits. Filters derive any number of
+/// probes from them as `h1 + i * h2` (Kirsch-Mitzenmacher double hashing).
+#[inline]
+pub(crate) fn hash_pair(idx: VoxelChunkIndex) -> (u64, u64) {
+    let x = idx.0 as u64;
+    (
+        x.wrapping_mul(FIB_MULTIPLIER),
+        x.wrapping_mul(PAIR_MULTIPLIER) | 1,
+    )
+}
+
+/// MurmurHash3's finalizer, making every bit of the result depend on every bit of `hash`, which
+/// a multiplicative hash alone doesn't give.
+#[inline]
+pub(crate) fn mix64(mut hash: u64) -> u64 {
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xFF51_AFD7_ED55_8CCD);
+    hash ^= hash >> 33;
+    hash = hash.wrapping_mul(0xC4CE_B9FE_1A85_EC53);
+    hash ^ (hash >> 33)
+}
+
+/// Maps a hash onto `0..n` using its high bits.
+#[inline]
+pub(crate) fn reduce(hash: u64, n: usize) -> usize {
+    ((hash as u128 * n as u128) >> 64) as usize
+}
+
+/// Bloom filter of chunk indices, answering "maybe present" or "definitely absent". Typically
+/// used to skip touching region files for chunks that were never generated.
+#[derive(Clone)]
+pub struct ChunkBloom {
+    words: Vec<u64>,
+    bits: usize,
+    probes: u32,
+}
+
+impl ChunkBloom {
+    /// Filter sized so that, once `expected` chunks are inserted, a lookup of an absent chunk
+    /// says "maybe present" with probability `fp_rate`.
+    pub fn new(expected: usize, fp_rate: f64) -> ChunkBloom {
+        assert!(
+            fp_rate > 0.0 && fp_rate < 1.0,
+            "false positive rate must be in (0, 1)"
+        );
+        let ln2 = std::f64::consts::LN_2;
+        let expected = expected.max(1) as f64;
+        let bits = (-expected * fp_rate.ln() / (ln2 * ln2)).ceil().max(64.0) as usize;
+        let probes = ((bits as f64 / expected) * ln2).round().max(1.0) as u32;
+        Self::with_bits(bits, probes)
+    }
+
+    /// Filter of `bits` bits, rounded up to a multiple of 64, probing `probes` bits per chunk.
+    pub fn with_bits(bits: usize, probes: u32) -> ChunkBloom {
+        assert!(probes > 0, "a bloom filter needs at least one probe");
+        let words = bits.max(1).div_ceil(64);
+        ChunkBloom {
+            words: vec![0; words],
+            bits: words * 64,
+            probes,
+        }
+    }
+
+    /// Number of bits of the filter.
+    pub fn bit_count(&self) -> usize {
+        self.bits
+    }
+
+    /// Number of bits probed per chunk.
+    pub fn probe_count(&self) -> u32 {
+        self.probes
+    }
+
+    fn probes(&self, idx: VoxelChunkIndex) -> impl Iterator<Item = usize> {
+        let (h1, h2) = hash_pair(idx);
+        let bits = self.bits;
+        (0..self.probes as u64).map(move |i| reduce(h1.wrapping_add(i.wrapping_mul(h2)), bits))
+    }
+
+    /// Adds a chunk. Returns whether it was definitely absent before.
+    pub fn insert(&mut self, idx: VoxelChunkIndex) -> bool {
+        let mut added = false;
+        for bit in self.probes(idx) {
+            let word = &mut self.words[bit / 64];
+            let mask = 1 << (bit % 64);
+            added |= *word & mask == 0;
+            *word |= mask;
+        }
+        added
+    }
+
+    /// `false` if the chunk was never inserted; `true` if it probably was.
+    pub fn contains(&self, idx: VoxelChunkIndex) -> bool {
+        self.probes(idx)
+            .all(|bit| self.words[bit / 64] & (1 << (bit % 64)) != 0)
+    }
+
+    /// Adds every chunk of `other`, which must have the same size and probe count.
+    pub fn union(&mut self, other: &ChunkBloom) {
+        assert!(
+            self.bits == other.bits && self.probes == other.probes,
+            "bloom filters differ in shape"
+        );
+        for (word, other) in self.words.iter_mut().zip(&other.words) {
+            *word |= other;
+        }
+    }
+
+    /// False positive rate at the current fill, estimated from the share of bits set.
+    pub fn estimated_fp_rate(&self) -> f64 {
+        let set: u32 = self.words.iter().map(|w| w.count_ones()).sum();
+        (set as f64 / self.bits as f64).powi(self.probes as i32)
+    }
+
+    pub fn clear(&mut self) {
+        self.words.fill(0);
+    }
+}
+
+impl Extend<VoxelChunkIndex> for ChunkBloom {
+    fn extend<I: IntoIterator<Item = VoxelChunkIndex>>(&mut self, iter: I) {
+        for idx in iter {
+            self.insert(idx);
+        }
+    }
+}
+
+const BUCKET_SIZE: usize = 4;
+const MAX_KICKS: usize = 500;
+const MAGIC: &[u8; 4] = b"CKF1";
+
+/// Cuckoo filter of chunk indices: like [`ChunkBloom`], but chunks can be removed again, e.g.
+/// when they are permanently deleted from the world. Stores a 16-bit fingerprint per chunk, for
+/// a false positive rate of about 0.01%.
+///
+/// Only remove chunks that were inserted: removing an absent chunk may remove the fingerprint of
+/// another one, which then reads as absent.
+#[derive(Clone)]
+pub struct CuckooFilter {
+    /// Fingerprints, [`BUCKET_SIZE`] per bucket, 0 marking an empty slot.
+    slots: Vec<u16>,
+    /// Turns a hash into a bucket index by keeping its top bits.
+    shift: u32,
+    len: usize,
+    /// Fingerprint evicted by an insert that ran out of kicks, kept so that it isn't lost.
+    victim: Option<(usize, u16)>,
+    rng: u64,
+}
+
+impl CuckooFilter {
+    /// Filter able to hold about `capacity` chunks.
+    pub fn new(capacity: usize) -> CuckooFilter {
+        let buckets = (capacity.max(1) * 20 / 19).div_ceil(BUCKET_SIZE);
+        Self::with_buckets(buckets.next_power_of_two())
+    }
+
+    fn with_buckets(buckets: usize) -> CuckooFilter {
+        Self::from_slots(vec![0; buckets * BUCKET_SIZE])
+    }
+
+    /// Filter of the fingerprints in `slots`, a power of two of buckets.
+    fn from_slots(slots: Vec<u16>) -> CuckooFilter {
+        CuckooFilter {
+            shift: 64 - (slots.len() / BUCKET_SIZE).trailing_zeros(),
+            slots,
+            len: 0,
+            victim: None,
+            rng: FIB_MULTIPLIER,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of fingerprints the filter has room for.
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    /// Fingerprint and first bucket of `idx`.
+    fn locate(&self, idx: VoxelChunkIndex) -> (u16, usize) {
+        let (h1, h2) = hash_pair(idx);
+        let fingerprint = ((h2 >> 48) as u16).max(1);
+        (fingerprint, self.bucket_of(h1))
+    }
+
+    /// The other bucket `fingerprint` may live in; its own inverse.
+    fn alternate(&self, bucket: usize, fingerprint: u16) -> usize {
+        bucket ^ self.bucket_of((fingerprint as u64).wrapping_mul(PAIR_MULTIPLIER))
+    }
+
+    fn bucket_of(&self, hash: u64) -> usize {
+        hash.checked_shr(self.shift).unwrap_or(0) as usize
+    }
+
+    fn bucket(&self, bucket: usize) -> &[u16] {
+        &self.slots[bucket * BUCKET_SIZE..][..BUCKET_SIZE]
+    }
+
+    fn bucket_mut(&mut self, bucket: usize) -> &mut [u16] {
+        &mut self.slots[bucket * BUCKET_SIZE..][..BUCKET_SIZE]
+    }
+
+    fn put(&mut self, bucket: usize, fingerprint: u16) -> bool {
+        match self.bucket_mut(bucket).iter_mut().find(|slot| **slot == 0) {
+            Some(slot) => {
+                *slot = fingerprint;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Adds a chunk. Returns `false` if the filter is too full to take it, in which case it is
+    /// left unchanged.
+    pub fn insert(&mut self, idx: VoxelChunkIndex) -> bool {
+        if self.victim.is_some() {
+            return false;
+        }
+        let (mut fingerprint, i1) = self.locate(idx);
+        let i2 = self.alternate(i1, fingerprint);
+        self.len += 1;
+        if self.put(i1, fingerprint) || self.put(i2, fingerprint) {
+            return true;
+        }
+
+        let mut bucket = if self.next_random() & 1 == 0 { i1 } else { i2 };
+        for _ in 0..MAX_KICKS {
+            let slot = self.next_random() as usize % BUCKET_SIZE;
+            std::mem::swap(&mut fingerprint, &mut self.bucket_mut(bucket)[slot]);
+            bucket = self.alternate(bucket, fingerprint);
+            if self.put(bucket, fingerprint) {
+                return true;
+            }
+        }
+        // The new chunk is in, but some other fingerprint lost its place.
+        self.victim = Some((bucket, fingerprint));
+        true
+    }
+
+    /// `false` if the chunk is not in the filter; `true` if it probably is.
+    pub fn contains(&self, idx: VoxelChunkIndex) -> bool {
+        let (fingerprint, i1) = self.locate(idx);
+        let i2 = self.alternate(i1, fingerprint);
+        self.bucket(i1).contains(&fingerprint)
+            || self.bucket(i2).contains(&fingerprint)
+            || self
+                .victim
+                .is_some_and(|(b, f)| f == fingerprint && (b == i1 || b == i2))
+    }
+
+    /// Removes an inserted chunk. Returns whether it was found.
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        let (fingerprint, i1) = self.locate(idx);
+        let i2 = self.alternate(i1, fingerprint);
+        if let Some((b, f)) = self.victim {
+            if f == fingerprint && (b == i1 || b == i2) {
+                self.victim = None;
+                self.len -= 1;
+                return true;
+            }
+        }
+        for bucket in [i1, i2] {
+            if let Some(slot) = self
+                .bucket_mut(bucket)
+                .iter_mut()
+                .find(|slot| **slot == fingerprint)
+            {
+                *slot = 0;
+                self.len -= 1;
+                // Room was made, so the victim can go back in.
+                if let Some((b, f)) = self.victim.take() {
+                    if !self.put(b, f) && !self.put(self.alternate(b, f), f) {
+                        self.victim = Some((b, f));
+                    }
+                }
+                return true;
+            }
+        }
+        false
+    }
+
+    pub fn clear(&mut self) {
+        self.slots.fill(0);
+        self.len = 0;
+        self.victim = None;
+    }
+
+    fn next_random(&mut self) -> u64 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        self.rng
+    }
+
+    /// Writes the filter in a little-endian format closed by a CRC32 of everything before it.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(27 + self.slots.len() * 2);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&((self.slots.len() / BUCKET_SIZE) as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.len as u64).to_le_bytes());
+        let (bucket, fingerprint) = self.victim.unwrap_or((0, 0));
+        bytes.extend_from_slice(&(bucket as u32).to_le_bytes());
+        bytes.extend_from_slice(&fingerprint.to_le_bytes());
+        for slot in &self.slots {
+            bytes.extend_from_slice(&slot.to_le_bytes());
+        }
+        bytes.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+        writer.write_all(&bytes)
+    }
+
+    /// Reads a filter written by [`CuckooFilter::write_to`].
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<CuckooFilter> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut header = [0; 22];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a cuckoo filter"));
+        }
+        let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+        let buckets = u32_at(4) as usize;
+        if !buckets.is_power_of_two() {
+            return Err(invalid("bucket count is not a power of two"));
+        }
+        let len = u64::from_le_bytes(header[8..16].try_into().unwrap()) as usize;
+        let victim_bucket = u32_at(16) as usize;
+        let victim = u16::from_le_bytes([header[20], header[21]]);
+
+        let slot_count = buckets
+            .checked_mul(BUCKET_SIZE)
+            .ok_or_else(|| invalid("cuckoo filter too large"))?;
+
+        // Slots are read a block at a time, so that a bucket count past the end of the input
+        // fails on reaching it rather than by allocating the whole filter up front.
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&header);
+        let mut slots = Vec::with_capacity(slot_count.min(BLOCK / 2));
+        let mut block = vec![0; BLOCK];
+        while slots.len() < slot_count {
+            let block = &mut block[..(slot_count - slots.len()).min(BLOCK / 2) * 2];
+            reader.read_exact(block)?;
+            hasher.update(block);
+            slots.extend(
+                block
+                    .chunks_exact(2)
+                    .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]])),
+            );
+        }
+        let mut crc = [0; 4];
+        reader.read_exact(&mut crc)?;
+        if hasher.finalize().to_le_bytes() != crc {
+            return Err(invalid("cuckoo filter checksum mismatch"));
+        }
+
+        let mut filter = CuckooFilter::from_slots(slots);
+        filter.len = len;
+        if victim != 0 {
+            if victim_bucket >= buckets {
+                return Err(invalid("cuckoo filter victim out of range"));
+            }
+            filter.victim = Some((victim_bucket, victim));
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read_error(bytes: &[u8]) -> io::ErrorKind {
+        match CuckooFilter::read_from(bytes) {
+            Ok(_) => panic!("read a filter out of a bad file"),
+            Err(err) => err.kind(),
+        }
+    }
+
+    #[test]
+    fn read_from_round_trips() {
+        let mut filter = CuckooFilter::new(1000);
+        let keys: Vec<_> = (0..1000)
+            .map(|i| VoxelChunkIndex::from_coords(i, i / 3, i % 5))
+            .collect();
+        for &idx in &keys {
+            filter.insert(idx);
+        }
+        let mut bytes = Vec::new();
+        filter.write_to(&mut bytes).unwrap();
+        let read = CuckooFilter::read_from(&bytes[..]).unwrap();
+        assert_eq!(read.len(), filter.len());
+        assert!(keys.iter().all(|&idx| read.contains(idx)));
+
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        assert_eq!(read_error(&bytes), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn read_from_fails_on_truncated_input_without_allocating_it() {
+        // A header claiming 2^31 buckets, 16 GiB of slots, and no body.
+        let mut header = MAGIC.to_vec();
+        header.extend_from_slice(&(1u32 << 31).to_le_bytes());
+        header.extend_from_slice(&[0; 14]);
+        assert_eq!(read_error(&header), io::ErrorKind::UnexpectedEof);
+    }
+}
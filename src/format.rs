@@ -0,0 +1,482 @@
+//! Versioned little-endian binary format of index lists, [`VoxelChunkSet`]s and
+//! [`VoxelChunkMap`]s. The header declares the bit layout of the packed indices, so that a file
+//! written by a build with another layout is rejected instead of having its keys silently
+//! misread.
+//!
+//! A file is the magic `VCIX`, the format version, the kind of collection, the X, Y and Z bit
+//! counts, the byte size of a value (0 but for maps) and the entry count as a `u64`. The packed
+//! keys follow, then the values in the same order, then a CRC32 of everything before it. Keys
+//! take 4 bytes; [`migrate`](crate::migrate) also writes files for layouts over 32 bits, whose
+//! keys take 8.
+//! Files are written and read a block at a time, so that a stream wrapped in a compressor, e.g.
+//! a `zstd::Encoder`, saves a world of any size without holding it in memory twice; only maps
+//! copy their keys, to sort them. [`write_indices_from`] and [`write_map_from`] write straight
+//! from iterators, and the `read_*_with` functions hand entries to a closure as they are read,
+//! for collections other than the ones of this module.
+//!
+//! For the network, [`encode_sorted_deltas`] packs a set of keys much tighter, as varint gaps.
+
+use std::io::{self, Read, Write};
+
+use crate::{VoxelChunkIndex, VoxelChunkMap, VoxelChunkSet, X_BITS, Y_BITS, Z_BITS};
+
+pub(crate) const MAGIC: &[u8; 4] = b"VCIX";
+pub const FORMAT_VERSION: u8 = 1;
+pub(crate) const HEADER_LEN: usize = 18;
+
+/// Value stored in a fixed number of little-endian bytes.
+pub trait LeBytes: Sized {
+    const SIZE: usize;
+
+    fn write_le(&self, out: &mut Vec<u8>);
+
+    /// Reads a value from exactly [`LeBytes::SIZE`] bytes.
+    fn read_le(bytes: &[u8]) -> Self;
+}
+
+macro_rules! le_bytes {
+    ($($ty:ty),*) => {
+        $(
+            impl LeBytes for $ty {
+                const SIZE: usize = std::mem::size_of::<$ty>();
+
+                fn write_le(&self, out: &mut Vec<u8>) {
+                    out.extend_from_slice(&self.to_le_bytes());
+                }
+
+                fn read_le(bytes: &[u8]) -> Self {
+                    <$ty>::from_le_bytes(bytes.try_into().unwrap())
+                }
+            }
+        )*
+    };
+}
+
+le_bytes!(u8, u16, u32, u64, i8, i16, i32, i64, f32, f64);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Kind {
+    List = 0,
+    Set = 1,
+    Map = 2,
+}
+
+impl Kind {
+    pub(crate) fn from_byte(byte: u8) -> Option<Kind> {
+        [Kind::List, Kind::Set, Kind::Map]
+            .into_iter()
+            .find(|kind| *kind as u8 == byte)
+    }
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Kind::List => "an index list",
+            Kind::Set => "a chunk set",
+            Kind::Map => "a chunk map",
+        }
+    }
+}
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Bytes buffered between two writes to, or reads from, the underlying stream, so that neither
+/// side ever holds a whole file: streams wrapped in a compressor work on worlds of any size.
+pub(crate) const BLOCK: usize = 1 << 16;
+
+/// Buffered writer keeping a CRC32 of everything written through it.
+struct CrcWriter<W> {
+    inner: W,
+    block: Vec<u8>,
+    hasher: crc32fast::Hasher,
+}
+
+impl<W: Write> CrcWriter<W> {
+    /// Starts a file of `len` entries.
+    fn new(inner: W, kind: Kind, value_size: usize, len: usize) -> CrcWriter<W> {
+        let mut writer = CrcWriter {
+            inner,
+            block: Vec::with_capacity(BLOCK + 16),
+            hasher: crc32fast::Hasher::new(),
+        };
+        writer.block.extend_from_slice(MAGIC);
+        writer.block.extend_from_slice(&[
+            FORMAT_VERSION,
+            kind as u8,
+            X_BITS,
+            Y_BITS,
+            Z_BITS,
+            u8::try_from(value_size).expect("values must fit in 255 bytes"),
+        ]);
+        writer.block.extend_from_slice(&(len as u64).to_le_bytes());
+        writer
+    }
+
+    /// The buffer to append to, written out first if full.
+    fn block(&mut self) -> io::Result<&mut Vec<u8>> {
+        if self.block.len() >= BLOCK {
+            self.flush_block()?;
+        }
+        Ok(&mut self.block)
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        self.hasher.update(&self.block);
+        self.inner.write_all(&self.block)?;
+        self.block.clear();
+        Ok(())
+    }
+
+    fn write_keys(&mut self, keys: impl IntoIterator<Item = VoxelChunkIndex>) -> io::Result<()> {
+        for idx in keys {
+            self.block()?.extend_from_slice(&idx.0.to_le_bytes());
+        }
+        Ok(())
+    }
+
+    /// Writes what is left in the buffer, then the checksum.
+    fn finish(mut self) -> io::Result<()> {
+        self.flush_block()?;
+        let crc = self.hasher.finalize();
+        self.inner.write_all(&crc.to_le_bytes())
+    }
+}
+
+/// Reader keeping a CRC32 of everything read through it.
+struct CrcReader<R> {
+    inner: R,
+    hasher: crc32fast::Hasher,
+}
+
+impl<R: Read> CrcReader<R> {
+    /// Checks the header against what this build expects, returning the reader positioned on
+    /// the keys and their count.
+    fn new(inner: R, kind: Kind, value_size: usize) -> io::Result<(CrcReader<R>, u64)> {
+        let mut reader = CrcReader {
+            inner,
+            hasher: crc32fast::Hasher::new(),
+        };
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a chunk index file".to_owned()));
+        }
+        if header[4] != FORMAT_VERSION {
+            return Err(invalid(format!(
+                "format version {} is not supported, only {FORMAT_VERSION} is",
+                header[4]
+            )));
+        }
+        if header[5] != kind as u8 {
+            let found = Kind::from_byte(header[5]).map_or("an unknown collection", Kind::name);
+            return Err(invalid(format!(
+                "the file holds {found}, not {}",
+                kind.name()
+            )));
+        }
+        if header[6..9] != [X_BITS, Y_BITS, Z_BITS] {
+            return Err(invalid(format!(
+                "indices packed with {}/{}/{} X/Y/Z bits, this build packs them with \
+                 {X_BITS}/{Y_BITS}/{Z_BITS}",
+                header[6], header[7], header[8]
+            )));
+        }
+        if header[9] as usize != value_size {
+            return Err(invalid(format!(
+                "values of {} bytes, expected {value_size}",
+                header[9]
+            )));
+        }
+        let len = u64::from_le_bytes(header[10..].try_into().unwrap());
+        Ok((reader, len))
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> io::Result<()> {
+        self.inner.read_exact(buf)?;
+        self.hasher.update(buf);
+        Ok(())
+    }
+
+    /// Reads `len` records of `size` bytes a block at a time, handing each to `f`.
+    fn read_records(&mut self, len: u64, size: usize, mut f: impl FnMut(&[u8])) -> io::Result<()> {
+        if size == 0 {
+            (0..len).for_each(|_| f(&[]));
+            return Ok(());
+        }
+        let per_block = (BLOCK / size) as u64;
+        let mut block = Vec::new();
+        let mut left = len;
+        while left > 0 {
+            let records = left.min(per_block);
+            block.resize(records as usize * size, 0);
+            self.read_exact(&mut block)?;
+            block.chunks_exact(size).for_each(&mut f);
+            left -= records;
+        }
+        Ok(())
+    }
+
+    fn read_keys(&mut self, len: u64, mut f: impl FnMut(VoxelChunkIndex)) -> io::Result<()> {
+        self.read_records(len, 4, |key| {
+            f(VoxelChunkIndex(u32::from_le_bytes(key.try_into().unwrap())))
+        })
+    }
+
+    /// Reads the checksum and compares it to the one of everything read before.
+    fn finish(mut self) -> io::Result<()> {
+        let mut crc = [0; 4];
+        self.inner.read_exact(&mut crc)?;
+        if self.hasher.finalize().to_le_bytes() != crc {
+            return Err(invalid("chunk index file checksum mismatch".to_owned()));
+        }
+        Ok(())
+    }
+}
+
+/// Checks the header of a map file with values of `value_size` bytes, returning its entry count.
+#[cfg(all(feature = "mmap", unix))]
+pub(crate) fn map_header(header: &[u8], value_size: usize) -> io::Result<u64> {
+    CrcReader::new(header, Kind::Map, value_size).map(|(_, len)| len)
+}
+
+/// Writes a list of indices, in order.
+pub fn write_indices<W: Write>(indices: &[VoxelChunkIndex], writer: W) -> io::Result<()> {
+    write_indices_from(indices.len(), indices.iter().copied(), writer)
+}
+
+/// Writes the `len` indices `indices` yields as a list, in order, as they come: for lists too
+/// big, or too slow to produce, to collect first.
+pub fn write_indices_from<W: Write>(
+    len: usize,
+    indices: impl IntoIterator<Item = VoxelChunkIndex>,
+    writer: W,
+) -> io::Result<()> {
+    let mut writer = CrcWriter::new(writer, Kind::List, 0, len);
+    let mut written = 0;
+    writer.write_keys(indices.into_iter().inspect(|_| written += 1))?;
+    check_len(len, written)?;
+    writer.finish()
+}
+
+/// Reads a list written by [`write_indices`].
+pub fn read_indices<R: Read>(reader: R) -> io::Result<Vec<VoxelChunkIndex>> {
+    let mut indices = Vec::new();
+    read_indices_with(reader, |idx| indices.push(idx))?;
+    Ok(indices)
+}
+
+/// Hands the indices of a list written by [`write_indices`] to `f` as they are read, returning
+/// their count. The checksum is only checked at the end, so `f` may see indices of a corrupted
+/// file before the error.
+pub fn read_indices_with<R: Read>(reader: R, f: impl FnMut(VoxelChunkIndex)) -> io::Result<u64> {
+    read_keys_with(reader, Kind::List, f)
+}
+
+fn read_keys_with<R: Read>(
+    reader: R,
+    kind: Kind,
+    f: impl FnMut(VoxelChunkIndex),
+) -> io::Result<u64> {
+    let (mut reader, len) = CrcReader::new(reader, kind, 0)?;
+    reader.read_keys(len, f)?;
+    reader.finish()?;
+    Ok(len)
+}
+
+fn check_len(len: usize, written: usize) -> io::Result<()> {
+    if written != len {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{written} entries written, {len} announced"),
+        ));
+    }
+    Ok(())
+}
+
+impl VoxelChunkSet {
+    /// Writes the set, its packed indices in increasing order.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut writer = CrcWriter::new(writer, Kind::Set, 0, self.len());
+        writer.write_keys(self.iter_sorted())?;
+        writer.finish()
+    }
+
+    /// Reads a set written by [`VoxelChunkSet::write_to`].
+    pub fn read_from<R: Read>(reader: R) -> io::Result<VoxelChunkSet> {
+        let mut set = VoxelChunkSet::new();
+        read_set_with(reader, |idx| {
+            set.insert(idx);
+        })?;
+        Ok(set)
+    }
+}
+
+/// Hands the indices of a set written by [`VoxelChunkSet::write_to`] to `f`, like
+/// [`read_indices_with`], e.g. to load them into another set type.
+pub fn read_set_with<R: Read>(reader: R, f: impl FnMut(VoxelChunkIndex)) -> io::Result<u64> {
+    read_keys_with(reader, Kind::Set, f)
+}
+
+/// Writes the `len` entries the iterators `entries` makes yield as a map file, the keys from a
+/// first iterator and the values from a second one, so that nothing is copied on the way.
+/// Entries must come in increasing key order, as the readers of [`mmap`](crate::mmap) expect
+/// them: `|| sorted.iter()` on a [`SortedChunkMap`](crate::SortedChunkMap) does.
+pub fn write_map_from<'a, W, V, I>(len: usize, entries: impl Fn() -> I, writer: W) -> io::Result<()>
+where
+    W: Write,
+    V: LeBytes + 'a,
+    I: IntoIterator<Item = (VoxelChunkIndex, &'a V)>,
+{
+    let mut writer = CrcWriter::new(writer, Kind::Map, V::SIZE, len);
+    let mut written = 0;
+    let mut previous = None;
+    let mut unsorted = false;
+    writer.write_keys(entries().into_iter().map(|(idx, _)| {
+        unsorted |= previous >= Some(idx);
+        previous = Some(idx);
+        written += 1;
+        idx
+    }))?;
+    check_len(len, written)?;
+    if unsorted {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "map entries out of increasing key order",
+        ));
+    }
+    let mut written = 0;
+    for (_, value) in entries().into_iter().take(len) {
+        value.write_le(writer.block()?);
+        written += 1;
+    }
+    check_len(len, written)?;
+    writer.finish()
+}
+
+/// Hands the entries of a map written by [`VoxelChunkMap::write_to`] or [`write_map_from`] to
+/// `f` as their values are read, returning their count, e.g. to load them into another map type
+/// without a [`VoxelChunkMap`] in between. All the values follow all the keys in the file, so
+/// only the keys, 4 bytes each, are held until their values come.
+pub fn read_map_with<R: Read, V: LeBytes>(
+    reader: R,
+    mut f: impl FnMut(VoxelChunkIndex, V),
+) -> io::Result<u64> {
+    read_map_entries(reader, |_| (), |_, idx, value| f(idx, value)).map(|(_, len)| len)
+}
+
+/// [`read_map_with`], handing the entries to the state `init` makes out of their count once the
+/// keys are read.
+fn read_map_entries<R: Read, V: LeBytes, T>(
+    reader: R,
+    init: impl FnOnce(usize) -> T,
+    mut f: impl FnMut(&mut T, VoxelChunkIndex, V),
+) -> io::Result<(T, u64)> {
+    let (mut reader, len) = CrcReader::new(reader, Kind::Map, V::SIZE)?;
+    let mut keys = Vec::with_capacity(len.min(BLOCK as u64) as usize);
+    reader.read_keys(len, |idx| keys.push(idx))?;
+    let mut state = init(keys.len());
+    let mut keys = keys.into_iter();
+    reader.read_records(len, V::SIZE, |value| {
+        f(&mut state, keys.next().unwrap(), V::read_le(value));
+    })?;
+    reader.finish()?;
+    Ok((state, len))
+}
+
+impl<V: LeBytes> VoxelChunkMap<V> {
+    /// Writes the map, its packed keys in increasing order and their values in the same order.
+    /// Only the sorted keys are copied on the way.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut keys: Vec<VoxelChunkIndex> = self.keys().collect();
+        keys.sort_unstable();
+        let entries = || {
+            keys.iter()
+                .map(|&idx| (idx, self.get(idx).expect("the key was just listed")))
+        };
+        write_map_from(keys.len(), entries, writer)
+    }
+
+    /// Reads a map written by [`VoxelChunkMap::write_to`] with values of the same type.
+    pub fn read_from<R: Read>(reader: R) -> io::Result<VoxelChunkMap<V>> {
+        let (map, _) =
+            read_map_entries(reader, VoxelChunkMap::with_capacity, |map, idx, value| {
+                map.insert(idx, value);
+            })?;
+        Ok(map)
+    }
+}
+
+/// Appends `value` as a LEB128 varint: 7 bits per byte, low bits first, the high bit of each
+/// byte but the last set.
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+/// Reads a varint written by [`write_varint`] from the front of `bytes`.
+fn read_varint(bytes: &mut &[u8]) -> io::Result<u64> {
+    let mut value = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes
+            .split_first()
+            .ok_or_else(|| invalid("truncated varint".to_owned()))?;
+        *bytes = rest;
+        value |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(invalid("varint longer than 64 bits".to_owned()))
+}
+
+/// Encodes the distinct `keys` compactly, e.g. for the list of chunks a client should request:
+/// sorted in packed order, as the count then the gaps between consecutive keys, the first one
+/// counting from 0, each as a varint. Chunks next to each other along X are one apart, so a
+/// box of chunks takes about a byte per chunk instead of four.
+pub fn encode_sorted_deltas(keys: &[VoxelChunkIndex]) -> Vec<u8> {
+    let mut keys = keys.to_vec();
+    keys.sort_unstable();
+    keys.dedup();
+
+    let mut out = Vec::with_capacity(keys.len() + 8);
+    write_varint(&mut out, keys.len() as u64);
+    let mut previous = 0;
+    for idx in keys {
+        write_varint(&mut out, (idx.0 - previous) as u64);
+        previous = idx.0;
+    }
+    out
+}
+
+/// Decodes keys encoded by [`encode_sorted_deltas`], in increasing packed order.
+pub fn decode_sorted_deltas(mut bytes: &[u8]) -> io::Result<Vec<VoxelChunkIndex>> {
+    let len = read_varint(&mut bytes)?;
+    // Every key takes at least a byte.
+    if len > bytes.len() as u64 {
+        return Err(invalid(format!(
+            "{len} keys can't fit in {} bytes",
+            bytes.len()
+        )));
+    }
+    let mut keys = Vec::with_capacity(len as usize);
+    let mut previous = 0u32;
+    for _ in 0..len {
+        let key = u32::try_from(read_varint(&mut bytes)?)
+            .ok()
+            .and_then(|delta| previous.checked_add(delta))
+            .ok_or_else(|| invalid("key past the last packed index".to_owned()))?;
+        keys.push(VoxelChunkIndex(key));
+        previous = key;
+    }
+    if !bytes.is_empty() {
+        return Err(invalid(format!(
+            "{} bytes left after the keys",
+            bytes.len()
+        )));
+    }
+    Ok(keys)
+}
@@ -0,0 +1,208 @@
+//! Packing of sets and maps into storage buffers for GPU-driven chunk culling, with a hash table
+//! over them that compute shaders look keys up in through [`LOOKUP_GLSL`].
+//!
+//! Buffers are arrays of std430 records, each the packed key followed by the words of its
+//! value and padded to a multiple of 16 bytes, e.g. for a value of three words:
+//!
+//! ```glsl
+//! struct ChunkRecord { uint key; uint value[3]; };
+//! layout(std430, binding = 0) readonly buffer Chunks { ChunkRecord chunks[]; };
+//! ```
+//!
+//! Records come in increasing packed order, or in Morton order of the chunk coordinates, which
+//! keeps chunks near in space near in memory for the threads of a workgroup.
+
+use crate::{VoxelChunkIndex, VoxelChunkMap, VoxelChunkSet, X_BITS, Y_BITS, Z_BITS};
+
+/// Functions looking records up in a [`GpuLookupTable`], for compute shaders that declare it as
+/// `layout(std430) readonly buffer ChunkLookup { uvec2 lookup[]; };`.
+pub const LOOKUP_GLSL: &str = "\
+uint chunk_hash(uint key) {
+    key ^= key >> 16;
+    key *= 0x7feb352du;
+    key ^= key >> 15;
+    key *= 0x846ca68bu;
+    key ^= key >> 16;
+    return key;
+}
+
+// Index of the record of `key` in the packed buffer, or -1 if it has none.
+int chunk_lookup(uint key) {
+    uint mask = uint(lookup.length()) - 1u;
+    for (uint slot = chunk_hash(key) & mask;; slot = (slot + 1u) & mask) {
+        uvec2 entry = lookup[slot];
+        if (entry.y == 0u) {
+            return -1;
+        }
+        if (entry.x == key) {
+            return int(entry.y - 1u);
+        }
+    }
+}
+";
+
+/// The hash of [`LOOKUP_GLSL`], Chris Wellons' lowbias32.
+pub fn gpu_hash(key: u32) -> u32 {
+    let mut key = key;
+    key ^= key >> 16;
+    key = key.wrapping_mul(0x7feb_352d);
+    key ^= key >> 15;
+    key = key.wrapping_mul(0x846c_a68b);
+    key ^ key >> 16
+}
+
+/// Order of the records of a [`GpuBuffer`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GpuOrder {
+    /// Increasing packed key.
+    Packed,
+    /// Increasing [`morton_code`].
+    Morton,
+}
+
+/// Spreads the low 21 bits of `v` three bits apart.
+fn spread(v: u64) -> u64 {
+    let mut v = v & 0x1f_ffff;
+    v = (v | v << 32) & 0x001f_0000_0000_ffff;
+    v = (v | v << 16) & 0x001f_0000_ff00_00ff;
+    v = (v | v << 8) & 0x100f_00f0_0f00_f00f;
+    v = (v | v << 4) & 0x10c3_0c30_c30c_30c3;
+    (v | v << 2) & 0x1249_2492_4924_9249
+}
+
+/// Morton code of the chunk coordinates of `idx`, each biased to be unsigned, interleaving
+/// the bits of X, Y and Z from the lowest.
+pub fn morton_code(idx: VoxelChunkIndex) -> u64 {
+    let (x, y, z) = idx.to_coords();
+    let x = (x + (1 << (X_BITS - 1))) as u64;
+    let y = (y + (1 << (Y_BITS - 1))) as u64;
+    let z = (z + (1 << (Z_BITS - 1))) as u64;
+    spread(x) | spread(y) << 1 | spread(z) << 2
+}
+
+/// Records ready for upload to a storage buffer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuBuffer {
+    /// The records, little-endian.
+    pub bytes: Vec<u8>,
+    /// Bytes a record takes, a multiple of 16.
+    pub stride: usize,
+    /// Keys of the records, in their order.
+    pub keys: Vec<VoxelChunkIndex>,
+}
+
+impl GpuBuffer {
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Hash table from packed keys to the indices of their records.
+    pub fn lookup_table(&self) -> GpuLookupTable {
+        GpuLookupTable::new(&self.keys)
+    }
+}
+
+fn sort_keys(keys: &mut [VoxelChunkIndex], order: GpuOrder) {
+    match order {
+        GpuOrder::Packed => keys.sort_unstable(),
+        GpuOrder::Morton => keys.sort_unstable_by_key(|&idx| morton_code(idx)),
+    }
+}
+
+/// Records of the keys of `set`, padded to 16 bytes each.
+pub fn pack_set(set: &VoxelChunkSet, order: GpuOrder) -> GpuBuffer {
+    let mut keys: Vec<_> = set.iter().collect();
+    sort_keys(&mut keys, order);
+    let mut bytes = vec![0; keys.len() * 16];
+    for (record, idx) in bytes.chunks_exact_mut(16).zip(&keys) {
+        record[..4].copy_from_slice(&idx.0.to_le_bytes());
+    }
+    GpuBuffer {
+        bytes,
+        stride: 16,
+        keys,
+    }
+}
+
+/// Records of the entries of `map`, each its key and the `N` words `value` turns its value
+/// into, e.g. a slot in a voxel atlas and a level of detail.
+pub fn pack_map<V, const N: usize>(
+    map: &VoxelChunkMap<V>,
+    order: GpuOrder,
+    mut value: impl FnMut(&V) -> [u32; N],
+) -> GpuBuffer {
+    let mut entries: Vec<_> = map.iter().collect();
+    match order {
+        GpuOrder::Packed => entries.sort_unstable_by_key(|&(idx, _)| idx),
+        GpuOrder::Morton => entries.sort_unstable_by_key(|&(idx, _)| morton_code(idx)),
+    }
+    let stride = (4 * (1 + N)).next_multiple_of(16);
+    let mut bytes = vec![0; entries.len() * stride];
+    for (record, &(idx, v)) in bytes.chunks_exact_mut(stride).zip(&entries) {
+        record[..4].copy_from_slice(&idx.0.to_le_bytes());
+        for (i, word) in value(v).into_iter().enumerate() {
+            record[4 + 4 * i..8 + 4 * i].copy_from_slice(&word.to_le_bytes());
+        }
+    }
+    GpuBuffer {
+        bytes,
+        stride,
+        keys: entries.into_iter().map(|(idx, _)| idx).collect(),
+    }
+}
+
+/// Open-addressing table from packed keys to record indices, as [`LOOKUP_GLSL`] reads it: a
+/// power-of-two array of `uvec2(key, index + 1)`, an index of 0 marking an empty slot, filled
+/// by linear probing from [`gpu_hash`] to at most half.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GpuLookupTable {
+    /// The slots, little-endian.
+    pub bytes: Vec<u8>,
+    pub capacity: usize,
+}
+
+impl GpuLookupTable {
+    /// Table from each of `keys`, which must be distinct, to its position.
+    pub fn new(keys: &[VoxelChunkIndex]) -> GpuLookupTable {
+        let capacity = (2 * keys.len()).next_power_of_two().max(2);
+        let mask = capacity - 1;
+        let mut slots = vec![(0u32, 0u32); capacity];
+        for (i, idx) in keys.iter().enumerate() {
+            let mut slot = gpu_hash(idx.0) as usize & mask;
+            while slots[slot].1 != 0 {
+                assert_ne!(slots[slot].0, idx.0, "duplicate key {:?}", idx.to_coords());
+                slot = (slot + 1) & mask;
+            }
+            slots[slot] = (idx.0, i as u32 + 1);
+        }
+        GpuLookupTable {
+            bytes: slots
+                .iter()
+                .flat_map(|&(key, index)| [key.to_le_bytes(), index.to_le_bytes()])
+                .flatten()
+                .collect(),
+            capacity,
+        }
+    }
+
+    /// Index of the record of `idx`, as [`LOOKUP_GLSL`] finds it.
+    pub fn lookup(&self, idx: VoxelChunkIndex) -> Option<usize> {
+        let mask = self.capacity - 1;
+        let mut slot = gpu_hash(idx.0) as usize & mask;
+        loop {
+            let entry = &self.bytes[8 * slot..8 * slot + 8];
+            let index = u32::from_le_bytes(entry[4..].try_into().unwrap());
+            if index == 0 {
+                return None;
+            }
+            if u32::from_le_bytes(entry[..4].try_into().unwrap()) == idx.0 {
+                return Some(index as usize - 1);
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+}
@@ -0,0 +1,339 @@
+//! Hashers for `VoxelChunkIndex` keys compared by the benchmarks and the [`analysis`](crate::analysis)
+//! functions, and [`ContentHasher`] for the payloads stored under them.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{BuildHasher, Hasher};
+
+use crate::{VoxelChunkIndex, X_MASK, X_SHIFT, Y_MASK, Y_SHIFT, Z_MASK, Z_SHIFT};
+
+/// Just doesn't do any hashing. Uses the number itself as hashed value.
+#[derive(Default)]
+pub struct IdentityHasher(u64);
+impl Hasher for IdentityHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        const U128SIZE: usize = std::mem::size_of::<u128>();
+        const U64SIZE: usize = std::mem::size_of::<u64>();
+        const U32SIZE: usize = std::mem::size_of::<u32>();
+        const U16SIZE: usize = std::mem::size_of::<u16>();
+        const U8SIZE: usize = std::mem::size_of::<u8>();
+
+        let sum = match bytes.len() {
+            U64SIZE => u64::from_be_bytes(bytes.try_into().unwrap()),
+            U32SIZE => u32::from_be_bytes(bytes.try_into().unwrap()) as u64,
+            U16SIZE => u16::from_be_bytes(bytes.try_into().unwrap()) as u64,
+            U8SIZE => u8::from_be_bytes(bytes.try_into().unwrap()) as u64,
+
+            U128SIZE => {
+                let mut sum = 0u64;
+                for chunk in bytes.chunks(U64SIZE) {
+                    sum = sum.wrapping_add(u64::from_be_bytes(chunk.try_into().unwrap()));
+                }
+                sum
+            }
+
+            _ => {
+                let mut sum = 0u64;
+                let mut it = bytes.chunks_exact(U64SIZE);
+                for chunk in it.by_ref() {
+                    sum = sum.wrapping_add(u64::from_be_bytes(chunk.try_into().unwrap()));
+                }
+
+                {
+                    let bytes = it.remainder();
+                    let mut result = [0u8; 8];
+                    result[U64SIZE - bytes.len()..].copy_from_slice(bytes);
+                    sum = sum.wrapping_add(u64::from_be_bytes(result));
+                }
+                sum
+            }
+        };
+        self.0 = self.0.wrapping_add(sum);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.0 = self.0.wrapping_add(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.0 = self.0.wrapping_add(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.0 = self.0.wrapping_add(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.0 = self.0.wrapping_add(i);
+    }
+}
+
+/// Fibonacci hashing - see https://probablydance.com/2018/06/16/fibonacci-hashing-the-optimization-that-the-world-forgot-or-a-better-alternative-to-integer-modulo/
+pub struct FibHasher<const N: u8> {
+    hash: u64,
+}
+impl<const N: u8> FibHasher<N> {
+    const SHIFT: u8 = 64 - N;
+}
+impl<const N: u8> Default for FibHasher<N> {
+    fn default() -> Self {
+        assert!(Self::SHIFT < 64);
+        FibHasher { hash: 0 }
+    }
+}
+impl<const N: u8> Hasher for FibHasher<N> {
+    fn finish(&self) -> u64 {
+        self.hash.wrapping_mul(11400714819323198485) >> Self::SHIFT
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        const U128SIZE: usize = std::mem::size_of::<u128>();
+        const U64SIZE: usize = std::mem::size_of::<u64>();
+        const U32SIZE: usize = std::mem::size_of::<u32>();
+        const U16SIZE: usize = std::mem::size_of::<u16>();
+        const U8SIZE: usize = std::mem::size_of::<u8>();
+
+        let sum = match bytes.len() {
+            U64SIZE => u64::from_be_bytes(bytes.try_into().unwrap()),
+            U32SIZE => u32::from_be_bytes(bytes.try_into().unwrap()) as u64,
+            U16SIZE => u16::from_be_bytes(bytes.try_into().unwrap()) as u64,
+            U8SIZE => u8::from_be_bytes(bytes.try_into().unwrap()) as u64,
+
+            U128SIZE => {
+                let mut sum = 0u64;
+                for chunk in bytes.chunks(U64SIZE) {
+                    sum = sum.wrapping_add(u64::from_be_bytes(chunk.try_into().unwrap()));
+                }
+                sum
+            }
+
+            _ => {
+                let mut sum = 0u64;
+                let mut it = bytes.chunks_exact(U64SIZE);
+                for chunk in it.by_ref() {
+                    sum = sum.wrapping_add(u64::from_be_bytes(chunk.try_into().unwrap()));
+                }
+
+                {
+                    let bytes = it.remainder();
+                    let mut result = [0u8; 8];
+                    result[U64SIZE - bytes.len()..].copy_from_slice(bytes);
+                    sum = sum.wrapping_add(u64::from_be_bytes(result));
+                }
+                sum
+            }
+        };
+        self.hash = self.hash.wrapping_add(sum);
+    }
+
+    fn write_u8(&mut self, i: u8) {
+        self.hash = self.hash.wrapping_add(i as u64);
+    }
+
+    fn write_u16(&mut self, i: u16) {
+        self.hash = self.hash.wrapping_add(i as u64);
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.hash = self.hash.wrapping_add(i as u64);
+    }
+
+    fn write_u64(&mut self, i: u64) {
+        self.hash = self.hash.wrapping_add(i);
+    }
+}
+
+/// Names of the hashers [`NamedHasher`] builds, as the CLI and the bindings take them.
+pub const HASHER_NAMES: [&str; 6] = ["vanilla", "crc", "fib", "fib64", "ahash", "id"];
+
+/// Hasher picked by name. Every hasher has fixed keys, so that runs are reproducible and can be
+/// compared to a baseline: `vanilla` is SipHash with the keys `DefaultHasher::new` uses, and
+/// `ahash` uses zero keys rather than its default ones, which are random per process. `fib` is
+/// the 20-bit `FibHasher` of the benchmarks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NamedHasher(&'static str);
+
+impl NamedHasher {
+    /// The hasher called `name`, one of [`HASHER_NAMES`].
+    pub fn new(name: &str) -> Option<NamedHasher> {
+        HASHER_NAMES
+            .into_iter()
+            .find(|&known| known == name)
+            .map(NamedHasher)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.0
+    }
+
+    /// Name the benchmarks give to the hasher.
+    pub fn bench_name(&self) -> &'static str {
+        match self.0 {
+            "vanilla" => "Vanilla",
+            "crc" => "Crc",
+            "fib" => "Fib",
+            "fib64" => "Fib64",
+            "ahash" => "AHash",
+            "id" => "Id",
+            _ => unreachable!("names are checked by NamedHasher::new"),
+        }
+    }
+
+    /// Low bits of the hash the hasher fills.
+    pub fn output_bits(&self) -> u32 {
+        match self.0 {
+            "crc" => 32,
+            "fib" => 20,
+            _ => 64,
+        }
+    }
+}
+
+impl BuildHasher for NamedHasher {
+    type Hasher = Box<dyn Hasher>;
+
+    fn build_hasher(&self) -> Box<dyn Hasher> {
+        match self.0 {
+            "vanilla" => Box::new(DefaultHasher::new()),
+            "crc" => Box::<crc32fast::Hasher>::default(),
+            "fib" => Box::<FibHasher<20>>::default(),
+            "fib64" => Box::<FibHasher<64>>::default(),
+            "ahash" => Box::new(ahash::RandomState::with_seeds(0, 0, 0, 0).build_hasher()),
+            "id" => Box::<IdentityHasher>::default(),
+            _ => unreachable!("names are checked by NamedHasher::new"),
+        }
+    }
+}
+
+/// Writes to `out` the hash of each of `keys` under `FibHasher<N>`, in one pass over plain
+/// integers that the compiler vectorizes, rather than one `Hasher` per key.
+pub fn fib_hash_batch<const N: u8>(keys: &[VoxelChunkIndex], out: &mut [u64]) {
+    assert_eq!(keys.len(), out.len(), "one output per key");
+    let shift = FibHasher::<N>::SHIFT;
+    for (hash, key) in out.iter_mut().zip(keys) {
+        *hash = (key.0 as u64).wrapping_mul(11400714819323198485) >> shift;
+    }
+}
+
+/// Writes to `out` the spatial hash of Teschner et al. of each of `keys`: the X, Y and Z fields
+/// of the packed index, each multiplied by a large prime, xor-ed together. It only takes 32-bit
+/// multiplications, which vectorize on any CPU and are what GPUs are fast at.
+pub fn field_mix_hash_batch(keys: &[VoxelChunkIndex], out: &mut [u32]) {
+    assert_eq!(keys.len(), out.len(), "one output per key");
+    for (hash, key) in out.iter_mut().zip(keys) {
+        let (x, y, z) = (
+            key.0 >> X_SHIFT & X_MASK,
+            key.0 >> Y_SHIFT & Y_MASK,
+            key.0 >> Z_SHIFT & Z_MASK,
+        );
+        *hash = x.wrapping_mul(73856093) ^ y.wrapping_mul(19349663) ^ z.wrapping_mul(83492791);
+    }
+}
+
+/// Streaming 64-bit hash of byte contents, such as chunk payloads, rather than keys: every byte
+/// goes through [`mix64`](crate::filter::mix64) a word at a time, and how the bytes are split
+/// between calls to `write` doesn't change the hash. Not cryptographic: anyone can craft
+/// collisions, so equal hashes only say that contents are likely equal.
+#[derive(Clone)]
+pub struct ContentHasher {
+    state: u64,
+    /// Bytes of the word being filled, in its low bytes.
+    tail: u64,
+    len: u64,
+}
+
+impl ContentHasher {
+    const SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+    pub fn new() -> ContentHasher {
+        ContentHasher {
+            state: Self::SEED,
+            tail: 0,
+            len: 0,
+        }
+    }
+
+    /// Hash of `bytes`, in one go.
+    pub fn hash(bytes: &[u8]) -> u64 {
+        let mut hasher = ContentHasher::new();
+        hasher.write(bytes);
+        hasher.finish()
+    }
+
+    #[inline]
+    fn absorb(&mut self, word: u64) {
+        self.state =
+            crate::filter::mix64(self.state ^ word).wrapping_add(self.state.rotate_left(23));
+    }
+}
+
+impl Default for ContentHasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Hasher for ContentHasher {
+    fn finish(&self) -> u64 {
+        let mut state = self.state;
+        if !self.len.is_multiple_of(8) {
+            state = crate::filter::mix64(state ^ self.tail).wrapping_add(state.rotate_left(23));
+        }
+        crate::filter::mix64(state ^ self.len)
+    }
+
+    fn write(&mut self, mut bytes: &[u8]) {
+        let filled = (self.len % 8) as usize;
+        self.len += bytes.len() as u64;
+        if filled != 0 {
+            let take = bytes.len().min(8 - filled);
+            for (i, &byte) in bytes[..take].iter().enumerate() {
+                self.tail |= (byte as u64) << (8 * (filled + i));
+            }
+            bytes = &bytes[take..];
+            if filled + take < 8 {
+                return;
+            }
+            let word = std::mem::take(&mut self.tail);
+            self.absorb(word);
+        }
+        let mut words = bytes.chunks_exact(8);
+        for word in words.by_ref() {
+            self.absorb(u64::from_le_bytes(word.try_into().unwrap()));
+        }
+        for (i, &byte) in words.remainder().iter().enumerate() {
+            self.tail |= (byte as u64) << (8 * i);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{X_BIAS, Y_BIAS, Z_BIAS};
+
+    #[test]
+    fn field_mix_hash_mixes_the_fields_of_the_key() {
+        let coords = [(0, 0, 0), (-4096, 4095, -32), (17, -300, 31)];
+        let keys: Vec<_> = coords
+            .iter()
+            .map(|&(x, y, z)| VoxelChunkIndex::from_coords(x, y, z))
+            .collect();
+        let mut out = vec![0; keys.len()];
+        field_mix_hash_batch(&keys, &mut out);
+        for (&(x, y, z), hash) in coords.iter().zip(out) {
+            let (x, y, z) = (
+                (x + X_BIAS) as u32,
+                (y + Y_BIAS) as u32,
+                (z + Z_BIAS) as u32,
+            );
+            assert_eq!(
+                hash,
+                x.wrapping_mul(73856093) ^ y.wrapping_mul(19349663) ^ z.wrapping_mul(83492791)
+            );
+        }
+    }
+}
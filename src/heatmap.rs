@@ -0,0 +1,148 @@
+//! Grayscale PNG renderings of how a hasher spreads keys, brighter meaning more keys sharing a
+//! bucket. The encoder stores the pixels uncompressed, which keeps it dependency-free at the
+//! cost of larger files.
+
+use std::hash::BuildHasher;
+use std::io::{self, Write};
+
+use crate::analysis::{bucket_counts, bucket_of};
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+/// Renders bucket `counts`, e.g. from [`bucket_counts`], as rows of `width` buckets. Empty
+/// buckets are black and the fullest one is white.
+pub fn write_occupancy_png<W: Write>(counts: &[usize], width: usize, out: W) -> io::Result<()> {
+    assert!(width > 0, "heatmap needs a width");
+    let height = counts.len().div_ceil(width).max(1);
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    let mut pixels: Vec<u8> = counts
+        .iter()
+        .map(|&count| (count * 255 / max) as u8)
+        .collect();
+    pixels.resize(width * height, 0);
+    write_gray_png(out, width, height, &pixels)
+}
+
+/// Renders the XY slice at height `z` of `region`, one pixel per chunk with `y` growing
+/// downwards, by how many other chunks of `region` share its bucket in a table of `table_size`
+/// buckets. Chunks without collisions are black and the most aliased ones are white. Parts of
+/// `region` past the edge of the representable world hold no chunks, and aren't drawn.
+pub fn write_slice_png<S: BuildHasher, W: Write>(
+    region: ChunkAabb,
+    build_hasher: &S,
+    table_size: usize,
+    z: i32,
+    out: W,
+) -> io::Result<()> {
+    let region = region.clipped_to_world();
+    assert!(
+        region.min.2 <= z && z < region.max.2,
+        "slice outside of the region"
+    );
+    let keys: Vec<VoxelChunkIndex> = region.iter().collect();
+    let counts = bucket_counts(&keys, build_hasher, table_size);
+
+    let (width, height, _) = region.size();
+    let collisions: Vec<usize> = (region.min.1..region.max.1)
+        .flat_map(|y| (region.min.0..region.max.0).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let idx = VoxelChunkIndex::from_coords(x, y, z);
+            counts[bucket_of(build_hasher, idx, table_size)] - 1
+        })
+        .collect();
+    let max = collisions.iter().copied().max().unwrap_or(0).max(1);
+    let pixels: Vec<u8> = collisions
+        .into_iter()
+        .map(|count| (count * 255 / max) as u8)
+        .collect();
+    write_gray_png(out, width, height, &pixels)
+}
+
+/// Writes an 8-bit grayscale PNG of `width × height` row-major `pixels`.
+fn write_gray_png<W: Write>(
+    mut out: W,
+    width: usize,
+    height: usize,
+    pixels: &[u8],
+) -> io::Result<()> {
+    let too_large = || io::Error::new(io::ErrorKind::InvalidInput, "image too large for a PNG");
+    let (w, h) = (
+        u32::try_from(width).map_err(|_| too_large())?,
+        u32::try_from(height).map_err(|_| too_large())?,
+    );
+
+    out.write_all(b"\x89PNG\r\n\x1a\n")?;
+
+    let mut header = Vec::with_capacity(13);
+    header.extend_from_slice(&w.to_be_bytes());
+    header.extend_from_slice(&h.to_be_bytes());
+    // Bit depth 8, grayscale, deflate, adaptive filtering, no interlacing.
+    header.extend_from_slice(&[8, 0, 0, 0, 0]);
+    write_chunk(&mut out, b"IHDR", &header)?;
+
+    // Every row starts with its filter type, 0 for none.
+    let mut raw = Vec::with_capacity((width + 1) * height);
+    for row in pixels.chunks(width.max(1)) {
+        raw.push(0);
+        raw.extend_from_slice(row);
+    }
+    write_chunk(&mut out, b"IDAT", &zlib_stored(&raw))?;
+    write_chunk(&mut out, b"IEND", &[])
+}
+
+fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    out.write_all(&(data.len() as u32).to_be_bytes())?;
+    out.write_all(kind)?;
+    out.write_all(data)?;
+    let mut crc = crc32fast::Hasher::new();
+    crc.update(kind);
+    crc.update(data);
+    out.write_all(&crc.finalize().to_be_bytes())
+}
+
+/// `data` in a zlib stream of uncompressed deflate blocks.
+fn zlib_stored(data: &[u8]) -> Vec<u8> {
+    const BLOCK: usize = u16::MAX as usize;
+    let mut stream = Vec::with_capacity(data.len() + data.len() / BLOCK * 5 + 11);
+    stream.extend_from_slice(&[0x78, 0x01]);
+    let mut blocks = data.chunks(BLOCK).peekable();
+    if blocks.peek().is_none() {
+        stream.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+    }
+    while let Some(block) = blocks.next() {
+        let last = blocks.peek().is_none() as u8;
+        let len = block.len() as u16;
+        stream.push(last);
+        stream.extend_from_slice(&len.to_le_bytes());
+        stream.extend_from_slice(&(!len).to_le_bytes());
+        stream.extend_from_slice(block);
+    }
+
+    // Adler-32 of the uncompressed data.
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for &byte in chunk {
+            a += byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    stream.extend_from_slice(&(b << 16 | a).to_be_bytes());
+    stream
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::RandomState;
+
+    use super::*;
+
+    #[test]
+    fn slices_past_the_world_edge_are_clipped() {
+        let mut png = Vec::new();
+        let region = ChunkAabb::new((4090, -4100, 0), (4100, -4090, 1));
+        write_slice_png(region, &RandomState::new(), 64, 0, &mut png).unwrap();
+        // Width and height, in the IHDR chunk right after the signature.
+        assert_eq!(png[16..24], [0, 0, 0, 6, 0, 0, 0, 6]);
+    }
+}
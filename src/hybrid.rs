@@ -0,0 +1,190 @@
+use std::collections::HashMap;
+use std::hash::{BuildHasher, RandomState};
+
+use crate::{ChunkAabb, DenseRegionMap, VoxelChunkIndex};
+
+/// Chunk map keeping a dense window around a movable center (typically the player) and spilling
+/// every chunk outside of it to a regular `HashMap`.
+pub struct HybridChunkMap<V, S = RandomState> {
+    center: (i32, i32, i32),
+    radius: (i32, i32, i32),
+    near: DenseRegionMap<V>,
+    far: HashMap<VoxelChunkIndex, V, S>,
+}
+
+fn window(center: (i32, i32, i32), radius: (i32, i32, i32)) -> ChunkAabb {
+    ChunkAabb::new(
+        (
+            center.0 - radius.0,
+            center.1 - radius.1,
+            center.2 - radius.2,
+        ),
+        (
+            center.0 + radius.0 + 1,
+            center.1 + radius.1 + 1,
+            center.2 + radius.2 + 1,
+        ),
+    )
+}
+
+impl<V, S: BuildHasher + Default> HybridChunkMap<V, S> {
+    /// The dense window spans `center ± radius` chunks on each axis.
+    pub fn new(center: (i32, i32, i32), radius: (i32, i32, i32)) -> HybridChunkMap<V, S> {
+        Self::with_hasher(center, radius, S::default())
+    }
+}
+
+impl<V, S: BuildHasher> HybridChunkMap<V, S> {
+    pub fn with_hasher(
+        center: (i32, i32, i32),
+        radius: (i32, i32, i32),
+        build_hasher: S,
+    ) -> HybridChunkMap<V, S> {
+        assert!(
+            radius.0 >= 0 && radius.1 >= 0 && radius.2 >= 0,
+            "HybridChunkMap radius must not be negative"
+        );
+        HybridChunkMap {
+            center,
+            radius,
+            near: DenseRegionMap::new(window(center, radius)),
+            far: HashMap::with_hasher(build_hasher),
+        }
+    }
+
+    pub fn center(&self) -> (i32, i32, i32) {
+        self.center
+    }
+
+    /// The region currently stored densely.
+    pub fn window(&self) -> &ChunkAabb {
+        self.near.region()
+    }
+
+    pub fn len(&self) -> usize {
+        self.near.len() + self.far.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of entries currently living in the dense window.
+    pub fn near_len(&self) -> usize {
+        self.near.len()
+    }
+
+    /// Slots of the dense window plus the spill map's own capacity.
+    pub fn capacity(&self) -> usize {
+        self.near.capacity() + self.far.capacity()
+    }
+
+    /// Reserves room for `additional` more spilled entries.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.far.reserve(additional);
+    }
+
+    /// Shrinks the spill map; the dense window keeps its fixed size.
+    pub fn shrink_to_fit(&mut self) {
+        self.far.shrink_to_fit();
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        if self.near.region().contains_index(idx) {
+            self.near.get(idx)
+        } else {
+            self.far.get(&idx)
+        }
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        if self.near.region().contains_index(idx) {
+            self.near.get_mut(idx)
+        } else {
+            self.far.get_mut(&idx)
+        }
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        if self.near.region().contains_index(idx) {
+            self.near.insert(idx, value)
+        } else {
+            self.far.insert(idx, value)
+        }
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        if self.near.region().contains_index(idx) {
+            self.near.remove(idx)
+        } else {
+            self.far.remove(&idx)
+        }
+    }
+
+    /// Moves the dense window: entries leaving it spill to the hash map, and spilled entries now
+    /// inside it are pulled back into dense storage.
+    pub fn recenter(&mut self, center: (i32, i32, i32)) {
+        if center == self.center {
+            return;
+        }
+
+        let new_window = window(center, self.radius);
+        let mut old = std::mem::replace(&mut self.near, DenseRegionMap::new(new_window));
+        for (idx, value) in old.drain() {
+            if new_window.contains_index(idx) {
+                self.near.insert(idx, value);
+            } else {
+                self.far.insert(idx, value);
+            }
+        }
+
+        // Chunks of the window past the world edge wrap onto keys of the far side, which belong
+        // to the spill map.
+        if !self.far.is_empty() {
+            for idx in new_window.clipped_to_world().iter() {
+                if let Some(value) = self.far.remove(&idx) {
+                    self.near.insert(idx, value);
+                }
+            }
+        }
+
+        self.center = center;
+    }
+
+    /// Iterates the dense window first, then the spilled entries in arbitrary order.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.near
+            .iter()
+            .chain(self.far.iter().map(|(&idx, value)| (idx, value)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        self.near
+            .iter_mut()
+            .chain(self.far.iter_mut().map(|(&idx, value)| (idx, value)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recenter_at_the_world_edge_keeps_wrapped_chunks_spilled() {
+        let mut map: HybridChunkMap<u32> = HybridChunkMap::new((0, 0, 0), (4, 4, 4));
+        let wrapped = VoxelChunkIndex::from_coords(-4095, 1, 0);
+        let inside = VoxelChunkIndex::from_coords(4093, 1, 0);
+        map.insert(wrapped, 1);
+        map.insert(inside, 2);
+
+        map.recenter((4094, 0, 0));
+        assert_eq!(map.len(), 2);
+        assert_eq!(map.near_len(), 1);
+        assert_eq!(map.get(wrapped), Some(&1));
+        assert_eq!(map.get(inside), Some(&2));
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+enum Storage<V, const N: usize> {
+    /// The first `len` entries are occupied.
+    Inline {
+        entries: [Option<(VoxelChunkIndex, V)>; N],
+        len: usize,
+    },
+    Spilled(VoxelChunkMap<V>),
+}
+
+/// Chunk map holding up to `N` entries inline, looked up by linear scan, and moving them to a
+/// [`VoxelChunkMap`] once an insert would exceed that. Suited to the small per-entity caches of
+/// nearby chunks, which then cost no allocation at all.
+pub struct InlineChunkMap<V, const N: usize> {
+    storage: Storage<V, N>,
+}
+
+impl<V, const N: usize> Default for InlineChunkMap<V, N> {
+    fn default() -> Self {
+        InlineChunkMap {
+            storage: Storage::Inline {
+                entries: std::array::from_fn(|_| None),
+                len: 0,
+            },
+        }
+    }
+}
+
+impl<V, const N: usize> InlineChunkMap<V, N> {
+    pub fn new() -> InlineChunkMap<V, N> {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        match &self.storage {
+            Storage::Inline { len, .. } => *len,
+            Storage::Spilled(map) => map.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether the entries moved to the heap.
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.storage, Storage::Spilled(_))
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        match &self.storage {
+            Storage::Inline { entries, len } => entries[..*len]
+                .iter()
+                .flatten()
+                .find(|(key, _)| *key == idx)
+                .map(|(_, value)| value),
+            Storage::Spilled(map) => map.get(idx),
+        }
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        match &mut self.storage {
+            Storage::Inline { entries, len } => entries[..*len]
+                .iter_mut()
+                .flatten()
+                .find(|(key, _)| *key == idx)
+                .map(|(_, value)| value),
+            Storage::Spilled(map) => map.get_mut(idx),
+        }
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        let (entries, len) = match &mut self.storage {
+            Storage::Inline { entries, len } => (entries, len),
+            Storage::Spilled(map) => return map.insert(idx, value),
+        };
+
+        if let Some((_, old)) = entries[..*len]
+            .iter_mut()
+            .flatten()
+            .find(|(key, _)| *key == idx)
+        {
+            return Some(std::mem::replace(old, value));
+        }
+        if *len < N {
+            entries[*len] = Some((idx, value));
+            *len += 1;
+            return None;
+        }
+
+        let mut map = VoxelChunkMap::with_capacity(N + 1);
+        map.extend(entries.iter_mut().filter_map(Option::take));
+        map.insert(idx, value);
+        self.storage = Storage::Spilled(map);
+        None
+    }
+
+    /// Removes an entry, keeping the inline entries packed. A spilled map stays on the heap until
+    /// [`InlineChunkMap::shrink_to_fit`].
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        match &mut self.storage {
+            Storage::Inline { entries, len } => {
+                let pos = entries[..*len]
+                    .iter()
+                    .position(|entry| entry.as_ref().is_some_and(|(key, _)| *key == idx))?;
+                *len -= 1;
+                entries.swap(pos, *len);
+                entries[*len].take().map(|(_, value)| value)
+            }
+            Storage::Spilled(map) => map.remove(idx),
+        }
+    }
+
+    /// Moves the entries back inline if they fit, releasing the heap map.
+    pub fn shrink_to_fit(&mut self) {
+        if let Storage::Spilled(map) = &mut self.storage {
+            if map.len() <= N {
+                let mut map = std::mem::take(map);
+                let keys: Vec<_> = map.keys().collect();
+                *self = keys
+                    .into_iter()
+                    .map(|idx| (idx, map.remove(idx).unwrap()))
+                    .collect();
+            } else {
+                map.shrink_to_fit();
+            }
+        }
+    }
+
+    /// Drops every entry and goes back to inline storage.
+    pub fn clear(&mut self) {
+        *self = InlineChunkMap::new();
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        let (inline, spilled) = match &self.storage {
+            Storage::Inline { entries, len } => (&entries[..*len], None),
+            Storage::Spilled(map) => (&[][..], Some(map)),
+        };
+        inline
+            .iter()
+            .flatten()
+            .map(|(idx, value)| (*idx, value))
+            .chain(spilled.into_iter().flat_map(VoxelChunkMap::iter))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        let (inline, spilled) = match &mut self.storage {
+            Storage::Inline { entries, len } => (&mut entries[..*len], None),
+            Storage::Spilled(map) => (&mut [][..], Some(map)),
+        };
+        inline
+            .iter_mut()
+            .flatten()
+            .map(|(idx, value)| (*idx, value))
+            .chain(spilled.into_iter().flat_map(VoxelChunkMap::iter_mut))
+    }
+}
+
+impl<V: Clone, const N: usize> Clone for InlineChunkMap<V, N> {
+    fn clone(&self) -> Self {
+        InlineChunkMap {
+            storage: match &self.storage {
+                Storage::Inline { entries, len } => Storage::Inline {
+                    entries: entries.clone(),
+                    len: *len,
+                },
+                Storage::Spilled(map) => Storage::Spilled(map.clone()),
+            },
+        }
+    }
+}
+
+impl<V, const N: usize> FromIterator<(VoxelChunkIndex, V)> for InlineChunkMap<V, N> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(iter: I) -> InlineChunkMap<V, N> {
+        let mut map = InlineChunkMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<V, const N: usize> Extend<(VoxelChunkIndex, V)> for InlineChunkMap<V, N> {
+    fn extend<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(&mut self, iter: I) {
+        for (idx, value) in iter {
+            self.insert(idx, value);
+        }
+    }
+}
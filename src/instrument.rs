@@ -0,0 +1,82 @@
+//! Events the containers report on the operations behind tick spikes: rehashes, evictions, long
+//! probe sequences and region drains. They go to the process-wide [`Subscriber`] installed with
+//! [`set_subscriber`], e.g. one forwarding them to `tracing` or to the game's own logs, along
+//! with the [`MapFields`] of the container, so that a slow tick can be traced back to the map
+//! that rehashed during it.
+//!
+//! Without a subscriber, an instrumented operation costs one atomic load.
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+/// Groups a probe must visit to be reported as a [`ContainerEvent::LongProbe`]. A table at its
+/// maximum load rarely probes more than two.
+pub const LONG_PROBE_GROUPS: usize = 8;
+
+/// The container an event comes from, as it was when the event happened.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapFields {
+    /// Type of the container, e.g. `"VoxelChunkMap"`.
+    pub container: &'static str,
+    /// Name given with [`VoxelChunkMap::set_label`](crate::VoxelChunkMap::set_label).
+    pub label: Option<&'static str>,
+    pub len: usize,
+    /// Entries it holds before growing, or before evicting for a cache, except for a
+    /// [`BudgetCache`](crate::BudgetCache), whose budget is in bytes.
+    pub capacity: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ContainerEvent {
+    /// The table was rebuilt with `to` slots from `from`, dropping its `tombstones`.
+    Rehash {
+        from: usize,
+        to: usize,
+        tombstones: usize,
+        elapsed: Duration,
+    },
+    /// A cache evicted `idx` to make room.
+    Evict { idx: VoxelChunkIndex },
+    /// Looking `idx` up visited `groups` groups of slots, at least [`LONG_PROBE_GROUPS`].
+    LongProbe { idx: VoxelChunkIndex, groups: usize },
+    /// A drain of `region` removed `drained` entries, by scanning the whole table if `scanned`
+    /// or else by looking each chunk of the region up. `elapsed` runs until the last entry was
+    /// yielded.
+    DrainRegion {
+        region: ChunkAabb,
+        drained: usize,
+        scanned: bool,
+        elapsed: Duration,
+    },
+}
+
+/// Receiver of the events of every container, called on the thread running the operation.
+pub trait Subscriber: Send + Sync {
+    fn event(&self, fields: &MapFields, event: &ContainerEvent);
+}
+
+impl<F: Fn(&MapFields, &ContainerEvent) + Send + Sync> Subscriber for F {
+    fn event(&self, fields: &MapFields, event: &ContainerEvent) {
+        self(fields, event)
+    }
+}
+
+static SUBSCRIBER: OnceLock<Box<dyn Subscriber>> = OnceLock::new();
+
+/// Installs the subscriber for the rest of the process, returning false if one already was.
+pub fn set_subscriber<S: Subscriber + 'static>(subscriber: S) -> bool {
+    SUBSCRIBER.set(Box::new(subscriber)).is_ok()
+}
+
+/// Whether a subscriber is installed, for operations to skip measuring otherwise.
+pub(crate) fn enabled() -> bool {
+    SUBSCRIBER.get().is_some()
+}
+
+pub(crate) fn emit(fields: impl FnOnce() -> MapFields, event: impl FnOnce() -> ContainerEvent) {
+    if let Some(subscriber) = SUBSCRIBER.get() {
+        subscriber.event(&fields(), &event());
+    }
+}
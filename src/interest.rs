@@ -0,0 +1,206 @@
+//! Interest management for multiplayer servers: every subscriber sees the chunks of a ball
+//! around its center, and [`InterestArea::tick`] lists, for each one whose ball changed, the
+//! chunks that entered and left it since the previous tick.
+//!
+//! Balls are compared column by column: the chunks of a ball in a column of constant X and Y
+//! are one Z interval, so the chunks entering a column are the new interval minus the old one.
+//! A tick then takes time in the area of the balls that changed, not in their volume.
+
+use std::collections::BTreeMap;
+
+use crate::{VoxelChunkIndex, X_BITS, Y_BITS, Z_BITS};
+
+const X_RANGE: (i32, i32) = (-(1 << (X_BITS - 1)), (1 << (X_BITS - 1)) - 1);
+const Y_RANGE: (i32, i32) = (-(1 << (Y_BITS - 1)), (1 << (Y_BITS - 1)) - 1);
+const Z_RANGE: (i32, i32) = (-(1 << (Z_BITS - 1)), (1 << (Z_BITS - 1)) - 1);
+
+/// Chunks within `radius` of `center`, clipped to the representable world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Ball {
+    center: (i32, i32, i32),
+    radius: i32,
+}
+
+impl Ball {
+    /// Inclusive range of the ball's columns along `axis`, 0 for X and 1 for Y.
+    fn span(self, axis: usize) -> (i32, i32) {
+        let (center, range) = match axis {
+            0 => (self.center.0, X_RANGE),
+            _ => (self.center.1, Y_RANGE),
+        };
+        (
+            (center - self.radius).max(range.0),
+            (center + self.radius).min(range.1),
+        )
+    }
+
+    /// Inclusive Z interval of the ball in column `(x, y)`, if it reaches it.
+    fn column(self, x: i32, y: i32) -> Option<(i32, i32)> {
+        let (dx, dy) = ((x - self.center.0) as i64, (y - self.center.1) as i64);
+        let left = (self.radius as i64).pow(2) - dx * dx - dy * dy;
+        if left < 0 {
+            return None;
+        }
+        let half = left.isqrt() as i32;
+        let (z0, z1) = (
+            (self.center.2 - half).max(Z_RANGE.0),
+            (self.center.2 + half).min(Z_RANGE.1),
+        );
+        (z0 <= z1).then_some((z0, z1))
+    }
+}
+
+/// Pushes the chunks of `ball` that `other` lacks, if any, to `out`.
+fn ball_minus(ball: Ball, other: Option<Ball>, out: &mut Vec<VoxelChunkIndex>) {
+    let (x0, x1) = ball.span(0);
+    let (y0, y1) = ball.span(1);
+    for x in x0..=x1 {
+        for y in y0..=y1 {
+            let Some((z0, z1)) = ball.column(x, y) else {
+                continue;
+            };
+            let mut push = |from: i32, to: i32| {
+                out.extend((from..=to).map(|z| VoxelChunkIndex::from_coords(x, y, z)));
+            };
+            match other.and_then(|other| other.column(x, y)) {
+                Some((o0, o1)) => {
+                    push(z0, z1.min(o0 - 1));
+                    push(z0.max(o1 + 1), z1);
+                }
+                None => push(z0, z1),
+            }
+        }
+    }
+}
+
+/// Chunks entering and leaving, each in increasing packed order, when a ball becomes `after`.
+fn ball_delta(before: Option<Ball>, after: Ball) -> (Vec<VoxelChunkIndex>, Vec<VoxelChunkIndex>) {
+    let (mut enter, mut leave) = (Vec::new(), Vec::new());
+    ball_minus(after, before, &mut enter);
+    if let Some(before) = before {
+        ball_minus(before, Some(after), &mut leave);
+    }
+    enter.sort_unstable();
+    leave.sort_unstable();
+    (enter, leave)
+}
+
+/// Chunks entering and leaving the view when a viewer moves from `(center, radius)` `before`,
+/// `None` if it had no view yet, to `after`, for callers keeping track of viewers themselves:
+/// what [`InterestArea::tick`] lists for one subscriber.
+pub fn view_delta(
+    before: Option<((i32, i32, i32), i32)>,
+    after: ((i32, i32, i32), i32),
+) -> (Vec<VoxelChunkIndex>, Vec<VoxelChunkIndex>) {
+    let ball = |(center, radius): ((i32, i32, i32), i32)| {
+        assert!(radius >= 0, "radius can't be negative");
+        Ball { center, radius }
+    };
+    ball_delta(before.map(ball), ball(after))
+}
+
+struct Subscriber {
+    ball: Ball,
+    /// Ball as of the last tick, `None` before the first one.
+    sent: Option<Ball>,
+}
+
+/// What changed for one subscriber during a tick, ready to be sent to it: both lists are in
+/// increasing packed order, as
+/// [`encode_sorted_deltas`](crate::format::encode_sorted_deltas) wants them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterestUpdate {
+    pub subscriber: u64,
+    /// Chunks now in the subscriber's ball that weren't at the previous tick.
+    pub enter: Vec<VoxelChunkIndex>,
+    /// Chunks that were in the subscriber's ball at the previous tick and aren't anymore.
+    pub leave: Vec<VoxelChunkIndex>,
+}
+
+/// Subscribers each watching the chunks within a radius of their center, typically the players
+/// connected to a server and their view distance.
+#[derive(Default)]
+pub struct InterestArea {
+    subscribers: BTreeMap<u64, Subscriber>,
+    next_id: u64,
+}
+
+impl InterestArea {
+    pub fn new() -> InterestArea {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.subscribers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.subscribers.is_empty()
+    }
+
+    /// Adds a subscriber watching the chunks within `radius` of `center`, returning its id. Its
+    /// whole ball enters on the next tick.
+    pub fn subscribe(&mut self, center: (i32, i32, i32), radius: i32) -> u64 {
+        assert!(radius >= 0, "radius can't be negative");
+        let id = self.next_id;
+        self.next_id += 1;
+        let ball = Ball { center, radius };
+        self.subscribers.insert(id, Subscriber { ball, sent: None });
+        id
+    }
+
+    /// Removes a subscriber, returning whether it existed. Nothing is listed for it anymore,
+    /// since there is no one left to send it to.
+    pub fn unsubscribe(&mut self, id: u64) -> bool {
+        self.subscribers.remove(&id).is_some()
+    }
+
+    /// Moves the ball of subscriber `id`, taking effect on the next tick.
+    pub fn move_to(&mut self, id: u64, center: (i32, i32, i32)) {
+        self.subscriber(id).ball.center = center;
+    }
+
+    /// Changes the radius of subscriber `id`, taking effect on the next tick.
+    pub fn set_radius(&mut self, id: u64, radius: i32) {
+        assert!(radius >= 0, "radius can't be negative");
+        self.subscriber(id).ball.radius = radius;
+    }
+
+    fn subscriber(&mut self, id: u64) -> &mut Subscriber {
+        self.subscribers
+            .get_mut(&id)
+            .unwrap_or_else(|| panic!("no subscriber {id}"))
+    }
+
+    /// Whether `idx` is in the ball of subscriber `id` as of the last tick.
+    pub fn sees(&self, id: u64, idx: VoxelChunkIndex) -> bool {
+        let Some(ball) = self.subscribers.get(&id).and_then(|s| s.sent) else {
+            return false;
+        };
+        let (x, y, z) = idx.to_coords();
+        ball.column(x, y)
+            .is_some_and(|(z0, z1)| (z0..=z1).contains(&z))
+    }
+
+    /// Updates for the subscribers whose ball changed since the previous tick, by increasing
+    /// id. Subscribers that didn't move get none.
+    pub fn tick(&mut self) -> Vec<InterestUpdate> {
+        let mut updates = Vec::new();
+        for (&id, subscriber) in &mut self.subscribers {
+            if subscriber.sent == Some(subscriber.ball) {
+                continue;
+            }
+            let (enter, leave) = ball_delta(subscriber.sent, subscriber.ball);
+            let update = InterestUpdate {
+                subscriber: id,
+                enter,
+                leave,
+            };
+            subscriber.sent = Some(subscriber.ball);
+            if !update.enter.is_empty() || !update.leave.is_empty() {
+                updates.push(update);
+            }
+        }
+        updates
+    }
+}
@@ -0,0 +1,205 @@
+use std::ops::RangeInclusive;
+
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+/// Set of chunk indices stored as maximal runs of consecutive packed indices. X being the lowest
+/// bits of a packed index, each row of a box along X is a single run, so "every chunk of this
+/// 1000×1000 area" costs a thousand runs, 8 KB, where a bitset needs megabytes.
+///
+/// Lookups binary-search the runs; inserts and removals shift the runs after them, which is
+/// cheap for the few thousand runs such sets typically hold.
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct IntervalChunkSet {
+    /// Sorted, disjoint and non-adjacent `(first, last)` packed indices.
+    runs: Vec<(u32, u32)>,
+    len: usize,
+}
+
+impl IntervalChunkSet {
+    pub fn new() -> IntervalChunkSet {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn run_count(&self) -> usize {
+        self.runs.len()
+    }
+
+    pub fn contains(&self, idx: VoxelChunkIndex) -> bool {
+        let i = self.runs.partition_point(|&(_, last)| last < idx.0);
+        self.runs.get(i).is_some_and(|&(first, _)| first <= idx.0)
+    }
+
+    /// Returns whether the chunk was newly added.
+    pub fn insert(&mut self, idx: VoxelChunkIndex) -> bool {
+        if self.contains(idx) {
+            return false;
+        }
+        self.insert_range(idx..=idx);
+        true
+    }
+
+    /// Returns whether the chunk was present.
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        if !self.contains(idx) {
+            return false;
+        }
+        self.remove_range(idx..=idx);
+        true
+    }
+
+    /// Adds every chunk whose packed index lies in `range`.
+    pub fn insert_range(&mut self, range: RangeInclusive<VoxelChunkIndex>) {
+        let (first, last) = (range.start().0, range.end().0);
+        if first > last {
+            return;
+        }
+
+        // Runs overlapping or touching the new one merge with it.
+        let start = self
+            .runs
+            .partition_point(|&(_, l)| (l as u64) + 1 < first as u64);
+        let end = self
+            .runs
+            .partition_point(|&(f, _)| f as u64 <= last as u64 + 1);
+        let mut merged = (first, last);
+        for &(f, l) in &self.runs[start..end] {
+            merged = (merged.0.min(f), merged.1.max(l));
+            self.len -= (l - f) as usize + 1;
+        }
+        self.len += (merged.1 - merged.0) as usize + 1;
+        self.runs.splice(start..end, [merged]);
+    }
+
+    /// Removes every chunk whose packed index lies in `range`.
+    pub fn remove_range(&mut self, range: RangeInclusive<VoxelChunkIndex>) {
+        let (first, last) = (range.start().0, range.end().0);
+        if first > last {
+            return;
+        }
+
+        let start = self.runs.partition_point(|&(_, l)| l < first);
+        let end = self.runs.partition_point(|&(f, _)| f <= last);
+        if start == end {
+            return;
+        }
+        let mut kept = Vec::with_capacity(2);
+        let (head, _) = self.runs[start];
+        let (_, tail) = self.runs[end - 1];
+        if head < first {
+            kept.push((head, first - 1));
+        }
+        if tail > last {
+            kept.push((last + 1, tail));
+        }
+        for &(f, l) in &self.runs[start..end] {
+            self.len -= (l.min(last) - f.max(first)) as usize + 1;
+        }
+        self.runs.splice(start..end, kept);
+    }
+
+    /// Adds every chunk of `region`, one row along X at a time. Parts of `region` past the edge
+    /// of the representable world hold no chunks.
+    pub fn insert_region(&mut self, region: ChunkAabb) {
+        for (first, last) in rows(region) {
+            self.insert_range(first..=last);
+        }
+    }
+
+    /// Removes every chunk of `region`, one row along X at a time. Parts of `region` past the
+    /// edge of the representable world hold no chunks.
+    pub fn remove_region(&mut self, region: ChunkAabb) {
+        for (first, last) in rows(region) {
+            self.remove_range(first..=last);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.runs.clear();
+        self.len = 0;
+    }
+
+    /// Iterates the runs in packed order.
+    pub fn runs(&self) -> impl Iterator<Item = RangeInclusive<VoxelChunkIndex>> + '_ {
+        self.runs
+            .iter()
+            .map(|&(first, last)| VoxelChunkIndex(first)..=VoxelChunkIndex(last))
+    }
+
+    /// Iterates chunks in packed order.
+    pub fn iter(&self) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        self.runs
+            .iter()
+            .flat_map(|&(first, last)| (first..=last).map(VoxelChunkIndex))
+    }
+}
+
+/// First and last chunk of every row of `region` along X, clipped to the representable world.
+fn rows(region: ChunkAabb) -> impl Iterator<Item = (VoxelChunkIndex, VoxelChunkIndex)> {
+    let ChunkAabb { min, max } = region.clipped_to_world();
+    let xs = if min.0 < max.0 {
+        Some((min.0, max.0 - 1))
+    } else {
+        None
+    };
+    xs.into_iter().flat_map(move |(x0, x1)| {
+        (min.2..max.2).flat_map(move |z| {
+            (min.1..max.1).map(move |y| {
+                (
+                    VoxelChunkIndex::from_coords(x0, y, z),
+                    VoxelChunkIndex::from_coords(x1, y, z),
+                )
+            })
+        })
+    })
+}
+
+impl Extend<VoxelChunkIndex> for IntervalChunkSet {
+    fn extend<I: IntoIterator<Item = VoxelChunkIndex>>(&mut self, iter: I) {
+        for idx in iter {
+            self.insert(idx);
+        }
+    }
+}
+
+impl FromIterator<VoxelChunkIndex> for IntervalChunkSet {
+    fn from_iter<I: IntoIterator<Item = VoxelChunkIndex>>(iter: I) -> IntervalChunkSet {
+        let mut set = IntervalChunkSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn regions_past_the_world_edge_are_clipped() {
+        let mut set = IntervalChunkSet::new();
+        set.insert_region(ChunkAabb::new((4090, 0, 0), (4100, 1, 1)));
+        assert_eq!(set.len(), 6);
+        assert!(set
+            .iter()
+            .all(|idx| (4090..4096).contains(&idx.to_coords().0)));
+
+        let mut set = IntervalChunkSet::new();
+        set.insert_region(ChunkAabb::new((-4100, 0, 0), (-4090, 1, 1)));
+        assert_eq!(set.len(), 6);
+        assert!(set
+            .iter()
+            .all(|idx| (-4096..-4090).contains(&idx.to_coords().0)));
+
+        let wrapped = VoxelChunkIndex::from_coords(-4096, 1, 0);
+        set.insert(wrapped);
+        set.remove_region(ChunkAabb::new((-4100, 0, 0), (4100, 1, 1)));
+        assert_eq!(set.iter().collect::<Vec<_>>(), [wrapped]);
+    }
+}
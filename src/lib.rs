@@ -1,16 +1,134 @@
-const X_BITS: u8 = 13;
-const Y_BITS: u8 = 13;
-const Z_BITS: u8 = 6;
+pub mod analysis;
+pub mod arena;
+pub mod backup;
+mod broadphase;
+pub mod budget;
+mod bvh;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cipher;
+pub mod dedup;
+pub mod dense;
+mod entities;
+mod epoch;
+pub mod filter;
+pub mod format;
+pub mod gpu;
+pub mod hashers;
+#[cfg(feature = "png")]
+pub mod heatmap;
+mod hybrid;
+mod inline;
+#[cfg(feature = "instrument")]
+pub mod instrument;
+pub mod interest;
+mod interval;
+mod linear;
+mod loader;
+mod lru;
+#[cfg(feature = "flatbuffers")]
+pub mod manifest;
+pub mod map;
+#[cfg(feature = "prometheus")]
+mod metrics;
+pub mod migrate;
+pub mod minecraft;
+#[cfg(all(feature = "mmap", unix))]
+pub mod mmap;
+pub mod mphf;
+#[cfg(feature = "noise")]
+pub mod noise;
+pub mod observe;
+mod octree;
+pub mod paged;
+#[cfg(all(feature = "perf", target_os = "linux"))]
+pub mod perf;
+mod perfect;
+pub mod plan;
+pub mod prehash;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod read_mostly;
+mod region;
+#[cfg(feature = "serde")]
+pub mod report;
+mod set;
+mod sharded;
+pub mod sketch;
+pub mod snapshot;
+pub mod soa;
+mod sorted;
+#[cfg(feature = "sqlite")]
+mod sqlite;
+mod store;
+pub mod streaming;
+mod tick;
+pub mod trace;
+mod wal;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod workload;
 
-const X_BIAS: i32 = 1 << (X_BITS - 1);
-const Y_BIAS: i32 = 1 << (Y_BITS - 1);
-const Z_BIAS: i32 = 1 << (Z_BITS - 1);
+pub use arena::ChunkArena;
+pub use broadphase::ColliderBroadPhase;
+pub use budget::BudgetCache;
+pub use bvh::ChunkBvh;
+pub use cipher::RecordCipher;
+pub use dedup::DedupChunkStore;
+pub use dense::DenseRegionMap;
+pub use entities::ChunkEntityIndex;
+pub use epoch::EpochChunkMap;
+pub use filter::{ChunkBloom, CuckooFilter};
+pub use hybrid::HybridChunkMap;
+pub use inline::InlineChunkMap;
+pub use interest::InterestArea;
+pub use interval::IntervalChunkSet;
+pub use linear::LinearChunkMap;
+pub use loader::AsyncChunkCache;
+pub use lru::ChunkLru;
+pub use map::VoxelChunkMap;
+#[cfg(feature = "prometheus")]
+pub use metrics::PrometheusMetrics;
+pub use mphf::{ChunkMphf, MphfChunkMap};
+pub use octree::OctreeChunkSet;
+pub use paged::PagedChunkMap;
+pub use perfect::PerfectChunkMap;
+pub use region::ChunkAabb;
+pub use set::VoxelChunkSet;
+pub use sharded::ShardedChunkMap;
+pub use sketch::{DistinctChunks, FrequencySketch};
+pub use snapshot::SnapshotChunkMap;
+pub use soa::SoaChunkMap;
+pub use sorted::SortedChunkMap;
+#[cfg(feature = "sqlite")]
+pub use sqlite::SqliteChunkStore;
+pub use store::{ChunkStore, CompactionReport, RegionStore};
+pub use streaming::{ChunkMapResource, ChunkStreamer};
+pub use tick::{TickBuffered, TickWriter};
+pub use trace::{Trace, TracedMap};
+pub use wal::WalChunkMap;
 
-const X_SHIFT: u8 = 0;
-const Y_SHIFT: u8 = X_BITS;
-const Z_SHIFT: u8 = X_BITS + Y_BITS;
+/// Layout of a [`VoxelChunkIndex`]: each coordinate, from `-BIAS` up to `BIAS - 1`, is stored
+/// plus `BIAS` in the `BITS` bits `(packed >> SHIFT) & MASK`.
+pub const X_BITS: u8 = 13;
+pub const Y_BITS: u8 = 13;
+pub const Z_BITS: u8 = 6;
 
-#[derive(Hash, PartialEq, Eq, Copy, Clone)]
+pub const X_BIAS: i32 = 1 << (X_BITS - 1);
+pub const Y_BIAS: i32 = 1 << (Y_BITS - 1);
+pub const Z_BIAS: i32 = 1 << (Z_BITS - 1);
+
+pub const X_SHIFT: u8 = 0;
+pub const Y_SHIFT: u8 = X_BITS;
+pub const Z_SHIFT: u8 = X_BITS + Y_BITS;
+
+pub const X_MASK: u32 = (1 << X_BITS) - 1;
+pub const Y_MASK: u32 = (1 << Y_BITS) - 1;
+pub const Z_MASK: u32 = (1 << Z_BITS) - 1;
+
+/// Laid out as its packed `u32`, so that slices of keys cross the FFI boundary as they are.
+#[derive(Debug, Hash, PartialEq, Eq, PartialOrd, Ord, Copy, Clone)]
+#[repr(transparent)]
 pub struct VoxelChunkIndex(pub u32);
 
 impl VoxelChunkIndex {
@@ -20,4 +138,27 @@ impl VoxelChunkIndex {
         let z: u32 = ((z + Z_BIAS) as u32) << Z_SHIFT;
         Self(z | y | x)
     }
+
+    /// Inverse of [`VoxelChunkIndex::from_coords`].
+    pub fn to_coords(self) -> (i32, i32, i32) {
+        let x = ((self.0 >> X_SHIFT) & X_MASK) as i32 - X_BIAS;
+        let y = ((self.0 >> Y_SHIFT) & Y_MASK) as i32 - Y_BIAS;
+        let z = ((self.0 >> Z_SHIFT) & Z_MASK) as i32 - Z_BIAS;
+        (x, y, z)
+    }
+
+    /// The up to 26 chunks sharing a face, an edge or a corner with this one, skipping those
+    /// past the edge of the representable world.
+    pub fn neighbors(self) -> impl Iterator<Item = VoxelChunkIndex> {
+        let (x, y, z) = self.to_coords();
+        (0..27)
+            .filter(|&i| i != 13)
+            .map(move |i| (x + i % 3 - 1, y + i / 3 % 3 - 1, z + i / 9 - 1))
+            .filter(|&(x, y, z)| {
+                (-X_BIAS..X_BIAS).contains(&x)
+                    && (-Y_BIAS..Y_BIAS).contains(&y)
+                    && (-Z_BIAS..Z_BIAS).contains(&z)
+            })
+            .map(|(x, y, z)| VoxelChunkIndex::from_coords(x, y, z))
+    }
 }
@@ -0,0 +1,313 @@
+//! [`LinearChunkMap`], the first layout of [`VoxelChunkMap`](crate::VoxelChunkMap), kept as a
+//! baseline for the benchmarks.
+
+use crate::map::{MapStats, SlotInfo, FIB_MULTIPLIER};
+use crate::VoxelChunkIndex;
+const MIN_CAPACITY: usize = 8;
+
+#[derive(Clone)]
+enum Slot<V> {
+    Empty,
+    Tombstone,
+    Full(VoxelChunkIndex, V),
+}
+
+/// Hash map keyed by [`VoxelChunkIndex`], using Fibonacci hashing to pick the home slot and
+/// linear probing, one slot at a time, to resolve collisions. Removals leave tombstones, which
+/// are purged whenever the table is rehashed.
+#[derive(Clone)]
+pub struct LinearChunkMap<V> {
+    slots: Vec<Slot<V>>,
+    shift: u32,
+    len: usize,
+    tombstones: usize,
+}
+
+/// Smallest power-of-two table keeping `len` entries under the 7/8 maximum load factor.
+fn capacity_for(len: usize) -> usize {
+    (len * 8).div_ceil(7).next_power_of_two().max(MIN_CAPACITY)
+}
+
+impl<V> Default for LinearChunkMap<V> {
+    fn default() -> Self {
+        LinearChunkMap {
+            slots: Vec::new(),
+            shift: 64,
+            len: 0,
+            tombstones: 0,
+        }
+    }
+}
+
+impl<V> LinearChunkMap<V> {
+    pub fn new() -> LinearChunkMap<V> {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> LinearChunkMap<V> {
+        let mut map = Self::default();
+        if capacity > 0 {
+            map.resize(capacity_for(capacity));
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of entries the map can hold before its next rehash.
+    pub fn capacity(&self) -> usize {
+        self.slots.len() * 7 / 8
+    }
+
+    /// Fraction of the table slots holding an entry.
+    pub fn load_factor(&self) -> f64 {
+        if self.slots.is_empty() {
+            0.0
+        } else {
+            self.len as f64 / self.slots.len() as f64
+        }
+    }
+
+    fn home(&self, idx: VoxelChunkIndex) -> usize {
+        ((idx.0 as u64).wrapping_mul(FIB_MULTIPLIER) >> self.shift) as usize
+    }
+
+    fn mask(&self) -> usize {
+        self.slots.len() - 1
+    }
+
+    /// `Ok` with the slot holding `idx`, or `Err` with the slot where it should be inserted.
+    fn find(&self, idx: VoxelChunkIndex) -> Result<usize, usize> {
+        if self.slots.is_empty() {
+            return Err(0);
+        }
+
+        let mask = self.mask();
+        let mut pos = self.home(idx);
+        let mut first_tombstone = None;
+        loop {
+            match &self.slots[pos] {
+                Slot::Empty => return Err(first_tombstone.unwrap_or(pos)),
+                Slot::Tombstone => {
+                    first_tombstone.get_or_insert(pos);
+                }
+                Slot::Full(k, _) if *k == idx => return Ok(pos),
+                Slot::Full(..) => {}
+            }
+            pos = (pos + 1) & mask;
+        }
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        debug_assert!(capacity.is_power_of_two() && capacity > self.len);
+
+        let mut slots = Vec::with_capacity(capacity);
+        slots.resize_with(capacity, || Slot::Empty);
+        let old = std::mem::replace(&mut self.slots, slots);
+        self.shift = 64 - capacity.trailing_zeros();
+        self.tombstones = 0;
+
+        let mask = self.mask();
+        for slot in old {
+            if let Slot::Full(idx, value) = slot {
+                let mut pos = self.home(idx);
+                while !matches!(self.slots[pos], Slot::Empty) {
+                    pos = (pos + 1) & mask;
+                }
+                self.slots[pos] = Slot::Full(idx, value);
+            }
+        }
+    }
+
+    /// Makes room for `additional` more entries without any further rehash.
+    pub fn reserve(&mut self, additional: usize) {
+        let used = self.len + self.tombstones + additional;
+        if used * 8 <= self.slots.len() * 7 {
+            return;
+        }
+
+        // Mostly tombstones: cleaning them up in place is enough.
+        let capacity = capacity_for(self.len + additional).max(self.slots.len());
+        self.resize(capacity);
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.find(idx).is_ok()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let pos = self.find(idx).ok()?;
+        Some(self.value_at(pos))
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        let pos = self.find(idx).ok()?;
+        Some(self.value_at_mut(pos))
+    }
+
+    fn value_at(&self, pos: usize) -> &V {
+        match &self.slots[pos] {
+            Slot::Full(_, value) => value,
+            _ => unreachable!("slot {pos} is not occupied"),
+        }
+    }
+
+    fn value_at_mut(&mut self, pos: usize) -> &mut V {
+        match &mut self.slots[pos] {
+            Slot::Full(_, value) => value,
+            _ => unreachable!("slot {pos} is not occupied"),
+        }
+    }
+
+    /// Fills the vacant slot `pos` found by [`LinearChunkMap::find`].
+    fn occupy(&mut self, pos: usize, idx: VoxelChunkIndex, value: V) {
+        if matches!(self.slots[pos], Slot::Tombstone) {
+            self.tombstones -= 1;
+        }
+        self.slots[pos] = Slot::Full(idx, value);
+        self.len += 1;
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.reserve(1);
+        match self.find(idx) {
+            Ok(pos) => Some(std::mem::replace(self.value_at_mut(pos), value)),
+            Err(pos) => {
+                self.occupy(pos, idx, value);
+                None
+            }
+        }
+    }
+
+    fn remove_at(&mut self, pos: usize) -> V {
+        // A slot followed by an empty one ends every probe sequence going through it, so it can
+        // go back to empty instead of becoming a tombstone.
+        let next = (pos + 1) & self.mask();
+        let freed = if matches!(self.slots[next], Slot::Empty) {
+            Slot::Empty
+        } else {
+            self.tombstones += 1;
+            Slot::Tombstone
+        };
+
+        self.len -= 1;
+        match std::mem::replace(&mut self.slots[pos], freed) {
+            Slot::Full(_, value) => value,
+            _ => unreachable!("slot {pos} is not occupied"),
+        }
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let pos = self.find(idx).ok()?;
+        Some(self.remove_at(pos))
+    }
+
+    /// Removes every entry but keeps the allocated table.
+    pub fn clear(&mut self) {
+        self.slots.iter_mut().for_each(|slot| *slot = Slot::Empty);
+        self.len = 0;
+        self.tombstones = 0;
+    }
+
+    /// Walks the whole table to gather probe and memory statistics.
+    pub fn stats(&self) -> MapStats {
+        let mut stats = MapStats {
+            len: self.len,
+            buckets: self.slots.len(),
+            tombstones: self.tombstones,
+            max_probe_length: 0,
+            mean_probe_length: 0.0,
+            bytes_per_entry: 0.0,
+            bucket_occupancy: vec![self.slots.len()],
+        };
+        if self.len == 0 {
+            return stats;
+        }
+
+        let mut home_counts = vec![0usize; self.slots.len()];
+        let mut total_probes = 0;
+        for slot in self.slots() {
+            stats.max_probe_length = stats.max_probe_length.max(slot.probe_length);
+            total_probes += slot.probe_length;
+            home_counts[slot.home] += 1;
+        }
+
+        let max_occupancy = home_counts.iter().copied().max().unwrap_or(0);
+        stats.bucket_occupancy = vec![0; max_occupancy + 1];
+        for count in home_counts {
+            stats.bucket_occupancy[count] += 1;
+        }
+        stats.mean_probe_length = total_probes as f64 / self.len as f64;
+        stats.bytes_per_entry =
+            (self.slots.len() * std::mem::size_of::<Slot<V>>()) as f64 / self.len as f64;
+        stats
+    }
+
+    /// Where each entry sits in the table, in table order. Buckets are single slots.
+    pub fn slots(&self) -> impl Iterator<Item = SlotInfo> + '_ {
+        let mask = self.mask();
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(move |(pos, slot)| match slot {
+                Slot::Full(idx, _) => {
+                    let home = self.home(*idx);
+                    Some(SlotInfo {
+                        idx: *idx,
+                        hash: (idx.0 as u64).wrapping_mul(FIB_MULTIPLIER),
+                        home,
+                        bucket: pos,
+                        probe_length: (pos.wrapping_sub(home) & mask) + 1,
+                    })
+                }
+                _ => None,
+            })
+    }
+
+    /// Iterates entries in table order, which is arbitrary.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.slots.iter().filter_map(|slot| match slot {
+            Slot::Full(idx, value) => Some((*idx, value)),
+            _ => None,
+        })
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        self.slots.iter_mut().filter_map(|slot| match slot {
+            Slot::Full(idx, value) => Some((*idx, value)),
+            _ => None,
+        })
+    }
+
+    pub fn keys(&self) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        self.iter().map(|(idx, _)| idx)
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.iter().map(|(_, value)| value)
+    }
+}
+
+impl<V> FromIterator<(VoxelChunkIndex, V)> for LinearChunkMap<V> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(iter: I) -> LinearChunkMap<V> {
+        let mut map = LinearChunkMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<V> Extend<(VoxelChunkIndex, V)> for LinearChunkMap<V> {
+    fn extend<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (idx, value) in iter {
+            self.insert(idx, value);
+        }
+    }
+}
@@ -0,0 +1,182 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Waker};
+
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+enum Load<V, E> {
+    Running(Vec<Waker>),
+    Done(Result<V, E>),
+    /// The task running the load was dropped before it finished.
+    Abandoned,
+}
+
+type Pending<V, E> = Arc<Mutex<Load<V, E>>>;
+
+struct State<V, E> {
+    ready: VoxelChunkMap<V>,
+    loading: VoxelChunkMap<Pending<V, E>>,
+}
+
+/// Chunk cache filling its misses with an async loader, such as a disk or network read.
+/// Concurrent requests for a chunk being loaded wait for that load instead of starting their
+/// own. Runtime agnostic: loads run on the task of the first request.
+///
+/// Successful loads are cached, failures are handed to every waiting request and not cached, so
+/// the next request tries again.
+pub struct AsyncChunkCache<V, E, L> {
+    loader: L,
+    state: Mutex<State<V, E>>,
+}
+
+/// Resolves once the load it waits for ends; `None` if it was abandoned.
+struct WaitLoad<V, E>(Pending<V, E>);
+
+impl<V: Clone, E: Clone> Future for WaitLoad<V, E> {
+    type Output = Option<Result<V, E>>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut load = self.0.lock().expect("load lock poisoned");
+        match &mut *load {
+            Load::Running(wakers) => {
+                if !wakers.iter().any(|w| w.will_wake(cx.waker())) {
+                    wakers.push(cx.waker().clone());
+                }
+                Poll::Pending
+            }
+            Load::Done(result) => Poll::Ready(Some(result.clone())),
+            Load::Abandoned => Poll::Ready(None),
+        }
+    }
+}
+
+enum Role<V, E> {
+    Load(Pending<V, E>),
+    Wait(Pending<V, E>),
+}
+
+/// Held by the request running a load, so that dropping it mid-load hands the load over.
+struct Leader<'a, V, E, L> {
+    cache: &'a AsyncChunkCache<V, E, L>,
+    idx: VoxelChunkIndex,
+    pending: Pending<V, E>,
+    finished: bool,
+}
+
+impl<V, E, L> Leader<'_, V, E, L> {
+    fn finish(&mut self, load: Load<V, E>) {
+        self.finished = true;
+        self.cache
+            .state
+            .lock()
+            .expect("cache lock poisoned")
+            .loading
+            .remove(self.idx);
+
+        let wakers =
+            match std::mem::replace(&mut *self.pending.lock().expect("load lock poisoned"), load) {
+                Load::Running(wakers) => wakers,
+                _ => Vec::new(),
+            };
+        wakers.into_iter().for_each(Waker::wake);
+    }
+}
+
+impl<V, E, L> Drop for Leader<'_, V, E, L> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.finish(Load::Abandoned);
+        }
+    }
+}
+
+impl<V: Clone, E: Clone, L> AsyncChunkCache<V, E, L> {
+    pub fn new(loader: L) -> AsyncChunkCache<V, E, L> {
+        AsyncChunkCache {
+            loader,
+            state: Mutex::new(State {
+                ready: VoxelChunkMap::new(),
+                loading: VoxelChunkMap::new(),
+            }),
+        }
+    }
+
+    fn state(&self) -> std::sync::MutexGuard<'_, State<V, E>> {
+        self.state.lock().expect("cache lock poisoned")
+    }
+
+    /// Cached value, without loading it.
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<V> {
+        self.state().ready.get(idx).cloned()
+    }
+
+    /// Whether a load of `idx` is in flight.
+    pub fn is_loading(&self, idx: VoxelChunkIndex) -> bool {
+        self.state().loading.contains_key(idx)
+    }
+
+    pub fn insert(&self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.state().ready.insert(idx, value)
+    }
+
+    /// Drops a cached value; a load in flight still caches its result.
+    pub fn remove(&self, idx: VoxelChunkIndex) -> Option<V> {
+        self.state().ready.remove(idx)
+    }
+
+    pub fn len(&self) -> usize {
+        self.state().ready.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.state().ready.is_empty()
+    }
+
+    /// Cached value of `idx`, loading it first on a miss. If the request running a load is
+    /// dropped, one of the requests waiting for it starts the load again.
+    pub async fn get_or_load<Fut>(&self, idx: VoxelChunkIndex) -> Result<V, E>
+    where
+        L: Fn(VoxelChunkIndex) -> Fut,
+        Fut: Future<Output = Result<V, E>>,
+    {
+        loop {
+            let role = {
+                let mut state = self.state();
+                if let Some(value) = state.ready.get(idx) {
+                    return Ok(value.clone());
+                }
+                match state.loading.get(idx) {
+                    Some(pending) => Role::Wait(pending.clone()),
+                    None => {
+                        let pending = Arc::new(Mutex::new(Load::Running(Vec::new())));
+                        state.loading.insert(idx, pending.clone());
+                        Role::Load(pending)
+                    }
+                }
+            };
+
+            match role {
+                Role::Load(pending) => {
+                    let mut leader = Leader {
+                        cache: self,
+                        idx,
+                        pending,
+                        finished: false,
+                    };
+                    let result = (self.loader)(idx).await;
+                    if let Ok(value) = &result {
+                        self.state().ready.insert(idx, value.clone());
+                    }
+                    leader.finish(Load::Done(result.clone()));
+                    return result;
+                }
+                Role::Wait(pending) => {
+                    if let Some(result) = WaitLoad(pending).await {
+                        return result;
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,363 @@
+use crate::observe::Observers;
+use crate::sketch::FrequencySketch;
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+const NIL: u32 = u32::MAX;
+
+struct Node<V> {
+    idx: VoxelChunkIndex,
+    value: V,
+    prev: u32,
+    next: u32,
+}
+
+/// Capacity-bounded chunk cache evicting the least recently used chunk. Entries live in a dense
+/// `Vec` linked into a recency list by position, and a [`VoxelChunkMap`] maps each key to its node.
+pub struct ChunkLru<V> {
+    nodes: Vec<Node<V>>,
+    positions: VoxelChunkMap<u32>,
+    head: u32,
+    tail: u32,
+    capacity: usize,
+    on_evict: Option<Box<dyn FnMut(VoxelChunkIndex, V)>>,
+    observers: Observers<V>,
+    admission: Option<FrequencySketch>,
+    evictions: u64,
+}
+
+impl<V> ChunkLru<V> {
+    /// Cache holding at most `capacity` chunks.
+    pub fn new(capacity: usize) -> ChunkLru<V> {
+        assert!(capacity > 0, "cache capacity must be non-zero");
+        assert!(capacity < NIL as usize, "cache capacity too large");
+        ChunkLru {
+            nodes: Vec::new(),
+            positions: VoxelChunkMap::new(),
+            head: NIL,
+            tail: NIL,
+            capacity,
+            on_evict: None,
+            observers: Observers::default(),
+            admission: None,
+            evictions: 0,
+        }
+    }
+
+    /// Turns on TinyLFU admission: accesses are counted in a [`FrequencySketch`], and a new chunk
+    /// only gets into a full cache if it was accessed more often than the chunk it would evict.
+    /// A rejected chunk goes straight to the eviction callback. Keeps frequently revisited
+    /// chunks resident while the player flies through many chunks only seen once.
+    pub fn enable_admission(&mut self) {
+        self.admission = Some(FrequencySketch::new(self.capacity));
+    }
+
+    /// The access counts admission decisions are based on, if admission is on.
+    pub fn frequency_sketch(&self) -> Option<&FrequencySketch> {
+        self.admission.as_ref()
+    }
+
+    fn record_access(&mut self, idx: VoxelChunkIndex) {
+        if let Some(sketch) = &mut self.admission {
+            sketch.increment(idx);
+        }
+    }
+
+    /// Registers the function evicted chunks are handed to, e.g. to persist them. Chunks removed
+    /// explicitly or dropped by [`ChunkLru::clear`] don't go through it.
+    pub fn on_evict<F: FnMut(VoxelChunkIndex, V) + 'static>(&mut self, f: F) {
+        self.on_evict = Some(Box::new(f));
+    }
+
+    /// Names the cache, and the map of its positions, in the [`instrument`](crate::instrument)
+    /// events they report.
+    #[cfg(feature = "instrument")]
+    pub fn set_label(&mut self, label: &'static str) {
+        self.positions.set_label(label);
+    }
+
+    /// Callbacks run on inserts, removals and evictions. Unlike the [`ChunkLru::on_evict`]
+    /// function, they only get to see evicted values, not keep them.
+    pub fn observers(&mut self) -> &mut Observers<V> {
+        &mut self.observers
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Chunks evicted or rejected by admission since the cache was created.
+    pub fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Memory the nodes and the map of their positions take, values included but not what they
+    /// own.
+    #[cfg(feature = "prometheus")]
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.nodes.capacity() * std::mem::size_of::<Node<V>>() + self.positions.heap_bytes()
+    }
+
+    /// Changes the capacity, evicting least recently used chunks until the cache fits.
+    pub fn set_capacity(&mut self, capacity: usize) {
+        assert!(capacity > 0, "cache capacity must be non-zero");
+        assert!(capacity < NIL as usize, "cache capacity too large");
+        self.capacity = capacity;
+        while self.nodes.len() > capacity {
+            let idx = self.nodes[self.tail as usize].idx;
+            self.positions.remove(idx);
+            let (idx, value) = self.remove_node(self.tail);
+            self.evicted(idx, value);
+        }
+    }
+
+    /// Does not update recency.
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.positions.contains_key(idx)
+    }
+
+    /// Looks a chunk up without updating recency.
+    pub fn peek(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let &pos = self.positions.get(idx)?;
+        Some(&self.nodes[pos as usize].value)
+    }
+
+    /// Looks a chunk up and marks it most recently used. With admission on, misses count as
+    /// accesses too.
+    pub fn get(&mut self, idx: VoxelChunkIndex) -> Option<&V> {
+        self.record_access(idx);
+        let &pos = self.positions.get(idx)?;
+        self.touch(pos);
+        Some(&self.nodes[pos as usize].value)
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        self.record_access(idx);
+        let &pos = self.positions.get(idx)?;
+        self.touch(pos);
+        Some(&mut self.nodes[pos as usize].value)
+    }
+
+    /// Inserts a chunk as most recently used, returning the value it replaced. Inserting a new
+    /// chunk into a full cache evicts the least recently used one, unless admission rejects it.
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.record_access(idx);
+        if let Some(&pos) = self.positions.get(idx) {
+            self.touch(pos);
+            let node = &mut self.nodes[pos as usize];
+            let old = std::mem::replace(&mut node.value, value);
+            self.observers.inserted(idx, &node.value);
+            return Some(old);
+        }
+
+        if self.nodes.len() < self.capacity {
+            let pos = self.nodes.len() as u32;
+            self.nodes.push(Node {
+                idx,
+                value,
+                prev: NIL,
+                next: NIL,
+            });
+            self.push_front(pos);
+            self.positions.insert(idx, pos);
+            self.observers
+                .inserted(idx, &self.nodes[pos as usize].value);
+            return None;
+        }
+
+        // Full: reuse the tail node for the new chunk.
+        let pos = self.tail;
+        if let Some(sketch) = &self.admission {
+            let victim = self.nodes[pos as usize].idx;
+            if sketch.frequency(idx) <= sketch.frequency(victim) {
+                self.evicted(idx, value);
+                return None;
+            }
+        }
+        let node = &mut self.nodes[pos as usize];
+        let old_idx = std::mem::replace(&mut node.idx, idx);
+        let old_value = std::mem::replace(&mut node.value, value);
+        self.positions.remove(old_idx);
+        self.positions.insert(idx, pos);
+        self.touch(pos);
+        self.evicted(old_idx, old_value);
+        self.observers
+            .inserted(idx, &self.nodes[pos as usize].value);
+        None
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let pos = self.positions.remove(idx)?;
+        let (idx, value) = self.remove_node(pos);
+        self.observers.removed(idx, &value);
+        Some(value)
+    }
+
+    /// Least recently used chunk, without updating recency.
+    pub fn peek_lru(&self) -> Option<(VoxelChunkIndex, &V)> {
+        let node = self.nodes.get(self.tail as usize)?;
+        Some((node.idx, &node.value))
+    }
+
+    /// Removes the least recently used chunk without handing it to the eviction callback.
+    pub fn pop_lru(&mut self) -> Option<(VoxelChunkIndex, V)> {
+        if self.tail == NIL {
+            return None;
+        }
+        let idx = self.nodes[self.tail as usize].idx;
+        self.remove(idx).map(|value| (idx, value))
+    }
+
+    pub fn clear(&mut self) {
+        for node in &self.nodes {
+            self.observers.removed(node.idx, &node.value);
+        }
+        self.nodes.clear();
+        self.positions.clear();
+        self.head = NIL;
+        self.tail = NIL;
+    }
+
+    /// Iterates from most to least recently used.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        let mut pos = self.head;
+        std::iter::from_fn(move || {
+            let node = self.nodes.get(pos as usize)?;
+            pos = node.next;
+            Some((node.idx, &node.value))
+        })
+    }
+
+    fn evicted(&mut self, idx: VoxelChunkIndex, value: V) {
+        self.evictions += 1;
+        #[cfg(feature = "instrument")]
+        crate::instrument::emit(
+            || crate::instrument::MapFields {
+                container: "ChunkLru",
+                label: self.positions.label(),
+                len: self.nodes.len(),
+                capacity: self.capacity,
+            },
+            || crate::instrument::ContainerEvent::Evict { idx },
+        );
+        self.observers.evicted(idx, &value);
+        if let Some(f) = &mut self.on_evict {
+            f(idx, value);
+        }
+    }
+
+    fn unlink(&mut self, pos: u32) {
+        let Node { prev, next, .. } = self.nodes[pos as usize];
+        match prev {
+            NIL => self.head = next,
+            prev => self.nodes[prev as usize].next = next,
+        }
+        match next {
+            NIL => self.tail = prev,
+            next => self.nodes[next as usize].prev = prev,
+        }
+    }
+
+    fn push_front(&mut self, pos: u32) {
+        let node = &mut self.nodes[pos as usize];
+        node.prev = NIL;
+        node.next = self.head;
+        match self.head {
+            NIL => self.tail = pos,
+            head => self.nodes[head as usize].prev = pos,
+        }
+        self.head = pos;
+    }
+
+    fn touch(&mut self, pos: u32) {
+        if self.head != pos {
+            self.unlink(pos);
+            self.push_front(pos);
+        }
+    }
+
+    /// Unlinks and removes the node at `pos`, moving the last node into the hole. The caller has
+    /// already dropped the node's key from `positions`.
+    fn remove_node(&mut self, pos: u32) -> (VoxelChunkIndex, V) {
+        self.unlink(pos);
+        let node = self.nodes.swap_remove(pos as usize);
+
+        if let Some(moved) = self.nodes.get(pos as usize) {
+            let Node {
+                idx, prev, next, ..
+            } = *moved;
+            *self.positions.get_mut(idx).unwrap() = pos;
+            match prev {
+                NIL => self.head = pos,
+                prev => self.nodes[prev as usize].next = pos,
+            }
+            match next {
+                NIL => self.tail = pos,
+                next => self.nodes[next as usize].prev = pos,
+            }
+        }
+        (node.idx, node.value)
+    }
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize)]
+struct LruRef<'a, V> {
+    capacity: usize,
+    entries: crate::map::packed::EntriesRef<'a, V>,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct LruEntries<V> {
+    capacity: usize,
+    entries: crate::map::packed::Entries<V>,
+}
+
+/// Serializes as the capacity and the entries from least to most recently used, so that the
+/// deserialized cache evicts in the same order. The eviction callback, observers and admission
+/// sketch are left out.
+#[cfg(feature = "serde")]
+impl<V: serde::Serialize> serde::Serialize for ChunkLru<V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<(VoxelChunkIndex, &V)> = self.iter().collect();
+        entries.reverse();
+        LruRef {
+            capacity: self.capacity,
+            entries: entries.into_iter().collect(),
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, V: serde::Deserialize<'de>> serde::Deserialize<'de> for ChunkLru<V> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let LruEntries { capacity, entries } = LruEntries::deserialize(deserializer)?;
+        if capacity == 0 || capacity >= NIL as usize {
+            return Err(D::Error::custom(format!(
+                "invalid cache capacity {capacity}"
+            )));
+        }
+        if entries.len() > capacity {
+            return Err(D::Error::custom(format!(
+                "{} entries in a cache of capacity {capacity}",
+                entries.len()
+            )));
+        }
+        let mut lru = ChunkLru::new(capacity);
+        for (idx, value) in entries.into_pairs()? {
+            lru.insert(idx, value);
+        }
+        Ok(lru)
+    }
+}
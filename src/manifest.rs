@@ -0,0 +1,222 @@
+//! Chunk manifests in the FlatBuffers format, so that game clients in other languages read the
+//! index lists and chunk metadata a server produces in place, with the accessors `flatc`
+//! generates from [`SCHEMA`], rather than parsing them or depending on this crate.
+//!
+//! [`encode_manifest`] writes the buffer itself, front to back, and [`ChunkManifest`] reads
+//! buffers of that schema from any FlatBuffers writer without copying them.
+
+use std::io;
+
+use crate::{VoxelChunkIndex, X_BITS, Y_BITS, Z_BITS};
+
+/// The FlatBuffers schema of manifests, `schema/chunk_manifest.fbs` in the repository.
+pub const SCHEMA: &str = include_str!("../schema/chunk_manifest.fbs");
+
+/// File identifier of manifests, right after the offset of the root table.
+pub const FILE_IDENTIFIER: &[u8; 4] = b"HFCM";
+
+/// Field ids of the `ChunkManifest` table, in schema order.
+const X_BITS_FIELD: usize = 0;
+const Y_BITS_FIELD: usize = 1;
+const Z_BITS_FIELD: usize = 2;
+const INDICES_FIELD: usize = 3;
+const CHUNKS_FIELD: usize = 4;
+/// Byte size of a `ChunkEntry` struct.
+const ENTRY_SIZE: usize = 16;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Metadata of one stored chunk, the `ChunkEntry` struct of the schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChunkEntry {
+    pub idx: VoxelChunkIndex,
+    /// Byte size of the chunk's blob.
+    pub size: u32,
+    /// [`ContentHasher`](crate::hashers::ContentHasher) hash of the blob.
+    pub content_hash: u64,
+}
+
+fn align(out: &mut Vec<u8>, to: usize) {
+    out.resize(out.len().next_multiple_of(to), 0);
+}
+
+/// Encodes a manifest of the list `indices`, kept in its order, and of the metadata `chunks`,
+/// sorted by key on the way so that readers can binary search them.
+pub fn encode_manifest(indices: &[VoxelChunkIndex], chunks: &[ChunkEntry]) -> Vec<u8> {
+    let mut chunks = chunks.to_vec();
+    chunks.sort_unstable_by_key(|entry| entry.idx);
+
+    // The root offset and the identifier, the vtable, then the table, then its vectors: every
+    // offset but the table's to its vtable points forward, as FlatBuffers wants them.
+    const VTABLE: usize = 8;
+    const VTABLE_LEN: u16 = 4 + 2 * 5;
+    const TABLE: usize = 24;
+    const TABLE_LEN: u16 = 16;
+    let mut out = Vec::with_capacity(48 + 4 * indices.len() + ENTRY_SIZE * chunks.len());
+    out.extend_from_slice(&(TABLE as u32).to_le_bytes());
+    out.extend_from_slice(FILE_IDENTIFIER);
+    // Field offsets within the table: the two vector offsets first, for their alignment, then
+    // the three bit counts.
+    for half in [VTABLE_LEN, TABLE_LEN, 12, 13, 14, 4, 8] {
+        out.extend_from_slice(&half.to_le_bytes());
+    }
+    align(&mut out, 4);
+    debug_assert_eq!(out.len(), TABLE);
+    out.extend_from_slice(&((TABLE - VTABLE) as i32).to_le_bytes());
+    out.extend_from_slice(&[0; 8]);
+    out.extend_from_slice(&[X_BITS, Y_BITS, Z_BITS, 0]);
+
+    let indices_at = out.len();
+    out[TABLE + 4..TABLE + 8].copy_from_slice(&((indices_at - (TABLE + 4)) as u32).to_le_bytes());
+    out.extend_from_slice(&(indices.len() as u32).to_le_bytes());
+    for idx in indices {
+        out.extend_from_slice(&idx.0.to_le_bytes());
+    }
+
+    // Structs with a `ulong` are 8-aligned, and their vector's length comes right before them.
+    out.resize((out.len() + 4).next_multiple_of(8) - 4, 0);
+    let chunks_at = out.len();
+    out[TABLE + 8..TABLE + 12].copy_from_slice(&((chunks_at - (TABLE + 8)) as u32).to_le_bytes());
+    out.extend_from_slice(&(chunks.len() as u32).to_le_bytes());
+    for entry in &chunks {
+        out.extend_from_slice(&entry.idx.0.to_le_bytes());
+        out.extend_from_slice(&entry.size.to_le_bytes());
+        out.extend_from_slice(&entry.content_hash.to_le_bytes());
+    }
+    out
+}
+
+/// Read-only view of a manifest buffer. [`ChunkManifest::new`] checks the buffer once, so that
+/// the accessors read it in place without checks.
+#[derive(Clone, Copy)]
+pub struct ChunkManifest<'a> {
+    bytes: &'a [u8],
+    /// Where the elements of the vectors start, and how many there are; absent vectors are
+    /// empty.
+    indices: (usize, usize),
+    chunks: (usize, usize),
+}
+
+impl<'a> ChunkManifest<'a> {
+    /// Checks that `bytes` is a manifest and that its keys are packed with this build's bit
+    /// layout.
+    pub fn new(bytes: &'a [u8]) -> io::Result<ChunkManifest<'a>> {
+        let u32_at = |at: usize| -> io::Result<u32> {
+            bytes
+                .get(at..at + 4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+                .ok_or_else(|| invalid(format!("manifest cut short at byte {at}")))
+        };
+        let u16_at = |at: usize| -> io::Result<u16> {
+            bytes
+                .get(at..at + 2)
+                .map(|b| u16::from_le_bytes(b.try_into().unwrap()))
+                .ok_or_else(|| invalid(format!("manifest cut short at byte {at}")))
+        };
+
+        let table = u32_at(0)? as usize;
+        if bytes.get(4..8) != Some(FILE_IDENTIFIER) {
+            return Err(invalid("not a chunk manifest".to_owned()));
+        }
+        let vtable = (table as i64 - u32_at(table)? as i32 as i64).max(0) as usize;
+        let vtable_len = u16_at(vtable)? as usize;
+        let table_len = u16_at(vtable + 2)? as usize;
+        if table + table_len > bytes.len() {
+            return Err(invalid(
+                "manifest table past the end of the buffer".to_owned(),
+            ));
+        }
+        // Position of field `id` in the buffer, if present.
+        let field = |id: usize| -> io::Result<Option<usize>> {
+            if 4 + 2 * id + 2 > vtable_len {
+                return Ok(None);
+            }
+            match u16_at(vtable + 4 + 2 * id)? as usize {
+                0 => Ok(None),
+                offset if offset + 1 > table_len => {
+                    Err(invalid(format!("manifest field {id} past its table")))
+                }
+                offset => Ok(Some(table + offset)),
+            }
+        };
+        let byte = |id: usize| -> io::Result<u8> { Ok(field(id)?.map_or(0, |at| bytes[at])) };
+        let vector = |id: usize, size: usize| -> io::Result<(usize, usize)> {
+            let Some(at) = field(id)? else {
+                return Ok((0, 0));
+            };
+            let start = at + u32_at(at)? as usize;
+            let len = u32_at(start)? as usize;
+            if (start + 4).saturating_add(len.saturating_mul(size)) > bytes.len() {
+                return Err(invalid(format!(
+                    "manifest vector {id} past the end of the buffer"
+                )));
+            }
+            Ok((start + 4, len))
+        };
+
+        let layout = [
+            byte(X_BITS_FIELD)?,
+            byte(Y_BITS_FIELD)?,
+            byte(Z_BITS_FIELD)?,
+        ];
+        if layout != [X_BITS, Y_BITS, Z_BITS] {
+            return Err(invalid(format!(
+                "indices packed with {}/{}/{} X/Y/Z bits, this build packs them with \
+                 {X_BITS}/{Y_BITS}/{Z_BITS}",
+                layout[0], layout[1], layout[2]
+            )));
+        }
+        Ok(ChunkManifest {
+            bytes,
+            indices: vector(INDICES_FIELD, 4)?,
+            chunks: vector(CHUNKS_FIELD, ENTRY_SIZE)?,
+        })
+    }
+
+    fn u32_at(&self, at: usize) -> u32 {
+        u32::from_le_bytes(self.bytes[at..at + 4].try_into().unwrap())
+    }
+
+    /// The list of indices, in its order.
+    pub fn indices(&self) -> impl ExactSizeIterator<Item = VoxelChunkIndex> + '_ {
+        let (start, len) = self.indices;
+        (0..len).map(move |i| VoxelChunkIndex(self.u32_at(start + 4 * i)))
+    }
+
+    pub fn chunks_len(&self) -> usize {
+        self.chunks.1
+    }
+
+    /// The `i`-th chunk, in increasing key order.
+    pub fn chunk(&self, i: usize) -> ChunkEntry {
+        assert!(i < self.chunks.1, "chunk {i} out of {}", self.chunks.1);
+        let at = self.chunks.0 + ENTRY_SIZE * i;
+        ChunkEntry {
+            idx: VoxelChunkIndex(self.u32_at(at)),
+            size: self.u32_at(at + 4),
+            content_hash: u64::from_le_bytes(self.bytes[at + 8..at + 16].try_into().unwrap()),
+        }
+    }
+
+    pub fn chunks(&self) -> impl ExactSizeIterator<Item = ChunkEntry> + '_ {
+        (0..self.chunks.1).map(|i| self.chunk(i))
+    }
+
+    /// Metadata of `idx`, by binary search: a manifest from another writer must keep its chunks
+    /// sorted too.
+    pub fn find(&self, idx: VoxelChunkIndex) -> Option<ChunkEntry> {
+        let (mut lo, mut hi) = (0, self.chunks.1);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            let key = VoxelChunkIndex(self.u32_at(self.chunks.0 + ENTRY_SIZE * mid));
+            match key.cmp(&idx) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(self.chunk(mid)),
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,1361 @@
+//! [`VoxelChunkMap`], an open-addressing hash map specialized for [`VoxelChunkIndex`] keys.
+
+use crate::observe::Observers;
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+/// Same multiplier as the benchmarks' `FibHasher`: 2^64 divided by the golden ratio.
+pub(crate) const FIB_MULTIPLIER: u64 = 11400714819323198485;
+
+const GROUP_WIDTH: usize = 16;
+const EMPTY: u8 = 0b1111_1111;
+const DELETED: u8 = 0b1000_0000;
+
+/// Control bytes of 16 consecutive slots, each being [`EMPTY`], [`DELETED`], or the 7-bit tag of
+/// the key stored in the slot. Aligned so that a group is scanned with a single SIMD load, and a
+/// cache line holds the tags of 64 slots.
+#[derive(Clone, Copy)]
+#[repr(align(16))]
+struct Group([u8; GROUP_WIDTH]);
+
+/// Slots of a group matching some predicate, one bit per slot.
+#[derive(Clone, Copy)]
+struct BitMask(u16);
+
+impl BitMask {
+    #[inline]
+    fn any(self) -> bool {
+        self.0 != 0
+    }
+
+    #[inline]
+    fn lowest(self) -> Option<usize> {
+        if self.any() {
+            Some(self.0.trailing_zeros() as usize)
+        } else {
+            None
+        }
+    }
+}
+
+impl Iterator for BitMask {
+    type Item = usize;
+
+    #[inline]
+    fn next(&mut self) -> Option<usize> {
+        let bit = self.lowest()?;
+        self.0 &= self.0 - 1;
+        Some(bit)
+    }
+}
+
+impl Group {
+    const EMPTY: Group = Group([EMPTY; GROUP_WIDTH]);
+
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    fn match_byte(&self, byte: u8) -> BitMask {
+        use core::arch::x86_64::*;
+
+        // SAFETY: `Group` is 16-byte aligned, and SSE2 is part of the x86_64 baseline.
+        unsafe {
+            let group = _mm_load_si128(self.0.as_ptr() as *const __m128i);
+            let matches = _mm_cmpeq_epi8(group, _mm_set1_epi8(byte as i8));
+            BitMask(_mm_movemask_epi8(matches) as u16)
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    #[inline]
+    fn match_byte(&self, byte: u8) -> BitMask {
+        let mut mask = 0;
+        for (i, &b) in self.0.iter().enumerate() {
+            mask |= ((b == byte) as u16) << i;
+        }
+        BitMask(mask)
+    }
+
+    #[inline]
+    fn match_empty(&self) -> BitMask {
+        self.match_byte(EMPTY)
+    }
+
+    /// Empty or deleted slots, which are the only control bytes with their top bit set.
+    #[cfg(target_arch = "x86_64")]
+    #[inline]
+    fn match_free(&self) -> BitMask {
+        use core::arch::x86_64::*;
+
+        // SAFETY: see `match_byte`.
+        unsafe {
+            let group = _mm_load_si128(self.0.as_ptr() as *const __m128i);
+            BitMask(_mm_movemask_epi8(group) as u16)
+        }
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    #[inline]
+    fn match_free(&self) -> BitMask {
+        let mut mask = 0;
+        for (i, &b) in self.0.iter().enumerate() {
+            mask |= ((b & 0x80 != 0) as u16) << i;
+        }
+        BitMask(mask)
+    }
+}
+
+#[inline]
+fn hash(idx: VoxelChunkIndex) -> u64 {
+    (idx.0 as u64).wrapping_mul(FIB_MULTIPLIER)
+}
+
+/// The 7 top bits of the hash, stored in the control byte of the key's slot.
+#[inline]
+fn tag(hash: u64) -> u8 {
+    (hash >> 57) as u8
+}
+
+/// Hash map keyed by [`VoxelChunkIndex`], laid out like a SwissTable: slots are split into groups
+/// of 16, and a separate array holds one control byte per slot with a 7-bit tag of its key. A
+/// probe loads a whole group of tags at once and compares them with SIMD, so most lookups touch a
+/// single group of tags and a single key. Keys and values live in separate arrays too, so probing
+/// never drags values through the cache.
+///
+/// The group index comes from Fibonacci hashing the key, and colliding groups are probed with
+/// triangular steps. Removals leave tombstones only in groups that were full, which are purged
+/// whenever the table is rehashed.
+///
+/// Values are stored inline, so [`Clone`] copies the whole table; use
+/// [`SnapshotChunkMap`](crate::SnapshotChunkMap) for cheap consistent snapshots.
+#[derive(Clone)]
+pub struct VoxelChunkMap<V> {
+    ctrl: Vec<Group>,
+    keys: Vec<VoxelChunkIndex>,
+    values: Vec<Option<V>>,
+    shift: u32,
+    len: usize,
+    tombstones: usize,
+    changes: Option<Box<VoxelChunkMap<Change>>>,
+    observers: Option<Box<Observers<V>>>,
+    #[cfg(feature = "instrument")]
+    label: Option<&'static str>,
+}
+
+/// How a key changed since change tracking was last drained, see
+/// [`VoxelChunkMap::track_changes`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+    /// The key was absent and is now present.
+    Inserted,
+    /// The key was present and still is, and its value may have changed.
+    Modified,
+    /// The key was present and is now absent.
+    Removed,
+}
+
+/// Folds one mutation of `idx` into the change log, given whether the key was present before and
+/// after it.
+fn record(
+    changes: &mut Option<Box<VoxelChunkMap<Change>>>,
+    idx: VoxelChunkIndex,
+    before: bool,
+    after: bool,
+) {
+    let Some(changes) = changes else {
+        return;
+    };
+    let change = match (changes.get(idx), before, after) {
+        (Some(Change::Inserted), _, true) => Change::Inserted,
+        (Some(Change::Inserted), _, false) => {
+            changes.remove(idx);
+            return;
+        }
+        (Some(_), _, true) | (None, true, true) => Change::Modified,
+        (Some(_), _, false) | (None, true, false) => Change::Removed,
+        (None, false, true) => Change::Inserted,
+        (None, false, false) => return,
+    };
+    changes.insert(idx, change);
+}
+
+/// Smallest power-of-two table keeping `len` entries under the 7/8 maximum load factor.
+fn capacity_for(len: usize) -> usize {
+    (len * 8).div_ceil(7).next_power_of_two().max(GROUP_WIDTH)
+}
+
+impl<V> Default for VoxelChunkMap<V> {
+    fn default() -> Self {
+        VoxelChunkMap {
+            ctrl: Vec::new(),
+            keys: Vec::new(),
+            values: Vec::new(),
+            shift: 64,
+            len: 0,
+            tombstones: 0,
+            changes: None,
+            observers: None,
+            #[cfg(feature = "instrument")]
+            label: None,
+        }
+    }
+}
+
+impl<V> VoxelChunkMap<V> {
+    pub fn new() -> VoxelChunkMap<V> {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> VoxelChunkMap<V> {
+        let mut map = Self::default();
+        if capacity > 0 {
+            map.resize(capacity_for(capacity));
+        }
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of entries the map can hold before its next rehash.
+    pub fn capacity(&self) -> usize {
+        self.keys.len() * 7 / 8
+    }
+
+    /// Deleted slots left in the table until its next rehash.
+    pub fn tombstones(&self) -> usize {
+        self.tombstones
+    }
+
+    /// Fraction of the table slots holding an entry.
+    pub fn load_factor(&self) -> f64 {
+        if self.keys.is_empty() {
+            0.0
+        } else {
+            self.len as f64 / self.keys.len() as f64
+        }
+    }
+
+    /// The group a hash starts probing from, taken from the bits just below the tag.
+    fn home(&self, hash: u64) -> usize {
+        (hash << 7).checked_shr(self.shift).unwrap_or(0) as usize
+    }
+
+    fn group_mask(&self) -> usize {
+        self.ctrl.len() - 1
+    }
+
+    fn set_ctrl(&mut self, pos: usize, byte: u8) {
+        self.ctrl[pos / GROUP_WIDTH].0[pos % GROUP_WIDTH] = byte;
+    }
+
+    /// `Ok` with the slot holding `idx`, or `Err` with the slot where it should be inserted.
+    fn find(&self, idx: VoxelChunkIndex) -> Result<usize, usize> {
+        if self.keys.is_empty() {
+            return Err(0);
+        }
+        self.find_hashed(idx, hash(idx))
+    }
+
+    /// [`VoxelChunkMap::find`] with an already computed hash, on an allocated table.
+    fn find_hashed(&self, idx: VoxelChunkIndex, hash: u64) -> Result<usize, usize> {
+        let tag = tag(hash);
+        let mask = self.group_mask();
+        let mut group = self.home(hash);
+        let mut stride = 0;
+        let mut free = None;
+        let found = 'probe: loop {
+            let ctrl = &self.ctrl[group];
+            for bit in ctrl.match_byte(tag) {
+                let pos = group * GROUP_WIDTH + bit;
+                if self.keys[pos] == idx {
+                    break 'probe Ok(pos);
+                }
+            }
+
+            if free.is_none() {
+                free = ctrl
+                    .match_free()
+                    .lowest()
+                    .map(|bit| group * GROUP_WIDTH + bit);
+            }
+            // A group with an empty slot was never full, so no probe ever went past it.
+            if ctrl.match_empty().any() {
+                break Err(free.unwrap());
+            }
+
+            stride += 1;
+            group = (group + stride) & mask;
+        };
+        #[cfg(feature = "instrument")]
+        self.report_probe(idx, stride + 1);
+        found
+    }
+
+    /// Emits a [`LongProbe`](crate::instrument::ContainerEvent::LongProbe) if looking `idx` up
+    /// visited too many groups.
+    #[cfg(feature = "instrument")]
+    fn report_probe(&self, idx: VoxelChunkIndex, groups: usize) {
+        if groups >= crate::instrument::LONG_PROBE_GROUPS {
+            crate::instrument::emit(
+                || self.fields(),
+                || crate::instrument::ContainerEvent::LongProbe { idx, groups },
+            );
+        }
+    }
+
+    fn resize(&mut self, capacity: usize) {
+        debug_assert!(capacity.is_power_of_two() && capacity > self.len);
+        #[cfg(feature = "instrument")]
+        let rehash = crate::instrument::enabled()
+            .then(|| (self.keys.len(), self.tombstones, std::time::Instant::now()));
+
+        let groups = capacity / GROUP_WIDTH;
+        let mut values = Vec::with_capacity(capacity);
+        values.resize_with(capacity, || None);
+        let old_keys = std::mem::replace(&mut self.keys, vec![VoxelChunkIndex(0); capacity]);
+        let old_values = std::mem::replace(&mut self.values, values);
+        self.ctrl = vec![Group::EMPTY; groups];
+        self.shift = 64 - groups.trailing_zeros();
+        self.tombstones = 0;
+
+        let mask = self.group_mask();
+        let old = old_keys.into_iter().zip(old_values);
+        for (idx, value) in old.filter_map(|(idx, value)| Some((idx, value?))) {
+            let hash = hash(idx);
+            let mut group = self.home(hash);
+            let mut stride = 0;
+            let pos = loop {
+                if let Some(bit) = self.ctrl[group].match_empty().lowest() {
+                    break group * GROUP_WIDTH + bit;
+                }
+                stride += 1;
+                group = (group + stride) & mask;
+            };
+            self.set_ctrl(pos, tag(hash));
+            self.keys[pos] = idx;
+            self.values[pos] = Some(value);
+        }
+
+        #[cfg(feature = "instrument")]
+        if let Some((from, tombstones, started)) = rehash {
+            crate::instrument::emit(
+                || self.fields(),
+                || crate::instrument::ContainerEvent::Rehash {
+                    from,
+                    to: capacity,
+                    tombstones,
+                    elapsed: started.elapsed(),
+                },
+            );
+        }
+    }
+
+    /// Names the map in the [`instrument`](crate::instrument) events it reports.
+    #[cfg(feature = "instrument")]
+    pub fn set_label(&mut self, label: &'static str) {
+        self.label = Some(label);
+    }
+
+    #[cfg(feature = "instrument")]
+    pub fn label(&self) -> Option<&'static str> {
+        self.label
+    }
+
+    #[cfg(feature = "instrument")]
+    pub(crate) fn fields(&self) -> crate::instrument::MapFields {
+        crate::instrument::MapFields {
+            container: "VoxelChunkMap",
+            label: self.label,
+            len: self.len,
+            capacity: self.capacity(),
+        }
+    }
+
+    /// Makes room for `additional` more entries without any further rehash.
+    pub fn reserve(&mut self, additional: usize) {
+        let used = self.len + self.tombstones + additional;
+        if used * 8 <= self.keys.len() * 7 {
+            return;
+        }
+
+        // Mostly tombstones: cleaning them up in place is enough.
+        let capacity = capacity_for(self.len + additional).max(self.keys.len());
+        self.resize(capacity);
+    }
+
+    /// Tables are always sized to a power of two, so this is the same as
+    /// [`VoxelChunkMap::reserve`]: the table grows to the smallest size fitting `additional` more
+    /// entries.
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.reserve(additional);
+    }
+
+    /// Shrinks the table as much as possible while keeping room for `min_capacity` entries, which
+    /// also purges every tombstone.
+    pub fn shrink_to(&mut self, min_capacity: usize) {
+        let wanted = self.len.max(min_capacity);
+        if wanted == 0 {
+            let changes = self.changes.take();
+            let observers = self.observers.take();
+            #[cfg(feature = "instrument")]
+            let label = self.label;
+            *self = Self::default();
+            self.changes = changes;
+            self.observers = observers;
+            #[cfg(feature = "instrument")]
+            {
+                self.label = label;
+            }
+            return;
+        }
+
+        let capacity = capacity_for(wanted);
+        if capacity < self.keys.len() || self.tombstones > 0 {
+            self.resize(capacity.min(self.keys.len()));
+        }
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.shrink_to(0);
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.lookup(idx).is_some()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let pos = self.lookup(idx)?;
+        Some(self.value_at(pos))
+    }
+
+    /// Read-only [`VoxelChunkMap::find`], which doesn't need to track an insertion slot.
+    fn lookup(&self, idx: VoxelChunkIndex) -> Option<usize> {
+        if self.keys.is_empty() {
+            return None;
+        }
+
+        let hash = hash(idx);
+        let tag = tag(hash);
+        let mask = self.group_mask();
+        let mut group = self.home(hash);
+        let mut stride = 0;
+        let found = 'probe: loop {
+            let ctrl = &self.ctrl[group];
+            for bit in ctrl.match_byte(tag) {
+                let pos = group * GROUP_WIDTH + bit;
+                if self.keys[pos] == idx {
+                    break 'probe Some(pos);
+                }
+            }
+            if ctrl.match_empty().any() {
+                break None;
+            }
+
+            stride += 1;
+            group = (group + stride) & mask;
+        };
+        #[cfg(feature = "instrument")]
+        self.report_probe(idx, stride + 1);
+        found
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        let pos = self.lookup(idx)?;
+        record(&mut self.changes, idx, true, true);
+        Some(self.value_at_mut(pos))
+    }
+
+    /// Looks up several keys at once, such as all the neighbors of a chunk. Every home group is
+    /// prefetched before any of them is probed, so the cache misses overlap instead of being
+    /// paid one after the other.
+    pub fn get_many<const N: usize>(&self, keys: [VoxelChunkIndex; N]) -> [Option<&V>; N] {
+        if self.keys.is_empty() {
+            return [None; N];
+        }
+
+        let hashes = keys.map(hash);
+        for &hash in &hashes {
+            let home = self.home(hash);
+            prefetch(&self.ctrl[home]);
+            prefetch(&self.keys[home * GROUP_WIDTH]);
+        }
+        std::array::from_fn(|i| {
+            let pos = self.find_hashed(keys[i], hashes[i]).ok()?;
+            Some(self.value_at(pos))
+        })
+    }
+
+    /// Value of a slot whose control byte holds a tag.
+    fn value_at(&self, pos: usize) -> &V {
+        debug_assert!(self.values[pos].is_some(), "slot {pos} is not occupied");
+        // SAFETY: a tag is only written to a control byte together with a value in the slot, and
+        // the value is only taken out after the control byte is cleared. Skipping the `None` check
+        // means a successful lookup never loads from the values array.
+        unsafe { self.values[pos].as_ref().unwrap_unchecked() }
+    }
+
+    fn value_at_mut(&mut self, pos: usize) -> &mut V {
+        debug_assert!(self.values[pos].is_some(), "slot {pos} is not occupied");
+        // SAFETY: see `value_at`.
+        unsafe { self.values[pos].as_mut().unwrap_unchecked() }
+    }
+
+    /// Fills the vacant slot `pos` found by [`VoxelChunkMap::find`].
+    fn occupy(&mut self, pos: usize, idx: VoxelChunkIndex, value: V) {
+        if self.ctrl[pos / GROUP_WIDTH].0[pos % GROUP_WIDTH] == DELETED {
+            self.tombstones -= 1;
+        }
+        self.set_ctrl(pos, tag(hash(idx)));
+        self.keys[pos] = idx;
+        self.values[pos] = Some(value);
+        self.len += 1;
+        self.notify_inserted(pos);
+    }
+
+    fn notify_inserted(&mut self, pos: usize) {
+        if let Some(observers) = &mut self.observers {
+            observers.inserted(self.keys[pos], self.values[pos].as_ref().unwrap());
+        }
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.reserve(1);
+        self.insert_reserved(idx, value)
+    }
+
+    /// Bulk insert sizing the table once up front, then inserting without any growth check.
+    /// When a key appears several times, the last value wins.
+    ///
+    /// Pairs are inserted in the order given; sorted or Morton-ordered input keeps producing
+    /// them cheap. Reordering them by home group was tried and doesn't pay for its sort.
+    pub fn extend_sorted<I>(&mut self, pairs: I)
+    where
+        I: IntoIterator<Item = (VoxelChunkIndex, V)>,
+        I::IntoIter: ExactSizeIterator,
+    {
+        let pairs = pairs.into_iter();
+        self.reserve(pairs.len());
+        for (idx, value) in pairs {
+            self.insert_reserved(idx, value);
+        }
+    }
+
+    /// [`VoxelChunkMap::insert`] into a table known to have room for one more entry.
+    fn insert_reserved(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        match self.find(idx) {
+            Ok(pos) => {
+                record(&mut self.changes, idx, true, true);
+                let old = std::mem::replace(self.value_at_mut(pos), value);
+                self.notify_inserted(pos);
+                Some(old)
+            }
+            Err(pos) => {
+                record(&mut self.changes, idx, false, true);
+                self.occupy(pos, idx, value);
+                None
+            }
+        }
+    }
+
+    fn remove_at(&mut self, pos: usize) -> V {
+        // If the group still has an empty slot, no probe sequence goes past it, so the freed
+        // slot can go back to empty instead of becoming a tombstone.
+        let freed = if self.ctrl[pos / GROUP_WIDTH].match_empty().any() {
+            EMPTY
+        } else {
+            self.tombstones += 1;
+            DELETED
+        };
+
+        self.set_ctrl(pos, freed);
+        self.len -= 1;
+        let value = match self.values[pos].take() {
+            Some(value) => value,
+            None => unreachable!("slot {pos} is not occupied"),
+        };
+        if let Some(observers) = &mut self.observers {
+            observers.removed(self.keys[pos], &value);
+        }
+        value
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let pos = self.lookup(idx)?;
+        record(&mut self.changes, idx, true, false);
+        Some(self.remove_at(pos))
+    }
+
+    /// Removes and yields every entry whose key lies in `region`. Small regions are drained by
+    /// looking up each of their chunks, big ones by scanning the table. Entries not yielded yet
+    /// when the iterator is dropped stay in the map. Parts of `region` past the edge of the
+    /// representable world hold no chunks.
+    pub fn drain_region(
+        &mut self,
+        region: ChunkAabb,
+    ) -> impl Iterator<Item = (VoxelChunkIndex, V)> + '_ {
+        let region = region.clipped_to_world();
+        // A miss costs about one group of tags, like scanning one group does.
+        let by_region = region.volume() <= self.ctrl.len();
+        let mut cursor = if by_region {
+            0..region.volume()
+        } else {
+            0..self.keys.len()
+        };
+        #[cfg(feature = "instrument")]
+        let mut drain = crate::instrument::enabled().then(|| (0, std::time::Instant::now()));
+
+        std::iter::from_fn(move || loop {
+            let Some(next) = cursor.next() else {
+                #[cfg(feature = "instrument")]
+                if let Some((drained, started)) = drain.take() {
+                    crate::instrument::emit(
+                        || self.fields(),
+                        || crate::instrument::ContainerEvent::DrainRegion {
+                            region,
+                            drained,
+                            scanned: !by_region,
+                            elapsed: started.elapsed(),
+                        },
+                    );
+                }
+                return None;
+            };
+            let pos = if by_region {
+                match self.lookup(region.index_at(next)) {
+                    Some(pos) => pos,
+                    None => continue,
+                }
+            } else {
+                let full = self.ctrl[next / GROUP_WIDTH].0[next % GROUP_WIDTH] & DELETED == 0;
+                if !full || !region.contains_index(self.keys[next]) {
+                    continue;
+                }
+                next
+            };
+
+            let idx = self.keys[pos];
+            record(&mut self.changes, idx, true, false);
+            #[cfg(feature = "instrument")]
+            if let Some((drained, _)) = &mut drain {
+                *drained += 1;
+            }
+            return Some((idx, self.remove_at(pos)));
+        })
+    }
+
+    /// Removes every entry whose key lies outside `region`.
+    pub fn retain_region(&mut self, region: ChunkAabb) {
+        for pos in self.occupied().collect::<Vec<_>>() {
+            let idx = self.keys[pos];
+            if !region.contains_index(idx) {
+                record(&mut self.changes, idx, true, false);
+                self.remove_at(pos);
+            }
+        }
+    }
+
+    /// Moves the entries lying in `region` to a new map.
+    pub fn split_off_region(&mut self, region: ChunkAabb) -> VoxelChunkMap<V> {
+        self.drain_region(region).collect()
+    }
+
+    /// Removes every entry but keeps the allocated table.
+    pub fn clear(&mut self) {
+        if self.changes.is_some() || self.observers.is_some() {
+            for pos in self.occupied().collect::<Vec<_>>() {
+                record(&mut self.changes, self.keys[pos], true, false);
+                if let Some(observers) = &mut self.observers {
+                    observers.removed(self.keys[pos], self.values[pos].as_ref().unwrap());
+                }
+            }
+        }
+        self.ctrl.iter_mut().for_each(|group| *group = Group::EMPTY);
+        self.values.iter_mut().for_each(|value| *value = None);
+        self.len = 0;
+        self.tombstones = 0;
+    }
+
+    /// Gets the entry for `idx`, resolving both the lookup and a potential insertion with a
+    /// single probe sequence.
+    pub fn entry(&mut self, idx: VoxelChunkIndex) -> Entry<'_, V> {
+        self.reserve(1);
+        match self.find(idx) {
+            Ok(pos) => Entry::Occupied(OccupiedEntry { map: self, pos }),
+            Err(pos) => Entry::Vacant(VacantEntry {
+                map: self,
+                idx,
+                pos,
+            }),
+        }
+    }
+
+    /// Walks the whole table to gather probe and memory statistics. Buckets are the 16-slot
+    /// groups.
+    pub fn stats(&self) -> MapStats {
+        let mut stats = MapStats {
+            len: self.len,
+            buckets: self.ctrl.len(),
+            tombstones: self.tombstones,
+            max_probe_length: 0,
+            mean_probe_length: 0.0,
+            bytes_per_entry: 0.0,
+            bucket_occupancy: vec![self.ctrl.len()],
+        };
+        if self.len == 0 {
+            return stats;
+        }
+
+        let mut home_counts = vec![0usize; self.ctrl.len()];
+        let mut total_probes = 0;
+        for slot in self.slots() {
+            stats.max_probe_length = stats.max_probe_length.max(slot.probe_length);
+            total_probes += slot.probe_length;
+            home_counts[slot.home] += 1;
+        }
+
+        let max_occupancy = home_counts.iter().copied().max().unwrap_or(0);
+        stats.bucket_occupancy = vec![0; max_occupancy + 1];
+        for count in home_counts {
+            stats.bucket_occupancy[count] += 1;
+        }
+        stats.mean_probe_length = total_probes as f64 / self.len as f64;
+        stats.bytes_per_entry = self.heap_bytes() as f64 / self.len as f64;
+        stats
+    }
+
+    /// Memory the table takes: its keys, values and control bytes.
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.keys.len()
+            * (std::mem::size_of::<VoxelChunkIndex>() + std::mem::size_of::<Option<V>>())
+            + self.ctrl.len() * std::mem::size_of::<Group>()
+    }
+
+    /// Where each entry sits in the table, in table order. Buckets are the 16-slot groups.
+    pub fn slots(&self) -> impl Iterator<Item = SlotInfo> + '_ {
+        let mask = self.group_mask();
+        self.occupied().map(move |pos| {
+            let idx = self.keys[pos];
+            let hash = hash(idx);
+            let home = self.home(hash);
+            let (mut group, mut stride, mut probe_length) = (home, 0, 1);
+            while group != pos / GROUP_WIDTH {
+                stride += 1;
+                group = (group + stride) & mask;
+                probe_length += 1;
+            }
+            SlotInfo {
+                idx,
+                hash,
+                home,
+                bucket: group,
+                probe_length,
+            }
+        })
+    }
+
+    /// Iterates entries in table order, which is arbitrary.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.keys
+            .iter()
+            .zip(&self.values)
+            .filter_map(|(idx, value)| Some((*idx, value.as_ref()?)))
+    }
+
+    /// Counts every yielded entry as modified when tracking changes.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        let changes = &mut self.changes;
+        self.keys
+            .iter()
+            .zip(&mut self.values)
+            .filter_map(move |(idx, value)| {
+                let value = value.as_mut()?;
+                record(changes, *idx, true, true);
+                Some((*idx, value))
+            })
+    }
+
+    /// Starts or stops recording which keys get inserted, modified or removed. Stopping drops
+    /// the changes recorded so far. Handing out a `&mut V` counts as a modification.
+    pub fn track_changes(&mut self, enabled: bool) {
+        match (enabled, &self.changes) {
+            (true, None) => self.changes = Some(Box::default()),
+            (false, Some(_)) => self.changes = None,
+            _ => {}
+        }
+    }
+
+    /// Callbacks run on inserts and removals, allocated on first use.
+    pub fn observers(&mut self) -> &mut Observers<V> {
+        self.observers.get_or_insert_with(Box::default)
+    }
+
+    pub fn is_tracking_changes(&self) -> bool {
+        self.changes.is_some()
+    }
+
+    /// Changes recorded since tracking started or since the last call, with successive
+    /// mutations of a key folded into one [`Change`]. Empty when not tracking.
+    pub fn take_changes(&mut self) -> VoxelChunkMap<Change> {
+        match &mut self.changes {
+            Some(changes) => std::mem::take(&mut **changes),
+            None => VoxelChunkMap::new(),
+        }
+    }
+
+    /// Iterates keys by scanning control bytes only, without touching any value.
+    pub fn keys(&self) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        self.occupied().map(|pos| self.keys[pos])
+    }
+
+    pub fn values(&self) -> impl Iterator<Item = &V> {
+        self.values.iter().flatten()
+    }
+
+    /// Positions of the occupied slots, found from the control bytes.
+    fn occupied(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ctrl.iter().enumerate().flat_map(|(group, ctrl)| {
+            BitMask(!ctrl.match_free().0).map(move |bit| group * GROUP_WIDTH + bit)
+        })
+    }
+}
+
+/// Snapshot of how well a chunk map is doing with its current keys, from
+/// [`VoxelChunkMap::stats`] or [`LinearChunkMap::stats`](crate::LinearChunkMap::stats).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapStats {
+    pub len: usize,
+    /// Number of units a probe step visits: 16-slot groups for `VoxelChunkMap`, single slots for
+    /// `LinearChunkMap`.
+    pub buckets: usize,
+    pub tombstones: usize,
+    /// Buckets visited by the longest successful lookup; 1 means every entry sits in its home
+    /// bucket.
+    pub max_probe_length: usize,
+    pub mean_probe_length: f64,
+    /// Table memory divided by the number of entries, empty and tombstone slots included.
+    pub bytes_per_entry: f64,
+    /// `bucket_occupancy[n]` is the number of buckets that exactly `n` keys hash to.
+    pub bucket_occupancy: Vec<usize>,
+}
+
+/// Where one entry sits in a table, see [`VoxelChunkMap::slots`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlotInfo {
+    pub idx: VoxelChunkIndex,
+    /// Hash the table picks the home bucket of the key from.
+    pub hash: u64,
+    /// Bucket a lookup of the key starts from, in the units of [`MapStats::buckets`].
+    pub home: usize,
+    /// Bucket the entry sits in.
+    pub bucket: usize,
+    /// Buckets a lookup of the key visits, 1 when it sits in its home bucket.
+    pub probe_length: usize,
+}
+
+/// Hints the CPU to start loading `value` into cache. No-op on architectures without a stable
+/// prefetch intrinsic.
+#[inline(always)]
+fn prefetch<T>(value: &T) {
+    #[cfg(target_arch = "x86_64")]
+    // SAFETY: prefetching is only a hint and never faults, and SSE is part of the x86_64 baseline.
+    unsafe {
+        use core::arch::x86_64::{_mm_prefetch, _MM_HINT_T0};
+        _mm_prefetch::<_MM_HINT_T0>(value as *const T as *const i8);
+    }
+
+    #[cfg(not(target_arch = "x86_64"))]
+    let _ = value;
+}
+
+/// Parallel iteration splits the slot arrays into ranges, so every worker walks its own run of
+/// groups.
+#[cfg(feature = "rayon")]
+impl<V: Send> VoxelChunkMap<V> {
+    pub fn par_iter(&self) -> impl rayon::iter::ParallelIterator<Item = (VoxelChunkIndex, &V)>
+    where
+        V: Sync,
+    {
+        use rayon::prelude::*;
+
+        self.keys
+            .par_iter()
+            .zip(&self.values)
+            .filter_map(|(idx, value)| Some((*idx, value.as_ref()?)))
+    }
+
+    /// Counts every entry as modified when tracking changes.
+    pub fn par_iter_mut(
+        &mut self,
+    ) -> impl rayon::iter::ParallelIterator<Item = (VoxelChunkIndex, &mut V)> {
+        use rayon::prelude::*;
+
+        if self.changes.is_some() {
+            for pos in self.occupied().collect::<Vec<_>>() {
+                record(&mut self.changes, self.keys[pos], true, true);
+            }
+        }
+        self.keys
+            .par_iter()
+            .zip(&mut self.values)
+            .filter_map(|(idx, value)| Some((*idx, value.as_mut()?)))
+    }
+
+    /// Removes every entry, yielding them in parallel. Unlike [`VoxelChunkMap::clear`], the
+    /// table is released too.
+    pub fn par_drain(&mut self) -> impl rayon::iter::ParallelIterator<Item = (VoxelChunkIndex, V)> {
+        use rayon::prelude::*;
+
+        if self.changes.is_some() || self.observers.is_some() {
+            for pos in self.occupied().collect::<Vec<_>>() {
+                record(&mut self.changes, self.keys[pos], true, false);
+                if let Some(observers) = &mut self.observers {
+                    observers.removed(self.keys[pos], self.values[pos].as_ref().unwrap());
+                }
+            }
+        }
+        let keys = std::mem::take(&mut self.keys);
+        let values = std::mem::take(&mut self.values);
+        self.ctrl = Vec::new();
+        self.shift = 64;
+        self.len = 0;
+        self.tombstones = 0;
+
+        keys.into_par_iter()
+            .zip(values)
+            .filter_map(|(idx, value)| Some((idx, value?)))
+    }
+}
+
+/// Parallel construction has each worker insert into a shard of its own, with no lock, and
+/// merges shards pairwise in parallel, each time the smaller into the larger. Like
+/// [`Extend`], a later pair replaces an earlier one with the same key.
+#[cfg(feature = "rayon")]
+impl<V: Send> VoxelChunkMap<V> {
+    fn into_entries(self) -> impl Iterator<Item = (VoxelChunkIndex, V)> {
+        let entries = self.keys.into_iter().zip(self.values);
+        entries.filter_map(|(idx, value)| Some((idx, value?)))
+    }
+
+    fn par_build<I>(par_iter: I) -> VoxelChunkMap<V>
+    where
+        I: rayon::iter::IntoParallelIterator<Item = (VoxelChunkIndex, V)>,
+    {
+        use rayon::prelude::*;
+
+        par_iter
+            .into_par_iter()
+            .fold(VoxelChunkMap::new, |mut shard, (idx, value)| {
+                shard.insert(idx, value);
+                shard
+            })
+            .reduce(VoxelChunkMap::new, |mut earlier, mut later| {
+                if earlier.len >= later.len {
+                    earlier.reserve(later.len);
+                    earlier.extend(later.into_entries());
+                    earlier
+                } else {
+                    later.reserve(earlier.len);
+                    for (idx, value) in earlier.into_entries() {
+                        later.entry(idx).or_insert(value);
+                    }
+                    later
+                }
+            })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<V: Send> rayon::iter::FromParallelIterator<(VoxelChunkIndex, V)> for VoxelChunkMap<V> {
+    fn from_par_iter<I>(par_iter: I) -> VoxelChunkMap<V>
+    where
+        I: rayon::iter::IntoParallelIterator<Item = (VoxelChunkIndex, V)>,
+    {
+        Self::par_build(par_iter)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl<V: Send> rayon::iter::ParallelExtend<(VoxelChunkIndex, V)> for VoxelChunkMap<V> {
+    fn par_extend<I>(&mut self, par_iter: I)
+    where
+        I: rayon::iter::IntoParallelIterator<Item = (VoxelChunkIndex, V)>,
+    {
+        let built = Self::par_build(par_iter);
+        self.reserve(built.len);
+        self.extend(built.into_entries());
+    }
+}
+
+impl<V> FromIterator<(VoxelChunkIndex, V)> for VoxelChunkMap<V> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(iter: I) -> VoxelChunkMap<V> {
+        let mut map = VoxelChunkMap::new();
+        map.extend(iter);
+        map
+    }
+}
+
+impl<V> Extend<(VoxelChunkIndex, V)> for VoxelChunkMap<V> {
+    fn extend<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(&mut self, iter: I) {
+        let iter = iter.into_iter();
+        self.reserve(iter.size_hint().0);
+        for (idx, value) in iter {
+            self.insert(idx, value);
+        }
+    }
+}
+
+/// A view into a single slot of a [`VoxelChunkMap`], obtained from [`VoxelChunkMap::entry`].
+pub enum Entry<'a, V> {
+    Occupied(OccupiedEntry<'a, V>),
+    Vacant(VacantEntry<'a, V>),
+}
+
+pub struct OccupiedEntry<'a, V> {
+    map: &'a mut VoxelChunkMap<V>,
+    pos: usize,
+}
+
+pub struct VacantEntry<'a, V> {
+    map: &'a mut VoxelChunkMap<V>,
+    idx: VoxelChunkIndex,
+    pos: usize,
+}
+
+impl<'a, V> Entry<'a, V> {
+    pub fn key(&self) -> VoxelChunkIndex {
+        match self {
+            Entry::Occupied(entry) => entry.key(),
+            Entry::Vacant(entry) => entry.key(),
+        }
+    }
+
+    pub fn or_insert(self, default: V) -> &'a mut V {
+        self.or_insert_with(|| default)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> V>(self, default: F) -> &'a mut V {
+        match self {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(default()),
+        }
+    }
+
+    pub fn or_default(self) -> &'a mut V
+    where
+        V: Default,
+    {
+        self.or_insert_with(V::default)
+    }
+
+    pub fn and_modify<F: FnOnce(&mut V)>(mut self, f: F) -> Self {
+        if let Entry::Occupied(entry) = &mut self {
+            f(entry.get_mut());
+        }
+        self
+    }
+}
+
+impl<'a, V> OccupiedEntry<'a, V> {
+    pub fn key(&self) -> VoxelChunkIndex {
+        self.map.keys[self.pos]
+    }
+
+    pub fn get(&self) -> &V {
+        self.map.value_at(self.pos)
+    }
+
+    pub fn get_mut(&mut self) -> &mut V {
+        record(&mut self.map.changes, self.map.keys[self.pos], true, true);
+        self.map.value_at_mut(self.pos)
+    }
+
+    pub fn into_mut(self) -> &'a mut V {
+        record(&mut self.map.changes, self.map.keys[self.pos], true, true);
+        self.map.value_at_mut(self.pos)
+    }
+
+    pub fn insert(&mut self, value: V) -> V {
+        let old = std::mem::replace(self.get_mut(), value);
+        self.map.notify_inserted(self.pos);
+        old
+    }
+
+    pub fn remove(self) -> V {
+        record(&mut self.map.changes, self.map.keys[self.pos], true, false);
+        self.map.remove_at(self.pos)
+    }
+}
+
+/// Serialized form of the maps and caches: packed keys, and their values in the same order.
+/// Packed keys take 4 bytes each in binary formats, and sorted ones compress well.
+#[cfg(feature = "serde")]
+pub(crate) mod packed {
+    use serde::de::Error;
+    use serde::{Deserialize, Serialize};
+
+    use crate::VoxelChunkIndex;
+
+    #[derive(Serialize)]
+    pub struct EntriesRef<'a, V> {
+        keys: Vec<u32>,
+        values: Vec<&'a V>,
+    }
+
+    impl<'a, V> FromIterator<(VoxelChunkIndex, &'a V)> for EntriesRef<'a, V> {
+        fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, &'a V)>>(iter: I) -> Self {
+            let (keys, values) = iter.into_iter().map(|(idx, value)| (idx.0, value)).unzip();
+            EntriesRef { keys, values }
+        }
+    }
+
+    #[derive(Deserialize)]
+    pub struct Entries<V> {
+        keys: Vec<u32>,
+        values: Vec<V>,
+    }
+
+    impl<V> Entries<V> {
+        pub fn len(&self) -> usize {
+            self.keys.len()
+        }
+
+        /// The entries, in serialized order, if there are as many values as keys.
+        pub fn into_pairs<E: Error>(
+            self,
+        ) -> Result<impl ExactSizeIterator<Item = (VoxelChunkIndex, V)>, E> {
+            if self.keys.len() != self.values.len() {
+                return Err(E::invalid_length(
+                    self.values.len(),
+                    &format!("{} values, one per key", self.keys.len()).as_str(),
+                ));
+            }
+            Ok(self.keys.into_iter().map(VoxelChunkIndex).zip(self.values))
+        }
+    }
+}
+
+/// Serializes as the packed keys in increasing order and the values in the same order. Change
+/// tracking and observers are left out.
+#[cfg(feature = "serde")]
+impl<V: serde::Serialize> serde::Serialize for VoxelChunkMap<V> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut entries: Vec<(VoxelChunkIndex, &V)> = self.iter().collect();
+        entries.sort_unstable_by_key(|&(idx, _)| idx);
+        entries
+            .into_iter()
+            .collect::<packed::EntriesRef<V>>()
+            .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, V: serde::Deserialize<'de>> serde::Deserialize<'de> for VoxelChunkMap<V> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let entries = packed::Entries::deserialize(deserializer)?;
+        let mut map = VoxelChunkMap::new();
+        map.extend_sorted(entries.into_pairs()?);
+        Ok(map)
+    }
+}
+
+impl<'a, V> VacantEntry<'a, V> {
+    pub fn key(&self) -> VoxelChunkIndex {
+        self.idx
+    }
+
+    pub fn insert(self, value: V) -> &'a mut V {
+        record(&mut self.map.changes, self.idx, false, true);
+        self.map.occupy(self.pos, self.idx, value);
+        self.map.value_at_mut(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    /// `n` keys whose probes all start at the same group of `map`'s table.
+    fn same_home_keys<V>(map: &VoxelChunkMap<V>, n: usize) -> Vec<VoxelChunkIndex> {
+        let home = map.home(hash(VoxelChunkIndex::from_coords(0, 0, 0)));
+        let keys: Vec<_> = (0..4096 * 64)
+            .map(|i| VoxelChunkIndex::from_coords(i % 4096, i / 4096, 0))
+            .filter(|&idx| map.home(hash(idx)) == home)
+            .take(n)
+            .collect();
+        assert_eq!(keys.len(), n);
+        keys
+    }
+
+    fn assert_matches(map: &VoxelChunkMap<u32>, model: &HashMap<VoxelChunkIndex, u32>) {
+        assert_eq!(map.len(), model.len());
+        assert!(map.len() <= map.capacity());
+        for (&idx, value) in model {
+            assert_eq!(map.get(idx), Some(value), "{idx:?}");
+        }
+        let entries: HashMap<_, _> = map.iter().map(|(idx, &value)| (idx, value)).collect();
+        assert_eq!(&entries, model);
+    }
+
+    #[test]
+    fn random_operations_match_a_hash_map() {
+        let mut map = VoxelChunkMap::new();
+        let mut model = HashMap::new();
+        let mut state = 1u64;
+        for step in 0..200_000u32 {
+            state = crate::filter::mix64(state.wrapping_add(0x9E37_79B9_7F4A_7C15));
+            // Few enough keys that removed ones keep coming back into tombstoned tables.
+            let n = (state >> 40) as i32 % 3000;
+            let idx = VoxelChunkIndex::from_coords(n % 40 - 20, n / 40 - 40, (n % 7) - 3);
+            match state % 8 {
+                0..=3 => assert_eq!(map.insert(idx, step), model.insert(idx, step)),
+                4..=6 => assert_eq!(map.remove(idx), model.remove(&idx)),
+                _ => assert_eq!(map.get(idx), model.get(&idx)),
+            }
+            if step % 20_000 == 0 {
+                assert_matches(&map, &model);
+                map.shrink_to_fit();
+                assert_eq!(map.tombstones(), 0);
+                assert_matches(&map, &model);
+            }
+        }
+        assert_matches(&map, &model);
+    }
+
+    #[test]
+    fn inserts_reuse_tombstones() {
+        let mut map = VoxelChunkMap::with_capacity(1024);
+        let keys = same_home_keys(&map, GROUP_WIDTH * 3);
+        // Two full groups, then a few more, so that removals from the first ones leave tombstones.
+        let (kept, spare) = keys.split_at(GROUP_WIDTH * 2 + 4);
+        for (value, &idx) in kept.iter().enumerate() {
+            map.insert(idx, value as u32);
+        }
+        let capacity = map.capacity();
+        for &idx in &kept[..4] {
+            assert!(map.remove(idx).is_some());
+        }
+        assert_eq!(map.tombstones(), 4);
+        for (value, &idx) in spare[..4].iter().enumerate() {
+            map.insert(idx, value as u32);
+        }
+        assert_eq!(map.tombstones(), 0);
+        assert_eq!(map.capacity(), capacity);
+        assert!(kept[..4].iter().all(|&idx| !map.contains_key(idx)));
+        assert!(kept[4..]
+            .iter()
+            .chain(&spare[..4])
+            .all(|&idx| map.contains_key(idx)));
+    }
+
+    #[test]
+    fn growing_and_shrinking_keep_every_entry() {
+        let mut map = VoxelChunkMap::new();
+        let mut model = HashMap::new();
+        let mut capacity = map.capacity();
+        for i in 0..5000 {
+            let idx = VoxelChunkIndex::from_coords(i % 50, i / 50, i % 3);
+            map.insert(idx, i as u32);
+            model.insert(idx, i as u32);
+            if map.capacity() != capacity {
+                assert!(map.capacity() > capacity);
+                capacity = map.capacity();
+                assert_matches(&map, &model);
+            }
+        }
+        assert_matches(&map, &model);
+
+        model.retain(|idx, _| idx.to_coords().1 < 10);
+        let removed: Vec<_> = map.keys().filter(|idx| !model.contains_key(idx)).collect();
+        for idx in removed {
+            map.remove(idx);
+        }
+        map.shrink_to_fit();
+        assert_eq!(map.capacity(), capacity_for(model.len()) * 7 / 8);
+        assert_eq!(map.tombstones(), 0);
+        assert_matches(&map, &model);
+
+        map.clear();
+        map.shrink_to_fit();
+        assert_eq!(map.capacity(), 0);
+        assert!(map.get(VoxelChunkIndex::from_coords(0, 0, 0)).is_none());
+    }
+
+    #[test]
+    fn drain_region_past_the_world_edge_keeps_wrapped_chunks() {
+        let mut map = VoxelChunkMap::new();
+        let wrapped = VoxelChunkIndex::from_coords(-4096, 1, 0);
+        let inside = VoxelChunkIndex::from_coords(4095, 0, 0);
+        map.insert(wrapped, 1);
+        map.insert(inside, 2);
+
+        let drained: Vec<_> = map
+            .drain_region(ChunkAabb::new((4095, 0, 0), (4097, 1, 1)))
+            .collect();
+        assert_eq!(drained, vec![(inside, 2)]);
+        assert_eq!(map.get(wrapped), Some(&1));
+
+        let everything = ChunkAabb::new(
+            (i32::MIN, i32::MIN, i32::MIN),
+            (i32::MAX, i32::MAX, i32::MAX),
+        );
+        assert_eq!(map.drain_region(everything).count(), 1);
+        assert!(map.is_empty());
+    }
+
+    #[test]
+    fn split_off_region_past_the_world_edge_keeps_wrapped_chunks() {
+        let mut map = VoxelChunkMap::new();
+        let wrapped = VoxelChunkIndex::from_coords(-4096, 1, 0);
+        let inside = VoxelChunkIndex::from_coords(4095, 0, 0);
+        map.insert(wrapped, 1);
+        map.insert(inside, 2);
+
+        let split = map.split_off_region(ChunkAabb::new((4095, 0, 0), (4097, 1, 1)));
+        assert_eq!(split.len(), 1);
+        assert_eq!(split.get(inside), Some(&2));
+        assert_eq!(map.len(), 1);
+        assert_eq!(map.get(wrapped), Some(&1));
+    }
+
+    #[cfg(feature = "instrument")]
+    #[test]
+    fn lookups_report_long_probes() {
+        use std::sync::Mutex;
+
+        use crate::instrument::{self, ContainerEvent};
+
+        static PROBES: Mutex<Vec<(VoxelChunkIndex, usize)>> = Mutex::new(Vec::new());
+        instrument::set_subscriber(|fields: &instrument::MapFields, event: &ContainerEvent| {
+            if let ContainerEvent::LongProbe { idx, groups } = *event {
+                if fields.label == Some("long probes") {
+                    PROBES.lock().unwrap().push((idx, groups));
+                }
+            }
+        });
+
+        // Keys sharing a home group fill it and the groups after it.
+        let mut map = VoxelChunkMap::with_capacity(4096);
+        map.set_label("long probes");
+        let keys = same_home_keys(&map, GROUP_WIDTH * instrument::LONG_PROBE_GROUPS);
+        for &idx in &keys {
+            map.insert(idx, ());
+        }
+
+        let last = *keys.last().unwrap();
+        PROBES.lock().unwrap().clear();
+        assert!(map.contains_key(last));
+        assert!(map.get(last).is_some());
+        assert!(map.get_mut(last).is_some());
+        let probes = PROBES.lock().unwrap();
+        assert_eq!(probes.len(), 3);
+        assert!(probes
+            .iter()
+            .all(|&(idx, groups)| idx == last && groups >= instrument::LONG_PROBE_GROUPS));
+    }
+}
@@ -0,0 +1,189 @@
+use std::fmt::Write;
+
+use crate::budget::{ChunkCost, EvictionPolicy};
+use crate::{BudgetCache, ChunkLru, VoxelChunkMap, VoxelChunkSet};
+
+/// Quantiles of the probe length summaries.
+const QUANTILES: [f64; 4] = [0.5, 0.9, 0.99, 1.0];
+
+#[derive(Clone, Copy)]
+struct Metric {
+    name: &'static str,
+    kind: &'static str,
+    help: &'static str,
+}
+
+const fn metric(name: &'static str, kind: &'static str, help: &'static str) -> Metric {
+    Metric { name, kind, help }
+}
+
+const ENTRIES: Metric = metric("hash_funsies_entries", "gauge", "Entries in the container.");
+const CAPACITY: Metric = metric(
+    "hash_funsies_capacity",
+    "gauge",
+    "Entries the container holds before growing or evicting, bytes for budget caches.",
+);
+const BYTES: Metric = metric(
+    "hash_funsies_bytes",
+    "gauge",
+    "Memory held by the container, value costs for budget caches.",
+);
+const LOAD_FACTOR: Metric = metric(
+    "hash_funsies_load_factor",
+    "gauge",
+    "Fraction of the table slots holding an entry.",
+);
+const TOMBSTONES: Metric = metric(
+    "hash_funsies_tombstones",
+    "gauge",
+    "Slots left deleted until the next rehash.",
+);
+const PROBE_LENGTH: Metric = metric(
+    "hash_funsies_probe_length",
+    "summary",
+    "Groups of slots a lookup of an entry visits.",
+);
+const EVICTIONS: Metric = metric(
+    "hash_funsies_evictions_total",
+    "counter",
+    "Entries the cache evicted to make room.",
+);
+
+/// Container metrics in the Prometheus text exposition format, gathered from the containers at
+/// scrape time: build one per scrape, add each container under the name dashboards know it by,
+/// and serve [`PrometheusMetrics::render`] from the `/metrics` endpoint.
+///
+/// Every sample is labelled with the `container` type and its `name`. Maps report their
+/// entries, capacity, load factor, tombstones, table bytes and a summary of their probe
+/// lengths, which walks the whole table; caches report their entries, capacity, bytes and
+/// `hash_funsies_evictions_total`. A [`BudgetCache`] counts bytes in its values' costs, and its
+/// capacity is its budget.
+#[derive(Default)]
+pub struct PrometheusMetrics {
+    families: Vec<(Metric, Vec<String>)>,
+}
+
+/// Escapes a label value as the exposition format requires.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+impl PrometheusMetrics {
+    pub fn new() -> PrometheusMetrics {
+        Self::default()
+    }
+
+    fn sample(&mut self, metric: Metric, suffix: &str, labels: &str, value: f64) {
+        let at = match self
+            .families
+            .iter()
+            .position(|(m, _)| m.name == metric.name)
+        {
+            Some(at) => at,
+            None => {
+                self.families.push((metric, Vec::new()));
+                self.families.len() - 1
+            }
+        };
+        let name = metric.name;
+        self.families[at]
+            .1
+            .push(format!("{name}{suffix}{{{labels}}} {value}"));
+    }
+
+    fn common(&mut self, labels: &str, entries: usize, capacity: usize, bytes: usize) {
+        self.sample(ENTRIES, "", labels, entries as f64);
+        self.sample(CAPACITY, "", labels, capacity as f64);
+        self.sample(BYTES, "", labels, bytes as f64);
+    }
+
+    fn evictions(&mut self, labels: &str, evictions: u64) {
+        self.sample(EVICTIONS, "", labels, evictions as f64);
+    }
+
+    fn labels(container: &str, name: &str) -> String {
+        format!("container=\"{container}\",name=\"{}\"", escape(name))
+    }
+
+    pub fn map<V>(&mut self, name: &str, map: &VoxelChunkMap<V>) -> &mut Self {
+        let labels = Self::labels("VoxelChunkMap", name);
+        self.common(&labels, map.len(), map.capacity(), map.heap_bytes());
+        self.sample(LOAD_FACTOR, "", &labels, map.load_factor());
+        self.sample(TOMBSTONES, "", &labels, map.tombstones() as f64);
+
+        // Lengths are small, so a histogram of them gives exact quantiles.
+        let mut counts = Vec::new();
+        for slot in map.slots() {
+            if counts.len() <= slot.probe_length {
+                counts.resize(slot.probe_length + 1, 0);
+            }
+            counts[slot.probe_length] += 1;
+        }
+        for q in QUANTILES {
+            let rank = ((q * map.len() as f64).ceil() as usize).max(1);
+            let mut seen = 0;
+            let length = counts.iter().position(|&count| {
+                seen += count;
+                seen >= rank
+            });
+            let value = match length {
+                Some(length) => length as f64,
+                None => f64::NAN,
+            };
+            self.sample(
+                PROBE_LENGTH,
+                "",
+                &format!("{labels},quantile=\"{q}\""),
+                value,
+            );
+        }
+        let sum: usize = counts
+            .iter()
+            .enumerate()
+            .map(|(length, count)| length * count)
+            .sum();
+        self.sample(PROBE_LENGTH, "_sum", &labels, sum as f64);
+        self.sample(PROBE_LENGTH, "_count", &labels, map.len() as f64);
+        self
+    }
+
+    pub fn set(&mut self, name: &str, set: &VoxelChunkSet) -> &mut Self {
+        let labels = Self::labels("VoxelChunkSet", name);
+        self.common(&labels, set.len(), set.capacity(), set.heap_bytes());
+        self
+    }
+
+    pub fn lru<V>(&mut self, name: &str, lru: &ChunkLru<V>) -> &mut Self {
+        let labels = Self::labels("ChunkLru", name);
+        self.common(&labels, lru.len(), lru.capacity(), lru.heap_bytes());
+        self.evictions(&labels, lru.evictions());
+        self
+    }
+
+    pub fn budget_cache<V: ChunkCost, P: EvictionPolicy>(
+        &mut self,
+        name: &str,
+        cache: &BudgetCache<V, P>,
+    ) -> &mut Self {
+        let labels = Self::labels("BudgetCache", name);
+        self.common(&labels, cache.len(), cache.budget(), cache.used());
+        self.evictions(&labels, cache.evictions());
+        self
+    }
+
+    /// The metrics added so far, grouped by family as the format requires.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for (metric, samples) in &self.families {
+            writeln!(out, "# HELP {} {}", metric.name, metric.help).unwrap();
+            writeln!(out, "# TYPE {} {}", metric.name, metric.kind).unwrap();
+            for sample in samples {
+                writeln!(out, "{sample}").unwrap();
+            }
+        }
+        out
+    }
+}
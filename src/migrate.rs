@@ -0,0 +1,202 @@
+//! Conversion of the index lists, sets and maps of [`format`](crate::format) between bit
+//! layouts, so that changing the widths of the packed fields, e.g. from 13/13/6 X/Y/Z bits to a
+//! taller 12/12/8, doesn't strand the files written before.
+//!
+//! Layouts of up to 32 bits pack keys in 4 bytes, as this build does; wider ones, up to 64
+//! bits, in 8, for a build with 64-bit indices to read. Keys of chunks the new layout can't
+//! reach are dropped and reported, or fail the migration.
+
+use std::io::{self, Read, Write};
+
+use crate::format::{Kind, FORMAT_VERSION, HEADER_LEN, MAGIC};
+use crate::{X_BITS, Y_BITS, Z_BITS};
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Bit counts of the X, Y and Z fields of packed keys, X in the low bits, then Y, then Z, each
+/// biased so that its range is centered on 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitLayout {
+    pub x: u8,
+    pub y: u8,
+    pub z: u8,
+}
+
+impl BitLayout {
+    /// The layout of this build.
+    pub const CURRENT: BitLayout = BitLayout {
+        x: X_BITS,
+        y: Y_BITS,
+        z: Z_BITS,
+    };
+
+    /// The layout of fields `x`, `y` and `z` bits wide, if each takes 1 to 32 bits and all of
+    /// them 64 at most.
+    pub fn new(x: u8, y: u8, z: u8) -> Option<BitLayout> {
+        let layout = BitLayout { x, y, z };
+        ([x, y, z].iter().all(|bits| (1..=32).contains(bits)) && layout.bits() <= 64)
+            .then_some(layout)
+    }
+
+    pub fn bits(self) -> u32 {
+        self.x as u32 + self.y as u32 + self.z as u32
+    }
+
+    /// Bytes a packed key takes in a file.
+    pub fn key_size(self) -> usize {
+        if self.bits() <= 32 {
+            4
+        } else {
+            8
+        }
+    }
+
+    /// Packs `(x, y, z)`, if the layout reaches it.
+    pub fn pack(self, (x, y, z): (i32, i32, i32)) -> Option<u64> {
+        let field = |value: i32, bits: u8| -> Option<u64> {
+            let biased = value as i64 + (1i64 << (bits - 1));
+            (0..1i64 << bits).contains(&biased).then_some(biased as u64)
+        };
+        Some(
+            field(x, self.x)?
+                | field(y, self.y)? << self.x
+                | field(z, self.z)? << (self.x + self.y),
+        )
+    }
+
+    /// Inverse of [`BitLayout::pack`].
+    pub fn unpack(self, key: u64) -> (i32, i32, i32) {
+        let field = |shift: u8, bits: u8| -> i32 {
+            ((key >> shift & ((1u64 << bits) - 1)) as i64 - (1i64 << (bits - 1))) as i32
+        };
+        (
+            field(0, self.x),
+            field(self.x, self.y),
+            field(self.x + self.y, self.z),
+        )
+    }
+}
+
+impl std::fmt::Display for BitLayout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}/{}", self.x, self.y, self.z)
+    }
+}
+
+/// What [`migrate`] does with the keys of chunks the new layout can't reach.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutOfRange {
+    /// Fails the migration, writing nothing.
+    Fail,
+    /// Leaves them, with their values, out of the new file.
+    Drop,
+}
+
+/// Outcome of a [`migrate`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MigrationReport {
+    /// Layout of the file read.
+    pub from: BitLayout,
+    /// Entries written.
+    pub entries: u64,
+    /// Coordinates of the chunks left out, in the order of the file read.
+    pub dropped: Vec<(i32, i32, i32)>,
+}
+
+/// Reads a file of [`format`](crate::format) in any layout from `reader` and writes it in
+/// layout `to` to `writer`. Lists keep their order; the keys of sets and maps, and the values
+/// of maps with them, are sorted again, as the order of packed keys changes with the layout.
+/// The whole file is held in memory.
+pub fn migrate<R: Read, W: Write>(
+    mut reader: R,
+    mut writer: W,
+    to: BitLayout,
+    out_of_range: OutOfRange,
+) -> io::Result<MigrationReport> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+    if bytes.len() < HEADER_LEN + 4 || &bytes[..4] != MAGIC {
+        return Err(invalid("not a chunk index file".to_owned()));
+    }
+    if bytes[4] != FORMAT_VERSION {
+        return Err(invalid(format!(
+            "format version {} is not supported, only {FORMAT_VERSION} is",
+            bytes[4]
+        )));
+    }
+    let kind = Kind::from_byte(bytes[5]).ok_or_else(|| invalid("unknown collection".to_owned()))?;
+    let from = BitLayout::new(bytes[6], bytes[7], bytes[8]).ok_or_else(|| {
+        invalid(format!(
+            "invalid layout {}/{}/{}",
+            bytes[6], bytes[7], bytes[8]
+        ))
+    })?;
+    let value_size = bytes[9] as usize;
+    let len = u64::from_le_bytes(bytes[10..HEADER_LEN].try_into().unwrap());
+    let (body, crc) = bytes.split_at(bytes.len() - 4);
+    let expected = len
+        .checked_mul((from.key_size() + value_size) as u64)
+        .and_then(|records| records.checked_add(HEADER_LEN as u64));
+    if expected != Some(body.len() as u64) {
+        return Err(invalid(format!(
+            "{} bytes can't hold {len} entries of {} bytes",
+            body.len(),
+            from.key_size() + value_size
+        )));
+    }
+    if crc32fast::hash(body).to_le_bytes() != crc {
+        return Err(invalid("chunk index file checksum mismatch".to_owned()));
+    }
+
+    let len = len as usize;
+    let keys = &body[HEADER_LEN..HEADER_LEN + len * from.key_size()];
+    let values = &body[HEADER_LEN + len * from.key_size()..];
+    let mut entries = Vec::with_capacity(len);
+    let mut dropped = Vec::new();
+    for (i, key) in keys.chunks_exact(from.key_size()).enumerate() {
+        let mut le = [0; 8];
+        le[..key.len()].copy_from_slice(key);
+        let coords = from.unpack(u64::from_le_bytes(le));
+        match to.pack(coords) {
+            Some(key) => entries.push((key, i)),
+            None => dropped.push(coords),
+        }
+    }
+    if out_of_range == OutOfRange::Fail && !dropped.is_empty() {
+        return Err(invalid(format!(
+            "{} chunks out of reach of {to} bits, the first at {:?}",
+            dropped.len(),
+            dropped[0]
+        )));
+    }
+    if kind != Kind::List {
+        entries.sort_unstable_by_key(|&(key, _)| key);
+    }
+
+    let mut out = Vec::with_capacity(HEADER_LEN + entries.len() * (to.key_size() + value_size) + 4);
+    out.extend_from_slice(MAGIC);
+    out.extend_from_slice(&[
+        FORMAT_VERSION,
+        kind as u8,
+        to.x,
+        to.y,
+        to.z,
+        value_size as u8,
+    ]);
+    out.extend_from_slice(&(entries.len() as u64).to_le_bytes());
+    for &(key, _) in &entries {
+        out.extend_from_slice(&key.to_le_bytes()[..to.key_size()]);
+    }
+    for &(_, i) in &entries {
+        out.extend_from_slice(&values[i * value_size..(i + 1) * value_size]);
+    }
+    out.extend_from_slice(&crc32fast::hash(&out).to_le_bytes());
+    writer.write_all(&out)?;
+    Ok(MigrationReport {
+        from,
+        entries: entries.len() as u64,
+        dropped,
+    })
+}
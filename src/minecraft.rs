@@ -0,0 +1,81 @@
+//! Conversions between chunk indices and Minecraft's coordinates, to run the containers and the
+//! analyses of this crate over the chunks of existing Anvil worlds.
+//!
+//! Minecraft's vertical axis is Y, where this crate's is Z: a section, the 16×16×16 blocks of a
+//! chunk at one height, at chunk `(x, z)` and section `y` is the index `(x, z, y)` here. Chunk
+//! columns are grouped 32×32 into region files `r.<x>.<z>.mca`. Every division rounds towards
+//! -∞, like Minecraft's arithmetic shifts, so that block -1 is in chunk -1 and chunk -1 in
+//! region -1.
+//!
+//! Indices only reach 4096 chunks from the origin horizontally and 32 sections vertically, less
+//! than a Minecraft world; the conversions to indices return `None` past that.
+
+use crate::{ChunkAabb, VoxelChunkIndex, X_BITS, Y_BITS, Z_BITS};
+
+/// Blocks along each edge of a section.
+pub const SECTION_EDGE: i32 = 16;
+/// Chunks along each edge of a region.
+pub const REGION_EDGE: i32 = 32;
+
+fn fits(value: i32, bits: u8) -> bool {
+    (-(1 << (bits - 1))..1 << (bits - 1)).contains(&value)
+}
+
+/// Index of the section at chunk `(chunk_x, chunk_z)` and height `section_y`, if indices reach
+/// it.
+pub fn section_index(chunk_x: i32, section_y: i32, chunk_z: i32) -> Option<VoxelChunkIndex> {
+    (fits(chunk_x, X_BITS) && fits(chunk_z, Y_BITS) && fits(section_y, Z_BITS))
+        .then(|| VoxelChunkIndex::from_coords(chunk_x, chunk_z, section_y))
+}
+
+/// Inverse of [`section_index`]: `(chunk_x, section_y, chunk_z)`.
+pub fn section_coords(idx: VoxelChunkIndex) -> (i32, i32, i32) {
+    let (x, y, z) = idx.to_coords();
+    (x, z, y)
+}
+
+/// Index of the section holding the block at `(x, y, z)`, in Minecraft's axes.
+pub fn block_section(x: i32, y: i32, z: i32) -> Option<VoxelChunkIndex> {
+    section_index(
+        x.div_euclid(SECTION_EDGE),
+        y.div_euclid(SECTION_EDGE),
+        z.div_euclid(SECTION_EDGE),
+    )
+}
+
+/// Region holding chunk `(chunk_x, chunk_z)`.
+pub fn region_of(chunk_x: i32, chunk_z: i32) -> (i32, i32) {
+    (
+        chunk_x.div_euclid(REGION_EDGE),
+        chunk_z.div_euclid(REGION_EDGE),
+    )
+}
+
+/// Name of the Anvil file of `region`.
+pub fn region_file_name((x, z): (i32, i32)) -> String {
+    format!("r.{x}.{z}.mca")
+}
+
+/// Position of chunk `(chunk_x, chunk_z)` in the header of its region file: the chunk's X in its
+/// region varies fastest.
+pub fn region_slot(chunk_x: i32, chunk_z: i32) -> usize {
+    (chunk_x.rem_euclid(REGION_EDGE) + chunk_z.rem_euclid(REGION_EDGE) * REGION_EDGE) as usize
+}
+
+/// Inverse of [`region_of`] and [`region_slot`]: the chunk at `slot` of `region`.
+pub fn slot_chunk((x, z): (i32, i32), slot: usize) -> (i32, i32) {
+    let slot = slot as i32;
+    (
+        x * REGION_EDGE + slot % REGION_EDGE,
+        z * REGION_EDGE + slot / REGION_EDGE,
+    )
+}
+
+/// Sections `section_y0..section_y1` of every chunk of `region`, if indices reach them all.
+pub fn region_sections((x, z): (i32, i32), section_y0: i32, section_y1: i32) -> Option<ChunkAabb> {
+    let (x0, z0) = (x * REGION_EDGE, z * REGION_EDGE);
+    let (x1, z1) = (x0 + REGION_EDGE, z0 + REGION_EDGE);
+    section_index(x0, section_y0, z0)?;
+    section_index(x1 - 1, section_y1 - 1, z1 - 1)?;
+    Some(ChunkAabb::new((x0, z0, section_y0), (x1, z1, section_y1)))
+}
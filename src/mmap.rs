@@ -0,0 +1,279 @@
+//! Read-only views of a [`RegionStore`](crate::RegionStore) directory and of map files written
+//! by [`VoxelChunkMap::write_to`](crate::VoxelChunkMap::write_to), memory-mapped so that a server
+//! can serve lookups into a huge static world from the page cache, without reading it into a map
+//! first. Lookups borrow from the mapping and copy nothing.
+//!
+//! The mappings are advised for random access, which stops the kernel from reading ahead around
+//! every chunk looked up.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::marker::PhantomData;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::ptr::{self, NonNull};
+
+use crate::format::{self, map_header, LeBytes};
+use crate::store::{self, locate, parse_region_file_name, parse_table, Entry, SECTOR};
+use crate::VoxelChunkIndex;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// A whole file mapped read-only.
+struct Mmap {
+    ptr: NonNull<u8>,
+    len: usize,
+}
+
+// SAFETY: the mapping is never written through, and unmapped only on drop.
+unsafe impl Send for Mmap {}
+unsafe impl Sync for Mmap {}
+
+impl Mmap {
+    fn map(file: &File) -> io::Result<Mmap> {
+        let len = usize::try_from(file.metadata()?.len())
+            .map_err(|_| invalid("file too large to map".to_owned()))?;
+        // Mapping nothing fails, and there is nothing to read either.
+        if len == 0 {
+            return Ok(Mmap {
+                ptr: NonNull::dangling(),
+                len,
+            });
+        }
+        // SAFETY: a fresh read-only mapping of an open file, which aliases no Rust memory.
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: `ptr` is the mapping just created, `len` bytes long. The advice is only a hint,
+        // so its failure doesn't matter.
+        unsafe { libc::madvise(ptr, len, libc::MADV_RANDOM) };
+        Ok(Mmap {
+            ptr: NonNull::new(ptr.cast()).expect("mmap never maps address 0"),
+            len,
+        })
+    }
+
+    fn bytes(&self) -> &[u8] {
+        // SAFETY: `ptr` points to `len` mapped bytes, or dangles with `len` 0, for as long as
+        // `self` lives. That nothing modifies the file meanwhile is on whoever opened it.
+        unsafe { std::slice::from_raw_parts(self.ptr.as_ptr(), self.len) }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        if self.len > 0 {
+            // SAFETY: the mapping was created by `Mmap::map` and nothing borrows from it anymore.
+            unsafe { libc::munmap(self.ptr.as_ptr().cast(), self.len) };
+        }
+    }
+}
+
+struct MappedRegion {
+    map: Mmap,
+    entries: Vec<Entry>,
+}
+
+/// Read-only view of every region file of a [`RegionStore`](crate::RegionStore) directory, as
+/// it was when opened.
+pub struct MappedRegionStore {
+    regions: HashMap<(i32, i32, i32), MappedRegion>,
+}
+
+impl MappedRegionStore {
+    /// Maps every region file of `dir` and checks their tables. Fails on encrypted stores,
+    /// whose blobs can't be handed out in place.
+    ///
+    /// # Safety
+    ///
+    /// Nothing may write to, or truncate, the region files while the store is alive, be it a
+    /// [`RegionStore`](crate::RegionStore) of this process or another process: the blobs handed
+    /// out would change under their borrowers.
+    pub unsafe fn open(dir: impl AsRef<Path>) -> io::Result<MappedRegionStore> {
+        let mut regions = HashMap::new();
+        for dir_entry in fs::read_dir(dir)? {
+            let path = dir_entry?.path();
+            let Some(region) = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .and_then(parse_region_file_name)
+            else {
+                continue;
+            };
+            let map = Mmap::map(&File::open(&path)?)?;
+            let bytes = map.bytes();
+            if bytes.len() < store::HEADER_LEN {
+                return Err(invalid(format!("{} is truncated", path.display())));
+            }
+            let (entries, _) = parse_table(
+                &bytes[..store::HEADER_LEN],
+                bytes.len() as u64,
+                &path,
+                false,
+            )?;
+            // The table only covers whole sectors, and the file may end in the middle of one.
+            if let Some(i) = entries
+                .iter()
+                .position(|e| e.sector as u64 * SECTOR + e.len as u64 > bytes.len() as u64)
+            {
+                return Err(invalid(format!(
+                    "{}: chunk {i} runs past the end of the file",
+                    path.display()
+                )));
+            }
+            regions.insert(region, MappedRegion { map, entries });
+        }
+        Ok(MappedRegionStore { regions })
+    }
+
+    /// Number of region files mapped.
+    pub fn regions(&self) -> usize {
+        self.regions.len()
+    }
+
+    /// Blob of `idx`, if one was written, straight from the mapping. Its checksum isn't checked,
+    /// see [`MappedRegionStore::read`].
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&[u8]> {
+        let (region, slot) = locate(idx);
+        let region = self.regions.get(&region)?;
+        let entry = region.entries[slot];
+        if entry.len == 0 {
+            return None;
+        }
+        let start = entry.sector as usize * SECTOR as usize;
+        Some(&region.map.bytes()[start..start + entry.len as usize])
+    }
+
+    /// Blob of `idx` like [`MappedRegionStore::get`], after checking its checksum, which reads
+    /// all of it.
+    pub fn read(&self, idx: VoxelChunkIndex) -> io::Result<Option<&[u8]>> {
+        let Some(blob) = self.get(idx) else {
+            return Ok(None);
+        };
+        let (region, slot) = locate(idx);
+        if crc32fast::hash(blob) != self.regions[&region].entries[slot].crc {
+            return Err(invalid("chunk blob checksum mismatch".to_owned()));
+        }
+        Ok(Some(blob))
+    }
+
+    pub fn contains(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+}
+
+/// Read-only map over a file written by [`VoxelChunkMap::write_to`](crate::VoxelChunkMap::write_to),
+/// looking keys up by binary search over the sorted keys of the file. Values are decoded on
+/// each lookup.
+///
+/// Opening only checks the header and the file size: checking the checksum and the order of the
+/// keys would read the whole file, which [`MappedChunkMap::verify`] does on demand.
+pub struct MappedChunkMap<V> {
+    map: Mmap,
+    len: usize,
+    _values: PhantomData<fn() -> V>,
+}
+
+impl<V: LeBytes> MappedChunkMap<V> {
+    /// Maps the map file at `path`, which must hold values of type `V`.
+    ///
+    /// # Safety
+    ///
+    /// Nothing may write to, or truncate, the file while the map is alive.
+    pub unsafe fn open(path: impl AsRef<Path>) -> io::Result<MappedChunkMap<V>> {
+        let map = Mmap::map(&File::open(path)?)?;
+        let bytes = map.bytes();
+        if bytes.len() < format::HEADER_LEN {
+            return Err(invalid("chunk index file is truncated".to_owned()));
+        }
+        let len = map_header(&bytes[..format::HEADER_LEN], V::SIZE)?;
+        let expected = len
+            .checked_mul(4 + V::SIZE as u64)
+            .and_then(|records| records.checked_add(format::HEADER_LEN as u64 + 4));
+        if expected != Some(bytes.len() as u64) {
+            return Err(invalid(format!(
+                "a map of {len} entries doesn't fit in {} bytes",
+                bytes.len()
+            )));
+        }
+        Ok(MappedChunkMap {
+            len: len as usize,
+            map,
+            _values: PhantomData,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn key_at(&self, i: usize) -> VoxelChunkIndex {
+        let at = format::HEADER_LEN + i * 4;
+        VoxelChunkIndex(u32::from_le_bytes(
+            self.map.bytes()[at..at + 4].try_into().unwrap(),
+        ))
+    }
+
+    fn value_at(&self, i: usize) -> V {
+        let at = format::HEADER_LEN + self.len * 4 + i * V::SIZE;
+        V::read_le(&self.map.bytes()[at..at + V::SIZE])
+    }
+
+    /// Position of `idx` among the keys.
+    fn position(&self, idx: VoxelChunkIndex) -> Option<usize> {
+        let (mut lo, mut hi) = (0, self.len);
+        while lo < hi {
+            let mid = lo + (hi - lo) / 2;
+            match self.key_at(mid).cmp(&idx) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => return Some(mid),
+            }
+        }
+        None
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<V> {
+        self.position(idx).map(|i| self.value_at(i))
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.position(idx).is_some()
+    }
+
+    /// Entries in increasing key order.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, V)> + '_ {
+        (0..self.len).map(|i| (self.key_at(i), self.value_at(i)))
+    }
+
+    /// Checks the checksum of the file and that its keys strictly increase, without which
+    /// lookups may miss keys.
+    pub fn verify(&self) -> io::Result<()> {
+        let bytes = self.map.bytes();
+        let (body, crc) = bytes.split_at(bytes.len() - 4);
+        if crc32fast::hash(body).to_le_bytes() != crc {
+            return Err(invalid("chunk index file checksum mismatch".to_owned()));
+        }
+        if (1..self.len).any(|i| self.key_at(i - 1) >= self.key_at(i)) {
+            return Err(invalid("the keys of the map file aren't sorted".to_owned()));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,367 @@
+//! Minimal perfect hashing of chunk indices: [`ChunkMphf`], and [`MphfChunkMap`] built on it.
+
+use std::io::{self, Read, Write};
+
+use crate::filter::{mix64, reduce};
+use crate::format::BLOCK;
+use crate::map::FIB_MULTIPLIER;
+use crate::VoxelChunkIndex;
+
+const MAGIC: &[u8; 4] = b"MPH1";
+/// Words covered by each precomputed rank.
+const RANK_WORDS: usize = 8;
+/// Bits of all levels a function read from a file may hold: 64 per chunk of the whole key
+/// space, far more than any gamma worth building with.
+const MAX_BITS: u64 = 64 << 32;
+
+#[inline]
+fn level_hash(idx: VoxelChunkIndex, level: usize) -> u64 {
+    mix64((idx.0 as u64 ^ ((level as u64 + 1) << 32)).wrapping_mul(FIB_MULTIPLIER))
+}
+
+/// Minimal perfect hash function of a fixed set of chunks: maps each of its `n` chunks to a
+/// distinct index in `0..n`, in about 3 to 4 bits per chunk. The chunks themselves aren't stored,
+/// so values can live anywhere indexed by it, e.g. a flat array in a file.
+///
+/// BBHash construction: each level is a bit array where the chunks that didn't collide at any
+/// previous level set the bit they hash to; chunks colliding there go down to the next level. A
+/// chunk's index is the rank of its bit over all levels.
+#[derive(Clone)]
+pub struct ChunkMphf {
+    words: Box<[u64]>,
+    /// First bit and bit count of each level.
+    levels: Box<[(usize, usize)]>,
+    /// Bits set before each block of [`RANK_WORDS`] words.
+    ranks: Box<[u64]>,
+    len: usize,
+}
+
+impl ChunkMphf {
+    /// Hash function of `keys`, which must be distinct, using bit arrays twice as large as the
+    /// chunks left at each level.
+    pub fn build(keys: &[VoxelChunkIndex]) -> ChunkMphf {
+        Self::with_gamma(keys, 2.0)
+    }
+
+    /// Like [`ChunkMphf::build`] with bit arrays `gamma` times as large as the chunks left at
+    /// each level: lower builds smaller functions, higher builds and evaluates faster.
+    pub fn with_gamma(keys: &[VoxelChunkIndex], gamma: f64) -> ChunkMphf {
+        assert!(gamma >= 1.0, "gamma must be at least 1");
+        let mut words = Vec::new();
+        let mut levels = Vec::new();
+        let mut remaining = keys.to_vec();
+        let mut collisions = Vec::new();
+
+        while !remaining.is_empty() {
+            let level = levels.len();
+            assert!(level < 64, "duplicate keys in minimal perfect hash input");
+            let level_words = ((remaining.len() as f64 * gamma) as usize)
+                .div_ceil(64)
+                .max(1);
+            let bits = level_words * 64;
+            let mut seen = vec![0u64; level_words];
+            collisions.clear();
+            collisions.resize(level_words, 0);
+
+            for &idx in &remaining {
+                let bit = reduce(level_hash(idx, level), bits);
+                let mask = 1 << (bit % 64);
+                if seen[bit / 64] & mask != 0 {
+                    collisions[bit / 64] |= mask;
+                }
+                seen[bit / 64] |= mask;
+            }
+            for (word, &collided) in seen.iter_mut().zip(&collisions) {
+                *word &= !collided;
+            }
+            remaining.retain(|&idx| {
+                let bit = reduce(level_hash(idx, level), bits);
+                collisions[bit / 64] & (1 << (bit % 64)) != 0
+            });
+
+            levels.push((words.len() * 64, bits));
+            words.extend(seen);
+        }
+
+        ChunkMphf::from_words(words.into(), levels.into())
+    }
+
+    fn from_words(words: Box<[u64]>, levels: Box<[(usize, usize)]>) -> ChunkMphf {
+        let mut total = 0;
+        let ranks: Box<[u64]> = words
+            .chunks(RANK_WORDS)
+            .map(|block| {
+                let rank = total;
+                total += block.iter().map(|w| w.count_ones() as u64).sum::<u64>();
+                rank
+            })
+            .collect();
+        ChunkMphf {
+            words,
+            levels,
+            ranks,
+            len: total as usize,
+        }
+    }
+
+    /// Number of chunks the function was built for.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Size of the function in bits.
+    pub fn bit_count(&self) -> usize {
+        self.words.len() * 64
+    }
+
+    /// Index of `idx` in `0..len()`. For a chunk the function wasn't built for, either `None` or
+    /// the index of some other chunk.
+    pub fn index(&self, idx: VoxelChunkIndex) -> Option<usize> {
+        for (level, &(start, bits)) in self.levels.iter().enumerate() {
+            let bit = start + reduce(level_hash(idx, level), bits);
+            let (word, offset) = (bit / 64, bit % 64);
+            if self.words[word] & (1 << offset) != 0 {
+                let block = word / RANK_WORDS;
+                let before: u32 = self.words[block * RANK_WORDS..word]
+                    .iter()
+                    .map(|w| w.count_ones())
+                    .sum();
+                let below = (self.words[word] & ((1 << offset) - 1)).count_ones();
+                return Some((self.ranks[block] + (before + below) as u64) as usize);
+            }
+        }
+        None
+    }
+
+    /// Writes the function as little-endian words, closed by a CRC32 of everything before it.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(8 + self.levels.len() * 8 + self.words.len() * 8 + 4);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.levels.len() as u32).to_le_bytes());
+        for &(_, bits) in self.levels.iter() {
+            bytes.extend_from_slice(&(bits as u64).to_le_bytes());
+        }
+        for word in self.words.iter() {
+            bytes.extend_from_slice(&word.to_le_bytes());
+        }
+        bytes.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+        writer.write_all(&bytes)
+    }
+
+    /// Reads a function written by [`ChunkMphf::write_to`].
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<ChunkMphf> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let mut hasher = crc32fast::Hasher::new();
+
+        let mut header = [0; 8];
+        reader.read_exact(&mut header)?;
+        hasher.update(&header);
+        if &header[..4] != MAGIC {
+            return Err(invalid("not a minimal perfect hash"));
+        }
+        let level_count = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        if level_count > 64 {
+            return Err(invalid("too many minimal perfect hash levels"));
+        }
+
+        let mut sizes = vec![0; level_count * 8];
+        reader.read_exact(&mut sizes)?;
+        hasher.update(&sizes);
+        let mut levels = Vec::with_capacity(level_count);
+        let mut start: usize = 0;
+        for size in sizes.chunks_exact(8) {
+            let bits = u64::from_le_bytes(size.try_into().unwrap());
+            if bits == 0 || !bits.is_multiple_of(64) {
+                return Err(invalid(
+                    "minimal perfect hash level size is not a whole word",
+                ));
+            }
+            let end = usize::try_from(bits)
+                .ok()
+                .and_then(|bits| start.checked_add(bits))
+                .filter(|&end| end as u64 <= MAX_BITS)
+                .ok_or_else(|| invalid("minimal perfect hash levels are too large"))?;
+            levels.push((start, end - start));
+            start = end;
+        }
+
+        // Words are read a block at a time, so that a size past the end of the input fails on
+        // reaching it rather than by allocating the whole function up front.
+        let word_count = start / 64;
+        let mut words = Vec::with_capacity(word_count.min(BLOCK / 8));
+        let mut block = vec![0; BLOCK];
+        while words.len() < word_count {
+            let block = &mut block[..(word_count - words.len()).min(BLOCK / 8) * 8];
+            reader.read_exact(block)?;
+            hasher.update(block);
+            words.extend(
+                block
+                    .chunks_exact(8)
+                    .map(|word| u64::from_le_bytes(word.try_into().unwrap())),
+            );
+        }
+        let mut crc = [0; 4];
+        reader.read_exact(&mut crc)?;
+        if hasher.finalize().to_le_bytes() != crc {
+            return Err(invalid("minimal perfect hash checksum mismatch"));
+        }
+        Ok(ChunkMphf::from_words(words.into(), levels.into()))
+    }
+}
+
+/// Read-only map indexing its values by a [`ChunkMphf`] of its keys, for very large immutable
+/// chunk tables where even [`PerfectChunkMap`](crate::PerfectChunkMap) slack is too much:
+/// values sit in one dense array without any empty slot.
+///
+/// The keys are kept alongside to tell absent chunks apart. Tables queried only for chunks known
+/// to be present can drop them and use the function and the values directly, see
+/// [`MphfChunkMap::into_parts`].
+pub struct MphfChunkMap<V> {
+    mphf: ChunkMphf,
+    keys: Box<[VoxelChunkIndex]>,
+    values: Box<[V]>,
+}
+
+impl<V> MphfChunkMap<V> {
+    /// Builds the map of `keys[i] -> values[i]`. When a key appears several times, the last value
+    /// wins.
+    pub fn build<K, I>(keys: K, values: I) -> MphfChunkMap<V>
+    where
+        K: IntoIterator<Item = VoxelChunkIndex>,
+        I: IntoIterator<Item = V>,
+    {
+        let keys: Vec<_> = keys.into_iter().collect();
+        let values: Vec<_> = values.into_iter().collect();
+        assert_eq!(
+            keys.len(),
+            values.len(),
+            "as many keys as values are needed"
+        );
+
+        let mut entries: Vec<_> = keys.into_iter().zip(values).collect();
+        entries.reverse();
+        entries.sort_by_key(|&(idx, _)| idx);
+        entries.dedup_by_key(|&mut (idx, _)| idx);
+
+        let keys: Vec<_> = entries.iter().map(|&(idx, _)| idx).collect();
+        let mphf = ChunkMphf::build(&keys);
+        let mut slots: Vec<Option<(VoxelChunkIndex, V)>> = (0..keys.len()).map(|_| None).collect();
+        for (idx, value) in entries {
+            slots[mphf.index(idx).unwrap()] = Some((idx, value));
+        }
+        let (keys, values): (Vec<_>, Vec<_>) = slots.into_iter().map(Option::unwrap).unzip();
+        MphfChunkMap {
+            mphf,
+            keys: keys.into(),
+            values: values.into(),
+        }
+    }
+
+    /// Reassembles a map from [`MphfChunkMap::into_parts`], e.g. after storing them on disk.
+    pub fn from_parts(
+        mphf: ChunkMphf,
+        keys: Box<[VoxelChunkIndex]>,
+        values: Box<[V]>,
+    ) -> MphfChunkMap<V> {
+        assert!(
+            keys.len() == mphf.len() && values.len() == mphf.len(),
+            "parts differ in length"
+        );
+        MphfChunkMap { mphf, keys, values }
+    }
+
+    /// The hash function, and the keys and values in the order of its indices.
+    pub fn into_parts(self) -> (ChunkMphf, Box<[VoxelChunkIndex]>, Box<[V]>) {
+        (self.mphf, self.keys, self.values)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn mphf(&self) -> &ChunkMphf {
+        &self.mphf
+    }
+
+    /// Values in the order of the hash function's indices.
+    pub fn values(&self) -> &[V] {
+        &self.values
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let i = self.mphf.index(idx)?;
+        if self.keys[i] == idx {
+            Some(&self.values[i])
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    /// Iterates in the order of the hash function's indices.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.keys.iter().copied().zip(self.values.iter())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(sizes: &[u64]) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&(sizes.len() as u32).to_le_bytes());
+        for size in sizes {
+            bytes.extend_from_slice(&size.to_le_bytes());
+        }
+        bytes
+    }
+
+    fn read_error(bytes: &[u8]) -> io::ErrorKind {
+        match ChunkMphf::read_from(bytes) {
+            Ok(_) => panic!("read a function out of a bad file"),
+            Err(err) => err.kind(),
+        }
+    }
+
+    #[test]
+    fn read_from_round_trips() {
+        let keys: Vec<_> = (0..1000)
+            .map(|i| VoxelChunkIndex::from_coords(i, -i, i % 7))
+            .collect();
+        let mphf = ChunkMphf::build(&keys);
+        let mut bytes = Vec::new();
+        mphf.write_to(&mut bytes).unwrap();
+        let read = ChunkMphf::read_from(&bytes[..]).unwrap();
+        for &idx in &keys {
+            assert_eq!(read.index(idx), mphf.index(idx));
+        }
+    }
+
+    #[test]
+    fn read_from_rejects_oversized_levels() {
+        // Sizes summing past `usize::MAX`, and past the cap.
+        for sizes in [&[u64::MAX - 63, 64][..], &[MAX_BITS, 64]] {
+            assert_eq!(read_error(&header(sizes)), io::ErrorKind::InvalidData);
+        }
+    }
+
+    #[test]
+    fn read_from_fails_on_truncated_input_without_allocating_it() {
+        assert_eq!(
+            read_error(&header(&[MAX_BITS])),
+            io::ErrorKind::UnexpectedEof
+        );
+    }
+}
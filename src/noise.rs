@@ -0,0 +1,194 @@
+//! Gradient noise and the chunk sets it carves out of a region: shells around a terrain surface
+//! and networks of caves, for procedural generators picking which chunks exist, and for
+//! benchmarks on worlds shaped like generated ones. Z is up, as in
+//! [`player_walk`](crate::workload::player_walk).
+//!
+//! Noise is sampled in chunk coordinates, once per chunk. The same seed always gives the same
+//! chunks. Parts of a region past the edge of the representable world hold no chunks.
+
+use crate::filter::mix64;
+use crate::{ChunkAabb, VoxelChunkIndex, VoxelChunkSet};
+
+/// Ken Perlin's improved gradient noise, with a permutation shuffled from a seed.
+#[derive(Clone)]
+pub struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Perlin {
+        let mut table: [u8; 256] = std::array::from_fn(|i| i as u8);
+        let mut state = seed;
+        for i in (1..256).rev() {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            table.swap(i, (mix64(state) % (i as u64 + 1)) as usize);
+        }
+        Perlin {
+            perm: std::array::from_fn(|i| table[i % 256]),
+        }
+    }
+
+    /// Noise at `(x, y, z)`, in `[-1, 1]` and 0 at every integer point.
+    pub fn get(&self, x: f64, y: f64, z: f64) -> f64 {
+        let fade = |t: f64| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+        let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+        let grad = |hash: u8, x: f64, y: f64, z: f64| {
+            let h = hash & 15;
+            let u = if h < 8 { x } else { y };
+            let v = match h {
+                0..=3 => y,
+                12 | 14 => x,
+                _ => z,
+            };
+            (if h & 1 == 0 { u } else { -u }) + (if h & 2 == 0 { v } else { -v })
+        };
+
+        let (fx, fy, fz) = (x.floor(), y.floor(), z.floor());
+        let (x, y, z) = (x - fx, y - fy, z - fz);
+        let (xi, yi, zi) = (
+            fx as i64 as usize & 255,
+            fy as i64 as usize & 255,
+            fz as i64 as usize & 255,
+        );
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let p = &self.perm;
+        let corner = |dx: usize, dy: usize, dz: usize| {
+            let hash = p[p[p[xi + dx] as usize + yi + dy] as usize + zi + dz];
+            grad(hash, x - dx as f64, y - dy as f64, z - dz as f64)
+        };
+        let plane = |dz| {
+            lerp(
+                lerp(corner(0, 0, dz), corner(1, 0, dz), u),
+                lerp(corner(0, 1, dz), corner(1, 1, dz), u),
+                v,
+            )
+        };
+        lerp(plane(0), plane(1), w).clamp(-1.0, 1.0)
+    }
+}
+
+/// Fractal Brownian motion: `octaves` layers of [`Perlin`] noise, each at `lacunarity` times
+/// the frequency and `persistence` times the amplitude of the previous one.
+#[derive(Clone)]
+pub struct Fbm {
+    noise: Perlin,
+    pub octaves: u32,
+    /// Frequency of the first octave, in cycles per chunk.
+    pub frequency: f64,
+    pub lacunarity: f64,
+    pub persistence: f64,
+}
+
+impl Fbm {
+    /// Four octaves, the first with features about 32 chunks wide.
+    pub fn new(seed: u64) -> Fbm {
+        Fbm {
+            noise: Perlin::new(seed),
+            octaves: 4,
+            frequency: 1.0 / 32.0,
+            lacunarity: 2.0,
+            persistence: 0.5,
+        }
+    }
+
+    /// Noise at `(x, y, z)`, in `[-1, 1]` but mostly within 0.4 of 0.
+    pub fn get(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (mut sum, mut total) = (0.0, 0.0);
+        let (mut frequency, mut amplitude) = (self.frequency, 1.0);
+        for octave in 0..self.octaves {
+            // Octaves are offset so that their zeros at integer points don't line up.
+            let offset = octave as f64 * 17.31;
+            sum += amplitude
+                * self.noise.get(
+                    x * frequency + offset,
+                    y * frequency + offset,
+                    z * frequency + offset,
+                );
+            total += amplitude;
+            frequency *= self.lacunarity;
+            amplitude *= self.persistence;
+        }
+        if total == 0.0 {
+            0.0
+        } else {
+            sum / total
+        }
+    }
+}
+
+/// Chunks of `region` whose `density` is above `threshold`.
+pub fn threshold_set(
+    region: ChunkAabb,
+    threshold: f64,
+    mut density: impl FnMut(i32, i32, i32) -> f64,
+) -> VoxelChunkSet {
+    region
+        .clipped_to_world()
+        .iter()
+        .filter(|idx| {
+            let (x, y, z) = idx.to_coords();
+            density(x, y, z) > threshold
+        })
+        .collect()
+}
+
+/// Chunks of `region` within `thickness` chunks of the surface at height
+/// `base + amplitude * surface(x, y)`, like the chunks of a terrain holding both ground and air,
+/// which are the ones worth meshing. Columns are walked from their surface only, so the cost is
+/// that of the shell, not of the region.
+pub fn terrain_shell(
+    region: ChunkAabb,
+    surface: &Fbm,
+    base: f64,
+    amplitude: f64,
+    thickness: i32,
+) -> VoxelChunkSet {
+    assert!(thickness >= 0, "thickness can't be negative");
+    let region = region.clipped_to_world();
+    let mut shell = VoxelChunkSet::new();
+    for y in region.min.1..region.max.1 {
+        for x in region.min.0..region.max.0 {
+            let height = base + amplitude * surface.get(x as f64, y as f64, 0.0);
+            let height = height.floor().clamp(i32::MIN as f64, i32::MAX as f64) as i32;
+            let low = height.saturating_sub(thickness).max(region.min.2);
+            let high = height.saturating_add(thickness + 1).min(region.max.2);
+            for z in low..high {
+                shell.insert(VoxelChunkIndex::from_coords(x, y, z));
+            }
+        }
+    }
+    shell
+}
+
+/// Chunks of `region` some cave runs through: those where `noise` is within `width` of 0, in
+/// winding caverns along its zero surfaces. With the default [`Fbm`], a width of 0.03 carves
+/// about a seventh of the region.
+pub fn caves(region: ChunkAabb, noise: &Fbm, width: f64) -> VoxelChunkSet {
+    threshold_set(region, -width, |x, y, z| {
+        -noise.get(x as f64, y as f64, z as f64).abs()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sets_past_the_world_edge_are_clipped() {
+        let region = ChunkAabb::new((4090, 0, -40), (4100, 4, 40));
+        let in_world = |set: &VoxelChunkSet| {
+            set.iter().all(|idx| {
+                let (x, _, z) = idx.to_coords();
+                (4090..4096).contains(&x) && (-32..32).contains(&z)
+            })
+        };
+
+        let all = threshold_set(region, f64::NEG_INFINITY, |_, _, _| 0.0);
+        assert_eq!(all.len(), 6 * 4 * 64);
+        assert!(in_world(&all));
+
+        let shell = terrain_shell(region, &Fbm::new(1), 0.0, 100.0, 2);
+        assert!(!shell.is_empty());
+        assert!(in_world(&shell));
+    }
+}
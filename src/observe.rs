@@ -0,0 +1,71 @@
+//! [`Observers`], callbacks run by the containers when entries come and go.
+
+use crate::VoxelChunkIndex;
+
+type Callback<V> = Box<dyn FnMut(VoxelChunkIndex, &V) + Send + Sync>;
+
+/// Callbacks registered on a container, each seeing the key and the value involved. They run
+/// after an insert, and before a removed or evicted value is returned, handed over or dropped.
+///
+/// Cloning a container doesn't clone its callbacks: the clone starts without observers.
+pub struct Observers<V> {
+    insert: Vec<Callback<V>>,
+    remove: Vec<Callback<V>>,
+    evict: Vec<Callback<V>>,
+}
+
+impl<V> Default for Observers<V> {
+    fn default() -> Self {
+        Observers {
+            insert: Vec::new(),
+            remove: Vec::new(),
+            evict: Vec::new(),
+        }
+    }
+}
+
+impl<V> Clone for Observers<V> {
+    fn clone(&self) -> Self {
+        Self::default()
+    }
+}
+
+impl<V> Observers<V> {
+    /// Runs on every insert, including ones replacing a value, with the new value.
+    pub fn on_insert<F: FnMut(VoxelChunkIndex, &V) + Send + Sync + 'static>(&mut self, f: F) {
+        self.insert.push(Box::new(f));
+    }
+
+    /// Runs on every explicit removal, including [`clear`](crate::VoxelChunkMap::clear).
+    pub fn on_remove<F: FnMut(VoxelChunkIndex, &V) + Send + Sync + 'static>(&mut self, f: F) {
+        self.remove.push(Box::new(f));
+    }
+
+    /// Runs when a cache evicts an entry to make room.
+    pub fn on_evict<F: FnMut(VoxelChunkIndex, &V) + Send + Sync + 'static>(&mut self, f: F) {
+        self.evict.push(Box::new(f));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.insert.is_empty() && self.remove.is_empty() && self.evict.is_empty()
+    }
+
+    /// Unregisters every callback.
+    pub fn clear(&mut self) {
+        self.insert.clear();
+        self.remove.clear();
+        self.evict.clear();
+    }
+
+    pub(crate) fn inserted(&mut self, idx: VoxelChunkIndex, value: &V) {
+        self.insert.iter_mut().for_each(|f| f(idx, value));
+    }
+
+    pub(crate) fn removed(&mut self, idx: VoxelChunkIndex, value: &V) {
+        self.remove.iter_mut().for_each(|f| f(idx, value));
+    }
+
+    pub(crate) fn evicted(&mut self, idx: VoxelChunkIndex, value: &V) {
+        self.evict.iter_mut().for_each(|f| f(idx, value));
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{ChunkAabb, VoxelChunkIndex, X_BIAS, Y_BIAS, Z_BIAS};
+
+/// Side of the cube covering every representable chunk coordinate.
+const ROOT_SIZE: u32 = 8192;
+/// Side of the 4×4×4 bricks of chunks the leaves store as one bitmask.
+const BRICK_SIZE: u32 = 4;
+const NONE: u32 = u32::MAX;
+
+#[derive(Clone)]
+struct Node {
+    /// Child node of each octant, or brick for the nodes just above the bricks.
+    children: [u32; 8],
+    /// Chunks below this node.
+    count: u32,
+}
+
+impl Node {
+    const EMPTY: Node = Node {
+        children: [NONE; 8],
+        count: 0,
+    };
+}
+
+/// Chunk coordinates shifted to `0..ROOT_SIZE`.
+fn unsigned(idx: VoxelChunkIndex) -> [u32; 3] {
+    let (x, y, z) = idx.to_coords();
+    [
+        (x + X_BIAS) as u32,
+        (y + Y_BIAS) as u32,
+        (z + Z_BIAS) as u32,
+    ]
+}
+
+fn octant(pos: [u32; 3], half: u32) -> usize {
+    (pos[0] & half != 0) as usize
+        | ((pos[1] & half != 0) as usize) << 1
+        | ((pos[2] & half != 0) as usize) << 2
+}
+
+/// Origin of the given octant, of side `half`, of the cube at `origin`.
+fn child_origin(origin: [u32; 3], half: u32, octant: usize) -> [u32; 3] {
+    [
+        origin[0] + half * (octant & 1) as u32,
+        origin[1] + half * (octant >> 1 & 1) as u32,
+        origin[2] + half * (octant >> 2 & 1) as u32,
+    ]
+}
+
+fn brick_bit(pos: [u32; 3]) -> u64 {
+    1 << ((pos[0] & 3) | (pos[1] & 3) << 2 | (pos[2] & 3) << 4)
+}
+
+/// Set of chunk indices stored as a sparse octree over chunk space, ending in 4×4×4 bitmask
+/// bricks. Each node counts the chunks below it, so [`OctreeChunkSet::any_in_region`] and
+/// [`OctreeChunkSet::count_in_region`] only descend into the nodes straddling the region's
+/// boundary instead of visiting every chunk.
+///
+/// Point operations walk the 12 levels of the tree. Prefer [`VoxelChunkSet`](crate::VoxelChunkSet)
+/// for sets mostly queried chunk by chunk.
+#[derive(Clone)]
+pub struct OctreeChunkSet {
+    /// The root is always node 0.
+    nodes: Vec<Node>,
+    bricks: Vec<u64>,
+    free_nodes: Vec<u32>,
+    free_bricks: Vec<u32>,
+}
+
+impl Default for OctreeChunkSet {
+    fn default() -> Self {
+        OctreeChunkSet {
+            nodes: vec![Node::EMPTY],
+            bricks: Vec::new(),
+            free_nodes: Vec::new(),
+            free_bricks: Vec::new(),
+        }
+    }
+}
+
+impl OctreeChunkSet {
+    pub fn new() -> OctreeChunkSet {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes[0].count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn contains(&self, idx: VoxelChunkIndex) -> bool {
+        let pos = unsigned(idx);
+        let mut node = 0;
+        let mut size = ROOT_SIZE;
+        loop {
+            let child = self.nodes[node].children[octant(pos, size / 2)];
+            if child == NONE {
+                return false;
+            }
+            if size / 2 == BRICK_SIZE {
+                return self.bricks[child as usize] & brick_bit(pos) != 0;
+            }
+            node = child as usize;
+            size /= 2;
+        }
+    }
+
+    /// Returns whether the chunk was newly added.
+    pub fn insert(&mut self, idx: VoxelChunkIndex) -> bool {
+        if self.contains(idx) {
+            return false;
+        }
+        let pos = unsigned(idx);
+        let mut node = 0;
+        let mut size = ROOT_SIZE;
+        loop {
+            self.nodes[node].count += 1;
+            let octant = octant(pos, size / 2);
+            let mut child = self.nodes[node].children[octant];
+            if size / 2 == BRICK_SIZE {
+                if child == NONE {
+                    child = self.alloc_brick();
+                    self.nodes[node].children[octant] = child;
+                }
+                self.bricks[child as usize] |= brick_bit(pos);
+                return true;
+            }
+            if child == NONE {
+                child = self.alloc_node();
+                self.nodes[node].children[octant] = child;
+            }
+            node = child as usize;
+            size /= 2;
+        }
+    }
+
+    /// Returns whether the chunk was present. Subtrees left empty are released.
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        if !self.contains(idx) {
+            return false;
+        }
+        let pos = unsigned(idx);
+        let mut node = 0;
+        let mut size = ROOT_SIZE;
+        loop {
+            let octant = octant(pos, size / 2);
+            let child = self.nodes[node].children[octant];
+            self.nodes[node].count -= 1;
+            if size / 2 == BRICK_SIZE {
+                let brick = &mut self.bricks[child as usize];
+                *brick &= !brick_bit(pos);
+                if *brick == 0 {
+                    self.nodes[node].children[octant] = NONE;
+                    self.free_bricks.push(child);
+                }
+                return true;
+            }
+            if self.nodes[child as usize].count == 1 {
+                // The chunk is the only one below `child`: drop the whole subtree.
+                self.nodes[node].children[octant] = NONE;
+                self.release(child, size / 2);
+                return true;
+            }
+            node = child as usize;
+            size /= 2;
+        }
+    }
+
+    /// Whether any chunk of the set lies in `region`.
+    pub fn any_in_region(&self, region: ChunkAabb) -> bool {
+        let (lo, hi) = bounds(region);
+        self.count_below(0, [0; 3], ROOT_SIZE, lo, hi, true) > 0
+    }
+
+    /// Number of chunks of the set lying in `region`.
+    pub fn count_in_region(&self, region: ChunkAabb) -> usize {
+        let (lo, hi) = bounds(region);
+        self.count_below(0, [0; 3], ROOT_SIZE, lo, hi, false)
+    }
+
+    /// Chunks below `node`, covering the cube of side `size` at `origin`, that lie in `lo..hi`.
+    /// With `any`, stops as soon as one is found.
+    fn count_below(
+        &self,
+        node: u32,
+        origin: [u32; 3],
+        size: u32,
+        lo: [u32; 3],
+        hi: [u32; 3],
+        any: bool,
+    ) -> usize {
+        if (0..3).any(|axis| origin[axis] >= hi[axis] || origin[axis] + size <= lo[axis]) {
+            return 0;
+        }
+        let node = &self.nodes[node as usize];
+        if (0..3).all(|axis| lo[axis] <= origin[axis] && origin[axis] + size <= hi[axis]) {
+            return node.count as usize;
+        }
+
+        let half = size / 2;
+        let mut count = 0;
+        for (octant, &child) in node.children.iter().enumerate() {
+            if child == NONE {
+                continue;
+            }
+            let child_origin = child_origin(origin, half, octant);
+            count += if half == BRICK_SIZE {
+                (self.bricks[child as usize] & brick_mask(child_origin, lo, hi)).count_ones()
+                    as usize
+            } else {
+                self.count_below(child, child_origin, half, lo, hi, any)
+            };
+            if any && count > 0 {
+                break;
+            }
+        }
+        count
+    }
+
+    pub fn clear(&mut self) {
+        *self = OctreeChunkSet::default();
+    }
+
+    /// Iterates chunks in octree order.
+    pub fn iter(&self) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        // Nodes or, for entries of side `BRICK_SIZE`, bricks left to visit.
+        let mut stack = vec![(0, [0; 3], ROOT_SIZE)];
+        let mut brick = ([0; 3], 0u64);
+        std::iter::from_fn(move || loop {
+            let (origin, bits) = &mut brick;
+            if *bits != 0 {
+                let bit = bits.trailing_zeros();
+                *bits &= *bits - 1;
+                return Some(VoxelChunkIndex::from_coords(
+                    (origin[0] + (bit & 3)) as i32 - X_BIAS,
+                    (origin[1] + (bit >> 2 & 3)) as i32 - Y_BIAS,
+                    (origin[2] + (bit >> 4)) as i32 - Z_BIAS,
+                ));
+            }
+
+            let (id, origin, size) = stack.pop()?;
+            if size == BRICK_SIZE {
+                brick = (origin, self.bricks[id as usize]);
+                continue;
+            }
+            let half = size / 2;
+            for (octant, &child) in self.nodes[id as usize].children.iter().enumerate().rev() {
+                if child != NONE {
+                    stack.push((child, child_origin(origin, half, octant), half));
+                }
+            }
+        })
+    }
+
+    fn alloc_node(&mut self) -> u32 {
+        match self.free_nodes.pop() {
+            Some(node) => {
+                self.nodes[node as usize] = Node::EMPTY;
+                node
+            }
+            None => {
+                self.nodes.push(Node::EMPTY);
+                (self.nodes.len() - 1) as u32
+            }
+        }
+    }
+
+    fn alloc_brick(&mut self) -> u32 {
+        match self.free_bricks.pop() {
+            Some(brick) => {
+                self.bricks[brick as usize] = 0;
+                brick
+            }
+            None => {
+                self.bricks.push(0);
+                (self.bricks.len() - 1) as u32
+            }
+        }
+    }
+
+    /// Frees `node`, of side `size`, and everything below it.
+    fn release(&mut self, node: u32, size: u32) {
+        let children = self.nodes[node as usize].children;
+        for child in children.into_iter().filter(|&child| child != NONE) {
+            if size / 2 == BRICK_SIZE {
+                self.free_bricks.push(child);
+            } else {
+                self.release(child, size / 2);
+            }
+        }
+        self.free_nodes.push(node);
+    }
+}
+
+/// `region` in unsigned coordinates, clamped to the representable cube.
+fn bounds(region: ChunkAabb) -> ([u32; 3], [u32; 3]) {
+    let shift = |v: i32, bias: i32| (v as i64 + bias as i64).clamp(0, ROOT_SIZE as i64) as u32;
+    (
+        [
+            shift(region.min.0, X_BIAS),
+            shift(region.min.1, Y_BIAS),
+            shift(region.min.2, Z_BIAS),
+        ],
+        [
+            shift(region.max.0, X_BIAS),
+            shift(region.max.1, Y_BIAS),
+            shift(region.max.2, Z_BIAS),
+        ],
+    )
+}
+
+/// Bits of the brick at `origin` lying in `lo..hi`.
+fn brick_mask(origin: [u32; 3], lo: [u32; 3], hi: [u32; 3]) -> u64 {
+    let range = |axis: usize| {
+        let start = lo[axis].saturating_sub(origin[axis]).min(BRICK_SIZE);
+        let end = hi[axis].saturating_sub(origin[axis]).min(BRICK_SIZE);
+        start..end
+    };
+    let row: u64 = range(0).map(|x| 1 << x).sum();
+    let mut mask = 0;
+    for z in range(2) {
+        for y in range(1) {
+            mask |= row << (y * 4 + z * 16);
+        }
+    }
+    mask
+}
+
+impl Extend<VoxelChunkIndex> for OctreeChunkSet {
+    fn extend<I: IntoIterator<Item = VoxelChunkIndex>>(&mut self, iter: I) {
+        for idx in iter {
+            self.insert(idx);
+        }
+    }
+}
+
+impl FromIterator<VoxelChunkIndex> for OctreeChunkSet {
+    fn from_iter<I: IntoIterator<Item = VoxelChunkIndex>>(iter: I) -> OctreeChunkSet {
+        let mut set = OctreeChunkSet::new();
+        set.extend(iter);
+        set
+    }
+}
@@ -0,0 +1,205 @@
+//! [`PagedChunkMap`], a two-level map grouping chunks into 8×8×8 superchunk pages.
+
+use crate::{ChunkAabb, VoxelChunkIndex, VoxelChunkMap};
+
+const PAGE_BITS: i32 = 3;
+
+/// Chunks per page edge.
+pub const PAGE_EDGE: i32 = 1 << PAGE_BITS;
+const PAGE_VOLUME: usize = (PAGE_EDGE * PAGE_EDGE * PAGE_EDGE) as usize;
+const LOCAL_MASK: i32 = PAGE_EDGE - 1;
+
+/// Direct-indexed storage for the chunks of one superchunk.
+#[derive(Clone)]
+pub struct ChunkPage<V> {
+    origin: (i32, i32, i32),
+    pub(crate) cells: Vec<Option<V>>,
+    pub(crate) len: usize,
+}
+
+/// Coordinates of the superchunk holding the chunk at `(x, y, z)`, rounding towards -∞.
+pub fn superchunk_of(x: i32, y: i32, z: i32) -> (i32, i32, i32) {
+    (x >> PAGE_BITS, y >> PAGE_BITS, z >> PAGE_BITS)
+}
+
+/// Offset of a chunk inside its page, in the same X-outermost order as [`ChunkAabb::iter`].
+fn local_offset(x: i32, y: i32, z: i32) -> usize {
+    (((x & LOCAL_MASK) << (2 * PAGE_BITS)) | ((y & LOCAL_MASK) << PAGE_BITS) | (z & LOCAL_MASK))
+        as usize
+}
+
+/// Inverse of [`local_offset`] for the page starting at `origin`.
+pub(crate) fn index_at(origin: (i32, i32, i32), offset: usize) -> VoxelChunkIndex {
+    let offset = offset as i32;
+    VoxelChunkIndex::from_coords(
+        origin.0 + (offset >> (2 * PAGE_BITS)),
+        origin.1 + ((offset >> PAGE_BITS) & LOCAL_MASK),
+        origin.2 + (offset & LOCAL_MASK),
+    )
+}
+
+impl<V> ChunkPage<V> {
+    pub(crate) fn new(superchunk: (i32, i32, i32)) -> ChunkPage<V> {
+        let mut cells = Vec::with_capacity(PAGE_VOLUME);
+        cells.resize_with(PAGE_VOLUME, || None);
+        ChunkPage {
+            origin: (
+                superchunk.0 << PAGE_BITS,
+                superchunk.1 << PAGE_BITS,
+                superchunk.2 << PAGE_BITS,
+            ),
+            cells,
+            len: 0,
+        }
+    }
+
+    /// Coordinates of the page's lowest chunk.
+    pub fn origin(&self) -> (i32, i32, i32) {
+        self.origin
+    }
+
+    pub fn region(&self) -> ChunkAabb {
+        let (x, y, z) = self.origin;
+        ChunkAabb::new((x, y, z), (x + PAGE_EDGE, y + PAGE_EDGE, z + PAGE_EDGE))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        let origin = self.origin;
+        self.cells
+            .iter()
+            .enumerate()
+            .filter_map(move |(offset, cell)| Some((index_at(origin, offset), cell.as_ref()?)))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        let origin = self.origin;
+        self.cells
+            .iter_mut()
+            .enumerate()
+            .filter_map(move |(offset, cell)| Some((index_at(origin, offset), cell.as_mut()?)))
+    }
+}
+
+/// Chunk map hashing the 8×8×8 superchunk of a key once, then direct-indexing the chunk inside
+/// that superchunk's page. Neighboring chunks mostly share a page, so neighbor walks mostly hit
+/// memory that is already in cache, and whole pages can be processed at once.
+pub struct PagedChunkMap<V> {
+    pages: VoxelChunkMap<ChunkPage<V>>,
+    len: usize,
+}
+
+impl<V> Default for PagedChunkMap<V> {
+    fn default() -> Self {
+        PagedChunkMap {
+            pages: VoxelChunkMap::new(),
+            len: 0,
+        }
+    }
+}
+
+/// Key of the page holding `idx`, and the offset of `idx` inside it.
+pub(crate) fn split(idx: VoxelChunkIndex) -> (VoxelChunkIndex, usize) {
+    let (x, y, z) = idx.to_coords();
+    let (sx, sy, sz) = superchunk_of(x, y, z);
+    (
+        VoxelChunkIndex::from_coords(sx, sy, sz),
+        local_offset(x, y, z),
+    )
+}
+
+impl<V> PagedChunkMap<V> {
+    pub fn new() -> PagedChunkMap<V> {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let (page, offset) = split(idx);
+        self.pages.get(page)?.cells[offset].as_ref()
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        let (page, offset) = split(idx);
+        self.pages.get_mut(page)?.cells[offset].as_mut()
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        let (page, offset) = split(idx);
+        let page = self
+            .pages
+            .entry(page)
+            .or_insert_with(|| ChunkPage::new(page.to_coords()));
+
+        let old = page.cells[offset].replace(value);
+        if old.is_none() {
+            page.len += 1;
+            self.len += 1;
+        }
+        old
+    }
+
+    /// Removes a chunk, releasing its page once it holds no chunk anymore.
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let (page_idx, offset) = split(idx);
+        let page = self.pages.get_mut(page_idx)?;
+        let old = page.cells[offset].take()?;
+        page.len -= 1;
+        self.len -= 1;
+        if page.len == 0 {
+            self.pages.remove(page_idx);
+        }
+        Some(old)
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.len = 0;
+    }
+
+    /// The page of the superchunk at `superchunk` coordinates, if it holds any chunk.
+    pub fn page(&self, superchunk: (i32, i32, i32)) -> Option<&ChunkPage<V>> {
+        let (x, y, z) = superchunk;
+        self.pages.get(VoxelChunkIndex::from_coords(x, y, z))
+    }
+
+    /// Non-empty pages, in arbitrary order.
+    pub fn pages(&self) -> impl Iterator<Item = &ChunkPage<V>> {
+        self.pages.values()
+    }
+
+    pub fn pages_mut(&mut self) -> impl Iterator<Item = &mut ChunkPage<V>> {
+        self.pages.iter_mut().map(|(_, page)| page)
+    }
+
+    /// Iterates page by page; pages come in arbitrary order, chunks within a page in region order.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.pages().flat_map(|page| page.iter())
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        self.pages_mut().flat_map(|page| page.iter_mut())
+    }
+}
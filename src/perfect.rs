@@ -0,0 +1,177 @@
+use crate::filter::{mix64, reduce};
+use crate::map::FIB_MULTIPLIER;
+use crate::VoxelChunkIndex;
+
+/// Average number of keys per bucket sharing a pilot.
+const BUCKET_KEYS: usize = 3;
+
+/// Immutable map built around a perfect hash function of its keys, for baked content such as
+/// pre-generated lobby worlds: every lookup probes exactly one slot, and the table only has about
+/// 3% empty slots.
+///
+/// Keys are hashed into buckets, and each bucket gets a pilot, the first one sending all its keys
+/// to free slots (PTHash-style). Construction costs a few hashes per key; a lookup costs two
+/// memory accesses, the bucket's pilot then the slot.
+#[derive(Clone)]
+pub struct PerfectChunkMap<V> {
+    seed: u64,
+    pilots: Box<[u16]>,
+    keys: Box<[VoxelChunkIndex]>,
+    values: Box<[Option<V>]>,
+    len: usize,
+}
+
+#[inline]
+fn key_hash(idx: VoxelChunkIndex, seed: u64) -> u64 {
+    mix64((idx.0 as u64).wrapping_mul(FIB_MULTIPLIER) ^ seed)
+}
+
+#[inline]
+fn slot_of(hash: u64, pilot: u16, slots: usize) -> usize {
+    reduce(
+        mix64(hash ^ (pilot as u64 + 1).wrapping_mul(FIB_MULTIPLIER)),
+        slots,
+    )
+}
+
+impl<V> PerfectChunkMap<V> {
+    /// Builds the map of `keys[i] -> values[i]`. When a key appears several times, the last value
+    /// wins.
+    pub fn build<K, I>(keys: K, values: I) -> PerfectChunkMap<V>
+    where
+        K: IntoIterator<Item = VoxelChunkIndex>,
+        I: IntoIterator<Item = V>,
+    {
+        let keys: Vec<_> = keys.into_iter().collect();
+        let values: Vec<_> = values.into_iter().collect();
+        assert_eq!(
+            keys.len(),
+            values.len(),
+            "as many keys as values are needed"
+        );
+
+        let mut entries: Vec<_> = keys.into_iter().zip(values).collect();
+        entries.reverse();
+        entries.sort_by_key(|&(idx, _)| idx);
+        entries.dedup_by_key(|&mut (idx, _)| idx);
+
+        let len = entries.len();
+        let buckets = len.div_ceil(BUCKET_KEYS).max(1);
+        let slots = len + len / 32 + 1;
+
+        let mut seed = 0;
+        let (pilots, positions) = loop {
+            seed += 1;
+            if let Some(found) = Self::find_pilots(&entries, seed, buckets, slots) {
+                break found;
+            }
+        };
+
+        let mut keys = vec![VoxelChunkIndex(0); slots].into_boxed_slice();
+        let mut values: Box<[Option<V>]> = (0..slots).map(|_| None).collect();
+        for ((idx, value), slot) in entries.into_iter().zip(positions) {
+            keys[slot] = idx;
+            values[slot] = Some(value);
+        }
+
+        PerfectChunkMap {
+            seed,
+            pilots,
+            keys,
+            values,
+            len,
+        }
+    }
+
+    /// Pilots of every bucket and slot of every entry, or `None` if some bucket has no pilot
+    /// placing its keys, in which case another seed is tried.
+    fn find_pilots(
+        entries: &[(VoxelChunkIndex, V)],
+        seed: u64,
+        buckets: usize,
+        slots: usize,
+    ) -> Option<(Box<[u16]>, Vec<usize>)> {
+        let hashes: Vec<u64> = entries
+            .iter()
+            .map(|&(idx, _)| key_hash(idx, seed))
+            .collect();
+
+        // Entry indices grouped by bucket, biggest buckets first while the table is still empty.
+        let mut by_bucket: Vec<(usize, usize)> = hashes
+            .iter()
+            .enumerate()
+            .map(|(i, &hash)| (reduce(hash, buckets), i))
+            .collect();
+        by_bucket.sort_unstable();
+        let mut groups: Vec<&[(usize, usize)]> = by_bucket.chunk_by(|a, b| a.0 == b.0).collect();
+        groups.sort_by_key(|group| std::cmp::Reverse(group.len()));
+
+        let mut pilots = vec![0; buckets].into_boxed_slice();
+        let mut positions = vec![0; entries.len()];
+        let mut taken = vec![false; slots];
+        let mut candidate = Vec::with_capacity(BUCKET_KEYS * 4);
+        for group in groups {
+            let pilot = (0..=u16::MAX).find(|&pilot| {
+                candidate.clear();
+                for &(_, i) in group {
+                    let slot = slot_of(hashes[i], pilot, slots);
+                    if taken[slot] || candidate.contains(&slot) {
+                        return false;
+                    }
+                    candidate.push(slot);
+                }
+                true
+            })?;
+
+            pilots[group[0].0] = pilot;
+            for (&(_, i), &slot) in group.iter().zip(&candidate) {
+                taken[slot] = true;
+                positions[i] = slot;
+            }
+        }
+        Some((pilots, positions))
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    #[inline]
+    fn slot(&self, idx: VoxelChunkIndex) -> usize {
+        let hash = key_hash(idx, self.seed);
+        let pilot = self.pilots[reduce(hash, self.pilots.len())];
+        slot_of(hash, pilot, self.keys.len())
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let slot = self.slot(idx);
+        if self.keys[slot] == idx {
+            self.values[slot].as_ref()
+        } else {
+            None
+        }
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    /// Iterates in slot order, which is unrelated to key order.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.keys
+            .iter()
+            .zip(self.values.iter())
+            .filter_map(|(&idx, value)| Some((idx, value.as_ref()?)))
+    }
+}
+
+impl<V> FromIterator<(VoxelChunkIndex, V)> for PerfectChunkMap<V> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(iter: I) -> PerfectChunkMap<V> {
+        let (keys, values): (Vec<_>, Vec<_>) = iter.into_iter().unzip();
+        Self::build(keys, values)
+    }
+}
@@ -0,0 +1,184 @@
+//! Table sizes picked from data rather than guessed: [`plan_capacity`] generates keys shaped like
+//! the expected ones and sweeps table sizes over them with
+//! [`collision_report`](crate::analysis::collision_report), the way `report_fib_sweep` does in
+//! the benchmarks.
+
+use std::hash::{BuildHasher, Hasher};
+
+use crate::analysis::collision_report;
+use crate::map::FIB_MULTIPLIER;
+use crate::workload::{caves, outposts};
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+/// Smallest table any of the planned containers allocates.
+const MIN_BUCKETS: usize = 16;
+/// Doublings of the table the sweep tries past the one the load factor calls for.
+const MAX_DOUBLINGS: u32 = 4;
+/// How much longer than under uniform hashing the mean probe may be before the sweep doubles
+/// the table.
+const MAX_PROBE_RATIO: f64 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Container {
+    /// `std::collections::HashMap` hashing with a `FibHasher`.
+    StdHashMap,
+    VoxelChunkMap,
+    LinearChunkMap,
+}
+
+impl Container {
+    /// Highest load factor the container keeps probes short at. Group probing copes with 7/8,
+    /// where the containers grow; linear probing doesn't.
+    fn max_load(self) -> f64 {
+        match self {
+            Container::StdHashMap | Container::VoxelChunkMap => 7.0 / 8.0,
+            Container::LinearChunkMap => 0.5,
+        }
+    }
+}
+
+/// How the keys are laid out in the world.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyShape {
+    /// Every chunk of a box, like the area around the players.
+    Dense,
+    /// Blobs filling about half of a box, like the non-empty chunks of a cavey world.
+    Caves,
+    /// Small balls scattered over the world, like outposts.
+    Scattered,
+}
+
+impl KeyShape {
+    /// `len` keys of this shape.
+    fn keys(self, len: usize) -> Vec<VoxelChunkIndex> {
+        // Boxes 16 chunks high, as wide as they need to be.
+        let square = |volume: usize| {
+            let side = (volume as f64 / 16.0).sqrt().ceil() as i32;
+            ChunkAabb::new(
+                (-side / 2, -side / 2, -8),
+                (side - side / 2, side - side / 2, 8),
+            )
+        };
+        let mut keys: Vec<VoxelChunkIndex> = match self {
+            KeyShape::Dense => square(len).iter().collect(),
+            KeyShape::Caves => caves(square(len * 9 / 4), 0.5, 0),
+            // A ball of radius 4 holds 257 chunks.
+            KeyShape::Scattered => outposts(len / 257 + 1, 4, 0),
+        };
+        keys.truncate(len);
+        keys
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorkloadProfile {
+    pub container: Container,
+    pub shape: KeyShape,
+    /// Keys loaded at peak on top of the expected ones, as a fraction of them: chunks loaded
+    /// ahead of the players, or waiting for the next unload pass.
+    pub headroom: f64,
+}
+
+impl WorkloadProfile {
+    pub fn new(container: Container, shape: KeyShape) -> WorkloadProfile {
+        WorkloadProfile {
+            container,
+            shape,
+            headroom: 0.0,
+        }
+    }
+}
+
+/// Recommended sizing of a container, see [`plan_capacity`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CapacityPlan {
+    /// What to give `with_capacity` or `reserve` for the table to have `buckets` buckets.
+    pub capacity: usize,
+    pub buckets: usize,
+    /// Load factor with the expected keys in.
+    pub load_factor: f64,
+    /// Output bits of the `FibHasher<N>` hashing keys into a std map of `buckets` buckets. The
+    /// maps of this crate derive their shift from their size themselves.
+    pub fib_bits: u32,
+    /// Mean probe length of the expected keys in a linear probing table of `buckets` buckets.
+    pub mean_probe: f64,
+}
+
+impl CapacityPlan {
+    /// Shift of the Fibonacci hash, 64 minus [`CapacityPlan::fib_bits`].
+    pub fn fib_shift(&self) -> u32 {
+        64 - self.fib_bits
+    }
+}
+
+/// Fibonacci hashing keeping the `64 - shift` high bits, like the maps of this crate do.
+struct FibShift(u32);
+
+struct FibShiftHasher {
+    shift: u32,
+    key: u64,
+}
+
+impl Hasher for FibShiftHasher {
+    fn finish(&self) -> u64 {
+        self.key.wrapping_mul(FIB_MULTIPLIER) >> self.shift
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.key = self.key << 8 | byte as u64;
+        }
+    }
+
+    fn write_u32(&mut self, i: u32) {
+        self.key = i as u64;
+    }
+}
+
+impl BuildHasher for FibShift {
+    type Hasher = FibShiftHasher;
+
+    fn build_hasher(&self) -> FibShiftHasher {
+        FibShiftHasher {
+            shift: self.0,
+            key: 0,
+        }
+    }
+}
+
+/// Plans the table of `profile.container` for `expected_keys` keys of `profile.shape`.
+///
+/// Starts from the smallest table holding the keys and their headroom under the container's
+/// maximum load factor, then doubles it while Fibonacci hashing packs sample keys of that shape
+/// into longer probes than uniform hashing would. Generating and probing the samples takes up to
+/// a few hundred milliseconds per million keys, caves being the slowest to generate.
+pub fn plan_capacity(expected_keys: usize, profile: WorkloadProfile) -> CapacityPlan {
+    let expected_keys = expected_keys.max(1);
+    let peak = expected_keys + (expected_keys as f64 * profile.headroom.max(0.0)).ceil() as usize;
+    let keys = profile.shape.keys(expected_keys);
+
+    let first = ((peak as f64 / profile.container.max_load()).ceil() as usize)
+        .next_power_of_two()
+        .max(MIN_BUCKETS);
+    let mut buckets = first;
+    let mut mean_probe;
+    loop {
+        let shift = 64 - buckets.trailing_zeros();
+        mean_probe = collision_report(&keys, &FibShift(shift), buckets).mean_probe;
+        let load = keys.len() as f64 / buckets as f64;
+        let uniform = 0.5 * (1.0 + 1.0 / (1.0 - load));
+        if mean_probe <= MAX_PROBE_RATIO * uniform || buckets >= first << MAX_DOUBLINGS {
+            break;
+        }
+        buckets *= 2;
+    }
+
+    CapacityPlan {
+        // The containers grow past 7/8, so this many keys keep them at `buckets`.
+        capacity: buckets / 8 * 7,
+        buckets,
+        load_factor: expected_keys as f64 / buckets as f64,
+        fib_bits: buckets.trailing_zeros(),
+        mean_probe,
+    }
+}
@@ -0,0 +1,63 @@
+//! Keys carrying their hash, to hash a [`VoxelChunkIndex`] once and probe several standard maps
+//! with it. `std`'s `HashMap` takes no precomputed hash, so code keeping per-layer data in
+//! separate maps otherwise hashes the same key once per map.
+
+use std::collections::HashMap;
+use std::hash::{BuildHasher, BuildHasherDefault, Hash, Hasher};
+
+use crate::VoxelChunkIndex;
+
+/// A key and its hash by some hasher. Maps keyed by it with [`PrehashedState`] use the stored
+/// hash as is, so every map it is looked up in must have been filled with keys hashed by the
+/// same hasher.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HashedIndex {
+    idx: VoxelChunkIndex,
+    hash: u64,
+}
+
+impl HashedIndex {
+    pub fn new<S: BuildHasher>(build_hasher: &S, idx: VoxelChunkIndex) -> HashedIndex {
+        HashedIndex {
+            idx,
+            hash: build_hasher.hash_one(idx),
+        }
+    }
+
+    pub fn idx(self) -> VoxelChunkIndex {
+        self.idx
+    }
+
+    pub fn hash(self) -> u64 {
+        self.hash
+    }
+}
+
+impl Hash for HashedIndex {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        state.write_u64(self.hash);
+    }
+}
+
+/// Hands the hash of a [`HashedIndex`] over to the map.
+#[derive(Default)]
+pub struct PrehashedHasher(u64);
+
+impl Hasher for PrehashedHasher {
+    fn finish(&self) -> u64 {
+        self.0
+    }
+
+    fn write(&mut self, _: &[u8]) {
+        unreachable!("only hashes a HashedIndex");
+    }
+
+    fn write_u64(&mut self, hash: u64) {
+        self.0 = hash;
+    }
+}
+
+pub type PrehashedState = BuildHasherDefault<PrehashedHasher>;
+
+/// Standard map keyed by already hashed indices.
+pub type PrehashedMap<V> = HashMap<HashedIndex, V, PrehashedState>;
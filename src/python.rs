@@ -0,0 +1,204 @@
+//! Functions exported for Python through `ctypes`, so that notebooks analyse worlds and hashers
+//! with the code paths production runs. `bindings/hash_funsies.py` in the repository wraps
+//! them in a module of plain Python functions.
+//!
+//! Build the library they load with
+//! `cargo rustc --release --lib --features python --crate-type cdylib`.
+//! Hashers are picked by their [`NamedHasher`] name, and reports come back as JSON strings,
+//! which the caller frees with [`hf_py_free_string`].
+
+use std::ffi::{c_char, CStr, CString};
+
+use crate::analysis::{avalanche, bucket_counts, collision_report, quality_score};
+use crate::hashers::{NamedHasher, HASHER_NAMES};
+use crate::region::write_region_keys;
+use crate::VoxelChunkIndex;
+
+/// # Safety
+///
+/// `name` must be a valid C string.
+unsafe fn hasher(name: *const c_char) -> Option<NamedHasher> {
+    // SAFETY: the caller passes a valid C string.
+    NamedHasher::new(unsafe { CStr::from_ptr(name) }.to_str().ok()?)
+}
+
+fn json<T: serde::Serialize + ?Sized>(value: &T) -> *mut c_char {
+    let json = serde_json::to_string(value).expect("reports serialize");
+    CString::new(json).unwrap().into_raw()
+}
+
+/// # Safety
+///
+/// `keys` must point to `len` keys, unless `len` is 0.
+unsafe fn keys<'a>(keys: *const u32, len: usize) -> &'a [VoxelChunkIndex] {
+    if len == 0 {
+        return &[];
+    }
+    // SAFETY: `VoxelChunkIndex` is a `repr(transparent)` `u32`, and the caller passes `len` of
+    // them.
+    unsafe { std::slice::from_raw_parts(keys.cast(), len) }
+}
+
+#[no_mangle]
+pub extern "C" fn hf_py_encode(x: i32, y: i32, z: i32) -> u32 {
+    VoxelChunkIndex::from_coords(x, y, z).0
+}
+
+/// Writes the coordinates of `key` to `out`.
+///
+/// # Safety
+///
+/// `out` must point to 3 writable `i32`s.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_decode(key: u32, out: *mut i32) {
+    let (x, y, z) = VoxelChunkIndex(key).to_coords();
+    // SAFETY: the caller passes room for 3 values.
+    unsafe { std::slice::from_raw_parts_mut(out, 3) }.copy_from_slice(&[x, y, z]);
+}
+
+/// Writes the [`VoxelChunkIndex::neighbors`] of `key` to `out`, returning how many.
+///
+/// # Safety
+///
+/// `out` must point to 26 writable `u32`s.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_neighbors(key: u32, out: *mut u32) -> u32 {
+    // SAFETY: the caller passes room for 26 keys, as many as a chunk has neighbors.
+    let out = unsafe { std::slice::from_raw_parts_mut(out, 26) };
+    let mut len = 0;
+    for (slot, idx) in out.iter_mut().zip(VoxelChunkIndex(key).neighbors()) {
+        *slot = idx.0;
+        len += 1;
+    }
+    len
+}
+
+/// Number of chunks in the box from `min` inclusive to `max` exclusive, clipped to the
+/// representable world, writing their keys to `out`, in
+/// [`ChunkAabb::iter`](crate::ChunkAabb::iter) order, if they fit in `capacity`. An inverted
+/// box, with `min` past `max` on some axis, holds no chunks.
+///
+/// # Safety
+///
+/// `out` must point to `capacity` writable `u32`s, and `min` and `max` to 3 `i32`s each.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_region(
+    min: *const i32,
+    max: *const i32,
+    out: *mut u32,
+    capacity: usize,
+) -> usize {
+    // SAFETY: forwarded from the caller.
+    unsafe { write_region_keys(min, max, out, capacity) }
+}
+
+/// The names of the hashers, with the low bits of the hash each fills, as JSON.
+#[no_mangle]
+pub extern "C" fn hf_py_hashers() -> *mut c_char {
+    let hashers: Vec<(&str, u32)> = HASHER_NAMES
+        .into_iter()
+        .map(|name| (name, NamedHasher::new(name).unwrap().output_bits()))
+        .collect();
+    json(&hashers)
+}
+
+/// Frees a string some other `hf_py_` function returned.
+///
+/// # Safety
+///
+/// `string` must come from such a function, and not be used after.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_free_string(string: *mut c_char) {
+    // SAFETY: the caller passes a string from `CString::into_raw`, once.
+    drop(unsafe { CString::from_raw(string) });
+}
+
+/// Writes to `out` the number of `keys` hashing to each of the `table_size` buckets, returning
+/// false if `hasher` is unknown.
+///
+/// # Safety
+///
+/// `hasher` must be a valid C string, `keys` must point to `len` keys and `out` to
+/// `table_size` writable `u64`s.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_bucket_counts(
+    hasher: *const c_char,
+    keys: *const u32,
+    len: usize,
+    table_size: usize,
+    out: *mut u64,
+) -> bool {
+    // SAFETY: forwarded from the caller.
+    let Some(hasher) = (unsafe { self::hasher(hasher) }) else {
+        return false;
+    };
+    // SAFETY: forwarded from the caller.
+    let keys = unsafe { self::keys(keys, len) };
+    // SAFETY: the caller passes room for `table_size` counts.
+    let out = unsafe { std::slice::from_raw_parts_mut(out, table_size) };
+    for (slot, count) in out.iter_mut().zip(bucket_counts(keys, &hasher, table_size)) {
+        *slot = count as u64;
+    }
+    true
+}
+
+/// The [`collision_report`] of `keys` in a table of `table_size` buckets as JSON, or null if
+/// `hasher` is unknown.
+///
+/// # Safety
+///
+/// `hasher` must be a valid C string and `keys` must point to `len` keys.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_collision_report(
+    hasher: *const c_char,
+    keys: *const u32,
+    len: usize,
+    table_size: usize,
+) -> *mut c_char {
+    // SAFETY: forwarded from the caller.
+    let Some(hasher) = (unsafe { self::hasher(hasher) }) else {
+        return std::ptr::null_mut();
+    };
+    // SAFETY: forwarded from the caller.
+    let keys = unsafe { self::keys(keys, len) };
+    json(&collision_report(keys, &hasher, table_size))
+}
+
+/// The [`avalanche`] report of `hasher` over `samples` keys as JSON, or null if it is unknown.
+///
+/// # Safety
+///
+/// `hasher` must be a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_avalanche(hasher: *const c_char, samples: usize) -> *mut c_char {
+    // SAFETY: forwarded from the caller.
+    match unsafe { self::hasher(hasher) } {
+        Some(hasher) => json(&avalanche(&hasher, samples)),
+        None => std::ptr::null_mut(),
+    }
+}
+
+/// The [`quality_score`] of the `output_bits` low bits of `hasher` as JSON, or null if it is
+/// unknown. An `output_bits` of 0 scores the bits the hasher fills.
+///
+/// # Safety
+///
+/// `hasher` must be a valid C string.
+#[no_mangle]
+pub unsafe extern "C" fn hf_py_quality_score(
+    hasher: *const c_char,
+    output_bits: u32,
+) -> *mut c_char {
+    // SAFETY: forwarded from the caller.
+    match unsafe { self::hasher(hasher) } {
+        Some(hasher) => {
+            let bits = if output_bits == 0 {
+                hasher.output_bits()
+            } else {
+                output_bits
+            };
+            json(&quality_score(&hasher, bits))
+        }
+        None => std::ptr::null_mut(),
+    }
+}
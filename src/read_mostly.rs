@@ -0,0 +1,225 @@
+//! Left-right chunk map for many readers and a single writer: [`ChunkMapWriter`] and
+//! [`ChunkMapReader`].
+//!
+//! Two copies of the map are kept. Readers look at one copy without taking any lock, while the
+//! writer mutates the other one and logs its operations. [`ChunkMapWriter::publish`] swaps the
+//! copies, waits for the readers still looking at the old copy to leave it, then replays the log
+//! on it. Reads never block; publishing waits for in-flight reads only.
+
+use std::cell::{Cell, UnsafeCell};
+use std::marker::PhantomData;
+use std::sync::atomic::{AtomicUsize, Ordering::SeqCst};
+use std::sync::{Arc, Mutex};
+
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+struct Shared<V> {
+    maps: [UnsafeCell<VoxelChunkMap<V>>; 2],
+    /// Copy the readers look at.
+    active: AtomicUsize,
+    /// One counter per reader, odd while it is reading.
+    epochs: Mutex<Vec<Arc<AtomicUsize>>>,
+}
+
+// SAFETY: readers only get shared references to the active copy, and the writer only mutates the
+// other copy once every reader that could still see it has left.
+unsafe impl<V: Send + Sync> Sync for Shared<V> {}
+unsafe impl<V: Send + Sync> Send for Shared<V> {}
+
+enum Op<V> {
+    Insert(VoxelChunkIndex, V),
+    Remove(VoxelChunkIndex),
+    Clear,
+}
+
+/// The one writer of a left-right chunk map. Writes are visible to [`ChunkMapWriter::pending`]
+/// right away, and to readers once published.
+pub struct ChunkMapWriter<V> {
+    shared: Arc<Shared<V>>,
+    log: Vec<Op<V>>,
+}
+
+/// Lock-free read access to a left-right chunk map. Each thread needs its own reader: clone one
+/// to get another.
+pub struct ChunkMapReader<V> {
+    shared: Arc<Shared<V>>,
+    epoch: Arc<AtomicUsize>,
+    depth: Cell<usize>,
+    _not_sync: PhantomData<Cell<()>>,
+}
+
+impl<V: Clone> Default for ChunkMapWriter<V> {
+    fn default() -> Self {
+        ChunkMapWriter {
+            shared: Arc::new(Shared {
+                maps: [
+                    UnsafeCell::new(VoxelChunkMap::new()),
+                    UnsafeCell::new(VoxelChunkMap::new()),
+                ],
+                active: AtomicUsize::new(0),
+                epochs: Mutex::new(Vec::new()),
+            }),
+            log: Vec::new(),
+        }
+    }
+}
+
+impl<V: Clone> ChunkMapWriter<V> {
+    pub fn new() -> ChunkMapWriter<V> {
+        Self::default()
+    }
+
+    pub fn reader(&self) -> ChunkMapReader<V> {
+        ChunkMapReader::register(self.shared.clone())
+    }
+
+    fn write_copy(&mut self) -> &mut VoxelChunkMap<V> {
+        let pending = 1 - self.shared.active.load(SeqCst);
+        // SAFETY: readers never look at the copy that isn't active, and only the writer, which
+        // we borrow mutably, ever changes which copy is active.
+        unsafe { &mut *self.shared.maps[pending].get() }
+    }
+
+    /// The map as readers will see it after the next publish.
+    pub fn pending(&self) -> &VoxelChunkMap<V> {
+        let pending = 1 - self.shared.active.load(SeqCst);
+        // SAFETY: see `write_copy`.
+        unsafe { &*self.shared.maps[pending].get() }
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.log.push(Op::Insert(idx, value.clone()));
+        self.write_copy().insert(idx, value)
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        self.log.push(Op::Remove(idx));
+        self.write_copy().remove(idx)
+    }
+
+    pub fn clear(&mut self) {
+        self.log.clear();
+        self.log.push(Op::Clear);
+        self.write_copy().clear();
+    }
+
+    /// Makes every write so far visible to readers. Waits for the reads started before the swap
+    /// to finish, then brings the other copy up to date.
+    pub fn publish(&mut self) {
+        if self.log.is_empty() {
+            return;
+        }
+
+        let old = self.shared.active.load(SeqCst);
+        self.shared.active.store(1 - old, SeqCst);
+
+        let epochs = self.shared.epochs.lock().expect("reader list poisoned");
+        for epoch in epochs.iter() {
+            let seen = epoch.load(SeqCst);
+            if seen % 2 == 1 {
+                while epoch.load(SeqCst) == seen {
+                    std::hint::spin_loop();
+                }
+            }
+        }
+        drop(epochs);
+
+        // The write copy now is the previously active one, which no reader looks at anymore.
+        let log = std::mem::take(&mut self.log);
+        let map = self.write_copy();
+        for op in log {
+            match op {
+                Op::Insert(idx, value) => {
+                    map.insert(idx, value);
+                }
+                Op::Remove(idx) => {
+                    map.remove(idx);
+                }
+                Op::Clear => map.clear(),
+            }
+        }
+    }
+}
+
+impl<V> ChunkMapReader<V> {
+    fn register(shared: Arc<Shared<V>>) -> ChunkMapReader<V> {
+        let epoch = Arc::new(AtomicUsize::new(0));
+        shared
+            .epochs
+            .lock()
+            .expect("reader list poisoned")
+            .push(epoch.clone());
+        ChunkMapReader {
+            shared,
+            epoch,
+            depth: Cell::new(0),
+            _not_sync: PhantomData,
+        }
+    }
+
+    /// Runs `f` on the published map. The writer's next publish waits for `f` to return, so
+    /// keep it short, and never publish from inside it.
+    pub fn read<R>(&self, f: impl FnOnce(&VoxelChunkMap<V>) -> R) -> R {
+        let _guard = ReadGuard::enter(self);
+        let active = self.shared.active.load(SeqCst);
+        // SAFETY: our epoch is odd, so the writer won't touch this copy until we leave it.
+        f(unsafe { &*self.shared.maps[active].get() })
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.read(|map| map.get(idx).cloned())
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.read(|map| map.contains_key(idx))
+    }
+
+    pub fn len(&self) -> usize {
+        self.read(|map| map.len())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.read(|map| map.is_empty())
+    }
+}
+
+/// Keeps a reader's epoch odd while it reads, even if the read panics.
+struct ReadGuard<'a, V>(&'a ChunkMapReader<V>);
+
+impl<'a, V> ReadGuard<'a, V> {
+    fn enter(reader: &'a ChunkMapReader<V>) -> ReadGuard<'a, V> {
+        let depth = reader.depth.get();
+        if depth == 0 {
+            reader.epoch.fetch_add(1, SeqCst);
+        }
+        reader.depth.set(depth + 1);
+        ReadGuard(reader)
+    }
+}
+
+impl<V> Drop for ReadGuard<'_, V> {
+    fn drop(&mut self) {
+        let depth = self.0.depth.get() - 1;
+        self.0.depth.set(depth);
+        if depth == 0 {
+            self.0.epoch.fetch_add(1, SeqCst);
+        }
+    }
+}
+
+impl<V> Clone for ChunkMapReader<V> {
+    fn clone(&self) -> Self {
+        ChunkMapReader::register(self.shared.clone())
+    }
+}
+
+impl<V> Drop for ChunkMapReader<V> {
+    fn drop(&mut self) {
+        if let Ok(mut epochs) = self.shared.epochs.lock() {
+            epochs.retain(|epoch| !Arc::ptr_eq(epoch, &self.epoch));
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::{VoxelChunkIndex, X_BIAS, Y_BIAS, Z_BIAS};
+
+/// Axis-aligned box of chunk coordinates, `min` inclusive and `max` exclusive on every axis.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct ChunkAabb {
+    pub min: (i32, i32, i32),
+    pub max: (i32, i32, i32),
+}
+
+impl ChunkAabb {
+    pub fn new(min: (i32, i32, i32), max: (i32, i32, i32)) -> ChunkAabb {
+        assert!(
+            min.0 <= max.0 && min.1 <= max.1 && min.2 <= max.2,
+            "ChunkAabb min must not exceed max"
+        );
+        ChunkAabb { min, max }
+    }
+
+    pub fn size(&self) -> (usize, usize, usize) {
+        (
+            self.max.0.abs_diff(self.min.0) as usize,
+            self.max.1.abs_diff(self.min.1) as usize,
+            self.max.2.abs_diff(self.min.2) as usize,
+        )
+    }
+
+    /// The part of the box inside the representable world, whose chunks all have a key of
+    /// their own: [`VoxelChunkIndex::from_coords`] wraps coordinates past the edge of the world
+    /// around onto unrelated chunks.
+    pub(crate) fn clipped_to_world(&self) -> ChunkAabb {
+        let clip = |v: i32, bias: i32| v.clamp(-bias, bias);
+        ChunkAabb {
+            min: (
+                clip(self.min.0, X_BIAS),
+                clip(self.min.1, Y_BIAS),
+                clip(self.min.2, Z_BIAS),
+            ),
+            max: (
+                clip(self.max.0, X_BIAS),
+                clip(self.max.1, Y_BIAS),
+                clip(self.max.2, Z_BIAS),
+            ),
+        }
+    }
+
+    /// The box from `min` to `max`, or `None` if `min` exceeds `max` on some axis.
+    pub fn try_new(min: (i32, i32, i32), max: (i32, i32, i32)) -> Option<ChunkAabb> {
+        (min.0 <= max.0 && min.1 <= max.1 && min.2 <= max.2).then_some(ChunkAabb { min, max })
+    }
+
+    /// [`ChunkAabb::volume`], or `None` if it overflows `usize`.
+    pub fn checked_volume(&self) -> Option<usize> {
+        let (sx, sy, sz) = self.size();
+        sx.checked_mul(sy)?.checked_mul(sz)
+    }
+
+    /// Number of chunks in the box.
+    pub fn volume(&self) -> usize {
+        let (sx, sy, sz) = self.size();
+        sx * sy * sz
+    }
+
+    pub fn contains(&self, x: i32, y: i32, z: i32) -> bool {
+        (self.min.0..self.max.0).contains(&x)
+            && (self.min.1..self.max.1).contains(&y)
+            && (self.min.2..self.max.2).contains(&z)
+    }
+
+    pub fn contains_index(&self, idx: VoxelChunkIndex) -> bool {
+        let (x, y, z) = idx.to_coords();
+        self.contains(x, y, z)
+    }
+
+    /// Linear offset of `idx` inside the box, in the same order as [`ChunkAabb::iter`].
+    pub fn offset_of(&self, idx: VoxelChunkIndex) -> Option<usize> {
+        let (x, y, z) = idx.to_coords();
+        if !self.contains(x, y, z) {
+            return None;
+        }
+
+        let (_, sy, sz) = self.size();
+        let dx = (x - self.min.0) as usize;
+        let dy = (y - self.min.1) as usize;
+        let dz = (z - self.min.2) as usize;
+        Some((dx * sy + dy) * sz + dz)
+    }
+
+    /// Inverse of [`ChunkAabb::offset_of`].
+    pub fn index_at(&self, offset: usize) -> VoxelChunkIndex {
+        let (_, sy, sz) = self.size();
+        let dz = offset % sz;
+        let dy = (offset / sz) % sy;
+        let dx = offset / (sz * sy);
+        VoxelChunkIndex::from_coords(
+            self.min.0 + dx as i32,
+            self.min.1 + dy as i32,
+            self.min.2 + dz as i32,
+        )
+    }
+
+    /// Iterates every chunk of the box, with X as the outermost loop and Z as the innermost one.
+    pub fn iter(&self) -> impl Iterator<Item = VoxelChunkIndex> {
+        let aabb = *self;
+        (0..aabb.volume()).map(move |offset| aabb.index_at(offset))
+    }
+}
+
+/// Number of chunks in the box from `min` inclusive to `max` exclusive, clipped to the
+/// representable world, writing their keys to `out`, in [`ChunkAabb::iter`] order, if they fit
+/// in `capacity`: what `hf_chunk_region` and `hf_py_region` share. As they must not panic, an
+/// inverted box holds no chunks, and a count past `usize::MAX` is reported as `usize::MAX`.
+///
+/// # Safety
+///
+/// `min` and `max` must point to 3 coordinates each, and `out` to `capacity` writable keys.
+#[cfg(any(feature = "capi", feature = "python"))]
+pub(crate) unsafe fn write_region_keys(
+    min: *const i32,
+    max: *const i32,
+    out: *mut u32,
+    capacity: usize,
+) -> usize {
+    // SAFETY: the caller passes 3 coordinates for each corner.
+    let (min, max) = unsafe { (&*min.cast::<[i32; 3]>(), &*max.cast::<[i32; 3]>()) };
+    let Some(region) = ChunkAabb::try_new((min[0], min[1], min[2]), (max[0], max[1], max[2]))
+    else {
+        return 0;
+    };
+    let region = region.clipped_to_world();
+    let Some(volume) = region.checked_volume() else {
+        return usize::MAX;
+    };
+    if volume <= capacity && volume > 0 {
+        // SAFETY: the caller passes room for `capacity` keys.
+        let out = unsafe { std::slice::from_raw_parts_mut(out, volume) };
+        for (slot, idx) in out.iter_mut().zip(region.iter()) {
+            *slot = idx.0;
+        }
+    }
+    volume
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clipped_to_world_drops_wrapping_chunks() {
+        let region = ChunkAabb::new((4090, -5000, -40), (5000, 3, 40)).clipped_to_world();
+        assert_eq!(region, ChunkAabb::new((4090, -4096, -32), (4096, 3, 32)));
+        let everything = ChunkAabb::new(
+            (i32::MIN, i32::MIN, i32::MIN),
+            (i32::MAX, i32::MAX, i32::MAX),
+        );
+        assert_eq!(everything.clipped_to_world().volume(), 1 << 32);
+    }
+
+    #[test]
+    fn try_new_rejects_inverted_boxes() {
+        assert_eq!(ChunkAabb::try_new((1, 0, 0), (0, 1, 1)), None);
+        assert!(ChunkAabb::try_new((0, 0, 0), (0, 0, 0)).is_some());
+    }
+
+    #[cfg(any(feature = "capi", feature = "python"))]
+    #[test]
+    fn write_region_keys_never_panics() {
+        let mut out = [0; 4];
+        // SAFETY: corners of 3 coordinates, and room for 4 keys.
+        let count = |min: [i32; 3], max: [i32; 3], out: &mut [u32; 4]| unsafe {
+            write_region_keys(min.as_ptr(), max.as_ptr(), out.as_mut_ptr(), out.len())
+        };
+        assert_eq!(count([1, 0, 0], [0, 1, 1], &mut out), 0);
+        assert_eq!(count([4095, 0, 0], [4097, 1, 1], &mut out), 1);
+        assert_eq!(out[0], VoxelChunkIndex::from_coords(4095, 0, 0).0);
+        assert_eq!(count([i32::MIN; 3], [i32::MAX; 3], &mut out), 1 << 32);
+    }
+}
@@ -0,0 +1,616 @@
+//! Export of [`analysis`](crate::analysis) and benchmark results, to track them over time and
+//! plot them with external tools. Every analysis result serializes to JSON with [`write_json`];
+//! the tabular ones also have CSV writers. [`write_markdown`] and [`write_html`] gather the
+//! timings and analyses of each hasher into a single readable document, and a [`Baseline`]
+//! saved from one run flags the [`regressions`] of later ones.
+
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::analysis::{
+    AvalancheReport, CollisionReport, QualityScore, UniformityTest, AVALANCHE_THRESHOLD,
+};
+use crate::map::SlotInfo;
+
+/// Timing of one Criterion benchmark, in nanoseconds per iteration.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BenchmarkResult {
+    /// `group/function`, as Criterion prints it.
+    pub id: String,
+    pub group: String,
+    pub function: String,
+    pub mean_ns: f64,
+    /// 95% confidence interval of the mean.
+    pub mean_lower_ns: f64,
+    pub mean_upper_ns: f64,
+    pub median_ns: f64,
+    pub std_dev_ns: f64,
+}
+
+#[derive(Deserialize)]
+struct CriterionBenchmark {
+    group_id: String,
+    function_id: Option<String>,
+    full_id: String,
+}
+
+#[derive(Deserialize)]
+struct CriterionEstimates {
+    mean: CriterionEstimate,
+    median: CriterionEstimate,
+    std_dev: CriterionEstimate,
+}
+
+#[derive(Deserialize)]
+struct CriterionEstimate {
+    point_estimate: f64,
+    confidence_interval: CriterionInterval,
+}
+
+#[derive(Deserialize)]
+struct CriterionInterval {
+    lower_bound: f64,
+    upper_bound: f64,
+}
+
+/// Latest results of every benchmark Criterion saved under `dir`, usually
+/// `target/criterion`, sorted by id.
+pub fn read_criterion<P: AsRef<Path>>(dir: P) -> io::Result<Vec<BenchmarkResult>> {
+    let mut results = Vec::new();
+    visit_criterion(dir.as_ref(), &mut results)?;
+    results.sort_by(|a, b| a.id.cmp(&b.id));
+    Ok(results)
+}
+
+fn visit_criterion(dir: &Path, results: &mut Vec<BenchmarkResult>) -> io::Result<()> {
+    let latest = dir.join("new");
+    if latest.join("benchmark.json").is_file() {
+        let benchmark: CriterionBenchmark =
+            serde_json::from_slice(&fs::read(latest.join("benchmark.json"))?)?;
+        let estimates: CriterionEstimates =
+            serde_json::from_slice(&fs::read(latest.join("estimates.json"))?)?;
+        results.push(BenchmarkResult {
+            id: benchmark.full_id,
+            group: benchmark.group_id,
+            function: benchmark.function_id.unwrap_or_default(),
+            mean_ns: estimates.mean.point_estimate,
+            mean_lower_ns: estimates.mean.confidence_interval.lower_bound,
+            mean_upper_ns: estimates.mean.confidence_interval.upper_bound,
+            median_ns: estimates.median.point_estimate,
+            std_dev_ns: estimates.std_dev.point_estimate,
+        });
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            visit_criterion(&entry.path(), results)?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes `value`, e.g. any analysis result or a list of them, as pretty-printed JSON.
+pub fn write_json<T: Serialize + ?Sized, W: Write>(value: &T, out: W) -> io::Result<()> {
+    serde_json::to_writer_pretty(out, value)?;
+    Ok(())
+}
+
+/// Writes the bucket occupancy histogram of `report`, one row per bucket load.
+pub fn write_occupancy_csv<W: Write>(report: &CollisionReport, mut out: W) -> io::Result<()> {
+    writeln!(out, "keys_in_bucket,buckets")?;
+    for (keys, buckets) in report.occupancy.iter().enumerate() {
+        writeln!(out, "{keys},{buckets}")?;
+    }
+    Ok(())
+}
+
+/// Writes the load factor curve of `report`.
+pub fn write_load_curve_csv<W: Write>(report: &CollisionReport, mut out: W) -> io::Result<()> {
+    writeln!(out, "load_factor,mean_probe,max_probe")?;
+    for point in &report.load_curve {
+        writeln!(
+            out,
+            "{},{},{}",
+            point.load_factor, point.mean_probe, point.max_probe
+        )?;
+    }
+    Ok(())
+}
+
+/// One line of [`write_slots_jsonl`].
+#[derive(Serialize)]
+struct SlotLine {
+    x: i32,
+    y: i32,
+    z: i32,
+    key: u32,
+    hash: u64,
+    home: usize,
+    bucket: usize,
+    probe_length: usize,
+}
+
+/// Writes one JSON object per line for each slot, from
+/// [`VoxelChunkMap::slots`](crate::VoxelChunkMap::slots),
+/// [`LinearChunkMap::slots`](crate::LinearChunkMap::slots) or
+/// [`simulated_slots`](crate::analysis::simulated_slots): the decoded coordinates, the packed
+/// key, its hash, its home bucket, the bucket it sits in and its probe length, for notebooks
+/// and dashboards to load with any JSON Lines reader.
+pub fn write_slots_jsonl<W: Write>(
+    slots: impl IntoIterator<Item = SlotInfo>,
+    mut out: W,
+) -> io::Result<()> {
+    for slot in slots {
+        let (x, y, z) = slot.idx.to_coords();
+        let line = SlotLine {
+            x,
+            y,
+            z,
+            key: slot.idx.0,
+            hash: slot.hash,
+            home: slot.home,
+            bucket: slot.bucket,
+            probe_length: slot.probe_length,
+        };
+        serde_json::to_writer(&mut out, &line)?;
+        out.write_all(b"\n")?;
+    }
+    out.flush()
+}
+
+/// Writes an avalanche or bit independence matrix, one row per row of the matrix.
+pub fn write_matrix_csv<W: Write>(matrix: &[[f64; 64]], mut out: W) -> io::Result<()> {
+    write!(out, "row")?;
+    for column in 0..64 {
+        write!(out, ",{column}")?;
+    }
+    writeln!(out)?;
+    for (row, values) in matrix.iter().enumerate() {
+        write!(out, "{row}")?;
+        for value in values {
+            write!(out, ",{value}")?;
+        }
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes benchmark results, one row each.
+pub fn write_benchmarks_csv<W: Write>(results: &[BenchmarkResult], mut out: W) -> io::Result<()> {
+    writeln!(
+        out,
+        "id,group,function,mean_ns,mean_lower_ns,mean_upper_ns,median_ns,std_dev_ns"
+    )?;
+    for result in results {
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            csv_field(&result.id),
+            csv_field(&result.group),
+            csv_field(&result.function),
+            result.mean_ns,
+            result.mean_lower_ns,
+            result.mean_upper_ns,
+            result.median_ns,
+            result.std_dev_ns
+        )?;
+    }
+    Ok(())
+}
+
+/// `field` quoted if it holds a separator, a quote or a line break.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Everything known about one hasher, for [`write_markdown`] and [`write_html`]. Analyses left
+/// out are skipped in the document.
+#[derive(Debug, Clone, Default)]
+pub struct HasherReport {
+    pub name: String,
+    /// Benchmarks of this hasher, usually the results of [`read_criterion`] whose function is
+    /// its name, see [`HasherReport::with_benchmarks`].
+    pub benchmarks: Vec<BenchmarkResult>,
+    pub collisions: Option<CollisionReport>,
+    pub chi_squared: Option<UniformityTest>,
+    pub ks: Option<UniformityTest>,
+    pub avalanche: Option<AvalancheReport>,
+    /// Output bits the avalanche results are judged on.
+    pub output_bits: u32,
+    pub quality: Option<QualityScore>,
+}
+
+impl HasherReport {
+    pub fn new(name: impl Into<String>) -> HasherReport {
+        HasherReport {
+            name: name.into(),
+            output_bits: 64,
+            ..Default::default()
+        }
+    }
+
+    /// Keeps the `results` benchmarking this hasher, which the benchmarks name after it.
+    pub fn with_benchmarks(mut self, results: &[BenchmarkResult]) -> HasherReport {
+        self.benchmarks = results
+            .iter()
+            .filter(|result| result.function == self.name)
+            .cloned()
+            .collect();
+        self
+    }
+}
+
+/// A table of a report section, rendered by both the Markdown and the HTML writers.
+struct Table {
+    title: &'static str,
+    header: &'static [&'static str],
+    rows: Vec<Vec<String>>,
+}
+
+fn tables(report: &HasherReport) -> Vec<Table> {
+    let mut tables = Vec::new();
+    if let Some(collisions) = &report.collisions {
+        tables.push(Table {
+            title: "Collisions",
+            header: &[
+                "keys",
+                "buckets",
+                "collisions",
+                "empty buckets",
+                "mean probe",
+                "max probe",
+            ],
+            rows: vec![vec![
+                collisions.keys.to_string(),
+                collisions.table_size.to_string(),
+                collisions.collisions().to_string(),
+                collisions.empty_buckets().to_string(),
+                format!("{:.3}", collisions.mean_probe),
+                collisions.max_probe.to_string(),
+            ]],
+        });
+    }
+    let tests: Vec<Vec<String>> = [
+        ("chi-squared", report.chi_squared, 1),
+        ("Kolmogorov-Smirnov", report.ks, 5),
+    ]
+    .into_iter()
+    .filter_map(|(name, test, decimals)| {
+        test.map(|test| {
+            vec![
+                name.to_owned(),
+                format!("{:.decimals$}", test.statistic),
+                format!("{:.4}", test.p_value),
+            ]
+        })
+    })
+    .collect();
+    if !tests.is_empty() {
+        tables.push(Table {
+            title: "Uniformity",
+            header: &["test", "statistic", "p-value"],
+            rows: tests,
+        });
+    }
+    if let Some(avalanche) = &report.avalanche {
+        let bits = report.output_bits;
+        tables.push(Table {
+            title: "Avalanche",
+            header: &["samples", "output bits", "max bias", "verdict"],
+            rows: vec![vec![
+                avalanche.samples.to_string(),
+                bits.to_string(),
+                format!("{:.4}", avalanche.max_bias(bits)),
+                if avalanche.passes(bits, AVALANCHE_THRESHOLD) {
+                    "passes"
+                } else {
+                    "fails"
+                }
+                .to_owned(),
+            ]],
+        });
+    }
+    if let Some(quality) = &report.quality {
+        tables.push(Table {
+            title: "Quality score",
+            header: &[
+                "avalanche",
+                "independence",
+                "sparse",
+                "sequential",
+                "voxel",
+                "total",
+            ],
+            rows: vec![[
+                quality.avalanche,
+                quality.independence,
+                quality.sparse,
+                quality.sequential,
+                quality.voxel,
+                quality.total,
+            ]
+            .map(|score| format!("{score:.3}"))
+            .to_vec()],
+        });
+    }
+    if !report.benchmarks.is_empty() {
+        tables.push(Table {
+            title: "Benchmarks",
+            header: &["benchmark", "mean", "95% interval", "median", "std dev"],
+            rows: report
+                .benchmarks
+                .iter()
+                .map(|result| {
+                    vec![
+                        result.id.clone(),
+                        format_ns(result.mean_ns),
+                        format!(
+                            "{} – {}",
+                            format_ns(result.mean_lower_ns),
+                            format_ns(result.mean_upper_ns)
+                        ),
+                        format_ns(result.median_ns),
+                        format_ns(result.std_dev_ns),
+                    ]
+                })
+                .collect(),
+        });
+    }
+    tables
+}
+
+/// `ns` with the unit Criterion would print it in.
+fn format_ns(ns: f64) -> String {
+    match ns {
+        ns if ns < 1e3 => format!("{ns:.2} ns"),
+        ns if ns < 1e6 => format!("{:.2} µs", ns / 1e3),
+        ns if ns < 1e9 => format!("{:.2} ms", ns / 1e6),
+        ns => format!("{:.2} s", ns / 1e9),
+    }
+}
+
+/// Writes one section per hasher of `reports`, with a table per analysis, as Markdown.
+pub fn write_markdown<W: Write>(
+    title: &str,
+    reports: &[HasherReport],
+    mut out: W,
+) -> io::Result<()> {
+    writeln!(out, "# {title}")?;
+    for report in reports {
+        writeln!(out, "\n## {}", report.name)?;
+        for table in tables(report) {
+            writeln!(out, "\n### {}\n", table.title)?;
+            writeln!(out, "| {} |", table.header.join(" | "))?;
+            writeln!(out, "|{}", " --- |".repeat(table.header.len()))?;
+            for row in &table.rows {
+                let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+                writeln!(out, "| {} |", cells.join(" | "))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Same as [`write_markdown`], as a standalone HTML page.
+pub fn write_html<W: Write>(title: &str, reports: &[HasherReport], mut out: W) -> io::Result<()> {
+    let title = html_escape(title);
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">"
+    )?;
+    writeln!(out, "<title>{title}</title>")?;
+    writeln!(
+        out,
+        "<style>body {{ font-family: sans-serif; }} table {{ border-collapse: collapse; }} \
+         th, td {{ border: 1px solid #999; padding: 2px 8px; text-align: right; }}</style>"
+    )?;
+    writeln!(out, "</head>\n<body>\n<h1>{title}</h1>")?;
+    for report in reports {
+        writeln!(out, "<h2>{}</h2>", html_escape(&report.name))?;
+        for table in tables(report) {
+            write!(out, "<h3>{}</h3>\n<table>\n<tr>", table.title)?;
+            for cell in table.header {
+                write!(out, "<th>{cell}</th>")?;
+            }
+            writeln!(out, "</tr>")?;
+            for row in &table.rows {
+                write!(out, "<tr>")?;
+                for cell in row {
+                    write!(out, "<td>{}</td>", html_escape(cell))?;
+                }
+                writeln!(out, "</tr>")?;
+            }
+            writeln!(out, "</table>")?;
+        }
+    }
+    writeln!(out, "</body>\n</html>")
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Probe lengths of one hasher on some workload, as kept in a [`Baseline`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProbeSummary {
+    pub hasher: String,
+    pub keys: usize,
+    pub table_size: usize,
+    pub mean_probe: f64,
+    pub max_probe: usize,
+}
+
+impl ProbeSummary {
+    pub fn new(hasher: impl Into<String>, report: &CollisionReport) -> ProbeSummary {
+        ProbeSummary {
+            hasher: hasher.into(),
+            keys: report.keys,
+            table_size: report.table_size,
+            mean_probe: report.mean_probe,
+            max_probe: report.max_probe,
+        }
+    }
+}
+
+/// Benchmark timings and probe lengths of a run, to compare later runs against.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Baseline {
+    pub benchmarks: Vec<BenchmarkResult>,
+    pub probes: Vec<ProbeSummary>,
+}
+
+impl Baseline {
+    /// Writes the baseline as JSON.
+    pub fn write_to<W: Write>(&self, out: W) -> io::Result<()> {
+        write_json(self, out)
+    }
+
+    /// Reads a baseline written by [`Baseline::write_to`].
+    pub fn read_from<R: Read>(reader: R) -> io::Result<Baseline> {
+        Ok(serde_json::from_reader(reader)?)
+    }
+}
+
+/// How much worse than its baseline a run may get before [`regressions`] flags it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Tolerance {
+    /// Relative increase of a benchmark's mean time.
+    pub time: f64,
+    /// Relative increase of a mean probe length.
+    pub mean_probe: f64,
+    /// Increase of a longest probe, in slots.
+    pub max_probe: usize,
+}
+
+impl Default for Tolerance {
+    /// Timings are noisy, probe lengths are not: 10% on times, 1% and 2 slots on probes.
+    fn default() -> Self {
+        Tolerance {
+            time: 0.10,
+            mean_probe: 0.01,
+            max_probe: 2,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Metric {
+    MeanTime,
+    MeanProbe,
+    MaxProbe,
+}
+
+impl Metric {
+    pub fn name(self) -> &'static str {
+        match self {
+            Metric::MeanTime => "mean time (ns)",
+            Metric::MeanProbe => "mean probe",
+            Metric::MaxProbe => "max probe",
+        }
+    }
+}
+
+/// A metric of a benchmark or a hasher that got worse than its baseline allows.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Regression {
+    /// Benchmark id or hasher name.
+    pub id: String,
+    pub metric: Metric,
+    pub baseline: f64,
+    pub current: f64,
+}
+
+impl Regression {
+    /// `current` relative to `baseline`, 0.1 meaning 10% worse.
+    pub fn change(&self) -> f64 {
+        self.current / self.baseline - 1.0
+    }
+}
+
+/// Metrics of `current` worse than in `baseline` by more than `tolerance`. Benchmarks and
+/// hashers missing from either side are skipped, as are the probes of a hasher measured on a
+/// different number of keys or buckets.
+pub fn regressions(
+    baseline: &Baseline,
+    current: &Baseline,
+    tolerance: Tolerance,
+) -> Vec<Regression> {
+    let mut regressions = Vec::new();
+    for now in &current.benchmarks {
+        let Some(before) = baseline.benchmarks.iter().find(|b| b.id == now.id) else {
+            continue;
+        };
+        if now.mean_ns > before.mean_ns * (1.0 + tolerance.time) {
+            regressions.push(Regression {
+                id: now.id.clone(),
+                metric: Metric::MeanTime,
+                baseline: before.mean_ns,
+                current: now.mean_ns,
+            });
+        }
+    }
+    for now in &current.probes {
+        let Some(before) = baseline.probes.iter().find(|p| {
+            p.hasher == now.hasher && p.keys == now.keys && p.table_size == now.table_size
+        }) else {
+            continue;
+        };
+        if now.mean_probe > before.mean_probe * (1.0 + tolerance.mean_probe) {
+            regressions.push(Regression {
+                id: now.hasher.clone(),
+                metric: Metric::MeanProbe,
+                baseline: before.mean_probe,
+                current: now.mean_probe,
+            });
+        }
+        if now.max_probe > before.max_probe + tolerance.max_probe {
+            regressions.push(Regression {
+                id: now.hasher.clone(),
+                metric: Metric::MaxProbe,
+                baseline: before.max_probe as f64,
+                current: now.max_probe as f64,
+            });
+        }
+    }
+    regressions
+}
+
+/// Serde adapter for the 64-column matrices of the analyses, which serde only supports for
+/// arrays of up to 32 elements.
+pub(crate) mod matrix {
+    use serde::de::Error;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        matrix: &[[f64; 64]],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let rows: Vec<&[f64]> = matrix.iter().map(|row| &row[..]).collect();
+        rows.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<[f64; 64]>, D::Error> {
+        Vec::<Vec<f64>>::deserialize(deserializer)?
+            .into_iter()
+            .map(|row| {
+                let len = row.len();
+                row.try_into()
+                    .map_err(|_| D::Error::invalid_length(len, &"a row of 64 values"))
+            })
+            .collect()
+    }
+}
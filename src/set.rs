@@ -0,0 +1,403 @@
+use std::collections::HashMap;
+
+use crate::{ChunkAabb, VoxelChunkIndex};
+
+const PAGE_BITS: u32 = 9;
+const PAGE_WORDS: usize = (1 << PAGE_BITS) / 64;
+
+/// 512 consecutive packed indices, which is exactly one cache line of bits.
+#[derive(Clone, Copy)]
+struct Page {
+    base: u32,
+    words: [u64; PAGE_WORDS],
+}
+
+/// Set of chunk indices stored as paged bitsets: one bit per chunk, and only the pages holding at
+/// least one chunk are allocated.
+#[derive(Clone, Default)]
+pub struct VoxelChunkSet {
+    pages: Vec<Page>,
+    directory: HashMap<u32, usize>,
+    len: usize,
+}
+
+impl Page {
+    /// Chunks of the page in packed order.
+    fn chunks(self) -> impl Iterator<Item = VoxelChunkIndex> {
+        let base = self.base << PAGE_BITS;
+        self.words
+            .into_iter()
+            .enumerate()
+            .flat_map(move |(w, bits)| {
+                let base = base + (w as u32) * 64;
+                BitIter(bits).map(move |bit| VoxelChunkIndex(base + bit))
+            })
+    }
+}
+
+fn split(idx: VoxelChunkIndex) -> (u32, usize, u64) {
+    let page = idx.0 >> PAGE_BITS;
+    let bit = (idx.0 & ((1 << PAGE_BITS) - 1)) as usize;
+    (page, bit / 64, 1 << (bit % 64))
+}
+
+impl VoxelChunkSet {
+    pub fn new() -> VoxelChunkSet {
+        Self::default()
+    }
+
+    /// Number of chunks in the set, kept up to date on every mutation.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Number of allocated pages.
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// Number of chunks covered by the page storage allocated so far.
+    pub fn capacity(&self) -> usize {
+        self.pages.capacity() << PAGE_BITS
+    }
+
+    /// Memory the pages and their directory take, the latter estimated.
+    #[cfg(feature = "prometheus")]
+    pub(crate) fn heap_bytes(&self) -> usize {
+        self.pages.capacity() * std::mem::size_of::<Page>()
+            + self.directory.capacity() * (std::mem::size_of::<(u32, usize)>() + 1)
+    }
+
+    /// Fraction of the bits of the allocated pages that are set.
+    pub fn load_factor(&self) -> f64 {
+        if self.pages.is_empty() {
+            0.0
+        } else {
+            self.len as f64 / (self.pages.len() << PAGE_BITS) as f64
+        }
+    }
+
+    /// Reserves storage for exactly `additional_pages` more pages.
+    pub fn reserve_exact(&mut self, additional_pages: usize) {
+        self.pages.reserve_exact(additional_pages);
+        self.directory.reserve(additional_pages);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.pages.shrink_to_fit();
+        self.directory.shrink_to_fit();
+    }
+
+    pub fn contains(&self, idx: VoxelChunkIndex) -> bool {
+        let (page, word, mask) = split(idx);
+        match self.directory.get(&page) {
+            Some(&slot) => self.pages[slot].words[word] & mask != 0,
+            None => false,
+        }
+    }
+
+    /// Returns whether the chunk was newly added.
+    pub fn insert(&mut self, idx: VoxelChunkIndex) -> bool {
+        let (page, word, mask) = split(idx);
+        let pages = &mut self.pages;
+        let slot = *self.directory.entry(page).or_insert_with(|| {
+            pages.push(Page {
+                base: page,
+                words: [0; PAGE_WORDS],
+            });
+            pages.len() - 1
+        });
+
+        let word = &mut self.pages[slot].words[word];
+        let added = *word & mask == 0;
+        *word |= mask;
+        if added {
+            self.len += 1;
+        }
+        added
+    }
+
+    /// Returns whether the chunk was present. Pages left empty are released.
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        let (page, word, mask) = split(idx);
+        let Some(&slot) = self.directory.get(&page) else {
+            return false;
+        };
+
+        let words = &mut self.pages[slot].words;
+        if words[word] & mask == 0 {
+            return false;
+        }
+        words[word] &= !mask;
+        self.len -= 1;
+
+        if words.iter().all(|&w| w == 0) {
+            self.directory.remove(&page);
+            self.pages.swap_remove(slot);
+            if let Some(moved) = self.pages.get(slot) {
+                self.directory.insert(moved.base, slot);
+            }
+        }
+        true
+    }
+
+    /// Removes and returns every chunk lying in `region`. Small regions are drained by testing
+    /// each of their chunks, big ones by walking the set. Parts of `region` past the edge of the
+    /// representable world hold no chunks.
+    pub fn drain_region(&mut self, region: ChunkAabb) -> impl Iterator<Item = VoxelChunkIndex> {
+        let region = region.clipped_to_world();
+        #[cfg(feature = "instrument")]
+        let started = crate::instrument::enabled().then(std::time::Instant::now);
+        let scanned = region.volume() > self.len;
+        let drained: Vec<VoxelChunkIndex> = if !scanned {
+            region.iter().filter(|&idx| self.contains(idx)).collect()
+        } else {
+            self.iter()
+                .filter(|&idx| region.contains_index(idx))
+                .collect()
+        };
+        for &idx in &drained {
+            self.remove(idx);
+        }
+        #[cfg(feature = "instrument")]
+        if let Some(started) = started {
+            let fields = crate::instrument::MapFields {
+                container: "VoxelChunkSet",
+                label: None,
+                len: self.len,
+                capacity: self.capacity(),
+            };
+            crate::instrument::emit(
+                || fields,
+                || crate::instrument::ContainerEvent::DrainRegion {
+                    region,
+                    drained: drained.len(),
+                    scanned,
+                    elapsed: started.elapsed(),
+                },
+            );
+        }
+        drained.into_iter()
+    }
+
+    /// Removes every chunk lying outside `region`.
+    pub fn retain_region(&mut self, region: ChunkAabb) {
+        let outside: Vec<VoxelChunkIndex> = self
+            .iter()
+            .filter(|&idx| !region.contains_index(idx))
+            .collect();
+        for idx in outside {
+            self.remove(idx);
+        }
+    }
+
+    /// Moves the chunks lying in `region` to a new set.
+    pub fn split_off_region(&mut self, region: ChunkAabb) -> VoxelChunkSet {
+        self.drain_region(region).collect()
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.directory.clear();
+        self.len = 0;
+    }
+
+    /// Iterates chunks page by page; pages come in arbitrary order, chunks within a page in
+    /// packed order.
+    pub fn iter(&self) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        self.pages.iter().flat_map(|page| page.chunks())
+    }
+
+    /// Iterates chunks in packed order, sorting only the pages.
+    pub(crate) fn iter_sorted(&self) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        let mut pages: Vec<&Page> = self.pages.iter().collect();
+        pages.sort_unstable_by_key(|page| page.base);
+        pages.into_iter().flat_map(|page| page.chunks())
+    }
+}
+
+/// Yields the positions of set bits, lowest first.
+struct BitIter(u64);
+
+impl Iterator for BitIter {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        if self.0 == 0 {
+            return None;
+        }
+        let bit = self.0.trailing_zeros();
+        self.0 &= self.0 - 1;
+        Some(bit)
+    }
+}
+
+/// Parallel iteration splits the page array into ranges.
+#[cfg(feature = "rayon")]
+impl VoxelChunkSet {
+    pub fn par_iter(&self) -> impl rayon::iter::ParallelIterator<Item = VoxelChunkIndex> + '_ {
+        use rayon::prelude::*;
+
+        self.pages.par_iter().flat_map_iter(|page| page.chunks())
+    }
+
+    /// Removes every chunk, yielding them in parallel.
+    pub fn par_drain(&mut self) -> impl rayon::iter::ParallelIterator<Item = VoxelChunkIndex> {
+        use rayon::prelude::*;
+
+        self.directory.clear();
+        self.len = 0;
+        std::mem::take(&mut self.pages)
+            .into_par_iter()
+            .flat_map_iter(Page::chunks)
+    }
+}
+
+/// Serializes as the packed indices in increasing order.
+#[cfg(feature = "serde")]
+impl serde::Serialize for VoxelChunkSet {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut keys: Vec<u32> = self.iter().map(|idx| idx.0).collect();
+        keys.sort_unstable();
+        serializer.collect_seq(keys)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for VoxelChunkSet {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let keys = <Vec<u32> as serde::Deserialize>::deserialize(deserializer)?;
+        Ok(keys.into_iter().map(VoxelChunkIndex).collect())
+    }
+}
+
+/// Parallel construction has each worker insert into a set of its own, with no lock, and
+/// merges sets pairwise in parallel by ORing the pages of the smaller into the larger.
+#[cfg(feature = "rayon")]
+impl VoxelChunkSet {
+    fn union_pages(&mut self, other: VoxelChunkSet) {
+        for page in other.pages {
+            match self.directory.get(&page.base) {
+                Some(&slot) => {
+                    for (word, bits) in self.pages[slot].words.iter_mut().zip(page.words) {
+                        self.len += (bits & !*word).count_ones() as usize;
+                        *word |= bits;
+                    }
+                }
+                None => {
+                    self.len += page
+                        .words
+                        .iter()
+                        .map(|w| w.count_ones() as usize)
+                        .sum::<usize>();
+                    self.directory.insert(page.base, self.pages.len());
+                    self.pages.push(page);
+                }
+            }
+        }
+    }
+
+    fn par_build<I>(par_iter: I) -> VoxelChunkSet
+    where
+        I: rayon::iter::IntoParallelIterator<Item = VoxelChunkIndex>,
+    {
+        use rayon::prelude::*;
+
+        par_iter
+            .into_par_iter()
+            .fold(VoxelChunkSet::new, |mut shard, idx| {
+                shard.insert(idx);
+                shard
+            })
+            .reduce(VoxelChunkSet::new, |a, b| {
+                let (mut larger, smaller) = if a.pages.len() >= b.pages.len() {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                larger.union_pages(smaller);
+                larger
+            })
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl rayon::iter::FromParallelIterator<VoxelChunkIndex> for VoxelChunkSet {
+    fn from_par_iter<I>(par_iter: I) -> VoxelChunkSet
+    where
+        I: rayon::iter::IntoParallelIterator<Item = VoxelChunkIndex>,
+    {
+        Self::par_build(par_iter)
+    }
+}
+
+#[cfg(feature = "rayon")]
+impl rayon::iter::ParallelExtend<VoxelChunkIndex> for VoxelChunkSet {
+    fn par_extend<I>(&mut self, par_iter: I)
+    where
+        I: rayon::iter::IntoParallelIterator<Item = VoxelChunkIndex>,
+    {
+        let built = Self::par_build(par_iter);
+        self.union_pages(built);
+    }
+}
+
+impl FromIterator<VoxelChunkIndex> for VoxelChunkSet {
+    fn from_iter<I: IntoIterator<Item = VoxelChunkIndex>>(iter: I) -> VoxelChunkSet {
+        let mut set = VoxelChunkSet::new();
+        set.extend(iter);
+        set
+    }
+}
+
+impl Extend<VoxelChunkIndex> for VoxelChunkSet {
+    fn extend<I: IntoIterator<Item = VoxelChunkIndex>>(&mut self, iter: I) {
+        for idx in iter {
+            self.insert(idx);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drain_region_past_the_world_edge_keeps_wrapped_chunks() {
+        let mut set = VoxelChunkSet::new();
+        let wrapped = VoxelChunkIndex::from_coords(-4096, 1, 0);
+        let inside = VoxelChunkIndex::from_coords(4095, 0, 0);
+        set.insert(wrapped);
+        set.insert(inside);
+
+        let drained: Vec<_> = set
+            .drain_region(ChunkAabb::new((4095, 0, 0), (4097, 1, 1)))
+            .collect();
+        assert_eq!(drained, vec![inside]);
+        assert!(set.contains(wrapped));
+
+        let everything = ChunkAabb::new(
+            (i32::MIN, i32::MIN, i32::MIN),
+            (i32::MAX, i32::MAX, i32::MAX),
+        );
+        assert_eq!(set.drain_region(everything).count(), 1);
+        assert!(set.is_empty());
+    }
+
+    #[test]
+    fn split_off_region_past_the_world_edge_keeps_wrapped_chunks() {
+        let mut set = VoxelChunkSet::new();
+        let wrapped = VoxelChunkIndex::from_coords(-4096, 1, 0);
+        let inside = VoxelChunkIndex::from_coords(4095, 0, 0);
+        set.insert(wrapped);
+        set.insert(inside);
+
+        let split = set.split_off_region(ChunkAabb::new((4095, 0, 0), (4097, 1, 1)));
+        assert_eq!(split.iter().collect::<Vec<_>>(), vec![inside]);
+        assert_eq!(set.iter().collect::<Vec<_>>(), vec![wrapped]);
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+/// Multiplier picking the shard. It differs from the one the shards hash with, so that keys of
+/// one shard still spread over that shard's whole table.
+const SHARD_MULTIPLIER: u64 = 0xD6E8_FEB8_6659_FD93;
+
+/// Keeps each shard's lock on its own cache line.
+#[repr(align(64))]
+struct Shard<V>(RwLock<VoxelChunkMap<V>>);
+
+/// Chunk map split into independently locked [`VoxelChunkMap`] shards, so that threads working
+/// on different chunks rarely wait on each other.
+///
+/// Every method locks a single shard, and only for the duration of the call. Callbacks run with
+/// their shard locked, so they must not access the map themselves.
+pub struct ShardedChunkMap<V> {
+    shards: Box<[Shard<V>]>,
+    shift: u32,
+}
+
+impl<V> Default for ShardedChunkMap<V> {
+    /// Four shards per available core, rounded up to a power of two.
+    fn default() -> Self {
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self::with_shards((cores * 4).next_power_of_two())
+    }
+}
+
+impl<V> ShardedChunkMap<V> {
+    pub fn new() -> ShardedChunkMap<V> {
+        Self::default()
+    }
+
+    /// Map with `shards` shards, a power of two.
+    pub fn with_shards(shards: usize) -> ShardedChunkMap<V> {
+        assert!(
+            shards.is_power_of_two(),
+            "shard count must be a power of two"
+        );
+        ShardedChunkMap {
+            shards: (0..shards)
+                .map(|_| Shard(RwLock::new(VoxelChunkMap::new())))
+                .collect(),
+            shift: 64 - shards.trailing_zeros(),
+        }
+    }
+
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
+
+    /// Index of the shard holding `idx`.
+    pub fn shard_of(&self, idx: VoxelChunkIndex) -> usize {
+        ((idx.0 as u64).wrapping_mul(SHARD_MULTIPLIER))
+            .checked_shr(self.shift)
+            .unwrap_or(0) as usize
+    }
+
+    /// Read access to a whole shard.
+    pub fn read_shard(&self, shard: usize) -> RwLockReadGuard<'_, VoxelChunkMap<V>> {
+        self.shards[shard].0.read().expect("shard lock poisoned")
+    }
+
+    /// Write access to a whole shard. Only keys routed to this shard may be inserted into it.
+    pub fn write_shard(&self, shard: usize) -> RwLockWriteGuard<'_, VoxelChunkMap<V>> {
+        self.shards[shard].0.write().expect("shard lock poisoned")
+    }
+
+    fn read_key(&self, idx: VoxelChunkIndex) -> RwLockReadGuard<'_, VoxelChunkMap<V>> {
+        self.read_shard(self.shard_of(idx))
+    }
+
+    fn write_key(&self, idx: VoxelChunkIndex) -> RwLockWriteGuard<'_, VoxelChunkMap<V>> {
+        self.write_shard(self.shard_of(idx))
+    }
+
+    /// Locks every shard in turn, so the count is only exact when nothing writes concurrently.
+    pub fn len(&self) -> usize {
+        (0..self.shards.len())
+            .map(|s| self.read_shard(s).len())
+            .sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        (0..self.shards.len()).all(|s| self.read_shard(s).is_empty())
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.read_key(idx).contains_key(idx)
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<V>
+    where
+        V: Clone,
+    {
+        self.read_key(idx).get(idx).cloned()
+    }
+
+    /// Runs `f` on the value of `idx` under the shard's read lock.
+    pub fn with<R>(&self, idx: VoxelChunkIndex, f: impl FnOnce(&V) -> R) -> Option<R> {
+        self.read_key(idx).get(idx).map(f)
+    }
+
+    /// Runs `f` on the value of `idx` under the shard's write lock.
+    pub fn with_mut<R>(&self, idx: VoxelChunkIndex, f: impl FnOnce(&mut V) -> R) -> Option<R> {
+        self.write_key(idx).get_mut(idx).map(f)
+    }
+
+    pub fn insert(&self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.write_key(idx).insert(idx, value)
+    }
+
+    pub fn remove(&self, idx: VoxelChunkIndex) -> Option<V> {
+        self.write_key(idx).remove(idx)
+    }
+
+    pub fn clear(&self) {
+        for s in 0..self.shards.len() {
+            self.write_shard(s).clear();
+        }
+    }
+
+    /// Visits every entry, read-locking one shard at a time and never two at once.
+    pub fn for_each(&self, mut f: impl FnMut(VoxelChunkIndex, &V)) {
+        for s in 0..self.shards.len() {
+            for (idx, value) in self.read_shard(s).iter() {
+                f(idx, value);
+            }
+        }
+    }
+
+    /// Like [`ShardedChunkMap::for_each`], write-locking one shard at a time.
+    pub fn for_each_mut(&self, mut f: impl FnMut(VoxelChunkIndex, &mut V)) {
+        for s in 0..self.shards.len() {
+            for (idx, value) in self.write_shard(s).iter_mut() {
+                f(idx, value);
+            }
+        }
+    }
+
+    /// Takes the shards apart, one plain map per shard.
+    pub fn into_shards(self) -> Vec<VoxelChunkMap<V>> {
+        self.shards
+            .into_vec()
+            .into_iter()
+            .map(|shard| shard.0.into_inner().expect("shard lock poisoned"))
+            .collect()
+    }
+}
+
+impl<V> FromIterator<(VoxelChunkIndex, V)> for ShardedChunkMap<V> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(iter: I) -> ShardedChunkMap<V> {
+        let map = ShardedChunkMap::new();
+        for (idx, value) in iter {
+            map.insert(idx, value);
+        }
+        map
+    }
+}
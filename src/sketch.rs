@@ -0,0 +1,185 @@
+//! Compact approximate statistics over chunk indices: [`FrequencySketch`] and
+//! [`DistinctChunks`].
+
+use crate::filter::{hash_pair, mix64, reduce};
+use crate::VoxelChunkIndex;
+
+const ROWS: usize = 4;
+const COUNTERS_PER_WORD: usize = 16;
+const MAX_COUNT: u64 = 15;
+
+/// Count-min sketch of chunk access frequencies with 4-bit counters, as used by TinyLFU: it
+/// estimates how often each chunk was accessed recently, in a few bits per tracked chunk.
+///
+/// Counts only ever overestimate, and saturate at 15. Once ten times as many accesses as there
+/// are counters per row were recorded, every counter is halved, so that old popularity fades.
+#[derive(Clone)]
+pub struct FrequencySketch {
+    /// [`ROWS`] rows of `width` counters each, packed [`COUNTERS_PER_WORD`] to a word.
+    table: Vec<u64>,
+    width: usize,
+    additions: usize,
+    sample_size: usize,
+}
+
+impl FrequencySketch {
+    /// Sketch sized to track about `expected` distinct chunks, typically a cache's capacity.
+    pub fn new(expected: usize) -> FrequencySketch {
+        let width = expected
+            .max(COUNTERS_PER_WORD)
+            .next_multiple_of(COUNTERS_PER_WORD);
+        FrequencySketch {
+            table: vec![0; ROWS * width / COUNTERS_PER_WORD],
+            width,
+            additions: 0,
+            sample_size: 10 * width,
+        }
+    }
+
+    /// Word and bit offset of the counter of `idx` in each row.
+    fn counters(&self, idx: VoxelChunkIndex) -> impl Iterator<Item = (usize, u32)> {
+        let (h1, h2) = hash_pair(idx);
+        let width = self.width;
+        (0..ROWS).map(move |row| {
+            let counter =
+                row * width + reduce(h1.wrapping_add((row as u64).wrapping_mul(h2)), width);
+            (
+                counter / COUNTERS_PER_WORD,
+                (counter % COUNTERS_PER_WORD) as u32 * 4,
+            )
+        })
+    }
+
+    /// Records an access to `idx`.
+    pub fn increment(&mut self, idx: VoxelChunkIndex) {
+        let mut added = false;
+        for (word, shift) in self.counters(idx) {
+            let word = &mut self.table[word];
+            if (*word >> shift) & MAX_COUNT < MAX_COUNT {
+                *word += 1 << shift;
+                added = true;
+            }
+        }
+        if added {
+            self.additions += 1;
+            if self.additions == self.sample_size {
+                self.age();
+            }
+        }
+    }
+
+    /// Estimated recent access count of `idx`, at most 15.
+    pub fn frequency(&self, idx: VoxelChunkIndex) -> u8 {
+        self.counters(idx)
+            .map(|(word, shift)| (self.table[word] >> shift) & MAX_COUNT)
+            .min()
+            .unwrap_or(0) as u8
+    }
+
+    /// Halves every counter.
+    pub fn age(&mut self) {
+        for word in &mut self.table {
+            *word = (*word >> 1) & 0x7777_7777_7777_7777;
+        }
+        self.additions /= 2;
+    }
+
+    pub fn clear(&mut self) {
+        self.table.fill(0);
+        self.additions = 0;
+    }
+}
+
+/// HyperLogLog estimating how many distinct chunks were inserted, in `2^precision` bytes and
+/// with a standard error of about `1.04 / sqrt(2^precision)`: 1.6% at the default precision of 12.
+///
+/// For a sliding time window, keep one estimator per interval and [`DistinctChunks::merge`] the
+/// ones the window covers.
+#[derive(Clone)]
+pub struct DistinctChunks {
+    registers: Box<[u8]>,
+    precision: u32,
+}
+
+impl Default for DistinctChunks {
+    fn default() -> Self {
+        Self::with_precision(12)
+    }
+}
+
+impl DistinctChunks {
+    pub fn new() -> DistinctChunks {
+        Self::default()
+    }
+
+    /// Estimator with `2^precision` registers, `precision` being in `4..=16`.
+    pub fn with_precision(precision: u32) -> DistinctChunks {
+        assert!((4..=16).contains(&precision), "precision must be in 4..=16");
+        DistinctChunks {
+            registers: vec![0; 1 << precision].into_boxed_slice(),
+            precision,
+        }
+    }
+
+    pub fn precision(&self) -> u32 {
+        self.precision
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex) {
+        // Leading zero counts need a fully mixed hash.
+        let hash = mix64(hash_pair(idx).0);
+        let register = (hash >> (64 - self.precision)) as usize;
+        let rank = ((hash << self.precision) | (1 << (self.precision - 1))).leading_zeros() + 1;
+        let slot = &mut self.registers[register];
+        *slot = (*slot).max(rank as u8);
+    }
+
+    /// Estimated number of distinct chunks inserted.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let sum: f64 = self
+            .registers
+            .iter()
+            .map(|&r| 1.0 / (1u64 << r) as f64)
+            .sum();
+        let raw = alpha * m * m / sum;
+
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting is more accurate while many registers are still empty.
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /// Adds the chunks counted by `other`, which must have the same precision, as if they had
+    /// been inserted here.
+    pub fn merge(&mut self, other: &DistinctChunks) {
+        assert_eq!(
+            self.precision, other.precision,
+            "estimators differ in precision"
+        );
+        for (r, &o) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *r = (*r).max(o);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.registers.fill(0);
+    }
+}
+
+impl Extend<VoxelChunkIndex> for DistinctChunks {
+    fn extend<I: IntoIterator<Item = VoxelChunkIndex>>(&mut self, iter: I) {
+        for idx in iter {
+            self.insert(idx);
+        }
+    }
+}
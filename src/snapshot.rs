@@ -0,0 +1,391 @@
+//! [`SnapshotChunkMap`], a chunk map handing out cheap copy-on-write [`ChunkMapSnapshot`]s, and
+//! [`ChunkMapDiff`]s of what changed since one of them.
+
+use std::collections::BTreeMap;
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+
+use crate::format::{
+    decode_sorted_deltas, encode_sorted_deltas, read_map_with, write_map_from, LeBytes,
+};
+use crate::paged::{index_at, split, ChunkPage};
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+const DIFF_MAGIC: &[u8; 4] = b"HFDF";
+const DIFF_VERSION: u8 = 1;
+
+type SharedPage<V> = Arc<ChunkPage<Arc<V>>>;
+
+/// Paged chunk map whose pages and values are reference counted, so that [`snapshot`] only
+/// copies the page directory. The first write to a page after a snapshot copies that page's 512
+/// value pointers, and the first write to a value copies that value; everything else stays
+/// shared with the snapshots.
+///
+/// [`VoxelChunkMap`] stores its values inline and can't be shared this way without charging
+/// every lookup for it, hence the separate type.
+///
+/// [`snapshot`]: SnapshotChunkMap::snapshot
+pub struct SnapshotChunkMap<V> {
+    pages: VoxelChunkMap<SharedPage<V>>,
+    len: usize,
+    checkpoints: BTreeMap<u64, ChunkMapSnapshot<V>>,
+    next_checkpoint: u64,
+}
+
+/// Immutable view of a [`SnapshotChunkMap`] as it was when the snapshot was taken. Unaffected by
+/// later writes to the map, and can be sent to another thread when `V` is `Send + Sync`.
+#[derive(Clone)]
+pub struct ChunkMapSnapshot<V> {
+    pages: VoxelChunkMap<SharedPage<V>>,
+    len: usize,
+}
+
+impl<V> Default for SnapshotChunkMap<V> {
+    fn default() -> Self {
+        SnapshotChunkMap {
+            pages: VoxelChunkMap::new(),
+            len: 0,
+            checkpoints: BTreeMap::new(),
+            next_checkpoint: 0,
+        }
+    }
+}
+
+fn page_iter<V>(page: &ChunkPage<Arc<V>>) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+    page.iter().map(|(idx, value)| (idx, &**value))
+}
+
+impl<V: Clone> SnapshotChunkMap<V> {
+    pub fn new() -> SnapshotChunkMap<V> {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// `O(pages)`: clones the page directory, sharing every page and value.
+    pub fn snapshot(&self) -> ChunkMapSnapshot<V> {
+        ChunkMapSnapshot {
+            pages: self.pages.clone(),
+            len: self.len,
+        }
+    }
+
+    /// Keeps a snapshot of the map under a new id, for [`SnapshotChunkMap::diff_since`]. Like
+    /// any snapshot, it keeps the pages and values it shares alive, and the first write to each
+    /// of them copies it, until [`SnapshotChunkMap::release_checkpoint`].
+    pub fn checkpoint(&mut self) -> u64 {
+        let id = self.next_checkpoint;
+        self.next_checkpoint += 1;
+        self.checkpoints.insert(id, self.snapshot());
+        id
+    }
+
+    /// Drops the checkpoint `id`, returning whether it was kept.
+    pub fn release_checkpoint(&mut self, id: u64) -> bool {
+        self.checkpoints.remove(&id).is_some()
+    }
+
+    /// Ids of the checkpoints kept, oldest first.
+    pub fn checkpoints(&self) -> impl Iterator<Item = u64> + '_ {
+        self.checkpoints.keys().copied()
+    }
+
+    /// What changed since the checkpoint `id`, or `None` if it isn't kept.
+    ///
+    /// Pages still shared with the checkpoint are skipped whole, and only the values of the
+    /// others that aren't shared anymore are compared, so the diff takes time in the number of
+    /// pages plus the size of the pages written to. A value written to counts as changed even
+    /// if it was written back equal.
+    pub fn diff_since(&self, id: u64) -> Option<ChunkMapDiff<V>> {
+        let base = self.checkpoints.get(&id)?;
+        let mut diff = ChunkMapDiff {
+            base: id,
+            upserts: Vec::new(),
+            removals: Vec::new(),
+        };
+        for (page_idx, page) in self.pages.iter() {
+            match base.pages.get(page_idx) {
+                Some(old) if Arc::ptr_eq(old, page) => {}
+                old => {
+                    let old_cells = old.map(|old| &old.cells);
+                    for (offset, cell) in page.cells.iter().enumerate() {
+                        let old = old_cells.and_then(|cells| cells[offset].as_ref());
+                        let idx = || index_at(page.origin(), offset);
+                        match (old, cell) {
+                            (Some(old), Some(new)) if Arc::ptr_eq(old, new) => {}
+                            (_, Some(new)) => diff.upserts.push((idx(), (**new).clone())),
+                            (Some(_), None) => diff.removals.push(idx()),
+                            (None, None) => {}
+                        }
+                    }
+                }
+            }
+        }
+        for (page_idx, old) in base.pages.iter() {
+            if !self.pages.contains_key(page_idx) {
+                diff.removals.extend(old.iter().map(|(idx, _)| idx));
+            }
+        }
+        diff.upserts.sort_unstable_by_key(|&(idx, _)| idx);
+        diff.removals.sort_unstable();
+        Some(diff)
+    }
+
+    /// Applies a diff taken with [`SnapshotChunkMap::diff_since`] on a map that was in the state
+    /// of its base checkpoint, bringing it to the state the diff was taken in.
+    pub fn apply_diff(&mut self, diff: ChunkMapDiff<V>) {
+        for idx in diff.removals {
+            self.remove(idx);
+        }
+        for (idx, value) in diff.upserts {
+            self.insert(idx, value);
+        }
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let (page, offset) = split(idx);
+        self.pages.get(page)?.cells[offset].as_deref()
+    }
+
+    /// Copies the page and the value first if a snapshot still shares them.
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        let (page, offset) = split(idx);
+        let page = self.pages.get_mut(page)?;
+        page.cells[offset].as_ref()?;
+        Arc::make_mut(page).cells[offset]
+            .as_mut()
+            .map(Arc::make_mut)
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        let (page, offset) = split(idx);
+        let page = self
+            .pages
+            .entry(page)
+            .or_insert_with(|| Arc::new(ChunkPage::new(page.to_coords())));
+        let page = Arc::make_mut(page);
+
+        let old = page.cells[offset].replace(Arc::new(value));
+        if old.is_none() {
+            page.len += 1;
+            self.len += 1;
+        }
+        old.map(Arc::unwrap_or_clone)
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let (page_idx, offset) = split(idx);
+        let page = self.pages.get_mut(page_idx)?;
+        page.cells[offset].as_ref()?;
+
+        let page = Arc::make_mut(page);
+        let old = page.cells[offset].take()?;
+        page.len -= 1;
+        self.len -= 1;
+        if page.len == 0 {
+            self.pages.remove(page_idx);
+        }
+        Some(Arc::unwrap_or_clone(old))
+    }
+
+    pub fn clear(&mut self) {
+        self.pages.clear();
+        self.len = 0;
+    }
+
+    /// Iterates page by page; pages come in arbitrary order, chunks within a page in region order.
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.pages.values().flat_map(|page| page_iter(page))
+    }
+}
+
+impl<V> ChunkMapSnapshot<V> {
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let (page, offset) = split(idx);
+        self.pages.get(page)?.cells[offset].as_deref()
+    }
+
+    /// Same order as [`SnapshotChunkMap::iter`].
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.pages.values().flat_map(|page| page_iter(page))
+    }
+}
+
+/// Keys inserted or modified, with their new values, and keys removed since a checkpoint of a
+/// [`SnapshotChunkMap`], both in increasing packed order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ChunkMapDiff<V> {
+    base: u64,
+    upserts: Vec<(VoxelChunkIndex, V)>,
+    removals: Vec<VoxelChunkIndex>,
+}
+
+impl<V> ChunkMapDiff<V> {
+    /// Id of the checkpoint the diff was taken since.
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    pub fn upserts(&self) -> &[(VoxelChunkIndex, V)] {
+        &self.upserts
+    }
+
+    pub fn removals(&self) -> &[VoxelChunkIndex] {
+        &self.removals
+    }
+
+    /// Number of keys changed.
+    pub fn len(&self) -> usize {
+        self.upserts.len() + self.removals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<V: LeBytes> ChunkMapDiff<V> {
+    /// Writes the diff compactly: the magic `HFDF`, the version, the byte size of a value and
+    /// the base checkpoint, then the upserted and the removed keys each as
+    /// [`encode_sorted_deltas`] does, prefixed by their byte length, then the values in key
+    /// order, then a CRC32 of everything before it.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let keys: Vec<VoxelChunkIndex> = self.upserts.iter().map(|&(idx, _)| idx).collect();
+        let keys = encode_sorted_deltas(&keys);
+        let removals = encode_sorted_deltas(&self.removals);
+
+        let mut bytes =
+            Vec::with_capacity(22 + keys.len() + removals.len() + self.upserts.len() * V::SIZE);
+        bytes.extend_from_slice(DIFF_MAGIC);
+        bytes.push(DIFF_VERSION);
+        bytes.push(u8::try_from(V::SIZE).expect("values must fit in 255 bytes"));
+        bytes.extend_from_slice(&self.base.to_le_bytes());
+        for encoded in [&keys, &removals] {
+            bytes.extend_from_slice(&(encoded.len() as u32).to_le_bytes());
+            bytes.extend_from_slice(encoded);
+        }
+        for (_, value) in &self.upserts {
+            value.write_le(&mut bytes);
+        }
+        bytes.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+        writer.write_all(&bytes)
+    }
+
+    /// Reads a diff written by [`ChunkMapDiff::write_to`] with values of the same type.
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<ChunkMapDiff<V>> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        if bytes.len() < 18 {
+            return Err(invalid("chunk map diff is truncated".to_owned()));
+        }
+        let (body, crc) = bytes.split_at(bytes.len() - 4);
+        if crc32fast::hash(body).to_le_bytes() != crc {
+            return Err(invalid("chunk map diff checksum mismatch".to_owned()));
+        }
+        if &body[..4] != DIFF_MAGIC {
+            return Err(invalid("not a chunk map diff".to_owned()));
+        }
+        if body[4] != DIFF_VERSION || body[5] as usize != V::SIZE {
+            return Err(invalid(format!(
+                "diff version {} with values of {} bytes, expected version {DIFF_VERSION} and {} \
+                 bytes",
+                body[4],
+                body[5],
+                V::SIZE
+            )));
+        }
+        let base = u64::from_le_bytes(body[6..14].try_into().unwrap());
+
+        let mut rest = &body[14..];
+        let mut section = || -> io::Result<Vec<VoxelChunkIndex>> {
+            let truncated = || invalid("chunk map diff is truncated".to_owned());
+            let len = rest.get(..4).ok_or_else(truncated)?;
+            let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+            let keys = rest.get(4..4 + len).ok_or_else(truncated)?;
+            rest = &rest[4 + len..];
+            decode_sorted_deltas(keys)
+        };
+        let keys = section()?;
+        let removals = section()?;
+        if rest.len() != keys.len() * V::SIZE {
+            return Err(invalid(format!(
+                "{} bytes of values for {} keys",
+                rest.len(),
+                keys.len()
+            )));
+        }
+        let upserts = if V::SIZE == 0 {
+            keys.into_iter().map(|idx| (idx, V::read_le(&[]))).collect()
+        } else {
+            keys.into_iter()
+                .zip(rest.chunks_exact(V::SIZE).map(V::read_le))
+                .collect()
+        };
+        Ok(ChunkMapDiff {
+            base,
+            upserts,
+            removals,
+        })
+    }
+}
+
+impl<V: LeBytes> ChunkMapSnapshot<V> {
+    /// Writes the snapshot in the format of [`VoxelChunkMap::write_to`], copying only its sorted
+    /// keys on the way: saving a million-chunk world on a background thread takes 4 MB on top of
+    /// the snapshot, which the map goes on being written to meanwhile.
+    pub fn write_to<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut keys: Vec<VoxelChunkIndex> = self.iter().map(|(idx, _)| idx).collect();
+        keys.sort_unstable();
+        let entries = || {
+            keys.iter()
+                .map(|&idx| (idx, self.get(idx).expect("the key was just listed")))
+        };
+        write_map_from(keys.len(), entries, writer)
+    }
+}
+
+impl<V: Clone + LeBytes> SnapshotChunkMap<V> {
+    /// Reads a map written by [`ChunkMapSnapshot::write_to`] or [`VoxelChunkMap::write_to`],
+    /// inserting each entry as its value is read.
+    pub fn read_from<R: Read>(reader: R) -> io::Result<SnapshotChunkMap<V>> {
+        let mut map = SnapshotChunkMap::new();
+        read_map_with(reader, |idx, value| {
+            map.insert(idx, value);
+        })?;
+        Ok(map)
+    }
+}
+
+impl<V: Clone> FromIterator<(VoxelChunkIndex, V)> for SnapshotChunkMap<V> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(iter: I) -> SnapshotChunkMap<V> {
+        let mut map = SnapshotChunkMap::new();
+        for (idx, value) in iter {
+            map.insert(idx, value);
+        }
+        map
+    }
+}
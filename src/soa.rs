@@ -0,0 +1,215 @@
+//! [`SoaChunkMap`], the structure-of-arrays flavor of [`VoxelChunkMap`].
+
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+/// A row type whose fields are each stored in their own column. Implemented for tuples of up to
+/// four fields; a single value is stored as a 1-tuple.
+pub trait Columns: Sized {
+    /// One `Vec` per field.
+    type Storage: Default;
+    type Ref<'a>
+    where
+        Self: 'a;
+    type Mut<'a>
+    where
+        Self: 'a;
+    type Slices<'a>
+    where
+        Self: 'a;
+    type SlicesMut<'a>
+    where
+        Self: 'a;
+
+    fn push(storage: &mut Self::Storage, row: Self);
+    fn swap_remove(storage: &mut Self::Storage, row: usize) -> Self;
+    fn replace(storage: &mut Self::Storage, row: usize, value: Self) -> Self;
+    fn row(storage: &Self::Storage, row: usize) -> Self::Ref<'_>;
+    fn row_mut(storage: &mut Self::Storage, row: usize) -> Self::Mut<'_>;
+    fn slices(storage: &Self::Storage) -> Self::Slices<'_>;
+    fn slices_mut(storage: &mut Self::Storage) -> Self::SlicesMut<'_>;
+    fn clear(storage: &mut Self::Storage);
+    fn reserve(storage: &mut Self::Storage, additional: usize);
+    fn shrink_to_fit(storage: &mut Self::Storage);
+}
+
+macro_rules! impl_columns {
+    ($($T:ident $i:tt),+) => {
+        impl<$($T),+> Columns for ($($T,)+) {
+            type Storage = ($(Vec<$T>,)+);
+            type Ref<'a> = ($(&'a $T,)+) where Self: 'a;
+            type Mut<'a> = ($(&'a mut $T,)+) where Self: 'a;
+            type Slices<'a> = ($(&'a [$T],)+) where Self: 'a;
+            type SlicesMut<'a> = ($(&'a mut [$T],)+) where Self: 'a;
+
+            fn push(storage: &mut Self::Storage, row: Self) {
+                $(storage.$i.push(row.$i);)+
+            }
+
+            fn swap_remove(storage: &mut Self::Storage, row: usize) -> Self {
+                ($(storage.$i.swap_remove(row),)+)
+            }
+
+            fn replace(storage: &mut Self::Storage, row: usize, value: Self) -> Self {
+                ($(std::mem::replace(&mut storage.$i[row], value.$i),)+)
+            }
+
+            fn row(storage: &Self::Storage, row: usize) -> Self::Ref<'_> {
+                ($(&storage.$i[row],)+)
+            }
+
+            fn row_mut(storage: &mut Self::Storage, row: usize) -> Self::Mut<'_> {
+                ($(&mut storage.$i[row],)+)
+            }
+
+            fn slices(storage: &Self::Storage) -> Self::Slices<'_> {
+                ($(&storage.$i[..],)+)
+            }
+
+            fn slices_mut(storage: &mut Self::Storage) -> Self::SlicesMut<'_> {
+                ($(&mut storage.$i[..],)+)
+            }
+
+            fn clear(storage: &mut Self::Storage) {
+                $(storage.$i.clear();)+
+            }
+
+            fn reserve(storage: &mut Self::Storage, additional: usize) {
+                $(storage.$i.reserve(additional);)+
+            }
+
+            fn shrink_to_fit(storage: &mut Self::Storage) {
+                $(storage.$i.shrink_to_fit();)+
+            }
+        }
+    };
+}
+
+impl_columns!(A 0);
+impl_columns!(A 0, B 1);
+impl_columns!(A 0, B 1, C 2);
+impl_columns!(A 0, B 1, C 2, D 3);
+
+/// Chunk map keeping keys and each value field in separate, densely packed arrays, so that a pass
+/// over the keys or over one hot field doesn't drag the other fields through the cache.
+///
+/// The hash table only maps each key to its row; removals move the last row into the hole, so
+/// rows are not stable across removals.
+pub struct SoaChunkMap<C: Columns> {
+    rows: VoxelChunkMap<usize>,
+    keys: Vec<VoxelChunkIndex>,
+    columns: C::Storage,
+}
+
+impl<C: Columns> Default for SoaChunkMap<C> {
+    fn default() -> Self {
+        SoaChunkMap {
+            rows: VoxelChunkMap::new(),
+            keys: Vec::new(),
+            columns: C::Storage::default(),
+        }
+    }
+}
+
+impl<C: Columns> SoaChunkMap<C> {
+    pub fn new() -> SoaChunkMap<C> {
+        Self::default()
+    }
+
+    pub fn with_capacity(capacity: usize) -> SoaChunkMap<C> {
+        let mut map = Self::default();
+        map.reserve(capacity);
+        map
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.rows.reserve(additional);
+        self.keys.reserve(additional);
+        C::reserve(&mut self.columns, additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.rows.shrink_to_fit();
+        self.keys.shrink_to_fit();
+        C::shrink_to_fit(&mut self.columns);
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.rows.contains_key(idx)
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<C::Ref<'_>> {
+        let &row = self.rows.get(idx)?;
+        Some(C::row(&self.columns, row))
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<C::Mut<'_>> {
+        let &row = self.rows.get(idx)?;
+        Some(C::row_mut(&mut self.columns, row))
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: C) -> Option<C> {
+        if let Some(&row) = self.rows.get(idx) {
+            return Some(C::replace(&mut self.columns, row, value));
+        }
+
+        self.rows.insert(idx, self.keys.len());
+        self.keys.push(idx);
+        C::push(&mut self.columns, value);
+        None
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<C> {
+        let row = self.rows.remove(idx)?;
+        self.keys.swap_remove(row);
+        let value = C::swap_remove(&mut self.columns, row);
+        if let Some(&moved) = self.keys.get(row) {
+            *self.rows.get_mut(moved).unwrap() = row;
+        }
+        Some(value)
+    }
+
+    pub fn clear(&mut self) {
+        self.rows.clear();
+        self.keys.clear();
+        C::clear(&mut self.columns);
+    }
+
+    /// Keys in row order, matching [`SoaChunkMap::columns`].
+    pub fn keys(&self) -> &[VoxelChunkIndex] {
+        &self.keys
+    }
+
+    /// One slice per value field, in row order.
+    pub fn columns(&self) -> C::Slices<'_> {
+        C::slices(&self.columns)
+    }
+
+    pub fn columns_mut(&mut self) -> C::SlicesMut<'_> {
+        C::slices_mut(&mut self.columns)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, C::Ref<'_>)> {
+        self.keys
+            .iter()
+            .enumerate()
+            .map(|(row, &idx)| (idx, C::row(&self.columns, row)))
+    }
+}
+
+impl<C: Columns> FromIterator<(VoxelChunkIndex, C)> for SoaChunkMap<C> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, C)>>(iter: I) -> SoaChunkMap<C> {
+        let mut map = SoaChunkMap::new();
+        for (idx, value) in iter {
+            map.insert(idx, value);
+        }
+        map
+    }
+}
@@ -0,0 +1,133 @@
+use std::ops::RangeBounds;
+
+use crate::VoxelChunkIndex;
+
+/// Map stored as a `Vec` of pairs sorted by packed key, looked up by binary search. Best suited to
+/// build-once/query-many workloads: construction is a single sort, single inserts are `O(n)`.
+#[derive(Clone)]
+pub struct SortedChunkMap<V> {
+    entries: Vec<(VoxelChunkIndex, V)>,
+}
+
+impl<V> Default for SortedChunkMap<V> {
+    fn default() -> Self {
+        SortedChunkMap {
+            entries: Vec::new(),
+        }
+    }
+}
+
+impl<V> SortedChunkMap<V> {
+    pub fn new() -> SortedChunkMap<V> {
+        Self::default()
+    }
+
+    /// Bulk construction. When a key appears several times, the last value wins.
+    pub fn from_vec(mut entries: Vec<(VoxelChunkIndex, V)>) -> SortedChunkMap<V> {
+        entries.reverse();
+        entries.sort_by_key(|&(idx, _)| idx);
+        entries.dedup_by_key(|&mut (idx, _)| idx);
+        SortedChunkMap { entries }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.entries.capacity()
+    }
+
+    pub fn load_factor(&self) -> f64 {
+        if self.entries.capacity() == 0 {
+            0.0
+        } else {
+            self.entries.len() as f64 / self.entries.capacity() as f64
+        }
+    }
+
+    pub fn reserve(&mut self, additional: usize) {
+        self.entries.reserve(additional);
+    }
+
+    pub fn reserve_exact(&mut self, additional: usize) {
+        self.entries.reserve_exact(additional);
+    }
+
+    pub fn shrink_to_fit(&mut self) {
+        self.entries.shrink_to_fit();
+    }
+
+    fn search(&self, idx: VoxelChunkIndex) -> Result<usize, usize> {
+        self.entries.binary_search_by_key(&idx, |&(k, _)| k)
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.search(idx).is_ok()
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        let pos = self.search(idx).ok()?;
+        Some(&self.entries[pos].1)
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        let pos = self.search(idx).ok()?;
+        Some(&mut self.entries[pos].1)
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        match self.search(idx) {
+            Ok(pos) => Some(std::mem::replace(&mut self.entries[pos].1, value)),
+            Err(pos) => {
+                self.entries.insert(pos, (idx, value));
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let pos = self.search(idx).ok()?;
+        Some(self.entries.remove(pos).1)
+    }
+
+    /// All entries whose packed key falls in `range`, in packed order.
+    pub fn range<R: RangeBounds<VoxelChunkIndex>>(&self, range: R) -> &[(VoxelChunkIndex, V)] {
+        use std::ops::Bound::*;
+
+        let start = match range.start_bound() {
+            Included(&idx) => self.entries.partition_point(|&(k, _)| k < idx),
+            Excluded(&idx) => self.entries.partition_point(|&(k, _)| k <= idx),
+            Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Included(&idx) => self.entries.partition_point(|&(k, _)| k <= idx),
+            Excluded(&idx) => self.entries.partition_point(|&(k, _)| k < idx),
+            Unbounded => self.entries.len(),
+        };
+        &self.entries[start..end.max(start)]
+    }
+
+    /// Entries in packed order.
+    pub fn as_slice(&self) -> &[(VoxelChunkIndex, V)] {
+        &self.entries
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.entries.iter().map(|(idx, value)| (*idx, value))
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (VoxelChunkIndex, &mut V)> {
+        self.entries.iter_mut().map(|(idx, value)| (*idx, value))
+    }
+}
+
+impl<V> FromIterator<(VoxelChunkIndex, V)> for SortedChunkMap<V> {
+    fn from_iter<I: IntoIterator<Item = (VoxelChunkIndex, V)>>(iter: I) -> SortedChunkMap<V> {
+        Self::from_vec(iter.into_iter().collect())
+    }
+}
@@ -0,0 +1,321 @@
+//! [`SqliteChunkStore`], a [`ChunkStore`] in a single SQLite database, for tools that would
+//! rather query a file with SQL than parse region files. Binds the few functions of the system's
+//! `libsqlite3` it needs itself.
+
+use std::ffi::{c_char, c_int, c_void, CStr, CString};
+use std::io;
+use std::path::Path;
+use std::ptr;
+
+use crate::store::ChunkStore;
+use crate::{VoxelChunkIndex, X_BITS, Y_BITS, Z_BITS};
+
+#[repr(C)]
+struct Sqlite3 {
+    _opaque: [u8; 0],
+}
+
+#[repr(C)]
+struct Sqlite3Stmt {
+    _opaque: [u8; 0],
+}
+
+const SQLITE_OK: c_int = 0;
+const SQLITE_ROW: c_int = 100;
+const SQLITE_DONE: c_int = 101;
+const SQLITE_OPEN_READWRITE: c_int = 0x2;
+const SQLITE_OPEN_CREATE: c_int = 0x4;
+/// `SQLITE_TRANSIENT`: SQLite copies bound blobs before the call returns.
+const SQLITE_TRANSIENT: isize = -1;
+
+#[link(name = "sqlite3")]
+extern "C" {
+    fn sqlite3_open_v2(
+        filename: *const c_char,
+        db: *mut *mut Sqlite3,
+        flags: c_int,
+        vfs: *const c_char,
+    ) -> c_int;
+    fn sqlite3_close_v2(db: *mut Sqlite3) -> c_int;
+    fn sqlite3_errmsg(db: *mut Sqlite3) -> *const c_char;
+    fn sqlite3_exec(
+        db: *mut Sqlite3,
+        sql: *const c_char,
+        callback: *const c_void,
+        arg: *mut c_void,
+        errmsg: *mut *mut c_char,
+    ) -> c_int;
+    fn sqlite3_prepare_v2(
+        db: *mut Sqlite3,
+        sql: *const c_char,
+        len: c_int,
+        stmt: *mut *mut Sqlite3Stmt,
+        tail: *mut *const c_char,
+    ) -> c_int;
+    fn sqlite3_bind_int64(stmt: *mut Sqlite3Stmt, i: c_int, value: i64) -> c_int;
+    fn sqlite3_bind_blob(
+        stmt: *mut Sqlite3Stmt,
+        i: c_int,
+        data: *const c_void,
+        len: c_int,
+        destructor: isize,
+    ) -> c_int;
+    fn sqlite3_step(stmt: *mut Sqlite3Stmt) -> c_int;
+    fn sqlite3_reset(stmt: *mut Sqlite3Stmt) -> c_int;
+    fn sqlite3_column_blob(stmt: *mut Sqlite3Stmt, i: c_int) -> *const c_void;
+    fn sqlite3_column_bytes(stmt: *mut Sqlite3Stmt, i: c_int) -> c_int;
+    fn sqlite3_column_int64(stmt: *mut Sqlite3Stmt, i: c_int) -> i64;
+    fn sqlite3_changes(db: *mut Sqlite3) -> c_int;
+    fn sqlite3_finalize(stmt: *mut Sqlite3Stmt) -> c_int;
+}
+
+/// Bit layout of the packed keys, kept in the database's `user_version`.
+const LAYOUT: i64 = (X_BITS as i64) << 16 | (Y_BITS as i64) << 8 | Z_BITS as i64;
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS chunks (
+        key INTEGER PRIMARY KEY,
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL,
+        z INTEGER NOT NULL,
+        data BLOB NOT NULL
+    );
+    CREATE INDEX IF NOT EXISTS chunks_xyz ON chunks (x, y, z);
+";
+
+/// A prepared statement, finalized on drop.
+struct Statement(*mut Sqlite3Stmt);
+
+impl Drop for Statement {
+    fn drop(&mut self) {
+        // SAFETY: the statement was prepared by `SqliteChunkStore::prepare` and is dropped once.
+        unsafe { sqlite3_finalize(self.0) };
+    }
+}
+
+/// [`ChunkStore`] keeping every blob in the `chunks` table of a SQLite database, keyed by the
+/// packed index as its integer primary key, so that lookups go straight through SQLite's
+/// B-tree. The decoded coordinates sit next to it, indexed, for queries like
+/// `SELECT x, y, z, length(data) FROM chunks WHERE z = 0`.
+///
+/// Writes go into a transaction that [`ChunkStore::sync`] commits, like they only reach the disk
+/// of a [`RegionStore`](crate::RegionStore) on sync; dropping the store commits it too.
+pub struct SqliteChunkStore {
+    db: *mut Sqlite3,
+    select: Statement,
+    upsert: Statement,
+    delete: Statement,
+    keys: Statement,
+}
+
+// SAFETY: SQLite connections may move between threads as long as only one uses them at a time,
+// which `&mut self` on every operation ensures.
+unsafe impl Send for SqliteChunkStore {}
+
+impl SqliteChunkStore {
+    /// Opens the database at `path`, creating it and its table if needed. Fails on databases
+    /// written by a build packing indices with another bit layout.
+    pub fn open(path: impl AsRef<Path>) -> io::Result<SqliteChunkStore> {
+        let path = path
+            .as_ref()
+            .to_str()
+            .and_then(|path| CString::new(path).ok())
+            .ok_or_else(|| {
+                io::Error::new(io::ErrorKind::InvalidInput, "database path isn't UTF-8")
+            })?;
+        let mut db = ptr::null_mut();
+        // SAFETY: `path` is a valid C string and `db` a valid out pointer.
+        let rc = unsafe {
+            sqlite3_open_v2(
+                path.as_ptr(),
+                &mut db,
+                SQLITE_OPEN_READWRITE | SQLITE_OPEN_CREATE,
+                ptr::null(),
+            )
+        };
+        if db.is_null() {
+            return Err(io::Error::other("sqlite: out of memory"));
+        }
+        let store = |db| -> io::Result<SqliteChunkStore> {
+            check(db, rc)?;
+            exec(db, SCHEMA)?;
+            let layout = query_int(db, "PRAGMA user_version")?;
+            if layout == 0 {
+                exec(db, &format!("PRAGMA user_version = {LAYOUT}"))?;
+            } else if layout != LAYOUT {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "indices packed with {}/{}/{} X/Y/Z bits, this build packs them with \
+                         {X_BITS}/{Y_BITS}/{Z_BITS}",
+                        layout >> 16,
+                        layout >> 8 & 0xFF,
+                        layout & 0xFF
+                    ),
+                ));
+            }
+            exec(db, "BEGIN")?;
+            Ok(SqliteChunkStore {
+                db,
+                select: prepare(db, "SELECT data FROM chunks WHERE key = ?1")?,
+                upsert: prepare(
+                    db,
+                    "INSERT OR REPLACE INTO chunks (key, x, y, z, data) VALUES (?1, ?2, ?3, ?4, ?5)",
+                )?,
+                delete: prepare(db, "DELETE FROM chunks WHERE key = ?1")?,
+                keys: prepare(db, "SELECT key FROM chunks ORDER BY key")?,
+            })
+        };
+        store(db).inspect_err(|_| {
+            // SAFETY: the connection failed to set up, and no statement of it survived.
+            unsafe { sqlite3_close_v2(db) };
+        })
+    }
+
+    /// Steps `stmt` to completion, returning whether it produced a row first, then resets it.
+    fn run(&self, stmt: &Statement, on_row: impl FnOnce(*mut Sqlite3Stmt)) -> io::Result<bool> {
+        // SAFETY: `stmt` was prepared on `self.db` and its parameters are bound.
+        let rc = unsafe { sqlite3_step(stmt.0) };
+        let row = rc == SQLITE_ROW;
+        if row {
+            on_row(stmt.0);
+        }
+        // SAFETY: as above; resetting also clears the error of a failed step.
+        unsafe { sqlite3_reset(stmt.0) };
+        if rc != SQLITE_ROW && rc != SQLITE_DONE {
+            return Err(error(self.db));
+        }
+        Ok(row)
+    }
+}
+
+impl ChunkStore for SqliteChunkStore {
+    fn read(&mut self, idx: VoxelChunkIndex) -> io::Result<Option<Vec<u8>>> {
+        // SAFETY: the statement belongs to `self.db`, and the blob pointer is read before the
+        // statement is stepped or reset again.
+        unsafe { check(self.db, sqlite3_bind_int64(self.select.0, 1, idx.0 as i64))? };
+        let mut blob = None;
+        self.run(&self.select, |stmt| unsafe {
+            let len = sqlite3_column_bytes(stmt, 0) as usize;
+            let data = sqlite3_column_blob(stmt, 0) as *const u8;
+            blob = Some(if len == 0 {
+                Vec::new()
+            } else {
+                std::slice::from_raw_parts(data, len).to_vec()
+            });
+        })?;
+        Ok(blob)
+    }
+
+    fn write(&mut self, idx: VoxelChunkIndex, blob: &[u8]) -> io::Result<()> {
+        if blob.is_empty() {
+            return self.delete(idx).map(|_| ());
+        }
+        let len = c_int::try_from(blob.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "chunk blob over 2 GiB"))?;
+        let (x, y, z) = idx.to_coords();
+        let stmt = self.upsert.0;
+        // SAFETY: the statement belongs to `self.db`, and SQLite copies the blob.
+        unsafe {
+            check(self.db, sqlite3_bind_int64(stmt, 1, idx.0 as i64))?;
+            check(self.db, sqlite3_bind_int64(stmt, 2, x as i64))?;
+            check(self.db, sqlite3_bind_int64(stmt, 3, y as i64))?;
+            check(self.db, sqlite3_bind_int64(stmt, 4, z as i64))?;
+            check(
+                self.db,
+                sqlite3_bind_blob(stmt, 5, blob.as_ptr().cast(), len, SQLITE_TRANSIENT),
+            )?;
+        }
+        self.run(&self.upsert, |_| {}).map(|_| ())
+    }
+
+    fn delete(&mut self, idx: VoxelChunkIndex) -> io::Result<bool> {
+        // SAFETY: the statement belongs to `self.db`.
+        unsafe { check(self.db, sqlite3_bind_int64(self.delete.0, 1, idx.0 as i64))? };
+        self.run(&self.delete, |_| {})?;
+        // SAFETY: `self.db` is open.
+        Ok(unsafe { sqlite3_changes(self.db) } > 0)
+    }
+
+    fn keys(&mut self) -> io::Result<Vec<VoxelChunkIndex>> {
+        let mut keys = Vec::new();
+        let stmt = self.keys.0;
+        loop {
+            // SAFETY: the statement belongs to `self.db` and has no parameters.
+            match unsafe { sqlite3_step(stmt) } {
+                SQLITE_ROW => {
+                    let key = unsafe { sqlite3_column_int64(stmt, 0) };
+                    keys.push(VoxelChunkIndex(key as u32));
+                }
+                rc => {
+                    // SAFETY: as above.
+                    unsafe { sqlite3_reset(stmt) };
+                    if rc != SQLITE_DONE {
+                        return Err(error(self.db));
+                    }
+                    return Ok(keys);
+                }
+            }
+        }
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        exec(self.db, "COMMIT; BEGIN")
+    }
+}
+
+impl Drop for SqliteChunkStore {
+    fn drop(&mut self) {
+        let _ = exec(self.db, "COMMIT");
+        // SAFETY: `close_v2` waits for the statements, finalized right after, to close.
+        unsafe { sqlite3_close_v2(self.db) };
+    }
+}
+
+fn error(db: *mut Sqlite3) -> io::Error {
+    // SAFETY: `db` is a connection, whose last error message is a valid C string.
+    let msg = unsafe { CStr::from_ptr(sqlite3_errmsg(db)) };
+    io::Error::other(format!("sqlite: {}", msg.to_string_lossy()))
+}
+
+fn check(db: *mut Sqlite3, rc: c_int) -> io::Result<()> {
+    if rc == SQLITE_OK {
+        Ok(())
+    } else {
+        Err(error(db))
+    }
+}
+
+fn exec(db: *mut Sqlite3, sql: &str) -> io::Result<()> {
+    let sql = CString::new(sql).expect("queries have no NUL");
+    // SAFETY: `db` is a connection and `sql` a valid C string; no callback.
+    let rc = unsafe {
+        sqlite3_exec(
+            db,
+            sql.as_ptr(),
+            ptr::null(),
+            ptr::null_mut(),
+            ptr::null_mut(),
+        )
+    };
+    check(db, rc)
+}
+
+fn prepare(db: *mut Sqlite3, sql: &str) -> io::Result<Statement> {
+    let sql = CString::new(sql).expect("queries have no NUL");
+    let mut stmt = ptr::null_mut();
+    // SAFETY: `db` is a connection, `sql` a valid C string and `stmt` a valid out pointer.
+    let rc = unsafe { sqlite3_prepare_v2(db, sql.as_ptr(), -1, &mut stmt, ptr::null_mut()) };
+    check(db, rc)?;
+    Ok(Statement(stmt))
+}
+
+/// First column of the first row of `sql`, as an integer.
+fn query_int(db: *mut Sqlite3, sql: &str) -> io::Result<i64> {
+    let stmt = prepare(db, sql)?;
+    // SAFETY: `stmt` was just prepared on `db`.
+    match unsafe { sqlite3_step(stmt.0) } {
+        SQLITE_ROW => Ok(unsafe { sqlite3_column_int64(stmt.0, 0) }),
+        _ => Err(error(db)),
+    }
+}
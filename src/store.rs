@@ -0,0 +1,843 @@
+//! [`RegionStore`], durable per-chunk blobs in region files, after Minecraft's Anvil format.
+//!
+//! The world is cut into regions of 16×16×16 chunks, each stored in its own file of 4 KiB
+//! sectors. A region file starts with a header: the magic `HFRG`, the format version, the
+//! region side as a power of two and whether blobs are encrypted, then a table with, for each chunk of the region, the first
+//! sector of its blob, its length in bytes and its CRC32. A blob takes whole consecutive sectors;
+//! the sectors no table entry covers are free, and new blobs go into the first free run large
+//! enough before the file grows.
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cipher::RecordCipher;
+use crate::VoxelChunkIndex;
+
+const MAGIC: &[u8; 4] = b"HFRG";
+const VERSION: u8 = 1;
+pub(crate) const SECTOR: u64 = 4096;
+/// Log2 of the side of a region, in chunks.
+const REGION_BITS: u32 = 4;
+const REGION_SIDE: i32 = 1 << REGION_BITS;
+const REGION_CHUNKS: usize = 1 << (3 * REGION_BITS);
+const PREAMBLE_LEN: usize = 16;
+/// Offset in the preamble of the byte telling whether blobs are encrypted.
+const ENCRYPTED_AT: usize = 6;
+const ENTRY_LEN: usize = 12;
+/// Sectors taken by the header.
+const HEADER_SECTORS: u32 = HEADER_LEN.div_ceil(SECTOR as usize) as u32;
+/// Bytes of the header that hold something.
+pub(crate) const HEADER_LEN: usize = PREAMBLE_LEN + REGION_CHUNKS * ENTRY_LEN;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Where a chunk's blob lives in its region file. A zero length means no blob.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Entry {
+    pub(crate) sector: u32,
+    pub(crate) len: u32,
+    pub(crate) crc: u32,
+}
+
+impl Entry {
+    fn sectors(self) -> u32 {
+        (self.len as u64).div_ceil(SECTOR) as u32
+    }
+}
+
+/// Region of `idx` and the position of `idx` in its table.
+pub(crate) fn locate(idx: VoxelChunkIndex) -> ((i32, i32, i32), usize) {
+    let (x, y, z) = idx.to_coords();
+    let region = (
+        x.div_euclid(REGION_SIDE),
+        y.div_euclid(REGION_SIDE),
+        z.div_euclid(REGION_SIDE),
+    );
+    let (lx, ly, lz) = (
+        x.rem_euclid(REGION_SIDE) as usize,
+        y.rem_euclid(REGION_SIDE) as usize,
+        z.rem_euclid(REGION_SIDE) as usize,
+    );
+    (region, lx | ly << REGION_BITS | lz << (2 * REGION_BITS))
+}
+
+/// Checks the first [`HEADER_LEN`] bytes of the region file at `path`, `file_len` bytes long,
+/// and that its blobs are `encrypted` or not, returning its table and which of its sectors are
+/// used. Blobs may run past the end of a file
+/// whose last write was cut short: reading them fails, until [`RegionStore::verify`]
+/// quarantines them. Only the sectors they have in the file are marked used, so that a corrupt
+/// entry can't make the map of a small file huge.
+pub(crate) fn parse_table(
+    header: &[u8],
+    file_len: u64,
+    path: &Path,
+    encrypted: bool,
+) -> io::Result<(Vec<Entry>, Vec<bool>)> {
+    if &header[..4] != MAGIC {
+        return Err(invalid(format!("{} is not a region file", path.display())));
+    }
+    if header[4] != VERSION || header[5] != REGION_BITS as u8 {
+        return Err(invalid(format!(
+            "{} has version {} and regions of 2^{} chunks, expected version {VERSION} and \
+             2^{REGION_BITS}",
+            path.display(),
+            header[4],
+            header[5]
+        )));
+    }
+    if (header[ENCRYPTED_AT] != 0) != encrypted {
+        return Err(invalid(if encrypted {
+            format!("{} holds plain blobs, not encrypted ones", path.display())
+        } else {
+            format!(
+                "{} holds encrypted blobs, and no cipher was given",
+                path.display()
+            )
+        }));
+    }
+
+    let sectors = file_len.div_ceil(SECTOR) as usize;
+    let mut used = vec![false; sectors.max(HEADER_SECTORS as usize)];
+    used[..HEADER_SECTORS as usize].fill(true);
+    let u32_at = |at: usize| u32::from_le_bytes(header[at..at + 4].try_into().unwrap());
+    let mut entries = Vec::with_capacity(REGION_CHUNKS);
+    for i in 0..REGION_CHUNKS {
+        let at = PREAMBLE_LEN + i * ENTRY_LEN;
+        let entry = Entry {
+            sector: u32_at(at),
+            len: u32_at(at + 4),
+            crc: u32_at(at + 8),
+        };
+        if entry.len > 0 {
+            let run = entry.sector as usize..entry.sector as usize + entry.sectors() as usize;
+            if run.start < HEADER_SECTORS as usize {
+                return Err(invalid(format!(
+                    "{}: chunk {i} overlaps the header",
+                    path.display()
+                )));
+            }
+            let run = run.start.min(used.len())..run.end.min(used.len());
+            if used[run.clone()].iter().any(|&used| used) {
+                return Err(invalid(format!(
+                    "{}: chunk {i} overlaps another one",
+                    path.display()
+                )));
+            }
+            used[run].fill(true);
+        }
+        entries.push(entry);
+    }
+    Ok((entries, used))
+}
+
+/// Name of the file of `region` in a store.
+pub(crate) fn region_file_name((x, y, z): (i32, i32, i32)) -> String {
+    format!("r.{x}.{y}.{z}.hfr")
+}
+
+/// Inverse of [`region_file_name`].
+pub(crate) fn parse_region_file_name(name: &str) -> Option<(i32, i32, i32)> {
+    let mut parts = name.strip_prefix("r.")?.strip_suffix(".hfr")?.split('.');
+    let region = (
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+        parts.next()?.parse().ok()?,
+    );
+    parts.next().is_none().then_some(region)
+}
+
+/// Inverse of [`locate`].
+fn index_of(region: (i32, i32, i32), slot: usize) -> VoxelChunkIndex {
+    let local = |shift: u32| (slot >> shift) as i32 & (REGION_SIDE - 1);
+    VoxelChunkIndex::from_coords(
+        region.0 * REGION_SIDE + local(0),
+        region.1 * REGION_SIDE + local(REGION_BITS),
+        region.2 * REGION_SIDE + local(2 * REGION_BITS),
+    )
+}
+
+struct RegionFile {
+    file: File,
+    entries: Vec<Entry>,
+    /// Whether each sector of the file holds the header or a blob.
+    used: Vec<bool>,
+    /// Slots whose blob ran past the end of the file when it was opened, with the end of the
+    /// sectors it had in it, the only ones to free with it.
+    cut_short: Vec<(usize, usize)>,
+}
+
+impl RegionFile {
+    fn create(path: &Path, encrypted: bool) -> io::Result<RegionFile> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)?;
+        let mut header = vec![0; HEADER_SECTORS as usize * SECTOR as usize];
+        header[..4].copy_from_slice(MAGIC);
+        header[4] = VERSION;
+        header[5] = REGION_BITS as u8;
+        header[ENCRYPTED_AT] = encrypted as u8;
+        file.write_all(&header)?;
+        Ok(RegionFile {
+            file,
+            entries: vec![Entry::default(); REGION_CHUNKS],
+            used: vec![true; HEADER_SECTORS as usize],
+            cut_short: Vec::new(),
+        })
+    }
+
+    fn open(path: &Path, encrypted: bool) -> io::Result<RegionFile> {
+        let mut file = OpenOptions::new().read(true).write(true).open(path)?;
+        let mut header = vec![0; HEADER_LEN];
+        file.read_exact(&mut header)?;
+        let (entries, used) = parse_table(&header, file.metadata()?.len(), path, encrypted)?;
+        let cut_short = (entries.iter().enumerate())
+            .filter(|(_, entry)| {
+                entry.len > 0 && entry.sector as usize + entry.sectors() as usize > used.len()
+            })
+            .map(|(slot, _)| (slot, used.len()))
+            .collect();
+        Ok(RegionFile {
+            file,
+            entries,
+            used,
+            cut_short,
+        })
+    }
+
+    fn read(&mut self, slot: usize) -> io::Result<Option<Vec<u8>>> {
+        let entry = self.entries[slot];
+        if entry.len == 0 {
+            return Ok(None);
+        }
+        let blob = self.read_raw(entry)?;
+        if blob.len() < entry.len as usize {
+            return Err(invalid(
+                "chunk blob cut short by the end of the file".to_owned(),
+            ));
+        }
+        if crc32fast::hash(&blob) != entry.crc {
+            return Err(invalid("chunk blob checksum mismatch".to_owned()));
+        }
+        Ok(Some(blob))
+    }
+
+    /// The bytes of the blob of `entry` that the file holds, unchecked.
+    fn read_raw(&mut self, entry: Entry) -> io::Result<Vec<u8>> {
+        // Not preallocated: the length may be that of a blob cut short, or a corrupt one.
+        let mut blob = Vec::new();
+        self.file
+            .seek(SeekFrom::Start(entry.sector as u64 * SECTOR))?;
+        (&mut self.file)
+            .take(entry.len as u64)
+            .read_to_end(&mut blob)?;
+        Ok(blob)
+    }
+
+    /// First run of `count` free sectors, extending the file's sector map if none is free.
+    fn allocate(&mut self, count: u32) -> u32 {
+        let count = count as usize;
+        let mut run = 0;
+        for (sector, &used) in self.used.iter().enumerate() {
+            run = if used { 0 } else { run + 1 };
+            if run == count {
+                let start = sector + 1 - count;
+                self.used[start..=sector].fill(true);
+                return start as u32;
+            }
+        }
+        // Reuse the free sectors at the end of the file, if any.
+        let start = self.used.len() - run;
+        self.used.resize(start + count, true);
+        self.used[start..].fill(true);
+        start as u32
+    }
+
+    fn free(&mut self, slot: usize, entry: Entry) {
+        let start = entry.sector as usize;
+        let mut end = start + entry.sectors() as usize;
+        if let Some(at) = self.cut_short.iter().position(|&(cut, _)| cut == slot) {
+            end = end.min(self.cut_short.swap_remove(at).1);
+        }
+        self.used[start.min(end)..end].fill(false);
+    }
+
+    fn write_entry(&mut self, slot: usize, entry: Entry) -> io::Result<()> {
+        let mut bytes = [0; ENTRY_LEN];
+        bytes[..4].copy_from_slice(&entry.sector.to_le_bytes());
+        bytes[4..8].copy_from_slice(&entry.len.to_le_bytes());
+        bytes[8..].copy_from_slice(&entry.crc.to_le_bytes());
+        self.file
+            .seek(SeekFrom::Start((PREAMBLE_LEN + slot * ENTRY_LEN) as u64))?;
+        self.file.write_all(&bytes)?;
+        self.entries[slot] = entry;
+        Ok(())
+    }
+
+    /// Writes the blob to fresh sectors before pointing the table at them, so that a crash
+    /// in between leaves the previous blob in place.
+    fn write(&mut self, slot: usize, blob: &[u8]) -> io::Result<()> {
+        let len = u32::try_from(blob.len())
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "chunk blob over 4 GiB"))?;
+        if len == 0 {
+            return self.delete(slot).map(|_| ());
+        }
+        let old = self.entries[slot];
+        let mut entry = Entry {
+            sector: 0,
+            len,
+            crc: crc32fast::hash(blob),
+        };
+        entry.sector = self.allocate(entry.sectors());
+
+        // Pad the last sector so that the file always ends on a sector boundary.
+        let mut padded = blob.to_vec();
+        padded.resize(entry.sectors() as usize * SECTOR as usize, 0);
+        self.file
+            .seek(SeekFrom::Start(entry.sector as u64 * SECTOR))?;
+        self.file.write_all(&padded)?;
+        self.write_entry(slot, entry)?;
+        if old.len > 0 {
+            self.free(slot, old);
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, slot: usize) -> io::Result<bool> {
+        let old = self.entries[slot];
+        if old.len == 0 {
+            return Ok(false);
+        }
+        self.write_entry(slot, Entry::default())?;
+        self.free(slot, old);
+        Ok(true)
+    }
+
+    /// Moves the blob ending the file to the first free run before it large enough, as long as
+    /// there is one and `budget` bytes of blobs are left to move; the budget may go over by one
+    /// blob. Like [`RegionFile::write`], a blob is copied before the table points at the copy.
+    /// Returns whether the file is as compact as moving its last blob gets it.
+    fn compact(&mut self, budget: &mut u64, moved: &mut u64) -> io::Result<bool> {
+        loop {
+            let Some((slot, entry)) = self
+                .entries
+                .iter()
+                .copied()
+                .enumerate()
+                .filter(|(_, entry)| entry.len > 0)
+                .max_by_key(|(_, entry)| entry.sector)
+            else {
+                return Ok(true);
+            };
+            let count = entry.sectors() as usize;
+            let mut run = 0;
+            let Some(start) = self.used[..entry.sector as usize]
+                .iter()
+                .position(|&used| {
+                    run = if used { 0 } else { run + 1 };
+                    run == count
+                })
+                .map(|end| end + 1 - count)
+            else {
+                return Ok(true);
+            };
+            if *budget == 0 {
+                return Ok(false);
+            }
+            let mut blob = self.read_raw(entry)?;
+            // Left for `RegionStore::verify` to quarantine.
+            if blob.len() < entry.len as usize {
+                return Ok(true);
+            }
+            blob.resize(count * SECTOR as usize, 0);
+            self.file.seek(SeekFrom::Start(start as u64 * SECTOR))?;
+            self.file.write_all(&blob)?;
+            self.used[start..start + count].fill(true);
+            self.write_entry(
+                slot,
+                Entry {
+                    sector: start as u32,
+                    ..entry
+                },
+            )?;
+            self.free(slot, entry);
+            *budget = budget.saturating_sub(entry.len as u64);
+            *moved += entry.len as u64;
+        }
+    }
+
+    /// Syncs the file, then cuts the free sectors off its end, returning the bytes reclaimed.
+    /// The moves of [`RegionFile::compact`] reach the disk first, so that a crash can't leave
+    /// the table pointing past the end of the file.
+    fn truncate(&mut self) -> io::Result<u64> {
+        let end = self
+            .used
+            .iter()
+            .rposition(|&used| used)
+            .map_or(0, |last| last + 1);
+        let len = self.file.metadata()?.len();
+        let new_len = (end as u64 * SECTOR).min(len);
+        if new_len == len {
+            return Ok(0);
+        }
+        self.file.sync_all()?;
+        self.file.set_len(new_len)?;
+        self.used.truncate(end);
+        Ok(len - new_len)
+    }
+}
+
+/// Durable storage of one byte blob per chunk, like a [`RegionStore`].
+///
+/// An empty blob is no blob: writing one deletes the chunk's. What a store keeps before
+/// [`ChunkStore::sync`] is up to it; only what it kept after survives a crash of the machine.
+pub trait ChunkStore {
+    /// Blob of `idx`, if one was written.
+    fn read(&mut self, idx: VoxelChunkIndex) -> io::Result<Option<Vec<u8>>>;
+
+    /// Replaces the blob of `idx`.
+    fn write(&mut self, idx: VoxelChunkIndex, blob: &[u8]) -> io::Result<()>;
+
+    /// Deletes the blob of `idx`, returning whether there was one.
+    fn delete(&mut self, idx: VoxelChunkIndex) -> io::Result<bool>;
+
+    /// Every chunk with a blob, in increasing packed order.
+    fn keys(&mut self) -> io::Result<Vec<VoxelChunkIndex>>;
+
+    /// Gets everything written so far to disk.
+    fn sync(&mut self) -> io::Result<()>;
+}
+
+/// Durable store of one byte blob per chunk, in a directory of region files named
+/// `r.<x>.<y>.<z>.hfr` after their region coordinates. Region files are opened, or created by
+/// the first write into them, on demand and stay open.
+///
+/// Every write and delete updates the file right away, but nothing is synced to disk before
+/// [`RegionStore::sync`]. Deleted and rewritten blobs leave free sectors behind, which new blobs
+/// reuse; [`RegionStore::compact`] gives them back to the file system.
+///
+/// A store opened with [`RegionStore::open_encrypted`] seals every blob with its
+/// [`RecordCipher`], bound to its chunk, and checks it on read.
+pub struct RegionStore {
+    dir: PathBuf,
+    cipher: Option<Box<dyn RecordCipher>>,
+    regions: HashMap<(i32, i32, i32), RegionFile>,
+    /// Regions [`RegionStore::compact_step`] has yet to compact, the next one last.
+    to_compact: Vec<(i32, i32, i32)>,
+}
+
+/// What a [`RegionStore::compact_step`] did.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Bytes of blobs moved to earlier sectors.
+    pub moved_bytes: u64,
+    /// Bytes cut off the end of region files.
+    pub reclaimed_bytes: u64,
+    /// Whether every region file is compact, as opposed to the budget having run out.
+    pub done: bool,
+}
+
+impl RegionStore {
+    /// Opens the store in `dir`, creating the directory if needed.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<RegionStore> {
+        Self::open_with(dir.as_ref(), None)
+    }
+
+    /// Opens the store in `dir` like [`RegionStore::open`], encrypting blobs with `cipher`. Its
+    /// region files can only be opened with the same cipher and key from then on.
+    pub fn open_encrypted(
+        dir: impl AsRef<Path>,
+        cipher: impl RecordCipher + 'static,
+    ) -> io::Result<RegionStore> {
+        Self::open_with(dir.as_ref(), Some(Box::new(cipher)))
+    }
+
+    fn open_with(dir: &Path, cipher: Option<Box<dyn RecordCipher>>) -> io::Result<RegionStore> {
+        let dir = dir.to_path_buf();
+        fs::create_dir_all(&dir)?;
+        Ok(RegionStore {
+            dir,
+            cipher,
+            regions: HashMap::new(),
+            to_compact: Vec::new(),
+        })
+    }
+
+    fn path(&self, region: (i32, i32, i32)) -> PathBuf {
+        self.dir.join(region_file_name(region))
+    }
+
+    /// Region file of `region`, opened if it exists, or created if `create` is set.
+    fn region(
+        &mut self,
+        region: (i32, i32, i32),
+        create: bool,
+    ) -> io::Result<Option<&mut RegionFile>> {
+        if !self.regions.contains_key(&region) {
+            let path = self.path(region);
+            let encrypted = self.cipher.is_some();
+            let file = match RegionFile::open(&path, encrypted) {
+                Ok(file) => file,
+                Err(err) if err.kind() == io::ErrorKind::NotFound && create => {
+                    RegionFile::create(&path, encrypted)?
+                }
+                Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+                Err(err) => return Err(err),
+            };
+            self.regions.insert(region, file);
+        }
+        Ok(self.regions.get_mut(&region))
+    }
+
+    /// Blob of `idx`, if one was written.
+    pub fn read(&mut self, idx: VoxelChunkIndex) -> io::Result<Option<Vec<u8>>> {
+        let (region, slot) = locate(idx);
+        let blob = match self.region(region, false)? {
+            Some(file) => file.read(slot)?,
+            None => None,
+        };
+        match (blob, &self.cipher) {
+            (Some(sealed), Some(cipher)) => cipher.open(&idx.0.to_le_bytes(), &sealed).map(Some),
+            (blob, _) => Ok(blob),
+        }
+    }
+
+    /// Replaces the blob of `idx`. Writing an empty blob deletes it.
+    pub fn write(&mut self, idx: VoxelChunkIndex, blob: &[u8]) -> io::Result<()> {
+        if blob.is_empty() {
+            return self.delete(idx).map(|_| ());
+        }
+        let sealed = self
+            .cipher
+            .as_ref()
+            .map(|cipher| cipher.seal(&idx.0.to_le_bytes(), blob));
+        let (region, slot) = locate(idx);
+        let file = self.region(region, true)?.expect("created on demand");
+        file.write(slot, sealed.as_deref().unwrap_or(blob))
+    }
+
+    /// Deletes the blob of `idx`, returning whether there was one.
+    pub fn delete(&mut self, idx: VoxelChunkIndex) -> io::Result<bool> {
+        let (region, slot) = locate(idx);
+        match self.region(region, false)? {
+            Some(file) => file.delete(slot),
+            None => Ok(false),
+        }
+    }
+
+    /// Every chunk with a blob, in increasing packed order.
+    pub fn keys(&mut self) -> io::Result<Vec<VoxelChunkIndex>> {
+        let mut keys = Vec::new();
+        for region in self.list_regions()? {
+            let file = self
+                .region(region, false)?
+                .expect("listed in the directory");
+            keys.extend(
+                (file.entries.iter().enumerate())
+                    .filter(|(_, entry)| entry.len > 0)
+                    .map(|(slot, _)| index_of(region, slot)),
+            );
+        }
+        keys.sort_unstable();
+        Ok(keys)
+    }
+
+    /// Checks the blob of every chunk of every region file in the store. Blobs that fail their
+    /// checksum or authentication, or run past the end of their file, are moved to the
+    /// `quarantine` directory of the store, as far as they go, in files named `<x>.<y>.<z>.blob`
+    /// after their chunk, and deleted from the store, so that reading them returns `None` from
+    /// then on rather than an error. Returns the chunks quarantined.
+    pub fn verify(&mut self) -> io::Result<Vec<VoxelChunkIndex>> {
+        let regions = self.list_regions()?;
+        let quarantine = self.dir.join("quarantine");
+        let mut quarantined = Vec::new();
+        for region in regions {
+            self.region(region, false)?;
+            let file = self
+                .regions
+                .get_mut(&region)
+                .expect("listed in the directory");
+            let cipher = &self.cipher;
+            for slot in 0..REGION_CHUNKS {
+                let entry = file.entries[slot];
+                if entry.len == 0 {
+                    continue;
+                }
+                let blob = file.read_raw(entry)?;
+                let idx = index_of(region, slot);
+                if blob.len() == entry.len as usize
+                    && crc32fast::hash(&blob) == entry.crc
+                    && cipher
+                        .as_ref()
+                        .is_none_or(|cipher| cipher.open(&idx.0.to_le_bytes(), &blob).is_ok())
+                {
+                    continue;
+                }
+                let (x, y, z) = idx.to_coords();
+                fs::create_dir_all(&quarantine)?;
+                fs::write(quarantine.join(format!("{x}.{y}.{z}.blob")), &blob)?;
+                file.delete(slot)?;
+                quarantined.push(idx);
+            }
+        }
+        Ok(quarantined)
+    }
+
+    /// Regions of the files in the store's directory, in increasing order.
+    fn list_regions(&self) -> io::Result<Vec<(i32, i32, i32)>> {
+        let mut regions = Vec::new();
+        for dir_entry in fs::read_dir(&self.dir)? {
+            let name = dir_entry?.file_name();
+            regions.extend(name.to_str().and_then(parse_region_file_name));
+        }
+        regions.sort_unstable();
+        Ok(regions)
+    }
+
+    /// Compacts every region file, as [`RegionStore::compact_step`] does without a budget.
+    pub fn compact(&mut self) -> io::Result<CompactionReport> {
+        self.to_compact.clear();
+        self.compact_step(u64::MAX)
+    }
+
+    /// Compacts region files one after the other, until about `budget` bytes of blobs were
+    /// moved: each file's last blob goes to the first free run before it large enough, for as
+    /// long as there is one, then the free sectors at the end of the file are cut off. The
+    /// next call picks up where this one stopped, so that a server can compact a little at a
+    /// time, during its quiet ticks; once a call is done, the next one starts over.
+    pub fn compact_step(&mut self, mut budget: u64) -> io::Result<CompactionReport> {
+        let mut report = CompactionReport::default();
+        if self.to_compact.is_empty() {
+            self.to_compact = self.list_regions()?;
+            self.to_compact.reverse();
+        }
+        while let Some(&region) = self.to_compact.last() {
+            // Deleted since it was listed.
+            let Some(file) = self.region(region, false)? else {
+                self.to_compact.pop();
+                continue;
+            };
+            let compact = file.compact(&mut budget, &mut report.moved_bytes)?;
+            report.reclaimed_bytes += file.truncate()?;
+            if !compact {
+                return Ok(report);
+            }
+            self.to_compact.pop();
+        }
+        report.done = true;
+        Ok(report)
+    }
+
+    /// Flushes every open region file to disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        for file in self.regions.values() {
+            file.file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl ChunkStore for RegionStore {
+    fn read(&mut self, idx: VoxelChunkIndex) -> io::Result<Option<Vec<u8>>> {
+        RegionStore::read(self, idx)
+    }
+
+    fn write(&mut self, idx: VoxelChunkIndex, blob: &[u8]) -> io::Result<()> {
+        RegionStore::write(self, idx, blob)
+    }
+
+    fn delete(&mut self, idx: VoxelChunkIndex) -> io::Result<bool> {
+        RegionStore::delete(self, idx)
+    }
+
+    fn keys(&mut self) -> io::Result<Vec<VoxelChunkIndex>> {
+        RegionStore::keys(self)
+    }
+
+    fn sync(&mut self) -> io::Result<()> {
+        RegionStore::sync(self)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Empty directory for a test, under the system's temporary one.
+    pub(crate) fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("hash_funsies-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Points the table entry of `idx` at `entry`, behind the store's back.
+    fn patch_entry(dir: &Path, idx: VoxelChunkIndex, entry: Entry) {
+        let (region, slot) = locate(idx);
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(dir.join(region_file_name(region)))
+            .unwrap();
+        let mut bytes = [0; ENTRY_LEN];
+        bytes[..4].copy_from_slice(&entry.sector.to_le_bytes());
+        bytes[4..8].copy_from_slice(&entry.len.to_le_bytes());
+        bytes[8..].copy_from_slice(&entry.crc.to_le_bytes());
+        file.seek(SeekFrom::Start((PREAMBLE_LEN + slot * ENTRY_LEN) as u64))
+            .unwrap();
+        file.write_all(&bytes).unwrap();
+    }
+
+    #[test]
+    fn corrupt_sectors_are_quarantined_without_mapping_them() {
+        let dir = scratch_dir("corrupt-sectors");
+        let (bad, good) = (
+            VoxelChunkIndex::from_coords(0, 0, 0),
+            VoxelChunkIndex::from_coords(1, 0, 0),
+        );
+        let mut store = RegionStore::open(&dir).unwrap();
+        store.write(bad, b"bad").unwrap();
+        store.write(good, b"good").unwrap();
+        drop(store);
+        patch_entry(
+            &dir,
+            bad,
+            Entry {
+                sector: u32::MAX - 1,
+                len: u32::MAX,
+                crc: 0,
+            },
+        );
+
+        #[cfg(all(feature = "mmap", unix))]
+        // SAFETY: nothing writes to the store while it is mapped.
+        assert!(unsafe { crate::mmap::MappedRegionStore::open(&dir) }.is_err());
+
+        let mut store = RegionStore::open(&dir).unwrap();
+        assert!(store.read(bad).is_err());
+        let file = &store.regions[&locate(bad).0];
+        assert!(file.used.len() < 16);
+        assert_eq!(store.verify().unwrap(), [bad]);
+        assert_eq!(store.read(bad).unwrap(), None);
+        assert_eq!(store.read(good).unwrap().as_deref(), Some(&b"good"[..]));
+        store.write(bad, b"rewritten").unwrap();
+        assert_eq!(store.read(bad).unwrap().as_deref(), Some(&b"rewritten"[..]));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn blobs_survive_reopening() {
+        let dir = scratch_dir("reopen");
+        let blob = |i: i32| vec![i as u8; 100 + i as usize * 500];
+        let keys: Vec<_> = (0..40)
+            .map(|i| VoxelChunkIndex::from_coords(i * 7 - 100, i % 5, -(i % 3)))
+            .collect();
+        let mut store = RegionStore::open(&dir).unwrap();
+        for (i, &idx) in keys.iter().enumerate() {
+            store.write(idx, &blob(i as i32)).unwrap();
+        }
+        assert!(store.delete(keys[0]).unwrap());
+        store.write(keys[1], b"").unwrap();
+        store.write(keys[2], b"rewritten").unwrap();
+        store.sync().unwrap();
+        drop(store);
+
+        let mut store = RegionStore::open(&dir).unwrap();
+        let mut expected = keys[2..].to_vec();
+        expected.sort_unstable();
+        assert_eq!(store.keys().unwrap(), expected);
+        assert_eq!(store.read(keys[0]).unwrap(), None);
+        assert_eq!(store.read(keys[1]).unwrap(), None);
+        assert_eq!(
+            store.read(keys[2]).unwrap().as_deref(),
+            Some(&b"rewritten"[..])
+        );
+        for (i, &idx) in keys.iter().enumerate().skip(3) {
+            assert_eq!(store.read(idx).unwrap(), Some(blob(i as i32)));
+        }
+        assert_eq!(store.verify().unwrap(), []);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verify_quarantines_corrupted_blobs() {
+        let dir = scratch_dir("quarantine");
+        let (bad, good) = (
+            VoxelChunkIndex::from_coords(-3, 4, 5),
+            VoxelChunkIndex::from_coords(-2, 4, 5),
+        );
+        let mut store = RegionStore::open(&dir).unwrap();
+        store.write(bad, b"corrupted soon").unwrap();
+        store.write(good, b"intact").unwrap();
+        let entry = store.regions[&locate(bad).0].entries[locate(bad).1];
+        drop(store);
+
+        let mut file = OpenOptions::new()
+            .write(true)
+            .open(dir.join(region_file_name(locate(bad).0)))
+            .unwrap();
+        file.seek(SeekFrom::Start(entry.sector as u64 * SECTOR))
+            .unwrap();
+        file.write_all(b"C").unwrap();
+        drop(file);
+
+        let mut store = RegionStore::open(&dir).unwrap();
+        assert_eq!(
+            store.read(bad).unwrap_err().kind(),
+            io::ErrorKind::InvalidData
+        );
+        assert_eq!(store.verify().unwrap(), [bad]);
+        assert_eq!(store.read(bad).unwrap(), None);
+        assert_eq!(store.read(good).unwrap().as_deref(), Some(&b"intact"[..]));
+        let (x, y, z) = bad.to_coords();
+        let quarantined = fs::read(dir.join(format!("quarantine/{x}.{y}.{z}.blob"))).unwrap();
+        assert_eq!(quarantined, b"Corrupted soon");
+        assert_eq!(store.verify().unwrap(), []);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn compaction_keeps_live_blobs() {
+        let dir = scratch_dir("compaction");
+        let blob = |i: i32| vec![i as u8 + 1; 1000 + i as usize * 100];
+        let keys: Vec<_> = (0..12)
+            .map(|i| VoxelChunkIndex::from_coords(i, 0, 0))
+            .collect();
+        let mut store = RegionStore::open(&dir).unwrap();
+        for (i, &idx) in keys.iter().enumerate() {
+            store.write(idx, &blob(i as i32)).unwrap();
+        }
+        for &idx in keys.iter().step_by(2) {
+            store.delete(idx).unwrap();
+        }
+        let path = dir.join(region_file_name(locate(keys[0]).0));
+        let before = fs::metadata(&path).unwrap().len();
+
+        // A budget of one byte moves one blob per step.
+        let step = store.compact_step(1).unwrap();
+        assert!(!step.done && step.moved_bytes > 0);
+        let report = store.compact().unwrap();
+        assert!(report.done);
+        let after = fs::metadata(&path).unwrap().len();
+        assert_eq!(
+            before - after,
+            step.reclaimed_bytes + report.reclaimed_bytes
+        );
+        assert!(after < before);
+        assert_eq!(store.compact().unwrap().reclaimed_bytes, 0);
+
+        store.sync().unwrap();
+        drop(store);
+        let mut store = RegionStore::open(&dir).unwrap();
+        for (i, &idx) in keys.iter().enumerate() {
+            let expected = (i % 2 == 1).then(|| blob(i as i32));
+            assert_eq!(store.read(idx).unwrap(), expected);
+        }
+        assert_eq!(store.verify().unwrap(), []);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
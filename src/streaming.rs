@@ -0,0 +1,261 @@
+//! Chunk streaming scaffolding for game engines: [`ChunkMapResource`], a map with tick-stamped
+//! change detection for systems that each catch up on what changed since they last ran, and
+//! [`ChunkStreamer`], the chunks entering and leaving the view of a set of cameras.
+//!
+//! Both are engine-agnostic. Integrating them with an ECS such as Bevy is a matter of wrapping
+//! them in its resource and event types, `VoxelChunkIndex` in a component, and driving
+//! [`ChunkStreamer::update`] from a system reading camera transforms.
+
+use std::collections::BTreeMap;
+
+use crate::{InterestArea, VoxelChunkIndex, VoxelChunkMap, VoxelChunkSet};
+
+struct Stamped<V> {
+    value: V,
+    added: u64,
+    changed: u64,
+}
+
+/// A chunk map remembering the tick at which each chunk was added and last changed, and the
+/// chunks removed, so that any number of readers can each ask what changed since their own
+/// last look.
+///
+/// Readers take a tick with [`ChunkMapResource::checkpoint`] when they look, and pass it to
+/// [`ChunkMapResource::changed_since`] and the like the next time.
+pub struct ChunkMapResource<V> {
+    map: VoxelChunkMap<Stamped<V>>,
+    removed: Vec<(VoxelChunkIndex, u64)>,
+    tick: u64,
+}
+
+impl<V> Default for ChunkMapResource<V> {
+    fn default() -> Self {
+        ChunkMapResource {
+            map: VoxelChunkMap::new(),
+            removed: Vec::new(),
+            tick: 1,
+        }
+    }
+}
+
+impl<V> ChunkMapResource<V> {
+    pub fn new() -> ChunkMapResource<V> {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    /// A tick after every write so far and before every later one. Passing 0 instead asks for
+    /// everything.
+    pub fn checkpoint(&mut self) -> u64 {
+        self.tick += 1;
+        self.tick - 1
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.map.contains_key(idx)
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        self.map.get(idx).map(|stamped| &stamped.value)
+    }
+
+    /// Marks `idx` as changed whether or not the value is then written to.
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        let stamped = self.map.get_mut(idx)?;
+        stamped.changed = self.tick;
+        Some(&mut stamped.value)
+    }
+
+    /// Inserts `value`, marking `idx` as changed, and as added unless it was there already.
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        let tick = self.tick;
+        match self.map.get_mut(idx) {
+            Some(stamped) => {
+                stamped.changed = tick;
+                Some(std::mem::replace(&mut stamped.value, value))
+            }
+            None => {
+                let stamped = Stamped {
+                    value,
+                    added: tick,
+                    changed: tick,
+                };
+                self.map.insert(idx, stamped);
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        let stamped = self.map.remove(idx)?;
+        self.removed.push((idx, self.tick));
+        Some(stamped.value)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.map.iter().map(|(idx, stamped)| (idx, &stamped.value))
+    }
+
+    /// Whether `idx` was inserted or accessed mutably after `tick`.
+    pub fn is_changed_since(&self, idx: VoxelChunkIndex, tick: u64) -> bool {
+        self.map
+            .get(idx)
+            .is_some_and(|stamped| stamped.changed > tick)
+    }
+
+    /// Chunks inserted or accessed mutably after `tick`, added ones included.
+    pub fn changed_since(&self, tick: u64) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.map
+            .iter()
+            .filter(move |(_, stamped)| stamped.changed > tick)
+            .map(|(idx, stamped)| (idx, &stamped.value))
+    }
+
+    /// Chunks added after `tick`.
+    pub fn added_since(&self, tick: u64) -> impl Iterator<Item = (VoxelChunkIndex, &V)> {
+        self.map
+            .iter()
+            .filter(move |(_, stamped)| stamped.added > tick)
+            .map(|(idx, stamped)| (idx, &stamped.value))
+    }
+
+    /// Chunks removed after `tick`, in the order they were, and only if they are still absent.
+    pub fn removed_since(&self, tick: u64) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        let start = self.removed.partition_point(|&(_, at)| at <= tick);
+        self.removed[start..]
+            .iter()
+            .map(|&(idx, _)| idx)
+            .filter(|&idx| !self.map.contains_key(idx))
+    }
+
+    /// Forgets the removals at or before `tick`, typically the oldest checkpoint of the
+    /// readers. Removals are kept until then, as nothing else bounds them.
+    pub fn forget_removed(&mut self, tick: u64) {
+        let end = self.removed.partition_point(|&(_, at)| at <= tick);
+        self.removed.drain(..end);
+    }
+}
+
+/// Whether a chunk came into view or went out of it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChunkEvent {
+    Enter(VoxelChunkIndex),
+    Leave(VoxelChunkIndex),
+}
+
+/// The chunks within a view distance of any of a set of cameras. A chunk enters when the first
+/// camera sees it and leaves when the last one stops seeing it, so that one passing from a
+/// camera to another is not unloaded in between.
+///
+/// Positions are in world units along the crate's axes, with Z up: a Y-up engine passes its
+/// `(x, z, y)`.
+pub struct ChunkStreamer {
+    area: InterestArea,
+    chunk_size: f32,
+    view_distance: i32,
+    /// Chunks each camera sees.
+    cameras: BTreeMap<u64, VoxelChunkSet>,
+    /// How many cameras see each visible chunk.
+    viewers: VoxelChunkMap<u32>,
+    /// Chunks the cameras removed since the last update saw.
+    orphaned: Vec<VoxelChunkIndex>,
+}
+
+impl ChunkStreamer {
+    /// A streamer of chunks `chunk_size` world units wide, seen within `view_distance` chunks
+    /// of a camera.
+    pub fn new(chunk_size: f32, view_distance: i32) -> ChunkStreamer {
+        assert!(chunk_size > 0.0, "chunk size must be positive");
+        assert!(view_distance >= 0, "view distance can't be negative");
+        ChunkStreamer {
+            area: InterestArea::new(),
+            chunk_size,
+            view_distance,
+            cameras: BTreeMap::new(),
+            viewers: VoxelChunkMap::new(),
+            orphaned: Vec::new(),
+        }
+    }
+
+    /// Chunk containing the world position `position`.
+    pub fn chunk_of(&self, position: [f32; 3]) -> (i32, i32, i32) {
+        let [x, y, z] = position.map(|axis| (axis / self.chunk_size).floor() as i32);
+        (x, y, z)
+    }
+
+    /// Adds a camera at `position`, returning its id. What it sees enters on the next update.
+    pub fn add_camera(&mut self, position: [f32; 3]) -> u64 {
+        let id = self
+            .area
+            .subscribe(self.chunk_of(position), self.view_distance);
+        self.cameras.insert(id, VoxelChunkSet::new());
+        id
+    }
+
+    /// Moves camera `id`, taking effect on the next update.
+    pub fn move_camera(&mut self, id: u64, position: [f32; 3]) {
+        self.area.move_to(id, self.chunk_of(position));
+    }
+
+    /// Removes camera `id`, returning whether it existed. What only it saw leaves on the next
+    /// update.
+    pub fn remove_camera(&mut self, id: u64) -> bool {
+        let Some(seen) = self.cameras.remove(&id) else {
+            return false;
+        };
+        self.area.unsubscribe(id);
+        self.orphaned.extend(seen.iter());
+        true
+    }
+
+    /// Whether some camera saw `idx` as of the last update.
+    pub fn is_visible(&self, idx: VoxelChunkIndex) -> bool {
+        self.viewers.contains_key(idx)
+    }
+
+    /// Chunks visible as of the last update.
+    pub fn visible(&self) -> impl Iterator<Item = VoxelChunkIndex> + '_ {
+        self.viewers.keys()
+    }
+
+    /// Chunks that entered and left the view since the last update, entering ones first.
+    pub fn update(&mut self) -> Vec<ChunkEvent> {
+        let updates = self.area.tick();
+        let mut events = Vec::new();
+        for update in &updates {
+            let seen = self.cameras.get_mut(&update.subscriber).unwrap();
+            for &idx in &update.enter {
+                seen.insert(idx);
+                let viewers = self.viewers.entry(idx).or_insert(0);
+                *viewers += 1;
+                if *viewers == 1 {
+                    events.push(ChunkEvent::Enter(idx));
+                }
+            }
+        }
+        let mut leaving = std::mem::take(&mut self.orphaned);
+        for update in &updates {
+            let seen = self.cameras.get_mut(&update.subscriber).unwrap();
+            for &idx in &update.leave {
+                seen.remove(idx);
+                leaving.push(idx);
+            }
+        }
+        for idx in leaving {
+            let viewers = self.viewers.get_mut(idx).unwrap();
+            *viewers -= 1;
+            if *viewers == 0 {
+                self.viewers.remove(idx);
+                events.push(ChunkEvent::Leave(idx));
+            }
+        }
+        events
+    }
+}
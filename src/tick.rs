@@ -0,0 +1,102 @@
+use crate::{VoxelChunkIndex, VoxelChunkMap, VoxelChunkSet};
+
+/// Two copies of a map: one frozen at the previous tick for reading, one receiving the next
+/// tick's writes, so a simulation step never reads its own writes. Writes are tracked so that
+/// [`TickBuffered::swap`] costs `O(changed)` rather than a full copy.
+pub struct TickBuffered<M> {
+    front: M,
+    back: M,
+    changed: VoxelChunkSet,
+}
+
+/// Write access to the next tick of a [`TickBuffered`] map.
+pub struct TickWriter<'a, V> {
+    back: &'a mut VoxelChunkMap<V>,
+    changed: &'a mut VoxelChunkSet,
+}
+
+impl<V: Clone> TickBuffered<VoxelChunkMap<V>> {
+    /// Both ticks start out as `map`.
+    pub fn new(map: VoxelChunkMap<V>) -> TickBuffered<VoxelChunkMap<V>> {
+        TickBuffered {
+            front: map.clone(),
+            back: map,
+            changed: VoxelChunkSet::new(),
+        }
+    }
+
+    /// The previous tick.
+    pub fn read(&self) -> &VoxelChunkMap<V> {
+        &self.front
+    }
+
+    /// The next tick as written so far.
+    pub fn pending(&self) -> &VoxelChunkMap<V> {
+        &self.back
+    }
+
+    /// Chunks written since the last swap.
+    pub fn changed(&self) -> &VoxelChunkSet {
+        &self.changed
+    }
+
+    /// Reads the previous tick while writing the next one.
+    pub fn split(&mut self) -> (&VoxelChunkMap<V>, TickWriter<'_, V>) {
+        (
+            &self.front,
+            TickWriter {
+                back: &mut self.back,
+                changed: &mut self.changed,
+            },
+        )
+    }
+
+    pub fn writer(&mut self) -> TickWriter<'_, V> {
+        self.split().1
+    }
+
+    /// Publishes the next tick: it becomes readable, and the chunks changed during the tick are
+    /// copied over so that writing starts again from it.
+    pub fn swap(&mut self) {
+        std::mem::swap(&mut self.front, &mut self.back);
+        for idx in self.changed.iter() {
+            match self.front.get(idx) {
+                Some(value) => {
+                    self.back.insert(idx, value.clone());
+                }
+                None => {
+                    self.back.remove(idx);
+                }
+            }
+        }
+        self.changed.clear();
+    }
+
+    /// Final state, including writes not swapped in yet.
+    pub fn into_inner(self) -> VoxelChunkMap<V> {
+        self.back
+    }
+}
+
+impl<V> TickWriter<'_, V> {
+    /// Reads the next tick, including this tick's writes.
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        self.back.get(idx)
+    }
+
+    /// Marks the chunk as changed whether or not it is present.
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        self.changed.insert(idx);
+        self.back.get_mut(idx)
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.changed.insert(idx);
+        self.back.insert(idx, value)
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        self.changed.insert(idx);
+        self.back.remove(idx)
+    }
+}
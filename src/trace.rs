@@ -0,0 +1,293 @@
+//! Recording of the accesses a map sees in an actual game, and their replay against any other
+//! container and hasher, to compare them on production-like access patterns rather than on
+//! synthetic loops.
+
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::io::{self, Read, Write};
+use std::sync::Mutex;
+
+use crate::{LinearChunkMap, PagedChunkMap, VoxelChunkIndex, VoxelChunkMap};
+
+const MAGIC: &[u8; 4] = b"TRC1";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TraceOp {
+    /// A lookup, whether through `get`, `get_mut` or `contains_key`.
+    Get(VoxelChunkIndex),
+    Insert(VoxelChunkIndex),
+    Remove(VoxelChunkIndex),
+}
+
+impl TraceOp {
+    fn tag(self) -> u8 {
+        match self {
+            TraceOp::Get(_) => 0,
+            TraceOp::Insert(_) => 1,
+            TraceOp::Remove(_) => 2,
+        }
+    }
+
+    pub fn key(self) -> VoxelChunkIndex {
+        match self {
+            TraceOp::Get(idx) | TraceOp::Insert(idx) | TraceOp::Remove(idx) => idx,
+        }
+    }
+}
+
+/// Sequence of map accesses, recorded by a [`TracedMap`] or built by hand. Only keys are kept:
+/// replays insert dummy values.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    ops: Vec<TraceOp>,
+}
+
+impl Trace {
+    pub fn new() -> Trace {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+
+    pub fn push(&mut self, op: TraceOp) {
+        self.ops.push(op);
+    }
+
+    pub fn ops(&self) -> &[TraceOp] {
+        &self.ops
+    }
+
+    pub fn clear(&mut self) {
+        self.ops.clear();
+    }
+
+    /// Runs the trace against `target`, returning how many lookups and removals found their key
+    /// so that the work can't be optimized away.
+    pub fn replay<T: ReplayTarget + ?Sized>(&self, target: &mut T) -> usize {
+        let mut hits = 0;
+        for (i, &op) in self.ops.iter().enumerate() {
+            hits += match op {
+                TraceOp::Get(idx) => target.replay_get(idx) as usize,
+                TraceOp::Insert(idx) => {
+                    target.replay_insert(idx, i as u32);
+                    0
+                }
+                TraceOp::Remove(idx) => target.replay_remove(idx) as usize,
+            };
+        }
+        hits
+    }
+
+    /// Writes the trace in a little-endian format, one byte of operation and four of key each,
+    /// ending with a checksum.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut bytes = Vec::with_capacity(16 + self.ops.len() * 5);
+        bytes.extend_from_slice(MAGIC);
+        bytes.extend_from_slice(&(self.ops.len() as u64).to_le_bytes());
+        for op in &self.ops {
+            bytes.push(op.tag());
+            bytes.extend_from_slice(&op.key().0.to_le_bytes());
+        }
+        bytes.extend_from_slice(&crc32fast::hash(&bytes).to_le_bytes());
+        writer.write_all(&bytes)
+    }
+
+    /// Reads a trace written by [`Trace::write_to`].
+    pub fn read_from<R: Read>(mut reader: R) -> io::Result<Trace> {
+        let invalid = |msg| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let mut header = [0; 12];
+        reader.read_exact(&mut header)?;
+        if &header[..4] != MAGIC {
+            return Err(invalid("not an access trace"));
+        }
+        let len = u64::from_le_bytes(header[4..12].try_into().unwrap()) as usize;
+
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&header);
+        let mut ops = Vec::with_capacity(len.min(1 << 20));
+        let mut op = [0; 5];
+        for _ in 0..len {
+            reader.read_exact(&mut op)?;
+            hasher.update(&op);
+            let idx = VoxelChunkIndex(u32::from_le_bytes(op[1..].try_into().unwrap()));
+            ops.push(match op[0] {
+                0 => TraceOp::Get(idx),
+                1 => TraceOp::Insert(idx),
+                2 => TraceOp::Remove(idx),
+                _ => return Err(invalid("unknown trace operation")),
+            });
+        }
+        let mut crc = [0; 4];
+        reader.read_exact(&mut crc)?;
+        if hasher.finalize().to_le_bytes() != crc {
+            return Err(invalid("trace checksum mismatch"));
+        }
+        Ok(Trace { ops })
+    }
+}
+
+impl FromIterator<TraceOp> for Trace {
+    fn from_iter<I: IntoIterator<Item = TraceOp>>(iter: I) -> Trace {
+        Trace {
+            ops: iter.into_iter().collect(),
+        }
+    }
+}
+
+impl Extend<TraceOp> for Trace {
+    fn extend<I: IntoIterator<Item = TraceOp>>(&mut self, iter: I) {
+        self.ops.extend(iter);
+    }
+}
+
+/// Container a [`Trace`] can be replayed against.
+pub trait ReplayTarget {
+    /// Returns whether the key was present.
+    fn replay_get(&mut self, idx: VoxelChunkIndex) -> bool;
+    fn replay_insert(&mut self, idx: VoxelChunkIndex, value: u32);
+    /// Returns whether the key was present.
+    fn replay_remove(&mut self, idx: VoxelChunkIndex) -> bool;
+}
+
+impl ReplayTarget for VoxelChunkMap<u32> {
+    fn replay_get(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    fn replay_insert(&mut self, idx: VoxelChunkIndex, value: u32) {
+        self.insert(idx, value);
+    }
+
+    fn replay_remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.remove(idx).is_some()
+    }
+}
+
+impl ReplayTarget for LinearChunkMap<u32> {
+    fn replay_get(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    fn replay_insert(&mut self, idx: VoxelChunkIndex, value: u32) {
+        self.insert(idx, value);
+    }
+
+    fn replay_remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.remove(idx).is_some()
+    }
+}
+
+impl ReplayTarget for PagedChunkMap<u32> {
+    fn replay_get(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.get(idx).is_some()
+    }
+
+    fn replay_insert(&mut self, idx: VoxelChunkIndex, value: u32) {
+        self.insert(idx, value);
+    }
+
+    fn replay_remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.remove(idx).is_some()
+    }
+}
+
+/// Standard maps, with any hasher.
+impl<S: BuildHasher> ReplayTarget for HashMap<VoxelChunkIndex, u32, S> {
+    fn replay_get(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.get(&idx).is_some()
+    }
+
+    fn replay_insert(&mut self, idx: VoxelChunkIndex, value: u32) {
+        self.insert(idx, value);
+    }
+
+    fn replay_remove(&mut self, idx: VoxelChunkIndex) -> bool {
+        self.remove(&idx).is_some()
+    }
+}
+
+/// [`VoxelChunkMap`] recording every access into a [`Trace`]. Lookups take `&self` as usual, so
+/// the trace sits behind a lock.
+pub struct TracedMap<V> {
+    map: VoxelChunkMap<V>,
+    trace: Mutex<Trace>,
+}
+
+impl<V> Default for TracedMap<V> {
+    fn default() -> Self {
+        TracedMap::from_map(VoxelChunkMap::new())
+    }
+}
+
+impl<V> TracedMap<V> {
+    pub fn new() -> TracedMap<V> {
+        Self::default()
+    }
+
+    /// Starts recording the accesses to `map`.
+    pub fn from_map(map: VoxelChunkMap<V>) -> TracedMap<V> {
+        TracedMap {
+            map,
+            trace: Mutex::new(Trace::new()),
+        }
+    }
+
+    fn record(&self, op: TraceOp) {
+        self.trace.lock().expect("trace lock poisoned").push(op);
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.record(TraceOp::Get(idx));
+        self.map.contains_key(idx)
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        self.record(TraceOp::Get(idx));
+        self.map.get(idx)
+    }
+
+    pub fn get_mut(&mut self, idx: VoxelChunkIndex) -> Option<&mut V> {
+        self.record(TraceOp::Get(idx));
+        self.map.get_mut(idx)
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> Option<V> {
+        self.record(TraceOp::Insert(idx));
+        self.map.insert(idx, value)
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> Option<V> {
+        self.record(TraceOp::Remove(idx));
+        self.map.remove(idx)
+    }
+
+    /// The map itself, for accesses that shouldn't be recorded.
+    pub fn map(&self) -> &VoxelChunkMap<V> {
+        &self.map
+    }
+
+    /// Returns the accesses recorded so far and starts a new trace.
+    pub fn take_trace(&self) -> Trace {
+        std::mem::take(&mut *self.trace.lock().expect("trace lock poisoned"))
+    }
+
+    pub fn into_parts(self) -> (VoxelChunkMap<V>, Trace) {
+        let trace = self.trace.into_inner().expect("trace lock poisoned");
+        (self.map, trace)
+    }
+}
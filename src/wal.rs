@@ -0,0 +1,519 @@
+//! [`WalChunkMap`], a [`VoxelChunkMap`] whose mutations go to a write-ahead log before the map,
+//! so that reopening it after a crash replays them on top of its last snapshot.
+//!
+//! The directory of the map holds `snapshot.vcix`, written by [`VoxelChunkMap::write_to`], and
+//! `wal.log`: the magic `HFWL`, the log version and the byte size of a value, then one record
+//! per mutation, made of a tag byte, the packed key, the value for inserts and a CRC32 of the
+//! record. Replay stops at the first torn or corrupted record, and the rest of the log is moved
+//! to the `quarantine` directory of the map.
+//!
+//! A map opened with [`WalChunkMap::open_encrypted`] seals its snapshot as one record, and the
+//! value of every log record, or nothing for removals, bound to the tag and the key of the
+//! record. Its log starts with the magic `HFWE` instead.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::cipher::RecordCipher;
+use crate::format::LeBytes;
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+const MAGIC: &[u8; 4] = b"HFWL";
+const ENCRYPTED_MAGIC: &[u8; 4] = b"HFWE";
+const VERSION: u8 = 1;
+const LOG_HEADER_LEN: u64 = 6;
+const INSERT: u8 = 0;
+const REMOVE: u8 = 1;
+/// Records the log may hold before a compaction, however small the map.
+const MIN_COMPACTION_RECORDS: u64 = 4096;
+
+fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+/// Crash-safe [`VoxelChunkMap`] backed by a directory holding a snapshot and a write-ahead log.
+///
+/// Every [`WalChunkMap::insert`] and [`WalChunkMap::remove`] appends a record to the log before
+/// updating the map. Records are buffered: [`WalChunkMap::sync`] gets them to disk, and a crash
+/// loses the records written since. Once the log holds more records than the map has keys, the
+/// map is compacted: written to a new snapshot, which replaces the old one, before the log is
+/// cleared.
+pub struct WalChunkMap<V> {
+    map: VoxelChunkMap<V>,
+    dir: PathBuf,
+    cipher: Option<Box<dyn RecordCipher>>,
+    log: BufWriter<File>,
+    log_records: u64,
+}
+
+impl<V: LeBytes> WalChunkMap<V> {
+    /// Opens the map stored in `dir`, creating the directory and an empty map if needed, and
+    /// replays its log on top of its snapshot.
+    pub fn open(dir: impl AsRef<Path>) -> io::Result<WalChunkMap<V>> {
+        Self::open_with(dir.as_ref(), None)
+    }
+
+    /// Opens the map stored in `dir` like [`WalChunkMap::open`], encrypting its snapshot and its
+    /// log with `cipher`. Records that don't authenticate fail the opening, unlike torn ones:
+    /// they were tampered with rather than cut short.
+    pub fn open_encrypted(
+        dir: impl AsRef<Path>,
+        cipher: impl RecordCipher + 'static,
+    ) -> io::Result<WalChunkMap<V>> {
+        Self::open_with(dir.as_ref(), Some(Box::new(cipher)))
+    }
+
+    fn open_with(dir: &Path, cipher: Option<Box<dyn RecordCipher>>) -> io::Result<WalChunkMap<V>> {
+        let dir = dir.to_path_buf();
+        let cipher_ref = cipher.as_deref();
+        fs::create_dir_all(&dir)?;
+        // A compaction that crashed before its rename left the previous snapshot valid.
+        match fs::remove_file(dir.join("snapshot.vcix.tmp")) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+            _ => {}
+        }
+        let mut map = read_snapshot(&dir.join("snapshot.vcix"), cipher_ref)?.unwrap_or_default();
+
+        let path = dir.join("wal.log");
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let bytes = fs::read(&path)?;
+        // A new log, or one whose header a crash cut short.
+        let header = log_header::<V>(cipher_ref.is_some());
+        let (valid, log_records) = if header.starts_with(&bytes) {
+            file.set_len(0)?;
+            file.write_all(&header)?;
+            (LOG_HEADER_LEN, 0)
+        } else {
+            replay(&bytes, &mut map, cipher_ref)?
+        };
+        // Set the torn or corrupted tail, if any, aside, so that new records follow the last
+        // valid one.
+        if valid < bytes.len() as u64 {
+            quarantine(&dir, "wal", &bytes[valid as usize..])?;
+        }
+        file.set_len(valid)?;
+        file.seek(SeekFrom::End(0))?;
+
+        Ok(WalChunkMap {
+            map,
+            dir,
+            cipher,
+            log: BufWriter::new(file),
+            log_records,
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.map.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.map.is_empty()
+    }
+
+    pub fn contains_key(&self, idx: VoxelChunkIndex) -> bool {
+        self.map.contains_key(idx)
+    }
+
+    pub fn get(&self, idx: VoxelChunkIndex) -> Option<&V> {
+        self.map.get(idx)
+    }
+
+    /// The map itself, read-only: mutations have to go through the log.
+    pub fn map(&self) -> &VoxelChunkMap<V> {
+        &self.map
+    }
+
+    /// Records logged since the last compaction.
+    pub fn log_len(&self) -> u64 {
+        self.log_records
+    }
+
+    fn append(&mut self, tag: u8, idx: VoxelChunkIndex, value: Option<&V>) -> io::Result<()> {
+        let mut record = Vec::with_capacity(9 + V::SIZE);
+        record.push(tag);
+        record.extend_from_slice(&idx.0.to_le_bytes());
+        if let Some(value) = value {
+            value.write_le(&mut record);
+        }
+        if let Some(cipher) = &self.cipher {
+            let sealed = cipher.seal(&record[..5], &record[5..]);
+            record.truncate(5);
+            record.extend_from_slice(&sealed);
+        }
+        record.extend_from_slice(&crc32fast::hash(&record).to_le_bytes());
+        self.log.write_all(&record)?;
+        self.log_records += 1;
+        Ok(())
+    }
+
+    /// Compacts the map if the log outgrew it.
+    fn maybe_compact(&mut self) -> io::Result<()> {
+        if self.log_records > (self.map.len() as u64).max(MIN_COMPACTION_RECORDS) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, idx: VoxelChunkIndex, value: V) -> io::Result<Option<V>> {
+        self.append(INSERT, idx, Some(&value))?;
+        let old = self.map.insert(idx, value);
+        self.maybe_compact()?;
+        Ok(old)
+    }
+
+    pub fn remove(&mut self, idx: VoxelChunkIndex) -> io::Result<Option<V>> {
+        if !self.map.contains_key(idx) {
+            return Ok(None);
+        }
+        self.append(REMOVE, idx, None)?;
+        let old = self.map.remove(idx);
+        self.maybe_compact()?;
+        Ok(old)
+    }
+
+    /// Writes the map to a new snapshot and clears the log. The snapshot is synced and renamed
+    /// over the previous one first, so that a crash at any point leaves a snapshot and a log
+    /// that replay to the current map: replaying records the snapshot already has changes
+    /// nothing.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let tmp = self.dir.join("snapshot.vcix.tmp");
+        let mut writer = BufWriter::new(File::create(&tmp)?);
+        match &self.cipher {
+            Some(cipher) => {
+                let mut snapshot = Vec::new();
+                self.map.write_to(&mut snapshot)?;
+                writer.write_all(&cipher.seal(SNAPSHOT_AD, &snapshot))?;
+            }
+            None => self.map.write_to(&mut writer)?,
+        }
+        writer
+            .into_inner()
+            .map_err(|err| err.into_error())?
+            .sync_all()?;
+        fs::rename(&tmp, self.dir.join("snapshot.vcix"))?;
+        // The rename only sticks once the directory itself is synced.
+        #[cfg(unix)]
+        File::open(&self.dir)?.sync_all()?;
+
+        self.log.flush()?;
+        let file = self.log.get_mut();
+        file.set_len(LOG_HEADER_LEN)?;
+        file.seek(SeekFrom::End(0))?;
+        file.sync_data()?;
+        self.log_records = 0;
+        Ok(())
+    }
+
+    /// Checks the snapshot and the log on disk against their checksums, and authenticates them
+    /// if the map is encrypted. If either is damaged,
+    /// it is copied to the `quarantine` directory of the map and the map, which is intact in
+    /// memory, is compacted over both. Returns whether anything was damaged.
+    pub fn verify(&mut self) -> io::Result<bool> {
+        let snapshot = self.dir.join("snapshot.vcix");
+        let mut damaged = false;
+        if let Err(err) = read_snapshot::<V>(&snapshot, self.cipher.as_deref()) {
+            if !matches!(
+                err.kind(),
+                io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof
+            ) {
+                return Err(err);
+            }
+            quarantine(&self.dir, "snapshot", &fs::read(&snapshot)?)?;
+            damaged = true;
+        }
+
+        self.log.flush()?;
+        let log = fs::read(self.dir.join("wal.log"))?;
+        let cipher = self.cipher.as_deref();
+        let scanned = scan::<V>(&log, cipher, |tag, idx, payload| {
+            open_payload(cipher, tag, idx, payload).map(|_| ())
+        });
+        if !matches!(scanned, Ok(scanned) if scanned == (log.len() as u64, self.log_records)) {
+            quarantine(&self.dir, "wal", &log)?;
+            damaged = true;
+        }
+
+        if damaged {
+            self.compact()?;
+        }
+        Ok(damaged)
+    }
+
+    /// Gets every record logged so far to disk.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.log.flush()?;
+        self.log.get_ref().sync_data()
+    }
+}
+
+/// Writes `bytes` to the first free `quarantine/<stem>.<n>.bin` of `dir`.
+fn quarantine(dir: &Path, stem: &str, bytes: &[u8]) -> io::Result<()> {
+    let quarantine = dir.join("quarantine");
+    fs::create_dir_all(&quarantine)?;
+    for n in 0.. {
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(quarantine.join(format!("{stem}.{n}.bin")))
+        {
+            Ok(mut file) => return file.write_all(bytes),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {}
+            Err(err) => return Err(err),
+        }
+    }
+    unreachable!("there is always a free name")
+}
+
+/// Associated data of a sealed snapshot.
+const SNAPSHOT_AD: &[u8] = b"snapshot";
+
+/// The snapshot at `path`, if there is one, opened with `cipher` if the map is encrypted.
+fn read_snapshot<V: LeBytes>(
+    path: &Path,
+    cipher: Option<&dyn RecordCipher>,
+) -> io::Result<Option<VoxelChunkMap<V>>> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err),
+    };
+    match cipher {
+        Some(cipher) => {
+            let snapshot = cipher.open(SNAPSHOT_AD, &fs::read(path)?)?;
+            VoxelChunkMap::read_from(&snapshot[..]).map(Some)
+        }
+        None => VoxelChunkMap::read_from(BufReader::new(file)).map(Some),
+    }
+}
+
+/// The value bytes of a record, opened with `cipher` if the map is encrypted.
+fn open_payload(
+    cipher: Option<&dyn RecordCipher>,
+    tag: u8,
+    idx: VoxelChunkIndex,
+    payload: &[u8],
+) -> io::Result<Vec<u8>> {
+    match cipher {
+        Some(cipher) => {
+            let mut associated_data = [tag, 0, 0, 0, 0];
+            associated_data[1..].copy_from_slice(&idx.0.to_le_bytes());
+            cipher.open(&associated_data, payload)
+        }
+        None => Ok(payload.to_vec()),
+    }
+}
+
+fn log_header<V: LeBytes>(encrypted: bool) -> [u8; LOG_HEADER_LEN as usize] {
+    let mut header = [0; LOG_HEADER_LEN as usize];
+    header[..4].copy_from_slice(if encrypted { ENCRYPTED_MAGIC } else { MAGIC });
+    header[4] = VERSION;
+    header[5] = u8::try_from(V::SIZE).expect("values must fit in 255 bytes");
+    header
+}
+
+/// Applies the records of `log` to `map`, returning the length of the log up to the end of the
+/// last valid record and the number of records.
+fn replay<V: LeBytes>(
+    log: &[u8],
+    map: &mut VoxelChunkMap<V>,
+    cipher: Option<&dyn RecordCipher>,
+) -> io::Result<(u64, u64)> {
+    scan::<V>(log, cipher, |tag, idx, payload| {
+        let value = open_payload(cipher, tag, idx, payload)?;
+        if tag == INSERT {
+            map.insert(idx, V::read_le(&value));
+        } else {
+            map.remove(idx);
+        }
+        Ok(())
+    })
+}
+
+/// Hands the tag, key and value bytes, sealed if the map is encrypted, of each valid record of
+/// `log` to `f`, like [`replay`], stopping at the first error of `f`.
+fn scan<V: LeBytes>(
+    log: &[u8],
+    cipher: Option<&dyn RecordCipher>,
+    mut f: impl FnMut(u8, VoxelChunkIndex, &[u8]) -> io::Result<()>,
+) -> io::Result<(u64, u64)> {
+    let header = log_header::<V>(cipher.is_some());
+    let overhead = cipher.map_or(0, |cipher| cipher.overhead());
+    if log.len() < header.len() || (&log[..4] != MAGIC && &log[..4] != ENCRYPTED_MAGIC) {
+        return Err(invalid("not a chunk map log".to_owned()));
+    }
+    if log[..4] != header[..4] {
+        return Err(invalid(if cipher.is_some() {
+            "the log is plain, not encrypted".to_owned()
+        } else {
+            "the log is encrypted, and no cipher was given".to_owned()
+        }));
+    }
+    if log[4] != VERSION || log[5] != header[5] {
+        return Err(invalid(format!(
+            "log version {} with values of {} bytes, expected version {VERSION} and {} bytes",
+            log[4], log[5], header[5]
+        )));
+    }
+
+    let mut at = header.len();
+    let mut records = 0;
+    while let Some(&tag) = log.get(at) {
+        let len = match tag {
+            INSERT => 5 + V::SIZE + overhead,
+            REMOVE => 5 + overhead,
+            _ => break,
+        };
+        let Some(record) = log.get(at..at + len + 4) else {
+            break;
+        };
+        let (body, crc) = record.split_at(len);
+        if crc32fast::hash(body).to_le_bytes() != crc {
+            break;
+        }
+        let idx = VoxelChunkIndex(u32::from_le_bytes(body[1..5].try_into().unwrap()));
+        f(tag, idx, &body[5..])?;
+        at += record.len();
+        records += 1;
+    }
+    Ok((at as u64, records))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::scratch_dir;
+
+    /// Bytes of a log record of a `u32` insert.
+    const INSERT_LEN: u64 = 1 + 4 + 4 + 4;
+
+    #[test]
+    fn mutations_survive_reopening() {
+        let dir = scratch_dir("wal-reopen");
+        let key = |i: i32| VoxelChunkIndex::from_coords(i, -i, i % 4);
+        let mut map = WalChunkMap::<u32>::open(&dir).unwrap();
+        for i in 0..100 {
+            map.insert(key(i), i as u32).unwrap();
+        }
+        map.compact().unwrap();
+        for i in 0..50 {
+            map.remove(key(i)).unwrap();
+        }
+        map.insert(key(99), 1000).unwrap();
+        map.sync().unwrap();
+        drop(map);
+
+        let mut map = WalChunkMap::<u32>::open(&dir).unwrap();
+        assert_eq!(map.len(), 50);
+        assert_eq!(map.log_len(), 51);
+        assert!((0..50).all(|i| !map.contains_key(key(i))));
+        assert!((50..99).all(|i| map.get(key(i)) == Some(&(i as u32))));
+        assert_eq!(map.get(key(99)), Some(&1000));
+        assert!(!map.verify().unwrap());
+        assert!(!dir.join("quarantine").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replay_stops_at_a_torn_tail() {
+        let dir = scratch_dir("wal-torn");
+        let key = |i: i32| VoxelChunkIndex::from_coords(i, 0, 0);
+        let mut map = WalChunkMap::<u32>::open(&dir).unwrap();
+        for i in 0..10 {
+            map.insert(key(i), i as u32).unwrap();
+        }
+        map.sync().unwrap();
+        drop(map);
+
+        // The last record loses its checksum, as if the crash came while writing it.
+        let log = dir.join("wal.log");
+        let len = fs::metadata(&log).unwrap().len();
+        assert_eq!(len, LOG_HEADER_LEN + 10 * INSERT_LEN);
+        OpenOptions::new()
+            .write(true)
+            .open(&log)
+            .unwrap()
+            .set_len(len - 3)
+            .unwrap();
+
+        let mut map = WalChunkMap::<u32>::open(&dir).unwrap();
+        assert_eq!(map.len(), 9);
+        assert!(!map.contains_key(key(9)));
+        assert_eq!(
+            fs::read(dir.join("quarantine/wal.0.bin")).unwrap().len() as u64,
+            INSERT_LEN - 3
+        );
+        assert_eq!(
+            fs::metadata(&log).unwrap().len(),
+            LOG_HEADER_LEN + 9 * INSERT_LEN
+        );
+
+        // New records follow the last valid one.
+        map.insert(key(9), 9).unwrap();
+        map.sync().unwrap();
+        drop(map);
+        let map = WalChunkMap::<u32>::open(&dir).unwrap();
+        assert_eq!(map.len(), 10);
+        assert_eq!(map.get(key(9)), Some(&9));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn replay_quarantines_everything_after_a_corrupted_record() {
+        let dir = scratch_dir("wal-corrupted");
+        let key = |i: i32| VoxelChunkIndex::from_coords(0, i, 0);
+        let mut map = WalChunkMap::<u32>::open(&dir).unwrap();
+        for i in 0..10 {
+            map.insert(key(i), i as u32).unwrap();
+        }
+        map.sync().unwrap();
+        drop(map);
+
+        let log = dir.join("wal.log");
+        let mut bytes = fs::read(&log).unwrap();
+        bytes[(LOG_HEADER_LEN + 4 * INSERT_LEN + 6) as usize] ^= 0xFF;
+        fs::write(&log, &bytes).unwrap();
+
+        let mut map = WalChunkMap::<u32>::open(&dir).unwrap();
+        assert_eq!(map.len(), 4);
+        assert!((0..4).all(|i| map.get(key(i)) == Some(&(i as u32))));
+        assert_eq!(
+            fs::read(dir.join("quarantine/wal.0.bin")).unwrap(),
+            &bytes[(LOG_HEADER_LEN + 4 * INSERT_LEN) as usize..]
+        );
+        assert!(!map.verify().unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn verify_quarantines_a_damaged_snapshot() {
+        let dir = scratch_dir("wal-snapshot");
+        let mut map = WalChunkMap::<u32>::open(&dir).unwrap();
+        for i in 0..10 {
+            map.insert(VoxelChunkIndex::from_coords(i, i, 0), i as u32)
+                .unwrap();
+        }
+        map.compact().unwrap();
+        let snapshot = dir.join("snapshot.vcix");
+        let mut bytes = fs::read(&snapshot).unwrap();
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        fs::write(&snapshot, &bytes).unwrap();
+
+        assert!(map.verify().unwrap());
+        assert_eq!(
+            fs::read(dir.join("quarantine/snapshot.0.bin")).unwrap(),
+            bytes
+        );
+        drop(map);
+        let map = WalChunkMap::<u32>::open(&dir).unwrap();
+        assert_eq!(map.len(), 10);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
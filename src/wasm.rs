@@ -0,0 +1,154 @@
+//! Functions exported to JavaScript by a WebAssembly build of the crate, so that a browser
+//! renderer packs keys exactly as a native server does. They take and return plain numbers,
+//! which WebAssembly exports hand to JavaScript as they are, with no binding generator;
+//! `bindings/hash_funsies.js` in the repository wraps them in a `ChunkMap` class.
+//!
+//! Build with
+//! `cargo rustc --release --lib --target wasm32-unknown-unknown --features wasm --crate-type cdylib`.
+//! Keys come back to JavaScript as signed 32-bit numbers, which `>>> 0` turns unsigned.
+
+use crate::{VoxelChunkIndex, VoxelChunkMap};
+
+#[no_mangle]
+pub extern "C" fn hf_encode(x: i32, y: i32, z: i32) -> u32 {
+    VoxelChunkIndex::from_coords(x, y, z).0
+}
+
+#[no_mangle]
+pub extern "C" fn hf_decode_x(key: u32) -> i32 {
+    VoxelChunkIndex(key).to_coords().0
+}
+
+#[no_mangle]
+pub extern "C" fn hf_decode_y(key: u32) -> i32 {
+    VoxelChunkIndex(key).to_coords().1
+}
+
+#[no_mangle]
+pub extern "C" fn hf_decode_z(key: u32) -> i32 {
+    VoxelChunkIndex(key).to_coords().2
+}
+
+/// Number of [`VoxelChunkIndex::neighbors`] of `key`.
+#[no_mangle]
+pub extern "C" fn hf_neighbor_count(key: u32) -> u32 {
+    VoxelChunkIndex(key).neighbors().count() as u32
+}
+
+/// The `i`-th of the [`VoxelChunkIndex::neighbors`] of `key`, `i` below their count.
+#[no_mangle]
+pub extern "C" fn hf_neighbor(key: u32, i: u32) -> u32 {
+    VoxelChunkIndex(key)
+        .neighbors()
+        .nth(i as usize)
+        .unwrap_or_else(|| panic!("chunk {key} has no neighbor {i}"))
+        .0
+}
+
+/// A map of JavaScript numbers, with the keys it held at its last
+/// [`hf_map_snapshot`], for JavaScript to iterate over.
+pub struct JsChunkMap {
+    map: VoxelChunkMap<f64>,
+    snapshot: Vec<VoxelChunkIndex>,
+}
+
+#[no_mangle]
+pub extern "C" fn hf_map_new() -> *mut JsChunkMap {
+    Box::into_raw(Box::new(JsChunkMap {
+        map: VoxelChunkMap::new(),
+        snapshot: Vec::new(),
+    }))
+}
+
+/// # Safety
+///
+/// `map` must come from [`hf_map_new`], and not be used after.
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_free(map: *mut JsChunkMap) {
+    // SAFETY: the caller passes a pointer from `Box::into_raw`, once.
+    drop(unsafe { Box::from_raw(map) });
+}
+
+/// # Safety
+///
+/// `map` must come from [`hf_map_new`] and not be freed yet, as for every `hf_map_` function.
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_len(map: *const JsChunkMap) -> u32 {
+    // SAFETY: the caller passes a live map.
+    unsafe { &*map }.map.len() as u32
+}
+
+/// Inserts `value` at `key`, returning whether it replaced a value.
+///
+/// # Safety
+///
+/// See [`hf_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_insert(map: *mut JsChunkMap, key: u32, value: f64) -> bool {
+    // SAFETY: the caller passes a live map, and JavaScript is single-threaded.
+    let map = unsafe { &mut *map };
+    map.map.insert(VoxelChunkIndex(key), value).is_some()
+}
+
+/// # Safety
+///
+/// See [`hf_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_contains(map: *const JsChunkMap, key: u32) -> bool {
+    // SAFETY: the caller passes a live map.
+    unsafe { &*map }.map.contains_key(VoxelChunkIndex(key))
+}
+
+/// The value at `key`, NaN if there is none: tell a stored NaN apart with
+/// [`hf_map_contains`].
+///
+/// # Safety
+///
+/// See [`hf_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_get(map: *const JsChunkMap, key: u32) -> f64 {
+    // SAFETY: the caller passes a live map.
+    let map = unsafe { &*map };
+    map.map
+        .get(VoxelChunkIndex(key))
+        .copied()
+        .unwrap_or(f64::NAN)
+}
+
+/// Removes the value at `key`, returning whether there was one.
+///
+/// # Safety
+///
+/// See [`hf_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_remove(map: *mut JsChunkMap, key: u32) -> bool {
+    // SAFETY: the caller passes a live map, and JavaScript is single-threaded.
+    let map = unsafe { &mut *map };
+    map.map.remove(VoxelChunkIndex(key)).is_some()
+}
+
+/// Takes a snapshot of the keys, in increasing packed order, returning how many there are.
+///
+/// # Safety
+///
+/// See [`hf_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_snapshot(map: *mut JsChunkMap) -> u32 {
+    // SAFETY: the caller passes a live map, and JavaScript is single-threaded.
+    let map = unsafe { &mut *map };
+    map.snapshot.clear();
+    map.snapshot.extend(map.map.keys());
+    map.snapshot.sort_unstable();
+    map.snapshot.len() as u32
+}
+
+/// The `i`-th key of the last [`hf_map_snapshot`].
+///
+/// # Safety
+///
+/// See [`hf_map_len`].
+#[no_mangle]
+pub unsafe extern "C" fn hf_map_snapshot_key(map: *const JsChunkMap, i: u32) -> u32 {
+    // SAFETY: the caller passes a live map.
+    unsafe { &*map }.snapshot[i as usize].0
+}
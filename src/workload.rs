@@ -0,0 +1,232 @@
+//! Seedable key sets shaped like actual worlds, to benchmark and analyse hashers on something
+//! less regular than a dense box of chunks. The same seed always gives the same keys. Chunks of
+//! real worlds can be loaded from a dump with [`read_world_dump`].
+
+use std::io::{self, BufRead, Write};
+
+use crate::filter::{mix64, reduce};
+use crate::{ChunkAabb, VoxelChunkIndex, VoxelChunkSet, X_BIAS, Y_BIAS, Z_BIAS};
+
+/// SplitMix64 generator.
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        mix64(self.0)
+    }
+
+    /// Uniform in `low..high`.
+    fn range(&mut self, low: i32, high: i32) -> i32 {
+        low + reduce(self.next_u64(), (high - low) as usize) as i32
+    }
+
+    fn unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// Value in `[0, 1)` attached to a lattice point.
+fn lattice(seed: u64, x: i32, y: i32, z: i32) -> f64 {
+    const MASK: u64 = (1 << 21) - 1;
+    let point = (x as u64 & MASK) | (y as u64 & MASK) << 21 | (z as u64 & MASK) << 42;
+    (mix64(mix64(point) ^ seed) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Value noise over a lattice of `cell` chunks, smoothly interpolated between lattice points.
+fn value_noise(seed: u64, cell: i32, x: i32, y: i32, z: i32) -> f64 {
+    let smooth = |t: f64| t * t * (3.0 - 2.0 * t);
+    let split = |v: i32| {
+        (
+            v.div_euclid(cell),
+            smooth(v.rem_euclid(cell) as f64 / cell as f64),
+        )
+    };
+    let ((x0, tx), (y0, ty), (z0, tz)) = (split(x), split(y), split(z));
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let corner = |dx, dy, dz| lattice(seed, x0 + dx, y0 + dy, z0 + dz);
+    let plane = |dz| {
+        lerp(
+            lerp(corner(0, 0, dz), corner(1, 0, dz), tx),
+            lerp(corner(0, 1, dz), corner(1, 1, dz), tx),
+            ty,
+        )
+    };
+    lerp(plane(0), plane(1), tz)
+}
+
+/// Chunks of `region` where a two-octave noise field exceeds `threshold`, like the non-empty
+/// chunks of a cavey world. The noise lies in `[0, 1)` and bunches around 0.5, so a threshold
+/// of 0.5 keeps about half of the region in blobs a few dozen chunks wide. Parts of `region`
+/// past the edge of the representable world hold no chunks.
+pub fn caves(region: ChunkAabb, threshold: f64, seed: u64) -> Vec<VoxelChunkIndex> {
+    let coarse = mix64(seed);
+    let fine = mix64(seed ^ 1);
+    region
+        .clipped_to_world()
+        .iter()
+        .filter(|idx| {
+            let (x, y, z) = idx.to_coords();
+            let noise =
+                value_noise(coarse, 16, x, y, z) * 2.0 / 3.0 + value_noise(fine, 4, x, y, z) / 3.0;
+            noise > threshold
+        })
+        .collect()
+}
+
+/// Chunks loaded, in loading order, by a player wandering `steps` chunks from the origin and
+/// keeping every chunk within `view_radius` horizontally, and a quarter of it vertically,
+/// loaded. The player mostly walks straight, turning now and then.
+pub fn player_walk(steps: usize, view_radius: i32, seed: u64) -> Vec<VoxelChunkIndex> {
+    assert!(view_radius >= 0, "view radius can't be negative");
+    let mut rng = Rng(seed);
+    let depth = (view_radius / 4).min(Z_BIAS - 1);
+    let (limit_x, limit_y) = (X_BIAS - 1 - view_radius, Y_BIAS - 1 - view_radius);
+    assert!(limit_x > 0 && limit_y > 0, "view radius too large");
+
+    let mut loaded = VoxelChunkSet::new();
+    let mut order = Vec::new();
+    let mut load_around = |(px, py): (i32, i32)| {
+        for dy in -view_radius..=view_radius {
+            for dx in -view_radius..=view_radius {
+                if dx * dx + dy * dy > view_radius * view_radius {
+                    continue;
+                }
+                for z in -depth..=depth {
+                    let idx = VoxelChunkIndex::from_coords(px + dx, py + dy, z);
+                    if loaded.insert(idx) {
+                        order.push(idx);
+                    }
+                }
+            }
+        }
+    };
+
+    const HEADINGS: [(i32, i32); 4] = [(1, 0), (0, 1), (-1, 0), (0, -1)];
+    let mut position = (0, 0);
+    let mut heading = rng.range(0, 4) as usize;
+    load_around(position);
+    for _ in 0..steps {
+        if rng.unit() < 0.2 {
+            heading = (heading + if rng.unit() < 0.5 { 1 } else { 3 }) % 4;
+        }
+        let (dx, dy) = HEADINGS[heading];
+        let next = (position.0 + dx, position.1 + dy);
+        if next.0.abs() > limit_x || next.1.abs() > limit_y {
+            // Turn back at the edge of the representable world.
+            heading = (heading + 2) % 4;
+            continue;
+        }
+        position = next;
+        load_around(position);
+    }
+    order
+}
+
+/// `count` balls of radius `radius` chunks scattered over the whole representable world, like
+/// isolated outposts, in generation order and without duplicates.
+pub fn outposts(count: usize, radius: i32, seed: u64) -> Vec<VoxelChunkIndex> {
+    assert!(
+        (0..Z_BIAS).contains(&radius),
+        "outposts must fit in the world's height"
+    );
+    let mut rng = Rng(seed);
+    let mut chunks = VoxelChunkSet::new();
+    let mut order = Vec::new();
+    for _ in 0..count {
+        let center = (
+            rng.range(radius - X_BIAS, X_BIAS - radius),
+            rng.range(radius - Y_BIAS, Y_BIAS - radius),
+            rng.range(radius - Z_BIAS, Z_BIAS - radius),
+        );
+        for dz in -radius..=radius {
+            for dy in -radius..=radius {
+                for dx in -radius..=radius {
+                    if dx * dx + dy * dy + dz * dz > radius * radius {
+                        continue;
+                    }
+                    let idx =
+                        VoxelChunkIndex::from_coords(center.0 + dx, center.1 + dy, center.2 + dz);
+                    if chunks.insert(idx) {
+                        order.push(idx);
+                    }
+                }
+            }
+        }
+    }
+    order
+}
+
+/// Reads the chunks of a world dump, in file order and without duplicates. Each line lists either
+/// a single chunk as `x y z`, or a column of chunks as `x y z0..z1`, `z1` excluded. Blank lines
+/// and text after a `#` are ignored.
+///
+/// `z` is the vertical axis: a Minecraft region converts to one column per chunk, its `x` and `z`
+/// as `x` and `y` here and its range of sections as `z0..z1`, see [`crate::minecraft`].
+pub fn read_world_dump<R: BufRead>(reader: R) -> io::Result<Vec<VoxelChunkIndex>> {
+    let mut chunks = VoxelChunkSet::new();
+    let mut order = Vec::new();
+    for (number, line) in reader.lines().enumerate() {
+        let line = line?;
+        let invalid = |msg: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("line {}: {msg}", number + 1),
+            )
+        };
+        let line = line.split('#').next().unwrap_or_default();
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.is_empty() {
+            continue;
+        }
+        let [x, y, z] = fields[..] else {
+            return Err(invalid("expected `x y z` or `x y z0..z1`"));
+        };
+        let coord = |field: &str, bias: i32| {
+            field
+                .parse::<i32>()
+                .ok()
+                .filter(|v| (-bias..bias).contains(v))
+                .ok_or_else(|| invalid("coordinate not a number or out of range"))
+        };
+        let (x, y) = (coord(x, X_BIAS)?, coord(y, Y_BIAS)?);
+        let column = match z.split_once("..") {
+            Some((z0, z1)) => coord(z0, Z_BIAS)?..coord(z1, Z_BIAS + 1)?,
+            None => {
+                let z = coord(z, Z_BIAS)?;
+                z..z + 1
+            }
+        };
+        for z in column {
+            let idx = VoxelChunkIndex::from_coords(x, y, z);
+            if chunks.insert(idx) {
+                order.push(idx);
+            }
+        }
+    }
+    Ok(order)
+}
+
+/// Writes `chunks` as a dump [`read_world_dump`] reads, one chunk per line.
+pub fn write_world_dump<W: Write>(chunks: &[VoxelChunkIndex], mut out: W) -> io::Result<()> {
+    for idx in chunks {
+        let (x, y, z) = idx.to_coords();
+        writeln!(out, "{x} {y} {z}")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caves_past_the_world_edge_are_clipped() {
+        let region = ChunkAabb::new((-4100, 0, 0), (-4090, 4, 4));
+        let chunks = caves(region, f64::NEG_INFINITY, 7);
+        assert_eq!(chunks.len(), 6 * 4 * 4);
+        assert!(chunks
+            .iter()
+            .all(|idx| (-4096..-4090).contains(&idx.to_coords().0)));
+    }
+}